@@ -0,0 +1,143 @@
+use std::{
+    collections::{hash_map::RandomState, HashMap, HashSet},
+    hash::{BuildHasher, Hasher},
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    cache::Cache,
+    song::{Song, StandardTagKey},
+    stats::Listen,
+};
+
+/// identifies the album of a song: the album tag together with the album artist,
+/// or the directory for albums without one, so that compilations stay together
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AlbumKey {
+    pub album: String,
+    owner: String,
+}
+
+impl AlbumKey {
+    /// `None` for songs without an album tag
+    pub fn new(song: &Song) -> Option<Self> {
+        let album = song.tag_string(StandardTagKey::Album)?.trim().to_string();
+        if album.is_empty() {
+            return None;
+        }
+
+        let owner = match song.tag_string(StandardTagKey::AlbumArtist) {
+            Some(artist) => artist.to_string(),
+            None => song
+                .path
+                .parent()
+                .map(|p| p.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+
+        Some(Self { album, owner })
+    }
+}
+
+/// other tags iTunes sets on the tracks of albums meant to be played without gaps
+const GAPLESS_ALBUM_TAGS: [&str; 1] = ["ITUNPGAP"];
+
+/// other tags of tracks ripped from a cd, verified with AccurateRip or with the table of contents of the cd
+const CD_RIP_TAGS: [&str; 2] = ["ACCURATERIPRESULT", "CDTOC"];
+
+/// the albums ripped track per file that are meant to be played without gaps, e.g. live or mixed albums,
+/// so that their tracks are played with the album gain: iTunes flagged a track as part of a gapless album,
+/// or all tracks are cd rips with the same ReplayGain album gain
+pub fn continuous(cache: &Cache) -> HashSet<AlbumKey> {
+    let flagged = |song: &Song| {
+        GAPLESS_ALBUM_TAGS.iter().any(|t| {
+            song.other_tag(t)
+                .is_some_and(|v| matches!(v.to_string().trim(), "1" | "true"))
+        })
+    };
+    let ripped = |song: &Song| CD_RIP_TAGS.iter().any(|t| song.other_tag(t).is_some());
+
+    albums(cache)
+        .into_iter()
+        .filter(|(_, tracks)| {
+            let gains = tracks
+                .iter()
+                .map(|(s, _)| s.album_gain_factor().map(f32::to_bits))
+                .collect::<HashSet<_>>();
+            tracks.iter().any(|(s, _)| flagged(s))
+                || (tracks.len() > 1
+                    && tracks.iter().all(|(s, _)| ripped(s))
+                    && gains.len() == 1
+                    && gains.iter().all(Option::is_some))
+        })
+        .map(|(key, _)| key)
+        .collect()
+}
+
+/// the songs of the album, in disc and track order
+pub fn tracks(cache: &Cache, key: &AlbumKey) -> Vec<PathBuf> {
+    let mut tracks = cache
+        .songs()
+        .filter(|(s, _)| AlbumKey::new(s).as_ref() == Some(key))
+        .collect::<Vec<_>>();
+    sort(&mut tracks);
+
+    tracks.into_iter().map(|(_, p)| p).collect()
+}
+
+/// all albums of the library with their songs
+fn albums(cache: &Cache) -> HashMap<AlbumKey, Vec<(&Song, PathBuf)>> {
+    let mut albums = HashMap::<_, Vec<_>>::new();
+    for (song, path) in cache.songs() {
+        if let Some(key) = AlbumKey::new(song) {
+            albums.entry(key).or_default().push((song, path));
+        }
+    }
+
+    albums
+}
+
+/// a randomly chosen album of the library with its songs in disc and track order,
+/// avoiding albums with songs or artists of `recent` unless there are no others
+pub fn random(cache: &Cache, recent: &[Listen]) -> Option<(AlbumKey, Vec<PathBuf>)> {
+    let paths = recent
+        .iter()
+        .map(|l| l.path.as_path())
+        .collect::<HashSet<&Path>>();
+    let artists = recent
+        .iter()
+        .filter_map(|l| l.artist.as_deref())
+        .collect::<HashSet<_>>();
+
+    let (fresh, played) = albums(cache)
+        .into_iter()
+        .partition::<Vec<_>, _>(|(_, tracks)| {
+            !tracks.iter().any(|(song, path)| {
+                paths.contains(path.as_path())
+                    || song
+                        .tag_string(StandardTagKey::Artist)
+                        .is_some_and(|a| artists.contains(a))
+            })
+        });
+    let mut candidates = if fresh.is_empty() { played } else { fresh };
+    if candidates.is_empty() {
+        return None;
+    }
+
+    // the random keys of the std hasher are good enough to pick an album
+    let index = RandomState::new().build_hasher().finish() as usize % candidates.len();
+    let (key, mut tracks) = candidates.swap_remove(index);
+    sort(&mut tracks);
+
+    Some((key, tracks.into_iter().map(|(_, p)| p).collect()))
+}
+
+fn sort(tracks: &mut [(&Song, PathBuf)]) {
+    tracks.sort_by_key(|(s, p)| {
+        (
+            s.number(StandardTagKey::DiscNumber),
+            s.number(StandardTagKey::TrackNumber),
+            p.clone(),
+        )
+    });
+}
@@ -0,0 +1,145 @@
+use std::path::PathBuf;
+
+const USAGE: &str = "\
+usage: ramp [options]
+       ramp play <file>
+       ramp report <csv|json>
+       ramp export <output> <file>...
+       ramp daemon
+       ramp attach
+       ramp status [--format <template>] [--json] [--follow]
+
+commands:
+  play <file>           play a single file without loading the library
+  report <csv|json>     print the listening statistics
+  export <output> <file>...
+                        render the files into one .wav or .flac file with replay gain
+                        and the configured dsp preset applied
+  daemon                play in the background without a terminal
+  attach                open the tui of the running daemon
+  status                print the song the daemon is playing, formatted like
+                        now_playing_format or the template given with --format,
+                        as json for waybar and polybar with --json, and again
+                        whenever it changes with --follow
+
+options:
+  --profile <name>      use the config, cache and stats of the named profile
+  --stdin               enqueue the newline separated file paths read from stdin
+  --import <file>       enqueue the queue of mpd's state file or a cmus playlist
+  -h, --help            show this help
+";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportFormat {
+    Csv,
+    Json,
+}
+
+/// command line arguments
+#[derive(Debug, Default)]
+pub struct Args {
+    /// enqueue the paths read from stdin on startup
+    pub stdin: bool,
+    /// enqueue the queue saved by another player in this file on startup, see `import`
+    pub import: Option<PathBuf>,
+    /// name of the profile, which has its own config directory
+    pub profile: Option<String>,
+    /// play this file without loading the cache
+    pub play: Option<PathBuf>,
+    /// print the listening statistics instead of starting the player
+    pub report: Option<ReportFormat>,
+    /// render the files into the output file instead of starting the player
+    pub export: Option<(PathBuf, Vec<PathBuf>)>,
+    /// run the player without a tui, controlled through its socket
+    pub daemon: bool,
+    /// run the tui as a client of the daemon
+    pub attach: bool,
+    /// print the now playing line of the daemon instead of starting the player
+    pub status: bool,
+    /// template of the now playing line instead of the configured one
+    pub format: Option<String>,
+    /// print the status as json, see `now_playing::json`
+    pub json: bool,
+    /// print the status again whenever it changes
+    pub follow: bool,
+}
+
+impl Args {
+    pub fn parse() -> anyhow::Result<Self> {
+        let mut args = Self::default();
+
+        let mut iter = std::env::args().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--stdin" => args.stdin = true,
+                "--import" => {
+                    let file = iter
+                        .next()
+                        .ok_or(anyhow::anyhow!("Missing file to import\n\n{USAGE}"))?;
+                    args.import = Some(PathBuf::from(file));
+                }
+                "--profile" => {
+                    let name = iter
+                        .next()
+                        .ok_or(anyhow::anyhow!("Missing profile name\n\n{USAGE}"))?;
+                    anyhow::ensure!(
+                        !name.is_empty()
+                            && name
+                                .chars()
+                                .all(|c| c.is_alphanumeric() || c == '-' || c == '_'),
+                        "Invalid profile name {name}, use letters, digits, '-' and '_'"
+                    );
+                    args.profile = Some(name);
+                }
+                "play" if args.play.is_none() => {
+                    let file = iter
+                        .next()
+                        .ok_or(anyhow::anyhow!("Missing file to play\n\n{USAGE}"))?;
+                    args.play = Some(PathBuf::from(file));
+                }
+                "report" if args.report.is_none() => {
+                    args.report = Some(match iter.next().as_deref() {
+                        Some("csv") => ReportFormat::Csv,
+                        Some("json") => ReportFormat::Json,
+                        _ => anyhow::bail!("Expected csv or json as report format\n\n{USAGE}"),
+                    });
+                }
+                "export" if args.export.is_none() => {
+                    let output = iter
+                        .next()
+                        .ok_or(anyhow::anyhow!("Missing output file\n\n{USAGE}"))?;
+                    let files = iter.by_ref().map(PathBuf::from).collect::<Vec<_>>();
+                    anyhow::ensure!(!files.is_empty(), "Missing files to export\n\n{USAGE}");
+                    args.export = Some((PathBuf::from(output), files));
+                }
+                "daemon" if !args.attach => args.daemon = true,
+                "attach" if !args.daemon => args.attach = true,
+                "status" => args.status = true,
+                "--format" => {
+                    let template = iter
+                        .next()
+                        .ok_or(anyhow::anyhow!("Missing format template\n\n{USAGE}"))?;
+                    args.format = Some(template);
+                }
+                "--json" => args.json = true,
+                "--follow" => args.follow = true,
+                "-h" | "--help" => {
+                    print!("{USAGE}");
+                    std::process::exit(0);
+                }
+                arg => anyhow::bail!("Unknown argument {arg}\n\n{USAGE}"),
+            }
+        }
+
+        anyhow::ensure!(
+            args.play.is_none() || !(args.daemon || args.attach),
+            "play can not be combined with daemon or attach\n\n{USAGE}"
+        );
+        anyhow::ensure!(
+            !(args.format.is_some() || args.json || args.follow) || args.status,
+            "--format, --json and --follow are only used by status\n\n{USAGE}"
+        );
+
+        Ok(args)
+    }
+}
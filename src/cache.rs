@@ -1,16 +1,201 @@
-use crate::{config::Config, song::Song};
+use crate::{
+    album::{self, AlbumKey},
+    config::Config,
+    index::SearchIndex,
+    paths,
+    player::analysis::Analysis,
+    song::{Song, SCANNER_VERSION},
+};
+use anyhow::Context;
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
+    io::Read,
     path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc, Arc, RwLock,
+    },
+    time::Duration,
 };
 
-use log::{trace, warn};
+use log::{info, trace, warn};
 
 use walkdir::WalkDir;
 
-#[derive(Debug, serde::Deserialize, serde::Serialize)]
+/// zstd compression level used for the cache file
+const ZSTD_LEVEL: i32 = 3;
+
+/// interval unavailable songs are checked for in
+const AVAILABILITY_INTERVAL: Duration = Duration::from_secs(30);
+
+/// offline tolerant directories not answering within this are skipped
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// number of cue points of a song
+pub const CUE_POINTS: usize = 4;
+
+type CuePoints = HashMap<Box<Path>, [Option<Duration>; CUE_POINTS]>;
+
+/// results computed in the background per song, shared with the copies made by `SharedCache::update`,
+/// the songs being computed are kept apart so that they never end up in the saved cache
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+#[serde(transparent)]
+struct Computed<T> {
+    results: Arc<RwLock<HashMap<Box<Path>, T>>>,
+    #[serde(skip)]
+    computing: Arc<RwLock<HashSet<Box<Path>>>>,
+}
+
+impl<T> Default for Computed<T> {
+    fn default() -> Self {
+        Self {
+            results: Arc::default(),
+            computing: Arc::default(),
+        }
+    }
+}
+
+impl<T: Clone> Computed<T> {
+    fn get(&self, path: &Path) -> Option<T> {
+        self.results.read().unwrap().get(path).cloned()
+    }
+
+    fn computing(&self, path: &Path) -> bool {
+        self.computing.read().unwrap().contains(path)
+    }
+
+    /// returns true if the result of `path` is neither known nor being computed,
+    /// in which case the caller is responsible for computing it
+    fn claim(&self, path: &Path) -> bool {
+        if self.results.read().unwrap().contains_key(path) {
+            return false;
+        }
+
+        self.computing.write().unwrap().insert(path.into())
+    }
+
+    /// store the result of `path`, `None` if it failed so that it can be claimed again,
+    /// returns whether a result was stored
+    fn insert(&self, path: &Path, result: Option<T>) -> bool {
+        let stored = result.is_some();
+        if let Some(result) = result {
+            self.results.write().unwrap().insert(path.into(), result);
+        }
+        self.computing.write().unwrap().remove(path);
+        stored
+    }
+
+    fn remove(&self, path: &Path) {
+        self.results.write().unwrap().remove(path);
+    }
+
+    fn retain(&self, keep: impl Fn(&Path) -> bool) {
+        self.results.write().unwrap().retain(|path, _| keep(path));
+    }
+}
+
+/// the cache shared by all threads, replaced as a whole when the library changes at runtime
+#[derive(Clone, Default)]
+pub struct SharedCache {
+    cache: Arc<RwLock<Arc<Cache>>>,
+}
+
+impl SharedCache {
+    pub fn new(cache: Cache) -> Self {
+        Self {
+            cache: Arc::new(RwLock::new(Arc::new(cache))),
+        }
+    }
+
+    /// the current cache, later changes are not visible in the returned snapshot
+    pub fn load(&self) -> Arc<Cache> {
+        self.cache.read().unwrap().clone()
+    }
+
+    /// change a copy of the cache and replace the current one with it, rebuilding the search index
+    pub fn update<T>(&self, f: impl FnOnce(&mut Cache) -> T) -> T {
+        let mut lock = self.cache.write().unwrap();
+
+        let mut cache = Cache::clone(&lock);
+        let result = f(&mut cache);
+        cache.build_index();
+        cache.changed.store(true, Ordering::Relaxed);
+
+        *lock = Arc::new(cache);
+        result
+    }
+
+    /// periodically check whether unavailable songs are back, e.g. when a network share was remounted
+    pub fn watch_unavailable(&self) {
+        let cache = self.clone();
+        std::thread::Builder::new()
+            .name("availability thread".to_string())
+            .spawn(move || loop {
+                std::thread::sleep(AVAILABILITY_INTERVAL);
+
+                let available = cache
+                    .load()
+                    .unavailable
+                    .iter()
+                    .filter(|p| p.is_file())
+                    .cloned()
+                    .collect::<Vec<_>>();
+                if !available.is_empty() {
+                    info!("{} songs are available again", available.len());
+                    cache.update(|c| {
+                        for path in &available {
+                            c.unavailable.remove(path);
+                        }
+                    });
+                }
+            })
+            .map_err(|e| warn!("Failed to spawn availability thread: {e:?}"))
+            .ok();
+    }
+}
+
+#[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
 pub struct Cache {
     root: HashMap<String, CacheEntry>,
+    #[serde(skip)]
+    index: SearchIndex,
+    /// albums meant to be played without gaps, rebuilt with the search index
+    #[serde(skip)]
+    continuous: HashSet<AlbumKey>,
+    /// amplitude envelopes of played songs, computed on first play
+    #[serde(default)]
+    envelopes: Computed<Box<[u8]>>,
+    /// technical analyses of songs, computed on request
+    #[serde(default)]
+    analyses: Computed<Analysis>,
+    /// positions in songs set to jump to, shared like the envelopes
+    #[serde(default)]
+    cue_points: Arc<RwLock<CuePoints>>,
+    /// crc32 of the content of the songs, only kept with `verify_checksums`
+    #[serde(default)]
+    checksums: HashMap<Box<Path>, u32>,
+    /// songs whose file was missing when validating, they are kept in case they reappear
+    #[serde(skip)]
+    unavailable: HashSet<Box<Path>>,
+    #[serde(skip)]
+    changed: Arc<AtomicBool>,
+}
+
+impl Clone for Cache {
+    /// the copy has an empty search index
+    fn clone(&self) -> Self {
+        Self {
+            root: self.root.clone(),
+            index: SearchIndex::default(),
+            continuous: HashSet::new(),
+            envelopes: self.envelopes.clone(),
+            analyses: self.analyses.clone(),
+            cue_points: self.cue_points.clone(),
+            checksums: self.checksums.clone(),
+            unavailable: self.unavailable.clone(),
+            changed: self.changed.clone(),
+        }
+    }
 }
 
 impl Cache {
@@ -24,41 +209,125 @@ impl Cache {
         })
     }
 
+    /// the roots of the directories of the library, `/` on unix and drives or shares like `C:\` on windows
+    pub fn roots(&self) -> &HashMap<String, CacheEntry> {
+        &self.root
+    }
+
+    pub fn index(&self) -> &SearchIndex {
+        &self.index
+    }
+
+    /// rebuild the search index and find the continuous albums, must be called after the cache content changed
+    pub fn build_index(&mut self) {
+        self.index = SearchIndex::build(self.songs());
+        self.continuous = album::continuous(self);
+    }
+
+    /// whether `song` belongs to an album meant to be played without gaps, see `album::continuous`
+    pub fn is_continuous(&self, song: &Song) -> bool {
+        AlbumKey::new(song).is_some_and(|key| self.continuous.contains(&key))
+    }
+
+    pub fn envelope(&self, path: &Path) -> Option<Box<[u8]>> {
+        self.envelopes.get(path)
+    }
+
+    /// returns true if the envelope of `path` is neither known nor being computed,
+    /// in which case the caller is responsible for computing it
+    pub fn claim_envelope(&self, path: &Path) -> bool {
+        self.envelopes.claim(path)
+    }
+
+    /// store the envelope of `path`, `None` if it failed so that it can be claimed again
+    pub fn insert_envelope(&self, path: &Path, envelope: Option<Box<[u8]>>) {
+        if self.envelopes.insert(path, envelope) {
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn analysis(&self, path: &Path) -> Option<Analysis> {
+        self.analyses.get(path)
+    }
+
+    /// whether the analysis of `path` is being computed
+    pub fn analyzing(&self, path: &Path) -> bool {
+        self.analyses.computing(path)
+    }
+
+    /// returns true if the analysis of `path` is neither known nor being computed,
+    /// in which case the caller is responsible for computing it
+    pub fn claim_analysis(&self, path: &Path) -> bool {
+        self.analyses.claim(path)
+    }
+
+    /// store the analysis of `path`, `None` if it failed so that it can be claimed again
+    pub fn insert_analysis(&self, path: &Path, analysis: Option<Analysis>) {
+        if self.analyses.insert(path, analysis) {
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// the cue points of `path`, numbered from 1 at index 0
+    pub fn cue_points(&self, path: &Path) -> [Option<Duration>; CUE_POINTS] {
+        self.cue_points
+            .read()
+            .unwrap()
+            .get(path)
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// set or with `None` remove cue point `number` from 1 to `CUE_POINTS` of `path`
+    pub fn set_cue_point(
+        &self,
+        path: &Path,
+        number: usize,
+        position: Option<Duration>,
+    ) -> anyhow::Result<()> {
+        anyhow::ensure!(
+            (1..=CUE_POINTS).contains(&number),
+            "Cue points are numbered from 1 to {CUE_POINTS}"
+        );
+
+        let mut cue_points = self.cue_points.write().unwrap();
+        let song = cue_points.entry(path.into()).or_default();
+        song[number - 1] = position;
+        if song.iter().all(Option::is_none) {
+            cue_points.remove(path);
+        }
+        self.changed.store(true, Ordering::Relaxed);
+
+        Ok(())
+    }
+
+    /// whether envelopes were added or files moved since loading, so the cache should be saved
+    pub fn changed(&self) -> bool {
+        self.changed.load(Ordering::Relaxed)
+    }
+
     pub fn load(config: &Config) -> anyhow::Result<(Self, Config)> {
         let s = std::fs::read(&config.cache_path)?;
+        let s = zstd::decode_all(s.as_slice())?;
         let config = bitcode::deserialize(&s)?;
         Ok(config)
     }
 
     pub fn save(&self, config: &Config) -> anyhow::Result<()> {
         let s = bitcode::serialize(&(self, config))?;
+        let s = zstd::encode_all(s.as_slice(), ZSTD_LEVEL)?;
         std::fs::write(&config.cache_path, s)?;
 
         Ok(())
     }
 
     pub fn build_from_config(config: &Config) -> Self {
-        let mut cache = Cache {
-            root: HashMap::new(),
-        };
-        config
-            .search_directories
-            .iter()
-            .flat_map(WalkDir::new)
-            .filter_map(|e| e.ok())
-            .filter(|e| e.file_type().is_file())
-            .filter(|e| {
-                e.path()
-                    .extension()
-                    .map(|e| config.extensions.contains(e.to_str().unwrap_or("")))
-                    .unwrap_or(false)
-            })
-            .inspect(|e| {
-                trace!("Found file {}", e.path().display());
-            })
-            .filter_map(|e| {
-                Song::load(e.path())
-                    .map(|s| (e.path().to_path_buf(), s))
+        let mut cache = Cache::default();
+        let offline = offline_directories(config);
+        library_files(config, &offline)
+            .filter_map(|p| {
+                read_song(&p, config)
+                    .map(|s| (p, s))
                     .map_err(|e| {
                         warn!("Failed to read song from {:?}: {}", e, e);
                     })
@@ -73,20 +342,66 @@ impl Cache {
         cache
     }
 
-    fn insert_file<P>(&mut self, path: P, song: Song) -> anyhow::Result<()>
+    /// the number of songs read by an older version of the scanner, see `SCANNER_VERSION`,
+    /// or stripped of their tags while `lazy_tags` is off
+    pub fn outdated(&self, config: &Config) -> usize {
+        self.songs().filter(|(s, _)| outdated(s, config)).count()
+    }
+
+    /// look for songs added to and removed from the library since the cache was built
+    /// and read songs again that are outdated,
+    /// with `full` all songs are read again, `progress` counts the files looked at
+    pub fn rescan(&self, config: &Config, full: bool, progress: &AtomicUsize) -> Rescan {
+        let offline = offline_directories(config);
+        let mut found = HashSet::new();
+        let songs = library_files(config, &offline)
+            .inspect(|p| {
+                progress.fetch_add(1, Ordering::Relaxed);
+                found.insert(p.clone());
+            })
+            .filter(|p| {
+                full || !matches!(
+                    self.get(p),
+                    Ok(Some(CacheEntry::File { song })) if !outdated(song, config)
+                )
+            })
+            .filter_map(|p| {
+                read_song(&p, config)
+                    .map(|s| (p, s))
+                    .map_err(|e| warn!("Failed to read song from {:?}: {}", e, e))
+                    .ok()
+            })
+            .collect();
+
+        // songs of offline directories are kept until they are reachable again
+        let removed = self
+            .songs()
+            .map(|(_, p)| p)
+            .filter(|p| !found.contains(p) && !offline.iter().any(|d| p.starts_with(d)))
+            .collect();
+
+        Rescan { songs, removed }
+    }
+
+    /// insert the songs read by a rescan and remove the songs it did not find anymore
+    pub fn apply(&mut self, rescan: Rescan) {
+        for (path, song) in rescan.songs {
+            self.insert_file(&path, song)
+                .unwrap_or_else(|e| warn!("Failed to insert file {:?}: {}", path, e));
+            self.unavailable.remove(path.as_path());
+        }
+        for path in rescan.removed {
+            self.remove_file(&path)
+                .unwrap_or_else(|e| warn!("Failed to remove file {:?}: {}", path, e));
+        }
+    }
+
+    pub fn insert_file<P>(&mut self, path: P, song: Song) -> anyhow::Result<()>
     where
         P: AsRef<Path>,
     {
-        let mut cs = path
-            .as_ref()
-            .components()
-            .map(|c| {
-                c.as_os_str().to_str().ok_or(anyhow::anyhow!(
-                    "Failed to convert OsString to str: {}",
-                    path.as_ref().display()
-                ))
-            })
-            .collect::<anyhow::Result<Vec<_>>>()?;
+        let components = paths::components(path.as_ref())?;
+        let mut cs = components.iter().map(String::as_str).collect::<Vec<_>>();
 
         let first = cs.drain(..1).next().ok_or(anyhow::anyhow!(
             "Failed to get first component from Path {}",
@@ -107,16 +422,8 @@ impl Cache {
     where
         P: AsRef<Path>,
     {
-        let mut cs = path
-            .as_ref()
-            .components()
-            .map(|c| {
-                c.as_os_str().to_str().ok_or(anyhow::anyhow!(
-                    "Failed to convert OsString to str: {}",
-                    path.as_ref().display()
-                ))
-            })
-            .collect::<anyhow::Result<Vec<_>>>()?;
+        let components = paths::components(path.as_ref())?;
+        let mut cs = components.iter().map(String::as_str).collect::<Vec<_>>();
 
         if cs.is_empty() {
             anyhow::bail!(
@@ -139,18 +446,243 @@ impl Cache {
         }
     }
 
-    pub fn validate(&mut self) {
-        self.root.retain(|k, v| {
-            v.validate(PathBuf::new().join(k))
-                .map_err(|e| {
-                    warn!("Failed to validate child {:?}: {}", k, e);
-                })
-                .is_ok()
-        });
+    /// mark missing files and the files of unreachable offline tolerant directories as unavailable,
+    /// and with `verify_checksums` rescan files whose content changed
+    pub fn validate(&mut self, config: &Config) {
+        let songs = self.songs().map(|(_, p)| p).collect::<HashSet<_>>();
+
+        let offline = offline_directories(config);
+        self.unavailable = songs
+            .iter()
+            .filter(|p| offline.iter().any(|d| p.starts_with(d)) || !p.is_file())
+            .map(|p| p.clone().into_boxed_path())
+            .collect();
+        if !self.unavailable.is_empty() {
+            warn!("{} songs are unavailable", self.unavailable.len());
+        }
+
+        self.envelopes.retain(|path| songs.contains(path));
+        self.analyses.retain(|path| songs.contains(path));
+        self.cue_points
+            .write()
+            .unwrap()
+            .retain(|path, _| songs.contains(path.as_ref()));
+        self.checksums
+            .retain(|path, _| songs.contains(path.as_ref()));
+
+        if config.lazy_tags {
+            self.strip_tags();
+        }
+        if config.verify_checksums {
+            self.verify_checksums(config);
+        }
     }
+
+    /// strip the tags of the songs cached before `lazy_tags` was turned on
+    fn strip_tags(&mut self) {
+        let songs = self
+            .songs()
+            .filter(|(s, _)| !s.stripped)
+            .map(|(s, p)| (p, s.clone()))
+            .collect::<Vec<_>>();
+        if songs.is_empty() {
+            return;
+        }
+
+        info!("Stripping the tags of {} songs", songs.len());
+        for (path, mut song) in songs {
+            song.strip();
+            self.insert_file(&path, song)
+                .unwrap_or_else(|e| warn!("Failed to insert file {:?}: {}", path, e));
+        }
+        self.changed.store(true, Ordering::Relaxed);
+    }
+
+    /// false for songs whose file was missing when validating and did not reappear yet
+    pub fn is_available(&self, path: &Path) -> bool {
+        !self.unavailable.contains(path)
+    }
+
+    /// compare the content of all available songs to their checksum, reloading the songs that were modified
+    /// and removing the ones that can no longer be read, songs without a checksum just get one
+    fn verify_checksums(&mut self, config: &Config) {
+        let paths = self
+            .songs()
+            .map(|(_, p)| p)
+            .filter(|p| self.is_available(p))
+            .collect::<Vec<_>>();
+        for path in paths {
+            let checksum = match checksum(&path) {
+                Ok(checksum) => checksum,
+                Err(e) => {
+                    warn!("Failed to compute checksum of {}: {e:?}", path.display());
+                    continue;
+                }
+            };
+
+            match self.checksums.insert(path.clone().into(), checksum) {
+                Some(old) if old == checksum => continue,
+                Some(_) => {
+                    info!("{} was modified, rescanning", path.display());
+                    self.envelopes.remove(&path);
+                    self.analyses.remove(&path);
+
+                    let result =
+                        read_song(&path, config).and_then(|song| self.insert_file(&path, song));
+                    if let Err(e) = result {
+                        warn!("Failed to rescan {}: {e:?}", path.display());
+                        self.remove_file(&path).unwrap_or_else(|e| {
+                            warn!("Failed to remove {}: {e:?}", path.display())
+                        });
+                    }
+                }
+                None => {}
+            }
+
+            self.changed.store(true, Ordering::Relaxed);
+        }
+    }
+
+    /// remove the file at `path` and the directories left empty by it
+    pub fn remove_file<P>(&mut self, path: P) -> anyhow::Result<()>
+    where
+        P: AsRef<Path>,
+    {
+        let components = paths::components(path.as_ref())?;
+        let cs = components.iter().map(String::as_str).collect::<Vec<_>>();
+
+        let (first, rest) = cs.split_first().ok_or(anyhow::anyhow!(
+            "Cache::remove_file called with empty path: {:?}",
+            path.as_ref().display()
+        ))?;
+
+        let entry = self.root.get_mut(*first).ok_or(anyhow::anyhow!(
+            "{} is not in the cache",
+            path.as_ref().display()
+        ))?;
+        entry.remove_file(rest)?;
+        if entry.is_empty_directory() {
+            self.root.remove(*first);
+        }
+
+        self.envelopes.remove(path.as_ref());
+        self.analyses.remove(path.as_ref());
+        self.cue_points.write().unwrap().remove(path.as_ref());
+        self.checksums.remove(path.as_ref());
+        self.unavailable.remove(path.as_ref());
+
+        Ok(())
+    }
+}
+
+/// the songs read and the songs removed by `Cache::rescan`
+#[derive(Debug, Default)]
+pub struct Rescan {
+    /// the new songs, or all songs of a full rescan
+    pub songs: Vec<(PathBuf, Song)>,
+    /// songs in the cache whose file is no longer in the library
+    pub removed: Vec<PathBuf>,
 }
 
-#[derive(serde::Deserialize, serde::Serialize)]
+/// the files with one of the configured extensions in the search directories except `offline`
+fn library_files<'a>(
+    config: &'a Config,
+    offline: &'a [PathBuf],
+) -> impl Iterator<Item = PathBuf> + 'a {
+    config
+        .search_directories
+        .iter()
+        .filter(|d| !offline.contains(d))
+        .flat_map(|d| {
+            let delay = match config.offline_tolerant_directories.contains(d) {
+                true => Duration::from_millis(config.network_scan_delay),
+                false => Duration::ZERO,
+            };
+            WalkDir::new(d).into_iter().inspect(move |_| {
+                if !delay.is_zero() {
+                    std::thread::sleep(delay);
+                }
+            })
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| {
+            e.path()
+                .extension()
+                .map(|e| config.extensions.contains(e.to_str().unwrap_or("")))
+                .unwrap_or(false)
+        })
+        .inspect(|e| {
+            trace!("Found file {}", e.path().display());
+        })
+        .map(|e| e.into_path())
+}
+
+/// the offline tolerant directories that are not reachable
+fn offline_directories(config: &Config) -> Vec<PathBuf> {
+    config
+        .offline_tolerant_directories
+        .iter()
+        .filter(|d| !reachable(d))
+        .inspect(|d| warn!("{} is offline, skipping it", d.display()))
+        .cloned()
+        .collect()
+}
+
+/// whether `dir` is a directory, checked in another thread so that a hung network mount
+/// only blocks that thread
+fn reachable(dir: &Path) -> bool {
+    let (tx, rx) = mpsc::channel();
+    let dir2 = dir.to_path_buf();
+    let probe = std::thread::Builder::new()
+        .name("probe thread".to_string())
+        .spawn(move || tx.send(dir2.is_dir()).ok());
+    if let Err(e) = probe {
+        warn!("Failed to spawn probe thread: {e:?}");
+        return dir.is_dir();
+    }
+
+    rx.recv_timeout(PROBE_TIMEOUT).unwrap_or_else(|e| {
+        warn!(
+            "{} did not answer within {PROBE_TIMEOUT:?}: {e:?}",
+            dir.display()
+        );
+        false
+    })
+}
+
+/// crc32 of the content of the file at `path`
+/// the song at `path`, stripped of the tags lists do not need with `lazy_tags`
+fn read_song(path: &Path, config: &Config) -> anyhow::Result<Song> {
+    let mut song = Song::load(path)?;
+    if config.lazy_tags {
+        song.strip();
+    }
+
+    Ok(song)
+}
+
+/// whether `song` has to be read again, see `Cache::outdated`
+fn outdated(song: &Song, config: &Config) -> bool {
+    song.scanner_version < SCANNER_VERSION || (song.stripped && !config.lazy_tags)
+}
+
+fn checksum(path: &Path) -> anyhow::Result<u32> {
+    let mut file = std::fs::File::open(path).context("Failed to open file")?;
+    let mut hasher = crc32fast::Hasher::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let n = file.read(&mut buffer).context("Failed to read file")?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+
+    Ok(hasher.finalize())
+}
+
+#[derive(Clone, serde::Deserialize, serde::Serialize)]
 pub enum CacheEntry {
     File {
         song: Song,
@@ -254,6 +786,37 @@ impl CacheEntry {
         }
     }
 
+    fn is_empty_directory(&self) -> bool {
+        matches!(self, CacheEntry::Directory { children } if children.is_empty())
+    }
+
+    fn remove_file(&mut self, path: &[&str]) -> anyhow::Result<()> {
+        let CacheEntry::Directory { children } = self else {
+            anyhow::bail!("CacheEntry::remove_file called on {:?}", self);
+        };
+        let (name, rest) = path.split_first().ok_or(anyhow::anyhow!(
+            "CacheEntry::remove_file called with empty path"
+        ))?;
+
+        let child = children
+            .get_mut(*name)
+            .ok_or(anyhow::anyhow!("{name} is not in the cache"))?;
+        match child {
+            CacheEntry::File { .. } if rest.is_empty() => {
+                children.remove(*name);
+            }
+            CacheEntry::File { .. } => anyhow::bail!("{name} is not a directory"),
+            CacheEntry::Directory { .. } => {
+                child.remove_file(rest)?;
+                if child.is_empty_directory() {
+                    children.remove(*name);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn get(&self, mut path: Vec<&str>) -> anyhow::Result<Option<&CacheEntry>> {
         if path.is_empty() {
             Ok(Some(self))
@@ -277,33 +840,4 @@ impl CacheEntry {
             }
         }
     }
-
-    fn validate(&mut self, path: PathBuf) -> anyhow::Result<()> {
-        if !path.exists() {
-            anyhow::bail!("Path {:?} does not exist", path);
-        }
-
-        match self {
-            CacheEntry::File { .. } => {
-                if !path.is_file() {
-                    anyhow::bail!("Path {:?} is not a file", path);
-                }
-            }
-            CacheEntry::Directory { children, .. } => {
-                if !path.is_dir() {
-                    anyhow::bail!("Path {:?} is not a directory", path);
-                }
-
-                children.retain(|k, v| {
-                    v.validate(path.join(k))
-                        .map_err(|e| {
-                            warn!("Failed to validate child {:?}: {}", k, e);
-                        })
-                        .is_ok()
-                });
-            }
-        }
-
-        Ok(())
-    }
 }
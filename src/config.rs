@@ -1,15 +1,294 @@
-use std::{collections::HashSet, path::PathBuf};
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::PathBuf,
+};
 
 use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 
+/// shell commands run on playback events
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Hooks {
+    #[serde(default)]
+    pub on_start: Option<String>,
+    #[serde(default)]
+    pub on_stop: Option<String>,
+    #[serde(default)]
+    pub on_pause: Option<String>,
+    #[serde(default)]
+    pub on_resume: Option<String>,
+}
+
+/// bauer crossfeed for headphone listening, see `player::dsp`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Crossfeed {
+    /// whether crossfeed is enabled on startup, it can be toggled at runtime
+    #[serde(default)]
+    pub enabled: bool,
+    /// cutoff frequency of the crossfed lowpass in Hz
+    #[serde(default = "default_crossfeed_cutoff")]
+    pub cutoff: OrderedFloat<f32>,
+    /// level of the crossfed signal in dB, higher values mix the channels more
+    #[serde(default = "default_crossfeed_feed")]
+    pub feed: OrderedFloat<f32>,
+}
+
+fn default_crossfeed_cutoff() -> OrderedFloat<f32> {
+    OrderedFloat(700.0)
+}
+
+fn default_crossfeed_feed() -> OrderedFloat<f32> {
+    OrderedFloat(4.5)
+}
+
+impl Default for Crossfeed {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            cutoff: default_crossfeed_cutoff(),
+            feed: default_crossfeed_feed(),
+        }
+    }
+}
+
+/// a named output device played to at the same time as the other zones, see `player::sink::ZoneSink`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Zone {
+    pub name: String,
+    /// name of the output device, the default device of the system if unset
+    #[serde(default)]
+    pub device: Option<String>,
+    /// volume of the zone from 0.0 to 1.0 on startup, on top of the volume of the player
+    #[serde(default = "default_zone_volume")]
+    pub volume: OrderedFloat<f32>,
+}
+
+fn default_zone_volume() -> OrderedFloat<f32> {
+    OrderedFloat(1.0)
+}
+
+/// songs started every day at a given time, see `scheduler`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Alarm {
+    /// local time of day, e.g. "07:30"
+    pub time: chrono::NaiveTime,
+    /// song or directory of songs to play, replacing the queue
+    pub path: PathBuf,
+    /// seconds over which the volume is raised from silence
+    #[serde(default = "default_alarm_ramp_up")]
+    pub ramp_up: u64,
+}
+
+fn default_alarm_ramp_up() -> u64 {
+    60
+}
+
+/// how the cover is drawn in the fancy tab
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoverStyle {
+    /// two pixels per cell, one in the foreground and one in the background color
+    #[default]
+    HalfBlock,
+    /// 2x4 braille dots per cell, four times the resolution but only two colors per cell
+    Braille,
+}
+
+/// a field of a song the fuzzy search scores the keyword against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchField {
+    /// the file name if the song has no title
+    Title,
+    /// the file name if the song has no artist
+    Artist,
+    Album,
+    AlbumArtist,
+    Filename,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Theme {
+    #[serde(default)]
+    pub cover: CoverStyle,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct Config {
     pub search_directories: Vec<PathBuf>,
+    /// search directories on network mounts that may be offline or hang, they are probed with a timeout
+    /// before scanning and validating, and their songs are kept as unavailable while they are unreachable
+    #[serde(default)]
+    pub offline_tolerant_directories: Vec<PathBuf>,
+    /// pause between files when scanning offline tolerant directories, in milliseconds
+    #[serde(default)]
+    pub network_scan_delay: u64,
     pub extensions: HashSet<String>,
     pub cache_path: PathBuf,
     pub log_path: PathBuf,
     pub gain: OrderedFloat<f32>,
+    /// loudness in LUFS songs with replay gain tags are brought to, e.g. -23.0 to match EBU R128 players
+    #[serde(default = "default_loudness_target")]
+    pub loudness_target: OrderedFloat<f32>,
+    /// lower the gain of songs whose ReplayGain peak would clip after normalization and the dsp preamp
+    #[serde(default)]
+    pub prevent_clipping: bool,
+    /// language of the track to play in files with multiple audio tracks, e.g. "eng"
+    #[serde(default)]
+    pub preferred_track_language: Option<String>,
+    #[serde(default)]
+    pub hooks: Hooks,
+    /// name of the output device to play on, the default device of the system if unset
+    #[serde(default)]
+    pub output_device: Option<String>,
+    /// output devices played to at the same time with their own volume, e.g. speakers and headphones,
+    /// `output_device` is used if there are none
+    #[serde(default)]
+    pub zones: Vec<Zone>,
+    /// named pipe the played samples are copied to, for external visualizers like cava
+    #[serde(default)]
+    pub visualizer_fifo: Option<PathBuf>,
+    /// directory of the dsp presets, see `player::dsp`
+    #[serde(default = "default_presets_directory")]
+    pub presets_directory: PathBuf,
+    /// directory queues are saved to as m3u playlists, see `playlist`
+    #[serde(default = "default_playlists_directory")]
+    pub playlists_directory: PathBuf,
+    /// directories of m3u and xspf playlists, e.g. of another player, shown in the playlists tab next to
+    /// the saved queues and read again when they change
+    #[serde(default)]
+    pub playlist_directories: Vec<PathBuf>,
+    /// directory the covers shown by desktop media controls and passed to hooks are written to
+    #[serde(default = "default_art_cache_directory")]
+    pub art_cache_directory: PathBuf,
+    /// name of the dsp preset applied on startup
+    #[serde(default)]
+    pub dsp_preset: Option<String>,
+    #[serde(default)]
+    pub crossfeed: Crossfeed,
+    /// file the play counts and ratings are stored in
+    #[serde(default = "default_stats_path")]
+    pub stats_path: PathBuf,
+    /// also write play counts and ratings into the tags of the files
+    #[serde(default)]
+    pub write_tags: bool,
+    /// store a checksum of every song to rescan files modified since they were scanned on startup,
+    /// reads the whole library on every start
+    #[serde(default)]
+    pub verify_checksums: bool,
+    /// keep only the tags lists and searches need in memory and in the cache, for very large libraries,
+    /// lyrics, comments and the other tags are read from the file when a song is played or its info is shown
+    #[serde(default)]
+    pub lazy_tags: bool,
+    /// detect the tempo of songs without a bpm tag after scanning, the tag is stored in the cache only
+    #[serde(default)]
+    pub detect_tempo: bool,
+    /// songs that can be queued at most
+    #[serde(default)]
+    pub max_queue_length: Option<usize>,
+    /// ignore enqueueing a song that is already in the queue
+    #[serde(default)]
+    pub deduplicate_queue: bool,
+    /// number of recently played songs whose albums and artists random album mode avoids
+    #[serde(default = "default_shuffle_history")]
+    pub shuffle_history: usize,
+    /// minimum similarity between 0 and 1 of the title or artist to the keyword for fuzzy search results
+    #[serde(default)]
+    pub search_threshold: OrderedFloat<f64>,
+    /// fields the fuzzy search scores with their weight, a song scores its best weighted similarity
+    #[serde(default = "default_search_weights")]
+    pub search_weights: BTreeMap<SearchField, OrderedFloat<f64>>,
+    /// show the similarity of the selected fuzzy search result next to the number of results
+    #[serde(default)]
+    pub show_search_score: bool,
+    /// moving up from the first row of a list selects the last one and down from the last row the first one
+    #[serde(default)]
+    pub wrap_selection: bool,
+    /// articles skipped at the start of names when sorting, e.g. "The" to sort "The Beatles" under b
+    #[serde(default = "default_sort_articles")]
+    pub sort_articles: Vec<String>,
+    /// rhai scripts loaded on startup
+    #[serde(default)]
+    pub scripts: Vec<PathBuf>,
+    /// directory new music is dropped into, shown in the inbox tab for triage
+    #[serde(default)]
+    pub inbox: Option<PathBuf>,
+    /// address the party page for song requests of guests is served on, e.g. `0.0.0.0:8080`
+    #[serde(default)]
+    pub party_address: Option<String>,
+    #[serde(default)]
+    pub alarm: Option<Alarm>,
+    /// where songs are moved to by the rename action of the files tab, relative to their search directory,
+    /// see `organize`
+    #[serde(default = "default_rename_pattern")]
+    pub rename_pattern: String,
+    /// file the now playing line is written to, e.g. for the status line of tmux
+    #[serde(default)]
+    pub now_playing_file: Option<PathBuf>,
+    /// template of the now playing line in `now_playing_file` and of `ramp status`, see `now_playing`
+    #[serde(default = "default_now_playing_format")]
+    pub now_playing_format: String,
+    /// milliseconds synced lyrics are shown earlier, e.g. to make up for the latency of bluetooth headphones,
+    /// adjusted with `<` and `>` in the lyrics popup
+    #[serde(default)]
+    pub lyrics_offset: i64,
+    #[serde(default)]
+    pub theme: Theme,
+}
+
+fn default_loudness_target() -> OrderedFloat<f32> {
+    OrderedFloat(crate::song::REPLAY_GAIN_REFERENCE)
+}
+
+fn default_shuffle_history() -> usize {
+    20
+}
+
+fn default_search_weights() -> BTreeMap<SearchField, OrderedFloat<f64>> {
+    BTreeMap::from([
+        (SearchField::Title, OrderedFloat(1.0)),
+        (SearchField::Artist, OrderedFloat(1.0)),
+    ])
+}
+
+fn default_sort_articles() -> Vec<String> {
+    vec!["The".to_string()]
+}
+
+fn default_rename_pattern() -> String {
+    "{artist}/{album}/{track:02} - {title}".to_string()
+}
+
+fn default_now_playing_format() -> String {
+    "{state} {artist} - {title}".to_string()
+}
+
+fn default_presets_directory() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_default()
+        .join("ramp")
+        .join("presets")
+}
+
+fn default_playlists_directory() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_default()
+        .join("ramp")
+        .join("playlists")
+}
+
+fn default_art_cache_directory() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_default()
+        .join("ramp")
+        .join("art")
+}
+
+fn default_stats_path() -> PathBuf {
+    dirs::config_dir()
+        .unwrap_or_default()
+        .join("ramp")
+        .join("stats.json")
 }
 
 impl Config {
@@ -37,10 +316,47 @@ impl Config {
     pub fn default_from_config_dir<P: AsRef<std::path::Path>>(config_dir: P) -> Self {
         Self {
             search_directories: vec![],
+            offline_tolerant_directories: vec![],
+            network_scan_delay: 0,
             extensions: HashSet::new(),
             cache_path: config_dir.as_ref().join("ramp.cache"),
             log_path: config_dir.as_ref().join("ramp.log"),
             gain: OrderedFloat(0.0),
+            loudness_target: default_loudness_target(),
+            prevent_clipping: false,
+            preferred_track_language: None,
+            hooks: Hooks::default(),
+            output_device: None,
+            zones: vec![],
+            visualizer_fifo: None,
+            presets_directory: config_dir.as_ref().join("presets"),
+            playlists_directory: config_dir.as_ref().join("playlists"),
+            playlist_directories: vec![],
+            art_cache_directory: config_dir.as_ref().join("art"),
+            dsp_preset: None,
+            crossfeed: Crossfeed::default(),
+            stats_path: config_dir.as_ref().join("stats.json"),
+            write_tags: false,
+            verify_checksums: false,
+            lazy_tags: false,
+            detect_tempo: false,
+            max_queue_length: None,
+            deduplicate_queue: false,
+            shuffle_history: default_shuffle_history(),
+            search_threshold: OrderedFloat(0.0),
+            search_weights: default_search_weights(),
+            show_search_score: false,
+            wrap_selection: false,
+            sort_articles: default_sort_articles(),
+            scripts: vec![],
+            inbox: None,
+            party_address: None,
+            alarm: None,
+            rename_pattern: default_rename_pattern(),
+            now_playing_file: None,
+            now_playing_format: default_now_playing_format(),
+            lyrics_offset: 0,
+            theme: Theme::default(),
         }
     }
 }
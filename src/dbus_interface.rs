@@ -0,0 +1,113 @@
+use std::{
+    path::Path,
+    sync::{mpsc, Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use dbus::{blocking::Connection, channel::MatchingReceiver, message::MatchRule, MethodErr};
+use dbus_crossroads::Crossroads;
+use log::{info, warn};
+
+use crate::{
+    cache::SharedCache,
+    player::{command::Command, facade::PlayerFacade},
+    query::Query,
+};
+
+/// well known name on the session bus, next to the MPRIS name
+const BUS_NAME: &str = "org.ramp";
+const OBJECT_PATH: &str = "/org/ramp";
+const INTERFACE: &str = "org.ramp.Player";
+
+/// expose the `org.ramp.Player` interface on the session bus, for scripting the library and queue
+/// beyond what MPRIS offers
+pub fn start(cache: SharedCache, cmd: mpsc::Sender<Command>, player: Arc<RwLock<PlayerFacade>>) {
+    std::thread::Builder::new()
+        .name("dbus thread".to_string())
+        .spawn(move || {
+            serve(cache, cmd, player).unwrap_or_else(|e| warn!("Error in D-Bus interface: {e:?}"))
+        })
+        .map_err(|e| warn!("Failed to spawn dbus thread: {e:?}"))
+        .ok();
+}
+
+fn send(cmd: &mpsc::Sender<Command>, command: Command) -> Result<(), MethodErr> {
+    cmd.send(command).map_err(|e| MethodErr::failed(&e))
+}
+
+fn serve(
+    cache: SharedCache,
+    cmd: mpsc::Sender<Command>,
+    player: Arc<RwLock<PlayerFacade>>,
+) -> anyhow::Result<()> {
+    let connection = Connection::new_session().context("Failed to connect to the session bus")?;
+    connection
+        .request_name(BUS_NAME, false, true, false)
+        .context("Failed to request the bus name")?;
+
+    let mut crossroads = Crossroads::new();
+    let interface = crossroads.register(INTERFACE, move |b| {
+        let cmd2 = cmd.clone();
+        b.method("Enqueue", ("path",), (), move |_, _, (path,): (String,)| {
+            send(&cmd2, Command::Enqueue(Path::new(&path).into()))
+        });
+
+        let cmd2 = cmd.clone();
+        b.method("Dequeue", ("index",), (), move |_, _, (index,): (u32,)| {
+            send(&cmd2, Command::Dequeue(index as usize))
+        });
+
+        b.method("Clear", (), (), move |_, _, ()| send(&cmd, Command::Clear));
+
+        let player2 = player.clone();
+        b.method("Queue", (), ("paths",), move |_, _, ()| {
+            let player = player2.read().unwrap();
+            Ok((player
+                .queue
+                .iter()
+                .map(|p| p.display().to_string())
+                .collect::<Vec<_>>(),))
+        });
+
+        b.method("CurrentSong", (), ("path",), move |_, _, ()| {
+            let player = player.read().unwrap();
+            Ok((player
+                .current_song()
+                .map(|s| s.path.display().to_string())
+                .unwrap_or_default(),))
+        });
+
+        b.method(
+            "Search",
+            ("query",),
+            ("paths",),
+            move |_, _, (query,): (String,)| {
+                let query = Query::parse(&query).map_err(|e| MethodErr::invalid_arg(&e))?;
+                Ok((query
+                    .lookup(&cache.load())
+                    .into_iter()
+                    .map(|p| p.display().to_string())
+                    .collect::<Vec<_>>(),))
+            },
+        );
+    });
+    crossroads.insert(OBJECT_PATH, &[interface], ());
+
+    connection.start_receive(
+        MatchRule::new_method_call(),
+        Box::new(move |message, connection| {
+            crossroads
+                .handle_message(message, connection)
+                .unwrap_or_else(|()| warn!("Failed to handle D-Bus message"));
+            true
+        }),
+    );
+    info!("Serving {INTERFACE} as {BUS_NAME} at {OBJECT_PATH}");
+
+    loop {
+        connection
+            .process(Duration::from_secs(60))
+            .context("Failed to process D-Bus messages")?;
+    }
+}
@@ -0,0 +1,128 @@
+//! importing the queue of another player, for carrying over the session when switching to ramp
+
+use std::{
+    collections::HashMap,
+    ffi::OsStr,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use log::info;
+
+use crate::cache::{Cache, CacheEntry};
+
+/// the songs of a queue saved by another player
+#[derive(Debug, Default)]
+pub struct Import {
+    /// the songs of the library, starting with the one that was playing
+    pub songs: Vec<PathBuf>,
+    /// entries that are not in the library, e.g. streams or songs that only the other player knows
+    pub missing: Vec<String>,
+}
+
+/// read the queue from MPD's state file, e.g. `~/.local/share/mpd/state`, or a cmus playlist,
+/// e.g. `~/.config/cmus/playlist.pl` or `queue.pl`, telling them apart by their contents
+pub fn import(path: &Path, cache: &Cache) -> anyhow::Result<Import> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+
+    let entries = match contents.lines().any(|l| l.trim() == "playlist_begin") {
+        true => mpd_queue(&contents),
+        false => contents
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+    };
+
+    let resolver = Resolver::new(cache);
+    let mut import = Import::default();
+    for entry in entries {
+        match resolver.resolve(&entry) {
+            Some(path) => import.songs.push(path),
+            None => import.missing.push(entry),
+        }
+    }
+    info!(
+        "Imported {} songs from {}, {} are not in the library",
+        import.songs.len(),
+        path.display(),
+        import.missing.len()
+    );
+
+    Ok(import)
+}
+
+/// the songs of the queue in an MPD state file from the current one on, relative to the music directory
+fn mpd_queue(contents: &str) -> Vec<String> {
+    let mut current = 0;
+    let mut queue = vec![];
+    let mut in_playlist = false;
+    for line in contents.lines().map(str::trim) {
+        match line {
+            "playlist_begin" => in_playlist = true,
+            "playlist_end" => in_playlist = false,
+            _ if in_playlist => {
+                // songs are written as "0:path" or, with a range or tags, as "song_begin: path"
+                if let Some(path) = line.strip_prefix("song_begin:") {
+                    queue.push(path.trim().to_string());
+                } else if let Some((index, path)) = line.split_once(':') {
+                    if index.parse::<usize>().is_ok() {
+                        queue.push(path.to_string());
+                    }
+                }
+            }
+            _ => {
+                if let Some(index) = line.strip_prefix("current:") {
+                    current = index.trim().parse().unwrap_or(0);
+                }
+            }
+        }
+    }
+
+    queue.drain(..current.min(queue.len()));
+    queue
+}
+
+/// finds the songs of the library other players refer to by absolute paths or by paths relative to their
+/// music directory, by looking for songs whose path ends with them
+struct Resolver<'a> {
+    cache: &'a Cache,
+    by_file_name: HashMap<&'a OsStr, Vec<PathBuf>>,
+}
+
+impl<'a> Resolver<'a> {
+    fn new(cache: &'a Cache) -> Self {
+        let mut by_file_name = HashMap::<_, Vec<_>>::new();
+        for (song, path) in cache.songs() {
+            if let Some(name) = song.path.file_name() {
+                by_file_name.entry(name).or_default().push(path);
+            }
+        }
+
+        Self {
+            cache,
+            by_file_name,
+        }
+    }
+
+    fn resolve(&self, entry: &str) -> Option<PathBuf> {
+        if entry.contains("://") {
+            return None;
+        }
+
+        let path = Path::new(entry);
+        if let Ok(Some(CacheEntry::File { .. })) = self.cache.get(path) {
+            return Some(path.to_path_buf());
+        }
+
+        let candidates = self.by_file_name.get(path.file_name()?)?;
+        let mut matching = candidates.iter().filter(|c| c.ends_with(path));
+        match (matching.next(), matching.next()) {
+            (Some(found), None) => Some(found.clone()),
+            // the same relative path in two search directories can not be told apart
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, RwLock, Weak},
+    time::Duration,
+};
+
+use anyhow::Context;
+use log::{debug, info, warn};
+use walkdir::WalkDir;
+
+use crate::{config::Config, notification::Notification, song::Song};
+
+/// time between two scans of the inbox directory
+const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// songs in the configured inbox directory, waiting to be enqueued, moved into the library or deleted
+#[derive(Clone)]
+pub struct Inbox {
+    songs: Arc<RwLock<Vec<(PathBuf, Song)>>>,
+    dir: PathBuf,
+    /// where songs are moved to, the first search directory
+    library: Option<PathBuf>,
+    notifications: mpsc::Sender<Notification>,
+}
+
+impl Inbox {
+    /// start scanning the inbox directory, if one is configured
+    pub fn watch(config: &Config, notifications: mpsc::Sender<Notification>) -> Option<Self> {
+        let dir = config.inbox.clone()?;
+        if !dir.is_dir() {
+            warn!("Inbox {} is not a directory", dir.display());
+            notifications
+                .send(Notification::error(format!(
+                    "Inbox {} is not a directory",
+                    dir.display()
+                )))
+                .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+            return None;
+        }
+
+        let inbox = Self {
+            songs: Arc::default(),
+            dir,
+            library: config.search_directories.first().cloned(),
+            notifications,
+        };
+
+        let songs = Arc::downgrade(&inbox.songs);
+        let dir = inbox.dir.clone();
+        let extensions = config.extensions.clone();
+        let notifications = inbox.notifications.clone();
+        std::thread::Builder::new()
+            .name("inbox thread".to_string())
+            .spawn(move || scan_loop(&dir, &extensions, songs, notifications))
+            .map_err(|e| warn!("Failed to spawn inbox thread: {e:?}"))
+            .ok()?;
+
+        Some(inbox)
+    }
+
+    pub fn songs(&self) -> Vec<(PathBuf, Song)> {
+        self.songs.read().unwrap().clone()
+    }
+
+    pub fn notify(&self, notification: Notification) {
+        self.notifications
+            .send(notification)
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+    }
+
+    /// move `path` into the library, keeping its path relative to the inbox
+    pub fn move_to_library(&self, path: &Path) -> anyhow::Result<PathBuf> {
+        let library = self
+            .library
+            .as_ref()
+            .ok_or(anyhow::anyhow!("No search directory to move songs to"))?;
+        let destination = library.join(path.strip_prefix(&self.dir).unwrap_or(path));
+        anyhow::ensure!(
+            !destination.exists(),
+            "{} already exists",
+            destination.display()
+        );
+
+        if let Some(parent) = destination.parent() {
+            std::fs::create_dir_all(parent).context("Failed to create directory")?;
+        }
+        // renaming fails across file systems
+        if std::fs::rename(path, &destination).is_err() {
+            std::fs::copy(path, &destination).context("Failed to copy song")?;
+            std::fs::remove_file(path).context("Failed to remove song from inbox")?;
+        }
+        info!("Moved {} to {}", path.display(), destination.display());
+
+        self.forget(path);
+        Ok(destination)
+    }
+
+    pub fn delete(&self, path: &Path) -> anyhow::Result<()> {
+        std::fs::remove_file(path).context("Failed to delete song")?;
+        info!("Deleted {}", path.display());
+
+        self.forget(path);
+        Ok(())
+    }
+
+    fn forget(&self, path: &Path) {
+        self.songs.write().unwrap().retain(|(p, _)| p != path);
+    }
+}
+
+fn scan_loop(
+    dir: &Path,
+    extensions: &HashSet<String>,
+    songs: Weak<RwLock<Vec<(PathBuf, Song)>>>,
+    notifications: mpsc::Sender<Notification>,
+) {
+    // sizes of files that failed to load, they are retried once they change, e.g. after being copied completely
+    let mut failed = HashMap::new();
+
+    loop {
+        let Some(songs) = songs.upgrade() else {
+            return;
+        };
+
+        let files = WalkDir::new(dir)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| {
+                e.path()
+                    .extension()
+                    .is_some_and(|e| extensions.contains(e.to_str().unwrap_or("")))
+            })
+            .map(|e| e.into_path())
+            .collect::<HashSet<_>>();
+
+        let known = songs
+            .read()
+            .unwrap()
+            .iter()
+            .map(|(p, _)| p.clone())
+            .collect::<HashSet<_>>();
+        failed.retain(|p, _| files.contains(p));
+
+        let mut new = vec![];
+        for path in files.iter().filter(|p| !known.contains(*p)) {
+            let size = std::fs::metadata(path).map(|m| m.len()).ok();
+            if failed.get(path) == Some(&size) {
+                continue;
+            }
+
+            match Song::load(path) {
+                Ok(song) => {
+                    failed.remove(path);
+                    new.push((path.clone(), song));
+                }
+                Err(e) => {
+                    debug!("Failed to load inbox song {}: {e:?}", path.display());
+                    failed.insert(path.clone(), size);
+                }
+            }
+        }
+
+        let count = new.len();
+        {
+            let mut songs = songs.write().unwrap();
+            songs.retain(|(p, _)| files.contains(p));
+            songs.extend(new);
+            songs.sort_by(|(a, _), (b, _)| a.cmp(b));
+        }
+        drop(songs);
+
+        if count > 0 {
+            info!("{count} new songs in the inbox");
+            notifications
+                .send(Notification::info(match count {
+                    1 => "1 new song in the inbox".to_string(),
+                    n => format!("{n} new songs in the inbox"),
+                }))
+                .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+        }
+
+        std::thread::sleep(SCAN_INTERVAL);
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use crate::song::{Song, StandardTagKey, Value};
+
+/// tags that are too long to be useful as search tokens
+const SKIPPED_TAGS: [StandardTagKey; 2] = [StandardTagKey::Lyrics, StandardTagKey::Comment];
+
+/// lowercase token -> song index, built from the cache at load time
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    paths: Vec<PathBuf>,
+    tokens: BTreeMap<String, Vec<usize>>,
+}
+
+impl SearchIndex {
+    pub fn build<'a, I>(songs: I) -> Self
+    where
+        I: Iterator<Item = (&'a Song, PathBuf)>,
+    {
+        let mut index = SearchIndex::default();
+
+        for (song, path) in songs {
+            let id = index.paths.len();
+
+            let tags = song
+                .standard_tags
+                .iter()
+                .filter(|(k, _)| !SKIPPED_TAGS.contains(k))
+                .filter_map(|(_, v)| match v {
+                    Value::String(s) => Some(s.as_str()),
+                    _ => None,
+                });
+            let filename = path.file_stem().and_then(|f| f.to_str());
+
+            tags.chain(filename)
+                .flat_map(tokenize)
+                .collect::<HashSet<_>>()
+                .into_iter()
+                .for_each(|t| index.tokens.entry(t).or_default().push(id));
+
+            index.paths.push(path);
+        }
+
+        index
+    }
+
+    /// all paths where every token of `query` is a prefix of one of the song's tokens
+    pub fn lookup(&self, query: &str) -> HashSet<&Path> {
+        let mut result: Option<HashSet<usize>> = None;
+
+        for token in tokenize(query) {
+            let ids = self
+                .tokens
+                .range(token.clone()..)
+                .take_while(|(t, _)| t.starts_with(&token))
+                .flat_map(|(_, ids)| ids.iter().copied())
+                .collect::<HashSet<_>>();
+
+            result = Some(match result {
+                Some(r) => r.intersection(&ids).copied().collect(),
+                None => ids,
+            });
+        }
+
+        result
+            .unwrap_or_default()
+            .into_iter()
+            .map(|i| self.paths[i].as_path())
+            .collect()
+    }
+}
+
+fn tokenize(s: &str) -> impl Iterator<Item = String> + '_ {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+}
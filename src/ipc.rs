@@ -0,0 +1,492 @@
+use std::{
+    collections::VecDeque,
+    path::Path,
+    sync::{atomic::AtomicBool, mpsc, Arc, Mutex, RwLock},
+    time::Duration,
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use symphonia::core::meta::{MetadataBuilder, StandardVisualKey, Visual};
+
+use crate::{
+    notification::Notification,
+    player::{
+        command::Command,
+        facade::{PlayerFacade, PlayerStatus, TrackInfo},
+    },
+    song::{Chapter, Song},
+    stats::SongStats,
+};
+
+/// interval the client polls the state of the daemon in
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// interval the client tries to reconnect in after the connection was lost
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// notifications kept by the daemon until a client fetches them
+const MAX_PENDING_NOTIFICATIONS: usize = 20;
+
+/// a request of a tui client, each line on the socket is one json encoded request
+#[derive(Debug, Serialize, Deserialize)]
+enum Request {
+    Command(Command),
+    State,
+    /// the state without taking the notifications meant for the tui, for `ramp status`
+    Status,
+    /// the cover of the current song, only requested when the song changed
+    Cover,
+}
+
+/// the answer to a request, one json encoded line
+#[derive(Debug, Serialize, Deserialize)]
+enum Response {
+    Ok,
+    State {
+        state: Box<State>,
+        notifications: Vec<Notification>,
+    },
+    Cover(Option<Cover>),
+}
+
+/// the front cover of the current song
+#[derive(Debug, Serialize, Deserialize)]
+struct Cover {
+    media_type: String,
+    data: Vec<u8>,
+}
+
+/// the serializable part of the player facade
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct State {
+    song: Option<Song>,
+    /// the song being opened
+    #[serde(default)]
+    loading: Option<Box<Path>>,
+    position: Option<Duration>,
+    /// audio decoded ahead of the position
+    #[serde(default)]
+    buffered: Option<Duration>,
+    paused: bool,
+    tracks: Vec<TrackInfo>,
+    track_id: u32,
+    chapters: Vec<Chapter>,
+    queue: Box<[Box<Path>]>,
+    presets: Vec<String>,
+    preset: Option<String>,
+    crossfeed: bool,
+    random_albums: bool,
+    volume: f32,
+    #[serde(default)]
+    zones: Vec<(String, f32)>,
+    stats: Option<SongStats>,
+    #[serde(default)]
+    bit_perfect: bool,
+    #[serde(default)]
+    clipping: bool,
+    #[serde(default)]
+    preview: bool,
+}
+
+impl State {
+    fn new(facade: &PlayerFacade) -> Self {
+        let (tracks, track_id) = facade
+            .tracks()
+            .map(|(t, id)| (t.to_vec(), id))
+            .unwrap_or_default();
+
+        Self {
+            song: facade.current_song().cloned(),
+            loading: facade.loading().map(Into::into),
+            position: facade.playing_duration(),
+            buffered: facade.buffered(),
+            paused: facade.is_paused(),
+            tracks,
+            track_id,
+            chapters: facade.chapters().to_vec(),
+            queue: facade.queue.clone(),
+            presets: facade.presets.clone(),
+            preset: facade.preset.clone(),
+            crossfeed: facade.crossfeed,
+            random_albums: facade.random_albums,
+            volume: facade.volume,
+            zones: facade.zones.clone(),
+            stats: facade.stats,
+            bit_perfect: facade.bit_perfect,
+            clipping: facade.clipping,
+            preview: facade.preview,
+        }
+    }
+
+    fn into_facade(self, cover: Option<&Cover>) -> PlayerFacade {
+        let status = match self.song {
+            Some(song) => PlayerStatus::PlayingOrPaused {
+                song,
+                metadata: cover.map(|cover| {
+                    let mut builder = MetadataBuilder::new();
+                    builder.add_visual(Visual {
+                        media_type: cover.media_type.clone(),
+                        dimensions: None,
+                        bits_per_pixel: None,
+                        color_mode: None,
+                        usage: Some(StandardVisualKey::FrontCover),
+                        tags: vec![],
+                        data: cover.data.clone().into_boxed_slice(),
+                    });
+                    builder.metadata()
+                }),
+                tracks: self.tracks,
+                track_id: self.track_id,
+                chapters: self.chapters,
+                playing_duration: Arc::new(RwLock::new(self.position.unwrap_or_default())),
+                buffered: Arc::new(RwLock::new(self.buffered.unwrap_or_default())),
+                paused: Arc::new(AtomicBool::new(self.paused)),
+            },
+            None => match self.loading {
+                Some(path) => PlayerStatus::Loading { path },
+                None => PlayerStatus::Stopped,
+            },
+        };
+
+        PlayerFacade {
+            status,
+            queue: self.queue,
+            presets: self.presets,
+            preset: self.preset,
+            crossfeed: self.crossfeed,
+            random_albums: self.random_albums,
+            volume: self.volume,
+            zones: self.zones,
+            stats: self.stats,
+            bit_perfect: self.bit_perfect,
+            clipping: self.clipping,
+            preview: self.preview,
+        }
+    }
+}
+
+/// path of the socket the daemon listens on
+pub fn socket_path(config_dir: &Path) -> std::path::PathBuf {
+    config_dir.join("ramp.sock")
+}
+
+/// run the daemon until it is asked to exit: keep the player thread alive, collect notifications
+/// for the clients and answer their requests on the socket at `path`
+#[cfg(unix)]
+pub fn serve(
+    path: &Path,
+    cmd: mpsc::Sender<Command>,
+    player: Arc<RwLock<PlayerFacade>>,
+    mut supervisor: crate::player::supervisor::Supervisor,
+    notifications: mpsc::Receiver<Notification>,
+    terminate: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    use anyhow::Context;
+
+    if path.exists() {
+        anyhow::ensure!(
+            UnixStream::connect(path).is_err(),
+            "A daemon is already listening on {}",
+            path.display()
+        );
+        std::fs::remove_file(path).context("Failed to remove stale socket")?;
+    }
+
+    let listener = UnixListener::bind(path).context("Failed to bind socket")?;
+    info!("Listening on {}", path.display());
+
+    let pending = Arc::new(Mutex::new(VecDeque::new()));
+    let pending2 = pending.clone();
+    std::thread::Builder::new()
+        .name("ipc thread".to_string())
+        .spawn(move || {
+            for stream in listener.incoming() {
+                let stream = match stream {
+                    Ok(stream) => stream,
+                    Err(e) => {
+                        warn!("Failed to accept client: {e:?}");
+                        continue;
+                    }
+                };
+
+                let cmd = cmd.clone();
+                let player = player.clone();
+                let pending = pending2.clone();
+                std::thread::Builder::new()
+                    .name("ipc client thread".to_string())
+                    .spawn(move || {
+                        info!("Client connected");
+                        if let Err(e) = handle_client(stream, &cmd, &player, &pending) {
+                            warn!("Client failed: {e:?}");
+                        }
+                        info!("Client disconnected");
+                    })
+                    .map_err(|e| warn!("Failed to spawn ipc client thread: {e:?}"))
+                    .ok();
+            }
+        })
+        .context("Failed to spawn ipc thread")?;
+
+    while !terminate.load(std::sync::atomic::Ordering::Relaxed) {
+        supervisor.check();
+
+        let mut pending = pending.lock().unwrap();
+        for notification in notifications.try_iter() {
+            info!("Notification: {notification:?}");
+            pending.push_back(notification);
+        }
+        while pending.len() > MAX_PENDING_NOTIFICATIONS {
+            pending.pop_front();
+        }
+        drop(pending);
+
+        std::thread::sleep(POLL_INTERVAL);
+    }
+
+    info!("Stopped listening on {}", path.display());
+    std::fs::remove_file(path).context("Failed to remove socket")
+}
+
+#[cfg(not(unix))]
+pub fn serve(
+    _path: &Path,
+    _cmd: mpsc::Sender<Command>,
+    _player: Arc<RwLock<PlayerFacade>>,
+    _supervisor: crate::player::supervisor::Supervisor,
+    _notifications: mpsc::Receiver<Notification>,
+    _terminate: Arc<AtomicBool>,
+) -> anyhow::Result<()> {
+    anyhow::bail!("The daemon is only supported on unix")
+}
+
+#[cfg(unix)]
+fn handle_client(
+    stream: std::os::unix::net::UnixStream,
+    cmd: &mpsc::Sender<Command>,
+    player: &RwLock<PlayerFacade>,
+    pending: &Mutex<VecDeque<Notification>>,
+) -> anyhow::Result<()> {
+    use std::io::{BufRead, BufReader, Write};
+
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let response = match serde_json::from_str(&line?)? {
+            Request::Command(command) => {
+                cmd.send(command)?;
+                Response::Ok
+            }
+            Request::State => Response::State {
+                state: Box::new(State::new(&player.read().unwrap())),
+                notifications: pending.lock().unwrap().drain(..).collect(),
+            },
+            Request::Status => Response::State {
+                state: Box::new(State::new(&player.read().unwrap())),
+                notifications: vec![],
+            },
+            Request::Cover => {
+                let player = player.read().unwrap();
+                let cover = match &player.status {
+                    PlayerStatus::PlayingOrPaused {
+                        metadata: Some(metadata),
+                        ..
+                    } => metadata
+                        .visuals()
+                        .iter()
+                        .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
+                        .map(|v| Cover {
+                            media_type: v.media_type.clone(),
+                            data: v.data.to_vec(),
+                        }),
+                    _ => None,
+                };
+                Response::Cover(cover)
+            }
+        };
+
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// connect to the daemon at `path`, returning the same handles the tui gets from a local player
+pub fn connect(
+    path: &Path,
+    notifications: mpsc::Sender<Notification>,
+) -> anyhow::Result<(mpsc::Sender<Command>, Arc<RwLock<PlayerFacade>>)> {
+    #[cfg(unix)]
+    {
+        use anyhow::Context;
+
+        let mut connection = Connection::open(path).context(format!(
+            "Failed to connect to the daemon at {}",
+            path.display()
+        ))?;
+
+        let (tx, rx) = mpsc::channel();
+        let facade = Arc::new(RwLock::new(PlayerFacade::default()));
+        let facade2 = facade.clone();
+        let path = path.to_path_buf();
+        std::thread::Builder::new()
+            .name("ipc client thread".to_string())
+            .spawn(move || loop {
+                if let Err(e) = connection.sync(&rx, &facade2, &notifications) {
+                    warn!("Lost connection to the daemon: {e:?}");
+                    notifications
+                        .send(Notification::error("Lost connection to the daemon"))
+                        .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+
+                    // commands sent while disconnected are dropped
+                    connection = loop {
+                        std::thread::sleep(RECONNECT_INTERVAL);
+                        rx.try_iter().for_each(drop);
+                        if let Ok(connection) = Connection::open(&path) {
+                            info!("Reconnected to the daemon");
+                            break connection;
+                        }
+                    };
+                }
+            })
+            .context("Failed to spawn ipc client thread")?;
+
+        Ok((tx, facade))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, notifications);
+        anyhow::bail!("The daemon is only supported on unix")
+    }
+}
+
+/// the state of the daemon listening on `path`, without a cover
+pub fn status(path: &Path) -> anyhow::Result<PlayerFacade> {
+    #[cfg(unix)]
+    {
+        use anyhow::Context;
+
+        let mut connection = Connection::open(path).context(format!(
+            "Failed to connect to the daemon at {}",
+            path.display()
+        ))?;
+        match connection.request(&Request::Status)? {
+            Response::State { state, .. } => Ok(state.into_facade(None)),
+            _ => anyhow::bail!("Unexpected response to status request"),
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = path;
+        anyhow::bail!("The daemon is only supported on unix")
+    }
+}
+
+/// call `f` with the state of the daemon listening on `path` whenever it is polled, until `f` fails,
+/// while the daemon is not running `f` gets a stopped player
+pub fn watch_status(
+    path: &Path,
+    mut f: impl FnMut(&PlayerFacade) -> anyhow::Result<()>,
+) -> anyhow::Result<()> {
+    #[cfg(unix)]
+    loop {
+        let Ok(mut connection) = Connection::open(path) else {
+            f(&PlayerFacade::default())?;
+            std::thread::sleep(RECONNECT_INTERVAL);
+            continue;
+        };
+        while let Ok(Response::State { state, .. }) = connection.request(&Request::Status) {
+            f(&state.into_facade(None))?;
+            std::thread::sleep(POLL_INTERVAL);
+        }
+        info!("Lost connection to the daemon");
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (path, &mut f);
+        anyhow::bail!("The daemon is only supported on unix")
+    }
+}
+
+#[cfg(unix)]
+struct Connection {
+    reader: std::io::BufReader<std::os::unix::net::UnixStream>,
+    writer: std::os::unix::net::UnixStream,
+    /// path and cover of the song the cover was last fetched for
+    cover: Option<(Box<Path>, Option<Cover>)>,
+}
+
+#[cfg(unix)]
+impl Connection {
+    fn open(path: &Path) -> anyhow::Result<Self> {
+        let stream = std::os::unix::net::UnixStream::connect(path)?;
+
+        Ok(Self {
+            reader: std::io::BufReader::new(stream.try_clone()?),
+            writer: stream,
+            cover: None,
+        })
+    }
+
+    fn request(&mut self, request: &Request) -> anyhow::Result<Response> {
+        use std::io::{BufRead, Write};
+
+        serde_json::to_writer(&mut self.writer, request)?;
+        self.writer.write_all(b"\n")?;
+
+        let mut line = String::new();
+        anyhow::ensure!(
+            self.reader.read_line(&mut line)? > 0,
+            "Daemon closed the connection"
+        );
+
+        Ok(serde_json::from_str(&line)?)
+    }
+
+    /// forward the commands sent within one poll interval and fetch the new state
+    fn sync(
+        &mut self,
+        rx: &mpsc::Receiver<Command>,
+        facade: &RwLock<PlayerFacade>,
+        notifications: &mpsc::Sender<Notification>,
+    ) -> anyhow::Result<()> {
+        if let Ok(command) = rx.recv_timeout(POLL_INTERVAL) {
+            self.request(&Request::Command(command))?;
+            for command in rx.try_iter() {
+                self.request(&Request::Command(command))?;
+            }
+        }
+
+        let Response::State {
+            state,
+            notifications: new,
+        } = self.request(&Request::State)?
+        else {
+            anyhow::bail!("Unexpected response to state request");
+        };
+
+        let path = state.song.as_ref().map(|s| s.path.clone());
+        if path != self.cover.as_ref().map(|(p, _)| p.clone()) {
+            self.cover = match path {
+                Some(path) => match self.request(&Request::Cover)? {
+                    Response::Cover(cover) => Some((path, cover)),
+                    _ => anyhow::bail!("Unexpected response to cover request"),
+                },
+                None => None,
+            };
+        }
+
+        *facade.write().unwrap() =
+            state.into_facade(self.cover.as_ref().and_then(|(_, c)| c.as_ref()));
+
+        for notification in new {
+            notifications
+                .send(notification)
+                .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+        }
+
+        Ok(())
+    }
+}
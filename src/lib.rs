@@ -0,0 +1,32 @@
+//! the engine of ramp without the terminal ui: scanning the library into a cache,
+//! reading songs and their tags, and the player with its queue, dsp, stats and desktop integration
+//!
+//! ```no_run
+//! use std::sync::{mpsc, Arc, Mutex};
+//!
+//! use ramp_core::{cache::{Cache, SharedCache}, config::Config, player::{command::Command, Player}, stats::Stats};
+//!
+//! let config = Config::default_from_config_dir("/tmp/ramp");
+//! let cache = SharedCache::new(Cache::build_from_config(&config));
+//! let stats = Arc::new(Mutex::new(Stats::default()));
+//! let (notifications, _) = mpsc::channel();
+//!
+//! let (cmd, player, _supervisor) =
+//!     Player::run(cache, Arc::new(config), stats, notifications).unwrap();
+//! cmd.send(Command::Enqueue(std::path::Path::new("/music/song.flac").into())).unwrap();
+//! ```
+
+pub mod album;
+pub mod cache;
+pub mod collation;
+pub mod config;
+pub mod index;
+pub mod lyrics;
+pub mod notification;
+pub mod paths;
+pub mod player;
+pub mod query;
+pub mod scan;
+pub mod song;
+pub mod stats;
+pub mod tags;
@@ -0,0 +1,117 @@
+//! lyrics of songs, plain or synced with the timestamps of lrc files like `[01:23.45]`
+
+use std::time::Duration;
+
+use log::warn;
+
+use crate::song::{Song, StandardTagKey};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LyricsLine {
+    /// when the line is sung, `None` for plain lyrics
+    pub time: Option<Duration>,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Lyrics {
+    /// in the order they are sung
+    pub lines: Vec<LyricsLine>,
+    /// milliseconds the lines are shown earlier, from the `[offset:]` tag
+    pub offset: i64,
+}
+
+impl Lyrics {
+    /// the lyrics tag of `song`, or the lrc file with the name of the song next to it
+    pub fn of(song: &Song) -> Option<Self> {
+        if let Some(text) = song.tag_string(StandardTagKey::Lyrics) {
+            return Some(Self::parse(text));
+        }
+
+        let path = song.path.with_extension("lrc");
+        if !path.is_file() {
+            return None;
+        }
+        std::fs::read_to_string(&path)
+            .map(|text| Self::parse(&text))
+            .map_err(|e| warn!("Failed to read lyrics from {}: {e:?}", path.display()))
+            .ok()
+    }
+
+    /// parse lrc lyrics, lines can have several timestamps, the tags like `[ar:]` are skipped
+    /// except for `[offset:]`, text without any timestamps is read as plain lyrics
+    pub fn parse(text: &str) -> Self {
+        let mut lyrics = Self::default();
+        let mut plain = vec![];
+
+        for line in text.lines() {
+            let mut rest = line.trim();
+            let mut times = vec![];
+            let mut tag = false;
+            while let Some((group, after)) = rest.strip_prefix('[').and_then(|r| r.split_once(']'))
+            {
+                match timestamp(group) {
+                    Some(time) => times.push(time),
+                    None => {
+                        if let Some(offset) = group.strip_prefix("offset:") {
+                            lyrics.offset = offset.trim().parse().unwrap_or(0);
+                        }
+                        tag = true;
+                    }
+                }
+                rest = after;
+            }
+
+            let text = rest.trim().to_string();
+            match (times.is_empty(), tag) {
+                (false, _) => lyrics
+                    .lines
+                    .extend(times.into_iter().map(|time| LyricsLine {
+                        time: Some(time),
+                        text: text.clone(),
+                    })),
+                (true, false) => plain.push(LyricsLine { time: None, text }),
+                (true, true) => {}
+            }
+        }
+
+        match lyrics.lines.is_empty() {
+            true => lyrics.lines = plain,
+            false => lyrics.lines.sort_by_key(|l| l.time),
+        }
+        // blank lines at the end of plain lyrics
+        while lyrics.lines.last().is_some_and(|l| l.text.is_empty()) {
+            lyrics.lines.pop();
+        }
+
+        lyrics
+    }
+
+    pub fn synced(&self) -> bool {
+        self.lines.iter().any(|l| l.time.is_some())
+    }
+
+    /// the index of the line sung at `position` of synced lyrics, shown `offset` milliseconds earlier
+    /// in addition to the offset of the lyrics
+    pub fn current(&self, position: Duration, offset: i64) -> Option<usize> {
+        let shift = self.offset + offset;
+        let position = match shift >= 0 {
+            true => position + Duration::from_millis(shift as u64),
+            false => position.saturating_sub(Duration::from_millis(shift.unsigned_abs())),
+        };
+
+        self.lines
+            .iter()
+            .rposition(|l| l.time.is_some_and(|t| t <= position))
+    }
+}
+
+/// `mm:ss`, `mm:ss.xx` or `mm:ss:xx`
+fn timestamp(group: &str) -> Option<Duration> {
+    let (minutes, seconds) = group.split_once(':')?;
+    let minutes = minutes.parse::<u64>().ok()?;
+    let seconds = seconds.replacen(':', ".", 1).parse::<f64>().ok()?;
+    (0.0..60.0)
+        .contains(&seconds)
+        .then(|| Duration::from_secs(minutes * 60) + Duration::from_secs_f64(seconds))
+}
@@ -1,22 +1,68 @@
-use std::{fs::File, sync::Arc};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{atomic::AtomicBool, mpsc, Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
 
 use anyhow::Context;
-use cache::Cache;
+use cache::{Cache, SharedCache};
 use log::{info, trace, warn, LevelFilter};
+// the engine modules are imported at the crate root, so that `crate::cache` and friends resolve
+use ramp_core::{
+    album, cache, collation, config, lyrics, notification, paths, player, query, scan, song, stats,
+};
 use simplelog::{CombinedLogger, WriteLogger};
 
-use crate::{config::Config, player::Player, tui::tui};
+use crate::{
+    args::{Args, ReportFormat},
+    cache::CacheEntry,
+    config::Config,
+    inbox::Inbox,
+    ipc::socket_path,
+    notification::Notification,
+    party::Party,
+    player::{
+        command::Command,
+        facade::{PlayerFacade, PlayerStatus},
+        Player,
+    },
+    playlist::Playlists,
+    report::Report,
+    scan::Scanner,
+    scheduler::Scheduler,
+    stats::Stats,
+    tui::tui,
+};
 
-mod cache;
-mod config;
-mod player;
-mod song;
+/// longest ramp waits for the player to stop and write the tags when shutting down
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+mod args;
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+mod dbus_interface;
+mod import;
+mod inbox;
+mod ipc;
+mod now_playing;
+mod organize;
+mod party;
+mod playlist;
+mod report;
+mod scheduler;
+mod scripting;
 mod tui;
 
 fn main() -> anyhow::Result<()> {
-    let config_dir = dirs::config_dir()
+    let args = Args::parse()?;
+
+    let mut config_dir = dirs::config_dir()
         .expect("Unable to find config directory")
         .join("ramp");
+    if let Some(profile) = &args.profile {
+        config_dir = config_dir.join("profiles").join(profile);
+    }
 
     if !config_dir.exists() {
         std::fs::create_dir_all(&config_dir).unwrap_or_else(|e| {
@@ -24,10 +70,27 @@ fn main() -> anyhow::Result<()> {
         });
     }
 
+    // the setup is only shown when the tui would start
+    let first_run = !config_dir.join("config.json").exists();
+    let interactive = args.report.is_none()
+        && args.export.is_none()
+        && args.play.is_none()
+        && !args.daemon
+        && !args.attach
+        && !args.status;
+
     let config = Arc::new(
         Config::load(config_dir.join("config.json")).unwrap_or_else(|e| {
-            eprintln!("Failed to load config, using default: {e:?}");
-            let config = Config::default_from_config_dir(&config_dir);
+            let mut config = Config::default_from_config_dir(&config_dir);
+            if first_run && interactive {
+                match tui::setup(config.clone()) {
+                    Ok(Some(c)) => config = c,
+                    Ok(None) => {}
+                    Err(e) => eprintln!("Failed to run setup: {e:?}"),
+                }
+            } else {
+                eprintln!("Failed to load config, using default: {e:?}");
+            }
             config
                 .save(config_dir.join("config.json"))
                 .unwrap_or_else(|e| {
@@ -37,6 +100,43 @@ fn main() -> anyhow::Result<()> {
         }),
     );
 
+    if let Some(format) = args.report {
+        let stats = Stats::load(&config.stats_path).context("Failed to load stats")?;
+        let report = Report::new(stats.history());
+        match format {
+            ReportFormat::Csv => print!("{}", report.to_csv()),
+            ReportFormat::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+        }
+
+        return Ok(());
+    }
+
+    if args.status {
+        let socket = socket_path(&config_dir);
+        let template = args.format.as_deref().unwrap_or(&config.now_playing_format);
+        let line = |player: &PlayerFacade| match args.json {
+            true => now_playing::json(template, player),
+            false => now_playing::format(template, player),
+        };
+
+        if args.follow {
+            let mut printed = None;
+            ipc::watch_status(&socket, |player| {
+                let line = line(player)?;
+                if printed.as_ref() != Some(&line) {
+                    // fails once the status bar closed the pipe
+                    writeln!(std::io::stdout(), "{line}")?;
+                    printed = Some(line);
+                }
+                Ok(())
+            })?;
+        } else {
+            println!("{}", line(&ipc::status(&socket)?)?);
+        }
+
+        return Ok(());
+    }
+
     CombinedLogger::init(vec![WriteLogger::new(
         #[cfg(debug_assertions)]
         LevelFilter::Trace,
@@ -53,41 +153,362 @@ fn main() -> anyhow::Result<()> {
     .context("Failed to initialize logger")?;
     info!("Logger initialized");
 
+    if let Some((output, files)) = &args.export {
+        eprintln!("Exporting {} files to {}", files.len(), output.display());
+        player::export::export(
+            &Cache::default(),
+            &config,
+            files,
+            output,
+            config.dsp_preset.as_deref(),
+            config.crossfeed.enabled,
+        )?;
+
+        return Ok(());
+    }
+
+    player::set_media_role();
+
+    let play = args
+        .play
+        .as_ref()
+        .map(|path| paths::canonicalize(path).context(format!("Failed to open {}", path.display())))
+        .transpose()?;
+
+    let (notification_tx, notification_rx) = mpsc::channel();
+
+    let cache = match play {
+        Some(_) => {
+            info!("playing a single file, not loading the cache");
+            SharedCache::new(Cache::default())
+        }
+        // scanning a whole library takes a while, the tui starts with an empty one meanwhile
+        None if first_run => SharedCache::new(Cache::default()),
+        None => SharedCache::new(load_cache(&config)),
+    };
+    cache.watch_unavailable();
+    let scanner = Scanner::new(config.clone(), cache.clone(), notification_tx.clone());
+    match (first_run, play.is_none()) {
+        (true, true) => scanner.start(false),
+        (false, true) if cache.load().outdated(&config) > 0 => {
+            info!(
+                "{} songs have to be read again",
+                cache.load().outdated(&config)
+            );
+            scanner.start(false);
+        }
+        (false, true) if config.detect_tempo => scanner.start_tempo_detection(),
+        _ => {}
+    }
+
+    for problem in health_check(&config, play.is_none(), !args.attach) {
+        warn!("{problem}");
+        notification_tx
+            .send(Notification::error(problem))
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+    }
+
+    let stats = Stats::load(&config.stats_path).unwrap_or_else(|e| {
+        warn!("Failed to load stats: {e:?}, using default");
+        Stats::default()
+    });
+    let stats = Arc::new(Mutex::new(stats));
+
+    let socket = socket_path(&config_dir);
+    let (cmd, player, supervisor) = if args.attach {
+        trace!("attaching to daemon");
+        let (cmd, player) = ipc::connect(&socket, notification_tx.clone())?;
+        (cmd, player, None)
+    } else {
+        trace!("initializing player");
+        let (cmd, player, supervisor) = Player::run(
+            cache.clone(),
+            config.clone(),
+            stats.clone(),
+            notification_tx.clone(),
+        )
+        .context("Failed to initialize player")?;
+        (cmd, player, Some(supervisor))
+    };
+
+    if args.stdin {
+        trace!("enqueueing paths from stdin");
+        let mut songs = vec![];
+        for line in std::io::stdin().lines() {
+            let line = line.context("Failed to read stdin")?;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let path =
+                paths::canonicalize(Path::new(line.trim())).unwrap_or(PathBuf::from(line.trim()));
+            if let Ok(Some(CacheEntry::File { .. })) = cache.load().get(&path) {
+                songs.push(Command::Enqueue(path.into()));
+            } else {
+                warn!("{} from stdin is not in the library", path.display());
+                notification_tx
+                    .send(Notification::error(format!(
+                        "{} is not in the library",
+                        path.display()
+                    )))
+                    .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+            }
+        }
+        cmd.send(Command::Batch(songs))?;
+    }
+
+    if let Some(path) = &args.import {
+        trace!("importing queue");
+        match import::import(path, &cache.load()) {
+            Ok(import) => {
+                let message = match import.missing.len() {
+                    0 => format!("Imported {} songs", import.songs.len()),
+                    missing => format!(
+                        "Imported {} songs, {missing} are not in the library",
+                        import.songs.len()
+                    ),
+                };
+                notification_tx
+                    .send(Notification::info(message))
+                    .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+                cmd.send(Command::Batch(
+                    import
+                        .songs
+                        .into_iter()
+                        .map(|p| Command::Enqueue(p.into()))
+                        .collect(),
+                ))?;
+            }
+            Err(e) => {
+                warn!("Failed to import queue: {e:?}");
+                notification_tx
+                    .send(Notification::error(format!("{e:#}")))
+                    .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+            }
+        }
+    }
+
+    if let Some(path) = play.clone() {
+        cmd.send(Command::Enqueue(path.into()))?;
+    }
+
+    trace!("starting scheduler");
+    let scheduler = Scheduler::start(
+        // the alarm already goes off in the daemon
+        match args.attach {
+            true => Arc::new(Config {
+                alarm: None,
+                ..Config::clone(&config)
+            }),
+            false => config.clone(),
+        },
+        cache.clone(),
+        cmd.clone(),
+        notification_tx.clone(),
+    );
+
+    // scripts run next to the player, so the daemon keeps them when no tui is attached
+    let scripts = match args.attach {
+        true => Default::default(),
+        false => {
+            trace!("loading scripts");
+            scripting::start(
+                config.clone(),
+                cache.clone(),
+                cmd.clone(),
+                player.clone(),
+                notification_tx.clone(),
+            )
+        }
+    };
+
+    #[cfg(all(feature = "mpris", target_os = "linux"))]
+    if !args.attach {
+        trace!("starting d-bus interface");
+        dbus_interface::start(cache.clone(), cmd.clone(), player.clone());
+    }
+
+    if !args.attach {
+        trace!("writing now playing file");
+        now_playing::watch(config.clone(), player.clone(), notification_tx.clone());
+    }
+
+    let terminate = terminate_on_signals();
+
+    if args.daemon {
+        info!("running as daemon");
+        let supervisor = supervisor.context("The daemon has no player")?;
+        let result = ipc::serve(
+            &socket,
+            cmd.clone(),
+            player.clone(),
+            supervisor,
+            notification_rx,
+            terminate,
+        )
+        .context("Error in daemon");
+        shutdown(&cmd, &player);
+        now_playing::clear(&config);
+        save_cache(&config, &cache, play.is_some());
+        return result;
+    }
+
+    trace!("watching inbox");
+    let inbox = Inbox::watch(&config, notification_tx.clone());
+
+    trace!("watching playlists");
+    let playlists = Playlists::watch(&config, notification_tx.clone());
+
+    trace!("serving party page");
+    let party = Party::serve(&config, cache.clone(), notification_tx.clone());
+
+    trace!("entering tui");
+    let attached = supervisor.is_none();
+    let result = tui(
+        config.clone(),
+        cache.clone(),
+        cmd.clone(),
+        player.clone(),
+        notification_tx,
+        notification_rx,
+        supervisor,
+        scripts,
+        inbox,
+        playlists,
+        party,
+        scheduler,
+        stats,
+        scanner,
+        terminate,
+    )
+    .context("Error in tui");
+    trace!("tui exited");
+
+    // an attached tui leaves the player to the daemon
+    if !attached {
+        shutdown(&cmd, &player);
+        now_playing::clear(&config);
+    }
+    save_cache(&config, &cache, play.is_some());
+
+    result
+}
+
+/// a flag that is set when ramp is asked to exit by SIGINT or SIGTERM, or by SIGHUP when its terminal is closed,
+/// so that the tui and the daemon return and ramp shuts down cleanly
+fn terminate_on_signals() -> Arc<AtomicBool> {
+    let terminate = Arc::new(AtomicBool::new(false));
+
+    #[cfg(unix)]
+    let signals = [
+        signal_hook::consts::SIGINT,
+        signal_hook::consts::SIGTERM,
+        signal_hook::consts::SIGHUP,
+    ];
+    #[cfg(not(unix))]
+    let signals = [signal_hook::consts::SIGINT, signal_hook::consts::SIGTERM];
+
+    for signal in signals {
+        signal_hook::flag::register(signal, terminate.clone())
+            .map_err(|e| warn!("Failed to handle signal {signal}: {e:?}"))
+            .ok();
+    }
+
+    terminate
+}
+
+/// stop the player so that the listen of the current song is counted and its tags are written,
+/// waiting until it stopped or `SHUTDOWN_TIMEOUT` passed
+fn shutdown(cmd: &mpsc::Sender<Command>, player: &RwLock<PlayerFacade>) {
+    info!("Shutting down");
+    if cmd.send(Command::Shutdown).is_err() {
+        warn!("Failed to shut down the player, it already exited");
+        return;
+    }
+
+    let start = Instant::now();
+    while !matches!(player.read().unwrap().status, PlayerStatus::Stopped) {
+        if start.elapsed() > SHUTDOWN_TIMEOUT {
+            warn!("The player did not stop in time");
+            return;
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
+
+/// save the cache if it changed, the cache of a single file must not overwrite the library
+fn save_cache(config: &Config, cache: &SharedCache, single_file: bool) {
+    let cache = cache.load();
+    if !single_file && cache.changed() {
+        trace!("saving changed cache");
+        cache
+            .save(config)
+            .unwrap_or_else(|e| warn!("Failed to save cache {e:?}"));
+    }
+}
+
+/// problems of the configuration that leave the library empty or the player silent,
+/// checking the library and the output device only if they are used
+fn health_check(config: &Config, library: bool, device: bool) -> Vec<String> {
+    let mut problems = vec![];
+
+    if library {
+        if config.search_directories.is_empty() {
+            problems.push("No search_directories configured, the library is empty".to_string());
+        }
+        if config.extensions.is_empty() {
+            problems.push("No extensions configured, the library is empty".to_string());
+        }
+        problems.extend(
+            config
+                .search_directories
+                .iter()
+                .filter(|d| !d.is_dir() && !config.offline_tolerant_directories.contains(d))
+                .map(|d| format!("Search directory {} does not exist", d.display())),
+        );
+    }
+
+    if device && player::sink::output_device(config.output_device.as_deref()).is_none() {
+        problems.push(match &config.output_device {
+            Some(name) => format!("Output device {name} not found, using the default device"),
+            None => "No output device found".to_string(),
+        });
+    }
+
+    problems
+}
+
+/// load the cache, rebuilding it if it is missing or the library configuration changed
+fn load_cache(config: &Arc<Config>) -> Cache {
     trace!("loading cache");
-    let (cache, old_config) = Cache::load(&config).unwrap_or_else(|e| {
+    let (cache, old_config) = Cache::load(config).unwrap_or_else(|e| {
         warn!("Failed to load cache: {e:?}, using default");
 
-        let cache = Cache::build_from_config(&config);
+        let cache = Cache::build_from_config(config);
 
         trace!("saving cache");
         cache
-            .save(&config)
+            .save(config)
             .unwrap_or_else(|e| warn!("Failed to save cache {e:?}"));
 
-        (cache, (*config).clone())
+        (cache, Config::clone(config))
     });
 
     let mut cache = if config.search_directories != old_config.search_directories
         || config.extensions != old_config.extensions
     {
         info!("config changed, rebuilding");
-        let cache = Cache::build_from_config(&config);
+        let cache = Cache::build_from_config(config);
         cache
-            .save(&config)
+            .save(config)
             .unwrap_or_else(|e| warn!("Failed to save cache {e:?}"));
         cache
     } else {
         cache
     };
-    cache.validate();
-    let cache = Arc::new(cache);
-
-    trace!("initializing player");
-    let (cmd, player) = Player::run(cache.clone()).context("Failed to initialize player")?;
-
-    trace!("entering tui");
-    tui(config.clone(), cache.clone(), cmd, player).context("Error in tui")?;
-    trace!("tui exited");
+    cache.validate(config);
+    trace!("building search index");
+    cache.build_index();
 
-    Ok(())
+    cache
 }
@@ -0,0 +1,28 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum Level {
+    Info,
+    Error,
+}
+
+/// a message from any subsystem to be shown to the user in the tui
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Notification {
+    pub level: Level,
+    pub message: String,
+}
+
+impl Notification {
+    pub fn info<S: Into<String>>(message: S) -> Self {
+        Self {
+            level: Level::Info,
+            message: message.into(),
+        }
+    }
+
+    pub fn error<S: Into<String>>(message: S) -> Self {
+        Self {
+            level: Level::Error,
+            message: message.into(),
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! a compact line about the current song for tmux status lines and shell prompts,
+//! kept up to date in `now_playing_file` and printed by `ramp status`
+
+use std::{
+    sync::{mpsc, Arc, RwLock},
+    time::Duration,
+};
+
+use log::warn;
+use serde::Serialize;
+
+use crate::{
+    config::Config, notification::Notification, player::facade::PlayerFacade, song::StandardTagKey,
+    tui::format_duration,
+};
+
+/// interval the now playing file is updated in
+const INTERVAL: Duration = Duration::from_secs(1);
+
+/// replace the `{name}` placeholders of `template` with the state of `player`,
+/// empty while nothing is playing
+pub fn format(template: &str, player: &PlayerFacade) -> anyhow::Result<String> {
+    let song = player.current_song();
+    let mut result = String::new();
+
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or(anyhow::anyhow!("Unclosed placeholder in {template}"))?
+            + start;
+
+        let tag = |key| {
+            song.and_then(|s| s.tag_string(key))
+                .unwrap_or_default()
+                .to_string()
+        };
+        let value = match &rest[start + 1..end] {
+            "state" => match player.is_paused() {
+                true => "⏸".to_string(),
+                false => "▶".to_string(),
+            },
+            "artist" => tag(StandardTagKey::Artist),
+            "album" => tag(StandardTagKey::Album),
+            // untagged songs are shown by their file name
+            "title" => song
+                .and_then(|s| {
+                    s.tag_string(StandardTagKey::TrackTitle)
+                        .map(str::to_string)
+                        .or_else(|| s.path.file_stem().map(|f| f.to_string_lossy().to_string()))
+                })
+                .unwrap_or_default(),
+            "position" => format_duration(player.playing_duration().unwrap_or_default()),
+            "duration" => format_duration(song.map(|s| s.duration).unwrap_or_default()),
+            "volume" => format!("{:.0}%", player.volume * 100.0),
+            name => anyhow::bail!("Unknown placeholder {{{name}}}"),
+        };
+        result.push_str(&value);
+
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    // the template is checked even while nothing is playing
+    Ok(match song {
+        Some(_) => result,
+        None => String::new(),
+    })
+}
+
+/// the status printed by `ramp status --json`, with the fields of waybar's custom modules
+/// and the song for other status bars
+#[derive(Debug, Serialize)]
+struct Json {
+    /// the now playing line
+    text: String,
+    /// artist, title and album on separate lines
+    tooltip: String,
+    /// `playing`, `paused` or `stopped`, for styling the module
+    class: &'static str,
+    alt: &'static str,
+    /// the played part of the song in percent
+    percentage: u8,
+    title: Option<String>,
+    artist: Option<String>,
+    album: Option<String>,
+    /// in seconds
+    position: u64,
+    duration: u64,
+}
+
+/// the status of `player` as one line of json, with `template` for the text
+pub fn json(template: &str, player: &PlayerFacade) -> anyhow::Result<String> {
+    let song = player.current_song();
+    let class = match (song, player.is_paused()) {
+        (None, _) => "stopped",
+        (Some(_), true) => "paused",
+        (Some(_), false) => "playing",
+    };
+    let tag = |key| song.and_then(|s| s.tag_string(key)).map(str::to_string);
+    let position = player.playing_duration().unwrap_or_default();
+    let duration = song.map(|s| s.duration).unwrap_or_default();
+
+    let (title, artist, album) = (
+        tag(StandardTagKey::TrackTitle),
+        tag(StandardTagKey::Artist),
+        tag(StandardTagKey::Album),
+    );
+    let tooltip = [&artist, &title, &album]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let json = Json {
+        text: format(template, player)?,
+        tooltip,
+        class,
+        alt: class,
+        percentage: match duration.is_zero() {
+            true => 0,
+            false => (position.as_secs_f64() / duration.as_secs_f64() * 100.0).min(100.0) as u8,
+        },
+        title,
+        artist,
+        album,
+        position: position.as_secs(),
+        duration: duration.as_secs(),
+    };
+
+    Ok(serde_json::to_string(&json)?)
+}
+
+/// keep `now_playing_file` up to date in a thread, if one is configured
+pub fn watch(
+    config: Arc<Config>,
+    player: Arc<RwLock<PlayerFacade>>,
+    notifications: mpsc::Sender<Notification>,
+) {
+    let Some(path) = config.now_playing_file.clone() else {
+        return;
+    };
+    if let Err(e) = format(&config.now_playing_format, &PlayerFacade::default()) {
+        warn!("Invalid now_playing_format: {e:?}");
+        notifications
+            .send(Notification::error(format!(
+                "Invalid now_playing_format: {e}"
+            )))
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+        return;
+    }
+
+    std::thread::Builder::new()
+        .name("now playing thread".to_string())
+        .spawn(move || {
+            let mut written = None;
+            loop {
+                let line =
+                    format(&config.now_playing_format, &player.read().unwrap()).unwrap_or_default();
+                if written.as_ref() != Some(&line) {
+                    match std::fs::write(&path, &line) {
+                        Ok(()) => written = Some(line),
+                        Err(e) => warn!("Failed to write {}: {e:?}", path.display()),
+                    }
+                }
+
+                std::thread::sleep(INTERVAL);
+            }
+        })
+        .map_err(|e| warn!("Failed to spawn now playing thread: {e:?}"))
+        .ok();
+}
+
+/// empty `now_playing_file` when ramp exits, so that it does not show a song that stopped
+pub fn clear(config: &Config) {
+    if let Some(path) = &config.now_playing_file {
+        std::fs::write(path, "")
+            .unwrap_or_else(|e| warn!("Failed to clear {}: {e:?}", path.display()));
+    }
+}
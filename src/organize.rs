@@ -0,0 +1,148 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+use log::info;
+
+use crate::{
+    cache::SharedCache,
+    config::Config,
+    song::{Song, StandardTagKey},
+};
+
+/// move the song at `path` to the trash and remove it from the cache
+pub fn trash(cache: &SharedCache, path: &Path) -> anyhow::Result<()> {
+    trash::delete(path).context("Failed to move song to the trash")?;
+    info!("Moved {} to the trash", path.display());
+
+    cache.update(|c| c.remove_file(path))
+}
+
+/// where `song` is moved to by `rename`, the rename pattern applied inside the search directory of the song
+pub fn destination(config: &Config, song: &Song) -> anyhow::Result<PathBuf> {
+    let library = config
+        .search_directories
+        .iter()
+        .find(|d| song.path.starts_with(d))
+        .ok_or(anyhow::anyhow!(
+            "{} is not in a search directory",
+            song.path.display()
+        ))?;
+
+    let mut name = apply_pattern(&config.rename_pattern, song)?;
+    if let Some(extension) = song.path.extension() {
+        name.push('.');
+        name.push_str(&extension.to_string_lossy());
+    }
+
+    Ok(library.join(name))
+}
+
+/// move the song at `path` according to the rename pattern and update its cache entry,
+/// returning the new path
+pub fn rename(cache: &SharedCache, config: &Config, path: &Path) -> anyhow::Result<PathBuf> {
+    let song = match cache.load().get(path)? {
+        Some(entry) => entry.as_file()?.clone(),
+        None => anyhow::bail!("{} is not in the cache", path.display()),
+    };
+
+    let destination = destination(config, &song)?;
+    if destination == path {
+        return Ok(destination);
+    }
+    anyhow::ensure!(
+        !destination.exists(),
+        "{} already exists",
+        destination.display()
+    );
+
+    if let Some(parent) = destination.parent() {
+        std::fs::create_dir_all(parent).context("Failed to create directory")?;
+    }
+    std::fs::rename(path, &destination).context("Failed to move song")?;
+    info!("Moved {} to {}", path.display(), destination.display());
+
+    // remove directories left empty, up to the search directory
+    for dir in path.ancestors().skip(1) {
+        if config.search_directories.iter().any(|d| d == dir) || std::fs::remove_dir(dir).is_err() {
+            break;
+        }
+    }
+
+    cache.update(|c| {
+        c.remove_file(path)?;
+        c.insert_file(
+            &destination,
+            Song {
+                path: destination.clone().into(),
+                ..song
+            },
+        )
+    })?;
+
+    Ok(destination)
+}
+
+/// replace the `{tag}` placeholders of `pattern` with the tags of `song`, `{tag:0N}` pads numbers to N digits
+fn apply_pattern(pattern: &str, song: &Song) -> anyhow::Result<String> {
+    let mut result = String::new();
+
+    let mut rest = pattern;
+    while let Some(start) = rest.find('{') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .ok_or(anyhow::anyhow!("Unclosed placeholder in {pattern}"))?
+            + start;
+
+        let placeholder = &rest[start + 1..end];
+        let (name, width) = match placeholder.split_once(":0") {
+            Some((name, width)) => (
+                name,
+                width
+                    .parse::<usize>()
+                    .context(format!("Invalid width in {{{placeholder}}}"))?,
+            ),
+            None => (placeholder, 0),
+        };
+
+        let key = match name {
+            "artist" => StandardTagKey::Artist,
+            "album_artist" => StandardTagKey::AlbumArtist,
+            "album" => StandardTagKey::Album,
+            "title" => StandardTagKey::TrackTitle,
+            "track" => StandardTagKey::TrackNumber,
+            "disc" => StandardTagKey::DiscNumber,
+            "year" => StandardTagKey::Date,
+            _ => anyhow::bail!("Unknown placeholder {{{name}}}"),
+        };
+        let mut value = song
+            .standard_tags
+            .get(&key)
+            .map(|v| v.to_string())
+            .filter(|v| !v.trim().is_empty())
+            .ok_or(anyhow::anyhow!("The song has no {name} tag"))?;
+
+        match key {
+            // "3/12" style numbers
+            StandardTagKey::TrackNumber | StandardTagKey::DiscNumber => {
+                value = value.split('/').next().unwrap_or_default().to_string();
+            }
+            StandardTagKey::Date => value = value.chars().take(4).collect(),
+            _ => {}
+        }
+        result.push_str(&format!("{:0>width$}", sanitize(value.trim())));
+
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// make a tag value usable as a single path component
+fn sanitize(value: &str) -> String {
+    match value {
+        "." | ".." => "_".to_string(),
+        _ => value.replace(['/', '\\', '\0'], "_"),
+    }
+}
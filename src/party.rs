@@ -0,0 +1,417 @@
+use std::{
+    collections::HashMap,
+    hash::{BuildHasher, RandomState},
+    io::{BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc, Arc, RwLock,
+    },
+    time::Duration,
+};
+
+use log::{info, warn};
+
+use crate::{
+    cache::{CacheEntry, SharedCache},
+    config::Config,
+    notification::Notification,
+    query::Query,
+    song::{Song, StandardTagKey},
+};
+
+/// guests waiting longer than this for an answer are disconnected
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// longest request line and headers accepted together, browsers send far less
+const MAX_HEAD: u64 = 8192;
+
+/// longest request body accepted, a request form is far smaller
+const MAX_BODY: usize = 4096;
+
+/// guests served at the same time, further connections are closed right away
+const MAX_CONNECTIONS: usize = 32;
+
+/// search results shown on the page
+const MAX_RESULTS: usize = 50;
+
+/// requests waiting for the host, further ones are refused
+const MAX_PENDING: usize = 100;
+
+/// a song requested by a guest
+#[derive(Debug, Clone)]
+pub struct Request {
+    pub path: PathBuf,
+    pub song: Song,
+    pub guest: String,
+}
+
+/// the party page guests search the library and request songs on, the host approves them in the requests tab
+#[derive(Clone)]
+pub struct Party {
+    requests: Arc<RwLock<Vec<Request>>>,
+    notifications: mpsc::Sender<Notification>,
+    /// keys the ids guests request songs by, so that the page does not show paths of the host
+    ids: RandomState,
+}
+
+impl Party {
+    /// serve the party page on `party_address`, if one is configured
+    pub fn serve(
+        config: &Config,
+        cache: SharedCache,
+        notifications: mpsc::Sender<Notification>,
+    ) -> Option<Self> {
+        let address = config.party_address.as_ref()?;
+        let listener = match TcpListener::bind(address) {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!("Failed to serve the party page on {address}: {e:?}");
+                notifications
+                    .send(Notification::error(format!(
+                        "Failed to serve the party page on {address}: {e}"
+                    )))
+                    .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+                return None;
+            }
+        };
+        info!("Serving the party page on {address}");
+
+        let party = Self {
+            requests: Arc::default(),
+            notifications,
+            ids: RandomState::new(),
+        };
+
+        let server = party.clone();
+        let connections = Arc::new(AtomicUsize::new(0));
+        std::thread::Builder::new()
+            .name("party thread".to_string())
+            .spawn(move || {
+                for stream in listener.incoming() {
+                    let stream = match stream {
+                        Ok(stream) => stream,
+                        Err(e) => {
+                            warn!("Failed to accept guest: {e:?}");
+                            continue;
+                        }
+                    };
+                    if connections.fetch_add(1, Ordering::Relaxed) >= MAX_CONNECTIONS {
+                        connections.fetch_sub(1, Ordering::Relaxed);
+                        warn!("Too many guests connected, closing the connection");
+                        continue;
+                    }
+
+                    // a slow guest must not hold up the others
+                    let (server, cache, served) =
+                        (server.clone(), cache.clone(), connections.clone());
+                    std::thread::Builder::new()
+                        .name("party guest thread".to_string())
+                        .spawn(move || {
+                            if let Err(e) = server.handle(stream, &cache) {
+                                warn!("Error while serving the party page: {e:?}");
+                            }
+                            served.fetch_sub(1, Ordering::Relaxed);
+                        })
+                        .map_err(|e| {
+                            warn!("Failed to spawn party guest thread: {e:?}");
+                            connections.fetch_sub(1, Ordering::Relaxed);
+                        })
+                        .ok();
+                }
+            })
+            .map_err(|e| warn!("Failed to spawn party thread: {e:?}"))
+            .ok()?;
+
+        Some(party)
+    }
+
+    pub fn requests(&self) -> Vec<Request> {
+        self.requests.read().unwrap().clone()
+    }
+
+    /// remove the request of `path`, after it was enqueued or rejected
+    pub fn dismiss(&self, path: &Path) {
+        self.requests.write().unwrap().retain(|r| r.path != path);
+    }
+
+    fn notify(&self, notification: Notification) {
+        self.notifications
+            .send(notification)
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+    }
+
+    fn handle(&self, stream: TcpStream, cache: &SharedCache) -> anyhow::Result<()> {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        // a line without an end must not be buffered without limit
+        let mut reader = BufReader::new(&stream).take(MAX_HEAD);
+
+        let mut line = String::new();
+        reader.read_line(&mut line)?;
+        if reader.limit() == 0 {
+            return respond(&stream, "414 URI Too Long", "Too long");
+        }
+        let mut parts = line.split_whitespace();
+        let (method, target) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+
+        let mut length = 0;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+                break;
+            }
+            if reader.limit() == 0 {
+                return respond(&stream, "431 Request Header Fields Too Large", "Too large");
+            }
+            if let Some((name, value)) = header.split_once(':') {
+                if name.trim().eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse::<usize>()?.min(MAX_BODY);
+                }
+            }
+        }
+        reader.set_limit(length as u64);
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+
+        let (path, query) = target.split_once('?').unwrap_or((target, ""));
+        let response = match (method, path) {
+            ("GET", "/") => {
+                let query = form(query);
+                let (Some(search), guest) = (query.get("q"), query.get("guest")) else {
+                    return respond(&stream, "200 OK", &page("", "", None, &[]));
+                };
+                let guest = guest.map_or("", String::as_str);
+                let message = query.get("requested").map(|r| match r.as_str() {
+                    "ok" => "Requested, the host will have a look",
+                    _ => "Sorry, the request did not go through, too many are waiting",
+                });
+                page(search, guest, message, &self.search(cache, search))
+            }
+            ("POST", "/request") => {
+                let body = form(&String::from_utf8_lossy(&body));
+                let guest = body.get("guest").map_or("", String::as_str);
+                let search = body.get("q").map_or("", String::as_str);
+                let requested = match body.get("song") {
+                    Some(id) => self.request(cache, id, guest),
+                    None => false,
+                };
+                let location = format!(
+                    "/?q={}&guest={}&requested={}",
+                    encode(search),
+                    encode(guest),
+                    if requested { "ok" } else { "failed" }
+                );
+                return write!(
+                    &stream,
+                    "HTTP/1.0 303 See Other\r\nLocation: {location}\r\nContent-Length: 0\r\n\r\n"
+                )
+                .map_err(Into::into);
+            }
+            _ => return respond(&stream, "404 Not Found", "Not found"),
+        };
+
+        respond(&stream, "200 OK", &response)
+    }
+
+    /// the id of the song at `path` on the page, the same for the lifetime of the party
+    fn id(&self, path: &Path) -> String {
+        format!("{:016x}", self.ids.hash_one(path))
+    }
+
+    /// the songs whose title, artist, album or file name contain the text of `search`
+    /// and that fulfill its predicates, sorted by path, with their id and description
+    fn search(&self, cache: &SharedCache, search: &str) -> Vec<(String, String)> {
+        let cache = cache.load();
+        let Ok(query) = Query::parse(search) else {
+            return vec![];
+        };
+        if search.trim().is_empty() {
+            return vec![];
+        }
+
+        let text = query.text.to_lowercase();
+        let contains = |s: &str| s.to_lowercase().contains(&text);
+        let mut songs = cache
+            .songs()
+            .filter(|(s, p)| query.matches(s) && cache.is_available(p))
+            .filter(|(s, p)| {
+                [
+                    StandardTagKey::TrackTitle,
+                    StandardTagKey::Artist,
+                    StandardTagKey::Album,
+                ]
+                .iter()
+                .filter_map(|k| s.tag_string(*k))
+                .any(contains)
+                    || p.file_name().and_then(|f| f.to_str()).is_some_and(contains)
+            })
+            .map(|(s, p)| (p, describe(s)))
+            .collect::<Vec<_>>();
+        songs.sort();
+        songs.truncate(MAX_RESULTS);
+
+        songs
+            .into_iter()
+            .map(|(p, name)| (self.id(&p), name))
+            .collect()
+    }
+
+    /// add a request for the song with `id` if it is in the library, false if there are too many
+    fn request(&self, cache: &SharedCache, id: &str, guest: &str) -> bool {
+        let cache = cache.load();
+        let Some(path) = cache
+            .songs()
+            .map(|(_, p)| p)
+            .find(|p| self.id(p) == id && cache.is_available(p))
+        else {
+            return false;
+        };
+        let Ok(Some(CacheEntry::File { song })) = cache.get(&path) else {
+            return false;
+        };
+
+        let mut requests = self.requests.write().unwrap();
+        if requests.iter().any(|r| r.path == path) {
+            return true;
+        }
+        if requests.len() >= MAX_PENDING {
+            return false;
+        }
+
+        let guest = match guest.trim() {
+            "" => "A guest".to_string(),
+            guest => guest.chars().take(30).collect(),
+        };
+        info!("{guest} requested {}", path.display());
+        self.notify(Notification::info(format!(
+            "🎉 {guest} requested {}",
+            describe(song)
+        )));
+        requests.push(Request {
+            path,
+            song: song.clone(),
+            guest,
+        });
+
+        true
+    }
+}
+
+/// "artist - title", or the file name without tags
+fn describe(song: &Song) -> String {
+    match (
+        song.tag_string(StandardTagKey::Artist),
+        song.tag_string(StandardTagKey::TrackTitle),
+    ) {
+        (Some(artist), Some(title)) => format!("{artist} - {title}"),
+        (None, Some(title)) => title.to_string(),
+        _ => song
+            .path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default(),
+    }
+}
+
+fn respond(mut stream: &TcpStream, status: &str, body: &str) -> anyhow::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.0 {status}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    )?;
+    Ok(())
+}
+
+/// the search form and the results with a request button each
+fn page(search: &str, guest: &str, message: Option<&str>, results: &[(String, String)]) -> String {
+    let (search, guest) = (escape(search), escape(guest));
+    let mut html = format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>ramp party</title>
+<style>
+body {{ font-family: sans-serif; max-width: 40em; margin: auto; padding: 1em; background: #222; color: #ddd; }}
+input, button {{ font-size: 1em; padding: 0.4em; }}
+li {{ display: flex; justify-content: space-between; align-items: center; padding: 0.3em 0; }}
+</style>
+</head>
+<body>
+<h1>🎉 Request a song</h1>
+<form method="get" action="/">
+<input name="guest" placeholder="Your name" value="{guest}">
+<input name="q" placeholder="Artist, title or album" value="{search}" autofocus>
+<button>Search</button>
+</form>
+"#
+    );
+
+    if let Some(message) = message {
+        html.push_str(&format!("<p><b>{}</b></p>\n", escape(message)));
+    }
+    if !search.is_empty() && results.is_empty() {
+        html.push_str("<p>Nothing found</p>\n");
+    }
+
+    html.push_str("<ul>\n");
+    for (id, name) in results {
+        html.push_str(&format!(
+            r#"<li>{}<form method="post" action="/request"><input type="hidden" name="song" value="{id}"><input type="hidden" name="guest" value="{guest}"><input type="hidden" name="q" value="{search}"><button>Request</button></form></li>
+"#,
+            escape(name),
+        ));
+    }
+    html.push_str("</ul>\n</body>\n</html>\n");
+
+    html
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// the fields of a url encoded form or query string
+fn form(s: &str) -> HashMap<String, String> {
+    s.split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .map(|(k, v)| (decode(k), decode(v)))
+        .collect()
+}
+
+fn decode(s: &str) -> String {
+    let mut bytes = vec![];
+    let mut input = s.bytes();
+    while let Some(b) = input.next() {
+        match b {
+            b'+' => bytes.push(b' '),
+            b'%' => {
+                let hex = [input.next(), input.next()];
+                let hex = hex.iter().flatten().map(|&b| b as char).collect::<String>();
+                match u8::from_str_radix(&hex, 16) {
+                    Ok(b) => bytes.push(b),
+                    Err(_) => bytes.extend(format!("%{hex}").bytes()),
+                }
+            }
+            b => bytes.push(b),
+        }
+    }
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+fn encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
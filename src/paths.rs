@@ -0,0 +1,77 @@
+//! platform independent handling of paths, for the cache keys and the file browser,
+//! on windows the root of a path is a drive like `C:\` or a share like `\\server\share\`
+
+use std::{
+    ffi::OsString,
+    path::{Component, Path, PathBuf, Prefix, MAIN_SEPARATOR_STR},
+};
+
+/// the components of `path` as strings, with the prefix and the root directory as one component,
+/// e.g. `C:\Music\a.flac` is `["C:\", "Music", "a.flac"]` and `/music/a.flac` is `["/", "music", "a.flac"]`,
+/// verbatim prefixes like `\\?\C:` are written as their usual form
+pub fn components(path: &Path) -> anyhow::Result<Vec<String>> {
+    let to_str = |s: &std::ffi::OsStr| {
+        s.to_str().map(str::to_string).ok_or(anyhow::anyhow!(
+            "Failed to convert OsString to str: {}",
+            path.display()
+        ))
+    };
+
+    let mut components = vec![];
+    for component in path.components() {
+        match component {
+            Component::Prefix(prefix) => components.push(match prefix.kind() {
+                Prefix::VerbatimDisk(disk) | Prefix::Disk(disk) => format!("{}:", disk as char),
+                Prefix::VerbatimUNC(server, share) | Prefix::UNC(server, share) => {
+                    format!(r"\\{}\{}", to_str(server)?, to_str(share)?)
+                }
+                _ => to_str(prefix.as_os_str())?,
+            }),
+            Component::RootDir => match components.last_mut() {
+                Some(prefix) => prefix.push_str(MAIN_SEPARATOR_STR),
+                None => components.push(MAIN_SEPARATOR_STR.to_string()),
+            },
+            component => components.push(to_str(component.as_os_str())?),
+        }
+    }
+
+    Ok(components)
+}
+
+/// `path` with the verbatim prefix `std::fs::canonicalize` adds on windows turned into its usual form,
+/// so that it matches the paths of the library
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut components = path.components();
+    let Some(Component::Prefix(prefix)) = components.next() else {
+        return path.to_path_buf();
+    };
+
+    let mut normalized = match prefix.kind() {
+        Prefix::VerbatimDisk(disk) => OsString::from(format!("{}:", disk as char)),
+        Prefix::VerbatimUNC(server, share) => {
+            let mut unc = OsString::from(r"\\");
+            unc.push(server);
+            unc.push(r"\");
+            unc.push(share);
+            unc
+        }
+        _ => return path.to_path_buf(),
+    };
+    // the rest starts with the root directory
+    normalized.push(components.as_path());
+
+    PathBuf::from(normalized)
+}
+
+/// the absolute form of `path` without the verbatim prefix of windows, see `normalize`
+pub fn canonicalize(path: &Path) -> std::io::Result<PathBuf> {
+    std::fs::canonicalize(path).map(|p| normalize(&p))
+}
+
+/// the root of the file system the current directory is on, `/` on unix
+pub fn root() -> PathBuf {
+    std::env::current_dir()
+        .ok()
+        .and_then(|dir| dir.ancestors().last().map(normalize))
+        .unwrap_or_else(|| PathBuf::from(MAIN_SEPARATOR_STR))
+}
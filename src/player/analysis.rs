@@ -0,0 +1,209 @@
+//! technical analysis of a whole song for comparing masterings: true peak, rms and a dynamic range score
+
+use std::{collections::VecDeque, f64::consts::PI, sync::mpsc, time::Duration};
+
+use log::{info, warn};
+
+use crate::{cache::SharedCache, notification::Notification, song::Song};
+
+use super::loader::LoadedSong;
+
+/// the true peak is measured on the signal upsampled by this factor
+const OVERSAMPLING: usize = 4;
+
+/// samples on each side of an interpolated point
+const HALF_TAPS: usize = 8;
+
+/// length of the blocks the dynamic range score is computed from
+const BLOCK: Duration = Duration::from_secs(3);
+
+/// share of the loudest blocks whose rms the dynamic range score compares the peak to
+const LOUDEST_BLOCKS: f64 = 0.2;
+
+#[derive(Debug, Clone, Copy, PartialEq, serde::Deserialize, serde::Serialize)]
+pub struct Analysis {
+    /// highest level of the signal including the peaks between samples, in dBTP
+    pub true_peak: f32,
+    /// rms of all channels over the whole song, in dBFS
+    pub rms: f32,
+    /// the DR score of the TT dynamic range meter: the second highest peak over the rms of the loudest 20%
+    /// of 3 second blocks in dB, averaged over the channels, heavily compressed masters score below 8
+    pub dynamic_range: f32,
+}
+
+/// the running measurements of one channel
+#[derive(Debug, Default)]
+struct Channel {
+    /// the last `2 * HALF_TAPS` samples
+    history: VecDeque<f32>,
+    true_peak: f32,
+    squares: f64,
+    /// sum of squares, peak and length of the current block
+    block: (f64, f32, usize),
+    /// rms and peak of the finished blocks
+    blocks: Vec<(f64, f32)>,
+}
+
+impl Channel {
+    fn push(&mut self, sample: f32, filter: &[[f32; 2 * HALF_TAPS]], block_len: usize) {
+        self.interpolate(sample, filter);
+
+        let square = sample as f64 * sample as f64;
+        self.squares += square;
+        self.block.0 += square;
+        self.block.1 = self.block.1.max(sample.abs());
+        self.block.2 += 1;
+        if self.block.2 == block_len {
+            self.finish_block();
+        }
+    }
+
+    /// update the true peak with `sample` and the points between it and the previous sample
+    fn interpolate(&mut self, sample: f32, filter: &[[f32; 2 * HALF_TAPS]]) {
+        if self.history.is_empty() {
+            self.history.resize(2 * HALF_TAPS, 0.0);
+        }
+        self.history.pop_front();
+        self.history.push_back(sample);
+
+        self.true_peak = self.true_peak.max(sample.abs());
+        for coefficients in filter {
+            let interpolated = self
+                .history
+                .iter()
+                .zip(coefficients)
+                .map(|(s, c)| s * c)
+                .sum::<f32>();
+            self.true_peak = self.true_peak.max(interpolated.abs());
+        }
+    }
+
+    fn finish_block(&mut self) {
+        let (squares, peak, len) = std::mem::take(&mut self.block);
+        if len > 0 {
+            // the TT meter scales the rms so that a sine has the rms of its peak
+            self.blocks
+                .push(((2.0 * squares / len as f64).sqrt(), peak));
+        }
+    }
+
+    /// the DR score of the channel, `None` if it is silent
+    fn dynamic_range(&self) -> Option<f64> {
+        let mut peaks = self.blocks.iter().map(|(_, p)| *p).collect::<Vec<_>>();
+        peaks.sort_by(|a, b| b.total_cmp(a));
+        let peak = *peaks.get(1).or(peaks.first())? as f64;
+
+        let mut rms = self.blocks.iter().map(|(r, _)| *r).collect::<Vec<_>>();
+        rms.sort_by(|a, b| b.total_cmp(a));
+        let loudest = ((rms.len() as f64 * LOUDEST_BLOCKS).round() as usize).max(1);
+        let rms = (rms.iter().take(loudest).map(|r| r * r).sum::<f64>() / loudest as f64).sqrt();
+
+        (rms > 0.0).then(|| 20.0 * (peak / rms).log10())
+    }
+}
+
+/// coefficients of the windowed sinc interpolating each point between two samples
+/// of the `OVERSAMPLING` times upsampled signal
+fn interpolation_filter() -> Vec<[f32; 2 * HALF_TAPS]> {
+    (1..OVERSAMPLING)
+        .map(|phase| {
+            let mut coefficients = [0.0; 2 * HALF_TAPS];
+            for (k, c) in coefficients.iter_mut().enumerate() {
+                // distance of sample k from the interpolated point between the two middle samples
+                let t = k as f64 - (HALF_TAPS - 1) as f64 - phase as f64 / OVERSAMPLING as f64;
+                let sinc = match t == 0.0 {
+                    true => 1.0,
+                    false => (PI * t).sin() / (PI * t),
+                };
+                let window = 0.5 * (1.0 + (PI * t / HALF_TAPS as f64).cos());
+                *c = (sinc * window) as f32;
+            }
+            coefficients
+        })
+        .collect()
+}
+
+fn decibels(amplitude: f64) -> f32 {
+    (20.0 * amplitude.log10()) as f32
+}
+
+/// decode the whole song and measure its true peak, rms and dynamic range
+pub fn compute(song: &Song) -> anyhow::Result<Analysis> {
+    let mut loaded = LoadedSong::load(song.clone(), Duration::ZERO, None, None)?;
+    let channels = loaded.signal_spec.channels.count().max(1);
+    let block_len = (BLOCK.as_secs_f64() * loaded.signal_spec.rate as f64) as usize;
+    let filter = interpolation_filter();
+
+    let mut measured = (0..channels)
+        .map(|_| Channel::default())
+        .collect::<Vec<_>>();
+    let mut frames = 0;
+    for buffer in loaded.decoder.buffers() {
+        let buffer = buffer?;
+        for samples in buffer.chunks(channels) {
+            for (channel, sample) in measured.iter_mut().zip(samples) {
+                channel.push(*sample, &filter, block_len);
+            }
+            frames += 1;
+        }
+    }
+
+    if frames == 0 {
+        anyhow::bail!("The song has no samples");
+    }
+
+    // the points after the last samples are interpolated with silence following them
+    for channel in &mut measured {
+        for _ in 0..HALF_TAPS {
+            channel.interpolate(0.0, &filter);
+        }
+        channel.finish_block();
+    }
+
+    let true_peak = measured.iter().fold(0.0f32, |acc, c| acc.max(c.true_peak));
+    let squares = measured.iter().map(|c| c.squares).sum::<f64>();
+    let scores = measured
+        .iter()
+        .filter_map(Channel::dynamic_range)
+        .collect::<Vec<_>>();
+
+    Ok(Analysis {
+        true_peak: decibels(true_peak as f64),
+        rms: decibels((squares / (frames * channels) as f64).sqrt()),
+        dynamic_range: match scores.is_empty() {
+            true => 0.0,
+            false => (scores.iter().sum::<f64>() / scores.len() as f64) as f32,
+        },
+    })
+}
+
+/// analyze `song` in the background if it is neither cached nor being analyzed,
+/// an error notification is sent if it fails
+pub fn start(cache: &SharedCache, song: &Song, notifications: mpsc::Sender<Notification>) {
+    let cache = cache.load();
+    if !cache.claim_analysis(&song.path) {
+        return;
+    }
+
+    let song = song.clone();
+    std::thread::Builder::new()
+        .name("analysis thread".to_string())
+        .spawn(move || match compute(&song) {
+            Ok(analysis) => {
+                info!("Analyzed {}: {analysis:?}", song.path.display());
+                cache.insert_analysis(&song.path, Some(analysis));
+            }
+            Err(e) => {
+                warn!("Failed to analyze {}: {e:?}", song.path.display());
+                cache.insert_analysis(&song.path, None);
+                notifications
+                    .send(Notification::error(format!(
+                        "Failed to analyze {}: {e:#}",
+                        song.path.display()
+                    )))
+                    .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+            }
+        })
+        .map_err(|e| warn!("Failed to spawn analysis thread: {e:?}"))
+        .ok();
+}
@@ -0,0 +1,67 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Context;
+
+use crate::song::StandardTagKey;
+
+use super::facade::PlayerFacade;
+
+/// the file extension of covers of the media type `media_type`
+fn extension(media_type: &str) -> &'static str {
+    match media_type {
+        "image/png" => "png",
+        "image/gif" => "gif",
+        "image/bmp" => "bmp",
+        "image/webp" => "webp",
+        _ => "jpg",
+    }
+}
+
+/// the front cover of the current song written to `directory`, at a path that stays the same for all songs
+/// of an album with the same cover and changes with the content of the cover
+pub fn cached_cover(directory: &Path, facade: &PlayerFacade) -> anyhow::Result<Option<PathBuf>> {
+    let (Some(song), Some(visual)) = (facade.current_song(), facade.current_visual()) else {
+        return Ok(None);
+    };
+
+    // songs without an album tag get a cover of their own
+    let album = match song.tag_string(StandardTagKey::Album) {
+        Some(album) => [
+            song.tag_string(StandardTagKey::AlbumArtist)
+                .or(song.tag_string(StandardTagKey::Artist))
+                .unwrap_or_default(),
+            album,
+        ]
+        .join("\0"),
+        None => song.path.to_string_lossy().to_string(),
+    };
+    let prefix = format!("{:08x}-", crc32fast::hash(album.as_bytes()));
+    let path = directory.join(format!(
+        "{prefix}{:08x}.{}",
+        crc32fast::hash(&visual.data),
+        extension(&visual.media_type)
+    ));
+    if path.exists() {
+        return Ok(Some(path));
+    }
+
+    std::fs::create_dir_all(directory)
+        .context(format!("Failed to create {}", directory.display()))?;
+    // remove the previous cover of the album, the name of the new one tells desktops that it changed
+    for entry in std::fs::read_dir(directory)
+        .context(format!("Failed to read {}", directory.display()))?
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with(&prefix))
+    {
+        std::fs::remove_file(entry.path())
+            .context(format!("Failed to remove {}", entry.path().display()))?;
+    }
+
+    // written next to its final path first, so that the cover is never read half written
+    let partial = path.with_extension("part");
+    std::fs::write(&partial, &visual.data)
+        .context(format!("Failed to write {}", partial.display()))?;
+    std::fs::rename(&partial, &path).context(format!("Failed to write {}", path.display()))?;
+
+    Ok(Some(path))
+}
@@ -1,10 +1,64 @@
+#[derive(Debug, serde::Deserialize, serde::Serialize)]
 pub enum Command {
+    #[cfg_attr(not(feature = "mpris"), allow(dead_code))]
     Play,
     Pause,
     PlayPause,
     Skip,
     Stop,
+    /// stop before ramp exits, waiting for the stats of the current song to be written to its tags,
+    /// and release the media controls
+    Shutdown,
     Clear,
     Enqueue(Box<std::path::Path>),
+    /// clear the queue and play the given songs instead
+    Replace(Vec<Box<std::path::Path>>),
+    /// enqueue all songs of a random album
+    EnqueueRandomAlbum,
+    /// turn enqueueing a random album when the queue runs out on or off
+    ToggleRandomAlbums,
+    /// remove the song at the given index from the queue
     Dequeue(usize),
+    /// play the song at the given index of the queue now
+    Jump(usize),
+    /// sent by the audio stream when decoding or the output device failed
+    PlaybackFailed(String),
+    /// sent by the loader thread once the song being loaded was opened
+    SongLoaded,
+    /// switch the current song to the track with the given id
+    SelectTrack(u32),
+    /// continue playing the current song at the given position
+    Seek(std::time::Duration),
+    /// seek relative to the current position, or to the position of a seek still in progress
+    SeekForward(std::time::Duration),
+    SeekBackward(std::time::Duration),
+    NextChapter,
+    PreviousChapter,
+    /// set the cue point with the given number from 1 to 4 of the current song to the position
+    SetCuePoint(usize),
+    /// continue playing the current song at the cue point with the given number
+    JumpToCuePoint(usize),
+    /// apply the dsp preset with the given name
+    SelectPreset(String),
+    /// save the active dsp settings as a preset with the given name
+    #[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+    SavePreset(String),
+    /// turn the headphone crossfeed on or off
+    ToggleCrossfeed,
+    /// rate the current song with 1 to 5 stars, or remove its rating
+    Rate(Option<u8>),
+    /// scale the output by a factor from 0.0 to 1.0, used to fade in and out
+    Fade(f32),
+    /// set the volume from 0.0 to 1.0, independent of fades
+    SetVolume(f32),
+    /// set the volume of the zone with the given name from 0.0 to 1.0
+    SetZoneVolume(String, f32),
+    /// run the commands one after another and publish the player state once afterwards,
+    /// e.g. for enqueueing many songs
+    Batch(Vec<Command>),
+    /// play the beginning of the given song quietly without touching the queue, then continue
+    /// with the song that was playing, previewing the same song again ends the preview
+    Preview(Box<std::path::Path>),
+    /// continue with the song that was playing before the preview
+    EndPreview,
 }
@@ -0,0 +1,34 @@
+/// converts f32 samples to 16 bit with triangular (TPDF) dither,
+/// which decorrelates the quantization error from the signal
+pub struct Dither {
+    /// xorshift state, the noise does not need to be cryptographically random
+    state: u32,
+}
+
+impl Default for Dither {
+    fn default() -> Self {
+        Self { state: 0x9e37_79b9 }
+    }
+}
+
+impl Dither {
+    /// uniform noise in [0, 1)
+    fn noise(&mut self) -> f32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        (self.state >> 8) as f32 / (1 << 24) as f32
+    }
+
+    pub fn quantize_i16(&mut self, sample: f32) -> i16 {
+        // the difference of two uniform values has a triangular distribution of +-1 lsb
+        let dither = self.noise() - self.noise();
+        (sample * i16::MAX as f32 + dither)
+            .round()
+            .clamp(i16::MIN as f32, i16::MAX as f32) as i16
+    }
+
+    pub fn quantize_u16(&mut self, sample: f32) -> u16 {
+        (self.quantize_i16(sample) as i32 - i16::MIN as i32) as u16
+    }
+}
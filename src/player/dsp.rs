@@ -0,0 +1,311 @@
+use std::{
+    collections::BTreeMap,
+    f32::consts::PI,
+    path::Path,
+    sync::{Arc, RwLock},
+};
+
+use anyhow::Context;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+
+const PRESET_EXTENSION: &str = "json";
+
+/// a peaking filter of the equalizer
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct EqBand {
+    /// center frequency in Hz
+    pub frequency: f32,
+    /// gain in dB
+    pub gain: f32,
+    #[serde(default = "default_q")]
+    pub q: f32,
+}
+
+fn default_q() -> f32 {
+    std::f32::consts::FRAC_1_SQRT_2
+}
+
+/// named dsp settings, stored as json files in the presets directory
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct DspPreset {
+    /// gain in dB applied before the equalizer, to make room for boosted bands
+    #[serde(default)]
+    pub preamp: f32,
+    #[serde(default)]
+    pub eq: Vec<EqBand>,
+    /// -1.0 is fully left, 1.0 fully right
+    #[serde(default)]
+    pub balance: f32,
+    /// turn crossfeed on or off when selecting the preset, keep it as is if unset
+    #[serde(default)]
+    pub crossfeed: Option<bool>,
+}
+
+impl DspPreset {
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let preset = serde_json::from_str(&contents)?;
+
+        Ok(preset)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)?;
+        let mut ser = serde_json::Serializer::pretty(file);
+        self.serialize(&mut ser)?;
+
+        Ok(())
+    }
+
+    fn is_neutral(&self) -> bool {
+        self.preamp == 0.0 && self.eq.iter().all(|b| b.gain == 0.0) && self.balance == 0.0
+    }
+}
+
+/// load all presets from `dir`, creating it with a flat preset if it does not exist
+pub fn load_presets(dir: &Path) -> anyhow::Result<BTreeMap<String, DspPreset>> {
+    if !dir.exists() {
+        std::fs::create_dir_all(dir).context("Failed to create presets directory")?;
+        DspPreset::default()
+            .save(&preset_path(dir, "flat")?)
+            .context("Failed to save flat preset")?;
+        info!("Created presets directory {}", dir.display());
+    }
+
+    let mut presets = BTreeMap::new();
+    for entry in std::fs::read_dir(dir).context("Failed to read presets directory")? {
+        let path = entry?.path();
+        if path.extension().is_none_or(|e| e != PRESET_EXTENSION) {
+            continue;
+        }
+        let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+            continue;
+        };
+
+        match DspPreset::load(&path) {
+            Ok(preset) => {
+                presets.insert(name.to_string(), preset);
+            }
+            Err(e) => warn!("Failed to load preset {}: {e:?}", path.display()),
+        }
+    }
+
+    Ok(presets)
+}
+
+/// path of the preset with the given name, which must not lead out of `dir`
+pub fn preset_path(dir: &Path, name: &str) -> anyhow::Result<std::path::PathBuf> {
+    anyhow::ensure!(!name.trim().is_empty(), "The preset needs a name");
+    // separators and windows drive prefixes like "C:" would replace `dir`
+    anyhow::ensure!(
+        !name.contains(['/', '\\', ':']) && !name.contains(".."),
+        "Invalid preset name {name}"
+    );
+
+    // `with_extension` would cut names like "v1.5" at their dot
+    Ok(dir.join(format!("{name}.{PRESET_EXTENSION}")))
+}
+
+/// parameters of the crossfeed filter
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CrossfeedParams {
+    pub cutoff: f32,
+    pub feed: f32,
+}
+
+impl From<&crate::config::Crossfeed> for CrossfeedParams {
+    fn from(config: &crate::config::Crossfeed) -> Self {
+        Self {
+            cutoff: config.cutoff.0,
+            feed: config.feed.0,
+        }
+    }
+}
+
+/// the preset currently applied by the audio stream
+#[derive(Debug, Default)]
+pub struct ActivePreset {
+    pub name: Option<String>,
+    pub preset: Arc<DspPreset>,
+    /// crossfeed is toggled independently of the preset, `None` if disabled
+    pub crossfeed: Option<CrossfeedParams>,
+}
+
+pub type SharedPreset = Arc<RwLock<ActivePreset>>;
+
+/// biquad in transposed direct form II
+#[derive(Debug, Clone, Copy)]
+struct Biquad {
+    b0: f32,
+    b1: f32,
+    b2: f32,
+    a1: f32,
+    a2: f32,
+    z1: f32,
+    z2: f32,
+}
+
+impl Biquad {
+    /// peaking eq from the audio eq cookbook
+    fn peaking(band: &EqBand, rate: u32) -> Self {
+        let a = 10f32.powf(band.gain / 40.0);
+        let w0 = 2.0 * PI * band.frequency / rate as f32;
+        let alpha = w0.sin() / (2.0 * band.q.max(0.01));
+        let a0 = 1.0 + alpha / a;
+
+        Self {
+            b0: (1.0 + alpha * a) / a0,
+            b1: (-2.0 * w0.cos()) / a0,
+            b2: (1.0 - alpha * a) / a0,
+            a1: (-2.0 * w0.cos()) / a0,
+            a2: (1.0 - alpha / a) / a0,
+            z1: 0.0,
+            z2: 0.0,
+        }
+    }
+
+    fn process(&mut self, x: f32) -> f32 {
+        let y = self.b0 * x + self.z1;
+        self.z1 = self.b1 * x - self.a1 * y + self.z2;
+        self.z2 = self.b2 * x - self.a2 * y;
+        y
+    }
+}
+
+/// bauer stereophonic-to-binaural crossfeed, as implemented by bs2b:
+/// each channel is mixed with a lowpassed copy of the other one, while a high shelf
+/// on the direct signal keeps the overall frequency response flat
+#[derive(Debug, Clone, Copy)]
+struct Crossfeed {
+    params: CrossfeedParams,
+    a0_lo: f32,
+    b1_lo: f32,
+    a0_hi: f32,
+    a1_hi: f32,
+    b1_hi: f32,
+    gain: f32,
+    lo: [f32; 2],
+    hi: [f32; 2],
+    last: [f32; 2],
+}
+
+impl Crossfeed {
+    fn new(params: CrossfeedParams, rate: u32) -> Self {
+        let gb_lo = params.feed * -5.0 / 6.0 - 3.0;
+        let gb_hi = params.feed / 6.0 - 3.0;
+        let g_lo = 10f32.powf(gb_lo / 20.0);
+        let g_hi = 1.0 - 10f32.powf(gb_hi / 20.0);
+        let fc_hi = params.cutoff * 2f32.powf((gb_lo - 20.0 * g_hi.log10()) / 12.0);
+
+        let x_lo = (-2.0 * PI * params.cutoff / rate as f32).exp();
+        let x_hi = (-2.0 * PI * fc_hi / rate as f32).exp();
+
+        Self {
+            params,
+            a0_lo: g_lo * (1.0 - x_lo),
+            b1_lo: x_lo,
+            a0_hi: 1.0 - g_hi * (1.0 - x_hi),
+            a1_hi: -x_hi,
+            b1_hi: x_hi,
+            gain: 1.0 / (1.0 - g_hi + g_lo),
+            lo: [0.0; 2],
+            hi: [0.0; 2],
+            last: [0.0; 2],
+        }
+    }
+
+    fn process(&mut self, left: &mut f32, right: &mut f32) {
+        let input = [*left, *right];
+        for (i, &x) in input.iter().enumerate() {
+            self.lo[i] = self.a0_lo * x + self.b1_lo * self.lo[i];
+            self.hi[i] = self.a0_hi * x + self.a1_hi * self.last[i] + self.b1_hi * self.hi[i];
+        }
+        self.last = input;
+
+        *left = (self.hi[0] + self.lo[1]) * self.gain;
+        *right = (self.hi[1] + self.lo[0]) * self.gain;
+    }
+}
+
+/// applies the active preset to the interleaved samples of a stream
+pub struct Dsp {
+    shared: SharedPreset,
+    preset: Arc<DspPreset>,
+    channels: usize,
+    rate: u32,
+    /// one filter per band and channel, band-major
+    filters: Vec<Biquad>,
+    crossfeed: Option<Crossfeed>,
+}
+
+impl Dsp {
+    pub fn new(shared: SharedPreset, channels: u16, rate: u32) -> Self {
+        let mut dsp = Self {
+            shared,
+            preset: Arc::default(),
+            channels: channels.max(1) as usize,
+            rate,
+            filters: vec![],
+            crossfeed: None,
+        };
+        dsp.update();
+        dsp
+    }
+
+    /// pick up a changed preset
+    fn update(&mut self) {
+        let (preset, crossfeed) = {
+            let shared = self.shared.read().unwrap();
+            (shared.preset.clone(), shared.crossfeed)
+        };
+
+        // crossfeed needs two channels, the filter state is kept as long as the parameters don't change
+        let crossfeed = crossfeed.filter(|_| self.channels >= 2);
+        if crossfeed != self.crossfeed.map(|c| c.params) {
+            self.crossfeed = crossfeed.map(|params| Crossfeed::new(params, self.rate));
+        }
+
+        if Arc::ptr_eq(&preset, &self.preset) {
+            return;
+        }
+
+        self.filters = preset
+            .eq
+            .iter()
+            .filter(|b| b.gain != 0.0 && b.frequency > 0.0 && b.frequency < self.rate as f32 / 2.0)
+            .flat_map(|b| std::iter::repeat_n(Biquad::peaking(b, self.rate), self.channels))
+            .collect();
+        self.preset = preset;
+    }
+
+    pub fn process(&mut self, samples: &mut [f32]) {
+        self.update();
+        if self.preset.is_neutral() && self.crossfeed.is_none() {
+            return;
+        }
+
+        let preamp = 10f32.powf(self.preset.preamp / 20.0);
+        let balance = self.preset.balance.clamp(-1.0, 1.0);
+        let (left, right) = ((1.0 - balance).min(1.0), (1.0 + balance).min(1.0));
+
+        for frame in samples.chunks_exact_mut(self.channels) {
+            for (channel, sample) in frame.iter_mut().enumerate() {
+                let mut x = *sample * preamp;
+                for filter in self.filters.iter_mut().skip(channel).step_by(self.channels) {
+                    x = filter.process(x);
+                }
+                *sample = x;
+            }
+
+            if let [l, r, ..] = frame {
+                if let Some(crossfeed) = &mut self.crossfeed {
+                    crossfeed.process(l, r);
+                }
+
+                *l *= left;
+                *r *= right;
+            }
+        }
+    }
+}
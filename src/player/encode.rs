@@ -0,0 +1,250 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use anyhow::Context;
+
+use super::sink::StreamSpec;
+
+/// most frames a flac frame holds, blocks of the renderer are at most this long
+pub const FLAC_MAX_BLOCK: usize = 4096;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// 32 bit float
+    Wav,
+    /// 24 bit, stored uncompressed in verbatim subframes
+    Flac,
+}
+
+/// writes samples to a wav or flac file, picked by the extension of the path,
+/// the header is kept up to date so that the file is valid at any time
+pub struct Encoder {
+    file: BufWriter<File>,
+    format: Format,
+    pub spec: StreamSpec,
+    /// frames written so far
+    frames: u64,
+}
+
+impl Encoder {
+    /// fails for paths that do not end in .wav or .flac
+    pub fn format_supported(path: &Path) -> anyhow::Result<()> {
+        format(path).map(|_| ())
+    }
+
+    pub fn create(path: &Path, spec: StreamSpec) -> anyhow::Result<Self> {
+        let format = format(path)?;
+        let file = File::create(path).context(format!("Failed to create {}", path.display()))?;
+
+        let mut encoder = Self {
+            file: BufWriter::new(file),
+            format,
+            spec,
+            frames: 0,
+        };
+        encoder.write_header()?;
+
+        Ok(encoder)
+    }
+
+    /// append interleaved samples, which have to be whole frames
+    pub fn write(&mut self, samples: &[f32]) -> anyhow::Result<()> {
+        if samples.is_empty() {
+            return Ok(());
+        }
+
+        let channels = self.spec.channels as usize;
+        let bytes = match self.format {
+            Format::Wav => samples.iter().flat_map(|s| s.to_le_bytes()).collect(),
+            Format::Flac => samples
+                .chunks(FLAC_MAX_BLOCK * channels)
+                .enumerate()
+                .flat_map(|(i, block)| {
+                    let start = self.frames + (i * FLAC_MAX_BLOCK) as u64;
+                    flac_frame(block, channels, start)
+                })
+                .collect::<Vec<_>>(),
+        };
+        self.file.write_all(&bytes)?;
+        self.frames += (samples.len() / channels) as u64;
+
+        self.write_header()?;
+        self.file.flush().context("Failed to write samples")
+    }
+
+    fn write_header(&mut self) -> anyhow::Result<()> {
+        let header = match self.format {
+            Format::Wav => wav_header(self.spec, self.frames),
+            Format::Flac => flac_header(self.spec, self.frames),
+        };
+
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&header)?;
+        self.file.seek(SeekFrom::End(0))?;
+
+        Ok(())
+    }
+}
+
+fn format(path: &Path) -> anyhow::Result<Format> {
+    match path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_lowercase)
+        .as_deref()
+    {
+        Some("wav") => Ok(Format::Wav),
+        Some("flac") => Ok(Format::Flac),
+        _ => anyhow::bail!(
+            "Can only write .wav and .flac files, not {}",
+            path.display()
+        ),
+    }
+}
+
+fn wav_header(spec: StreamSpec, frames: u64) -> Vec<u8> {
+    let StreamSpec {
+        channels,
+        sample_rate,
+    } = spec;
+    let length = (frames * channels as u64 * 4) as u32;
+
+    let mut header = Vec::with_capacity(44);
+    header.extend(b"RIFF");
+    header.extend((36 + length).to_le_bytes());
+    header.extend(b"WAVE");
+    header.extend(b"fmt ");
+    header.extend(16u32.to_le_bytes());
+    // ieee float
+    header.extend(3u16.to_le_bytes());
+    header.extend(channels.to_le_bytes());
+    header.extend(sample_rate.to_le_bytes());
+    header.extend((sample_rate * channels as u32 * 4).to_le_bytes());
+    header.extend((channels * 4).to_le_bytes());
+    header.extend(32u16.to_le_bytes());
+    header.extend(b"data");
+    header.extend(length.to_le_bytes());
+
+    header
+}
+
+/// the stream marker and the streaminfo block
+fn flac_header(spec: StreamSpec, frames: u64) -> Vec<u8> {
+    let mut info = BitWriter::default();
+    // blocks vary in length, the last block of a song is shorter
+    info.write(16, 16);
+    info.write(FLAC_MAX_BLOCK as u64, 16);
+    // unknown frame sizes
+    info.write(0, 24);
+    info.write(0, 24);
+    info.write(spec.sample_rate as u64, 20);
+    info.write(spec.channels as u64 - 1, 3);
+    info.write(24 - 1, 5);
+    info.write(frames, 36);
+    // unknown md5
+    info.write(0, 64);
+    info.write(0, 64);
+
+    let mut header = b"fLaC".to_vec();
+    // the only and therefore last metadata block
+    header.push(0x80);
+    header.extend(&(info.bytes.len() as u32).to_be_bytes()[1..]);
+    header.extend(info.bytes);
+
+    header
+}
+
+/// a frame of up to `FLAC_MAX_BLOCK` frames starting at the sample number `start`
+fn flac_frame(samples: &[f32], channels: usize, start: u64) -> Vec<u8> {
+    let mut frame = BitWriter::default();
+    // sync code and variable block size
+    frame.write(0b11111111111110, 14);
+    frame.write(0, 1);
+    frame.write(1, 1);
+    // block size from the end of the header, sample rate from the streaminfo
+    frame.write(0b0111, 4);
+    frame.write(0b0000, 4);
+    // independent channels of 24 bit
+    frame.write(channels as u64 - 1, 4);
+    frame.write(0b110, 3);
+    frame.write(0, 1);
+    for byte in utf8(start) {
+        frame.write(byte as u64, 8);
+    }
+    frame.write((samples.len() / channels - 1) as u64, 16);
+    let crc = crc8(&frame.bytes);
+    frame.write(crc as u64, 8);
+
+    for channel in 0..channels {
+        // verbatim subframe without wasted bits
+        frame.write(0b00000010, 8);
+        for sample in samples.iter().skip(channel).step_by(channels) {
+            let sample = (sample * 8388607.0).round().clamp(-8388608.0, 8388607.0) as i32;
+            frame.write(sample as u32 as u64 & 0xFF_FFFF, 24);
+        }
+    }
+
+    let crc = crc16(&frame.bytes);
+    frame.write(crc as u64, 16);
+
+    frame.bytes
+}
+
+/// the utf-8 like coding of sample numbers in flac frame headers, for up to 36 bits
+fn utf8(value: u64) -> Vec<u8> {
+    if value < 0x80 {
+        return vec![value as u8];
+    }
+
+    // n bytes hold 5n + 1 bits
+    let n = (2..7).find(|n| value < 1 << (5 * n + 1)).unwrap_or(7);
+    let mut bytes = vec![(0xff00u32 >> n) as u8 | (value >> (6 * (n - 1))) as u8];
+    bytes.extend(
+        (0..n - 1)
+            .rev()
+            .map(|i| 0x80 | ((value >> (6 * i)) & 0x3f) as u8),
+    );
+
+    bytes
+}
+
+/// writes big endian values of any bit width
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, width: u32) {
+        for i in (0..width).rev() {
+            if self.bits.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            *self.bytes.last_mut().expect("Failed to get last byte") |= bit << (7 - self.bits % 8);
+            self.bits += 1;
+        }
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x07,
+        })
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x8005,
+        })
+    })
+}
@@ -0,0 +1,33 @@
+use std::time::Duration;
+
+use crate::song::Song;
+
+use super::loader::LoadedSong;
+
+/// number of buckets of an envelope
+pub const RESOLUTION: usize = 200;
+
+/// decode the whole song and compute the peak amplitude of each of the `RESOLUTION` buckets,
+/// scaled to 0..=255
+pub fn compute(song: &Song) -> anyhow::Result<Box<[u8]>> {
+    let mut loaded = LoadedSong::load(song.clone(), Duration::ZERO, None, None)?;
+    let channels = loaded.signal_spec.channels.count().max(1);
+    let total_frames = (song.duration.as_secs_f64() * loaded.signal_spec.rate as f64) as usize;
+
+    let mut peaks = [0.0f32; RESOLUTION];
+    let mut frame = 0;
+    for buffer in loaded.decoder.buffers() {
+        let buffer = buffer?;
+        for samples in buffer.chunks(channels) {
+            let bucket = (frame * RESOLUTION / total_frames.max(1)).min(RESOLUTION - 1);
+            let peak = samples.iter().fold(0.0f32, |acc, s| acc.max(s.abs()));
+            peaks[bucket] = peaks[bucket].max(peak);
+            frame += 1;
+        }
+    }
+
+    Ok(peaks
+        .iter()
+        .map(|p| (p.clamp(0.0, 1.0) * u8::MAX as f32) as u8)
+        .collect())
+}
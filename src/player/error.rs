@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+use crate::notification::Level;
+
+/// why a player command failed, so that callers can tell mistakes of the user from broken playback
+#[derive(Debug, thiserror::Error)]
+pub enum PlayerError {
+    #[error("No song playing")]
+    NotPlaying,
+    #[error("No song at index {0}")]
+    NoSuchIndex(usize),
+    #[error("Nothing to play")]
+    NothingToPlay,
+    #[error("Queue is full, not enqueueing {}", .0.display())]
+    QueueFull(PathBuf),
+    #[error("{} is already queued", .0.display())]
+    AlreadyQueued(PathBuf),
+    #[error("{} is already playing", .0.display())]
+    AlreadyPlaying(PathBuf),
+    #[error("No albums in the library")]
+    NoAlbums,
+    #[error("Song has no chapters")]
+    NoChapters,
+    #[error("No next chapter")]
+    NoNextChapter,
+    #[error("Cue point {0} is not set")]
+    NoCuePoint(usize),
+    #[error("Preset {0} not found")]
+    PresetNotFound(String),
+    #[error("Zone {0} not found")]
+    ZoneNotFound(String),
+    /// the song is neither in the cache nor readable from its file
+    #[error("Failed to read {}", .path.display())]
+    NotInCache {
+        path: PathBuf,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("Failed to decode {}", .path.display())]
+    DecodeFailed {
+        path: PathBuf,
+        #[source]
+        source: anyhow::Error,
+    },
+    #[error("No audio output device available, playback is disabled until one is connected")]
+    DeviceUnavailable,
+    #[error("Failed to open the audio stream")]
+    Stream(#[source] anyhow::Error),
+    /// the audio stream failed and was restarted at the position it failed
+    #[error("Playback failed, restarted audio stream: {0}")]
+    PlaybackRestarted(String),
+    /// the audio stream failed right after starting and the song was skipped
+    #[error("Playback failed, skipped {}: {error}", .path.display())]
+    PlaybackSkipped { path: PathBuf, error: String },
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+impl PlayerError {
+    /// how the error is shown to the user, mistakes like seeking while stopped are no errors of ramp
+    pub fn level(&self) -> Level {
+        match self {
+            PlayerError::NotPlaying
+            | PlayerError::NoSuchIndex(_)
+            | PlayerError::NothingToPlay
+            | PlayerError::QueueFull(_)
+            | PlayerError::AlreadyQueued(_)
+            | PlayerError::AlreadyPlaying(_)
+            | PlayerError::NoChapters
+            | PlayerError::NoNextChapter
+            | PlayerError::NoCuePoint(_) => Level::Info,
+            _ => Level::Error,
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::{
+    path::Path,
+    sync::{mpsc, Arc, RwLock},
+    time::Duration,
+};
+
+use anyhow::Context;
+use log::{info, warn};
+
+use crate::{cache::Cache, config::Config};
+
+use super::{
+    command::Command,
+    dsp::{self, ActivePreset, CrossfeedParams},
+    error::PlayerError,
+    loader::LoadedSong,
+    playback::Playback,
+    sink::FileSink,
+};
+
+/// render `songs` one after another into the wav or flac file `output` as fast as possible,
+/// with replay gain, the dsp preset `preset` and, if `crossfeed`, the configured crossfeed applied,
+/// all songs have to have the sample rate and channels of the first one
+pub fn export<P: AsRef<Path>>(
+    cache: &Cache,
+    config: &Config,
+    songs: &[P],
+    output: &Path,
+    preset: Option<&str>,
+    crossfeed: bool,
+) -> anyhow::Result<()> {
+    anyhow::ensure!(!songs.is_empty(), "Nothing to export");
+    let sink = FileSink::new(output)?;
+
+    let mut active = ActivePreset {
+        crossfeed: crossfeed.then(|| CrossfeedParams::from(&config.crossfeed)),
+        ..ActivePreset::default()
+    };
+    if let Some(name) = preset {
+        let presets = dsp::load_presets(&config.presets_directory)?;
+        active.preset = Arc::new(
+            presets
+                .get(name)
+                .cloned()
+                .ok_or(PlayerError::PresetNotFound(name.to_string()))?,
+        );
+        active.name = Some(name.to_string());
+    }
+    let dsp = Arc::new(RwLock::new(active));
+    let unity = Arc::new(RwLock::new(1.0));
+
+    let (tx, rx) = mpsc::channel();
+    for path in songs {
+        let path = path.as_ref();
+        let song = super::lookup(cache, path)?;
+        let loaded = LoadedSong::load(
+            song,
+            Duration::ZERO,
+            None,
+            config.preferred_track_language.as_deref(),
+        )
+        .map_err(|source| PlayerError::DecodeFailed {
+            path: path.to_path_buf(),
+            source,
+        })?;
+
+        let _playback = Playback::new(
+            tx.clone(),
+            loaded,
+            config.loudness_target.0,
+            None,
+            dsp.clone(),
+            unity.clone(),
+            unity.clone(),
+            &sink,
+            false,
+        )
+        .context(format!("Failed to export {}", path.display()))?;
+
+        // the playback reports the end of the song like to the player
+        loop {
+            match rx.recv().context("Export stopped")? {
+                Command::Skip => break,
+                Command::PlaybackFailed(e) => {
+                    anyhow::bail!("Failed to export {}: {e}", path.display())
+                }
+                command => warn!("Ignoring {command:?} during export"),
+            }
+        }
+    }
+    sink.finish();
+    info!("Exported {} songs to {}", songs.len(), output.display());
+
+    Ok(())
+}
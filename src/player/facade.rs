@@ -3,21 +3,41 @@ use std::{
     time::Duration,
 };
 
-use symphonia::core::meta::{MetadataRevision, StandardVisualKey};
+use symphonia::core::meta::{MetadataRevision, StandardVisualKey, Visual};
 
-use crate::song::Song;
+use crate::{
+    song::{Chapter, Song},
+    stats::SongStats,
+};
 
 use super::Player;
 
+/// an audio track inside the song's container
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct TrackInfo {
+    pub id: u32,
+    pub language: Option<String>,
+    pub codec: String,
+    pub channels: Option<usize>,
+    pub sample_rate: Option<u32>,
+}
+
 #[derive(Default)]
 #[allow(clippy::large_enum_variant)]
 pub enum PlayerStatus {
     PlayingOrPaused {
         song: Song,
         metadata: Option<MetadataRevision>,
+        tracks: Vec<TrackInfo>,
+        track_id: u32,
+        chapters: Vec<Chapter>,
         playing_duration: Arc<RwLock<Duration>>,
+        /// audio decoded ahead of the position
+        buffered: Arc<RwLock<Duration>>,
         paused: Arc<AtomicBool>,
     },
+    /// the song is being opened, which can take seconds on network storage
+    Loading { path: Box<std::path::Path> },
     #[default]
     Stopped,
 }
@@ -29,13 +49,22 @@ impl PlayerStatus {
                 song,
                 metadata,
                 playback,
-                ..
+                tracks,
+                track_id,
+                chapters,
             } => PlayerStatus::PlayingOrPaused {
                 song: song.clone(),
                 metadata: metadata.clone(),
+                tracks: tracks.clone(),
+                track_id: *track_id,
+                chapters: chapters.clone(),
                 playing_duration: playback.played_duration.clone(),
+                buffered: playback.buffered.clone(),
                 paused: playback.pause.clone(),
             },
+            super::InternalPlayerStatus::Loading { path, .. } => {
+                PlayerStatus::Loading { path: path.clone() }
+            }
             super::InternalPlayerStatus::Stopped => PlayerStatus::Stopped,
         }
     }
@@ -45,6 +74,26 @@ impl PlayerStatus {
 pub struct PlayerFacade {
     pub status: PlayerStatus,
     pub queue: Box<[Box<std::path::Path>]>,
+    /// names of the available dsp presets
+    pub presets: Vec<String>,
+    /// name of the active dsp preset
+    pub preset: Option<String>,
+    pub crossfeed: bool,
+    /// whether a random album is enqueued when the queue runs out
+    pub random_albums: bool,
+    /// volume from 0.0 to 1.0
+    pub volume: f32,
+    /// names and volumes of the zones played to, empty for a single output device
+    pub zones: Vec<(String, f32)>,
+    /// play count and rating of the current song
+    pub stats: Option<SongStats>,
+    /// whether the current song reaches the output unmodified, without resampling, normalization,
+    /// volume or dsp
+    pub bit_perfect: bool,
+    /// whether the peak of the current song clips after normalization, volume and the dsp preamp
+    pub clipping: bool,
+    /// whether the current song is a preview, see `Command::Preview`
+    pub preview: bool,
 }
 
 impl PlayerFacade {
@@ -52,9 +101,47 @@ impl PlayerFacade {
         PlayerFacade {
             status: PlayerStatus::from_internal(player),
             queue: player.queue.clone().into_iter().collect(),
+            presets: player.presets.keys().cloned().collect(),
+            preset: player.dsp.read().unwrap().name.clone(),
+            crossfeed: player.dsp.read().unwrap().crossfeed.is_some(),
+            random_albums: player
+                .random_albums
+                .load(std::sync::atomic::Ordering::Relaxed),
+            volume: *player.volume.read().unwrap(),
+            zones: player.sink.zones(),
+            stats: match &player.status {
+                super::InternalPlayerStatus::PlayingOrPaused { song, .. } => Some(
+                    player
+                        .stats
+                        .lock()
+                        .unwrap()
+                        .get(&song.path, || crate::tags::read_stats(song)),
+                ),
+                super::InternalPlayerStatus::Loading { .. }
+                | super::InternalPlayerStatus::Stopped => None,
+            },
+            bit_perfect: Self::bit_perfect(player),
+            clipping: match &player.status {
+                super::InternalPlayerStatus::PlayingOrPaused { song, .. } => player
+                    .output_peak(song)
+                    .is_some_and(|peak| peak * *player.volume.read().unwrap() > 1.0),
+                super::InternalPlayerStatus::Loading { .. }
+                | super::InternalPlayerStatus::Stopped => false,
+            },
+            preview: player.preview.is_some(),
         }
     }
 
+    fn bit_perfect(player: &Player) -> bool {
+        let dsp = player.dsp.read().unwrap();
+        matches!(&player.status, super::InternalPlayerStatus::PlayingOrPaused { playback, .. } if playback.bit_perfect)
+            && dsp.name.is_none()
+            && dsp.crossfeed.is_none()
+            && *player.volume.read().unwrap() == 1.0
+            && *player.fade.read().unwrap() == 1.0
+            && player.sink.zones().iter().all(|(_, volume)| *volume == 1.0)
+    }
+
     pub fn current_song(&self) -> Option<&Song> {
         match &self.status {
             PlayerStatus::PlayingOrPaused { song, .. } => Some(song),
@@ -62,6 +149,14 @@ impl PlayerFacade {
         }
     }
 
+    /// the path of the song being opened
+    pub fn loading(&self) -> Option<&std::path::Path> {
+        match &self.status {
+            PlayerStatus::Loading { path } => Some(path),
+            _ => None,
+        }
+    }
+
     pub fn playing_duration(&self) -> Option<std::time::Duration> {
         match &self.status {
             PlayerStatus::PlayingOrPaused {
@@ -71,16 +166,60 @@ impl PlayerFacade {
         }
     }
 
+    /// how much audio is decoded ahead of the position
+    pub fn buffered(&self) -> Option<std::time::Duration> {
+        match &self.status {
+            PlayerStatus::PlayingOrPaused { buffered, .. } => Some(*buffered.read().unwrap()),
+            _ => None,
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        match &self.status {
+            PlayerStatus::PlayingOrPaused { paused, .. } => {
+                paused.load(std::sync::atomic::Ordering::Relaxed)
+            }
+            PlayerStatus::Loading { .. } | PlayerStatus::Stopped => false,
+        }
+    }
+
+    /// audio tracks of the current song and the id of the one playing
+    pub fn tracks(&self) -> Option<(&[TrackInfo], u32)> {
+        match &self.status {
+            PlayerStatus::PlayingOrPaused {
+                tracks, track_id, ..
+            } => Some((tracks, *track_id)),
+            PlayerStatus::Loading { .. } | PlayerStatus::Stopped => None,
+        }
+    }
+
+    pub fn chapters(&self) -> &[Chapter] {
+        match &self.status {
+            PlayerStatus::PlayingOrPaused { chapters, .. } => chapters,
+            PlayerStatus::Loading { .. } | PlayerStatus::Stopped => &[],
+        }
+    }
+
+    /// index of the chapter at the current position
+    pub fn current_chapter(&self) -> Option<usize> {
+        let position = self.playing_duration()?;
+        self.chapters().iter().rposition(|c| c.start <= position)
+    }
+
     pub fn current_cover(&self) -> Option<&[u8]> {
+        self.current_visual().map(|v| v.data.as_ref())
+    }
+
+    /// the front cover of the current song with its media type
+    pub fn current_visual(&self) -> Option<&Visual> {
         match &self.status {
             PlayerStatus::PlayingOrPaused { metadata, .. } => metadata.as_ref(),
-            PlayerStatus::Stopped => None,
+            PlayerStatus::Loading { .. } | PlayerStatus::Stopped => None,
         }
         .and_then(|m| {
             m.visuals()
                 .iter()
                 .find(|v| v.usage == Some(StandardVisualKey::FrontCover))
         })
-        .map(|v| v.data.as_ref())
     }
 }
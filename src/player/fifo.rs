@@ -0,0 +1,135 @@
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::mpsc,
+};
+
+use log::{debug, info, warn};
+
+/// sample rate of the pipe output, the default of cava's fifo input
+const OUTPUT_RATE: u32 = 44100;
+
+/// chunks buffered while the reader lags behind, older samples are dropped
+const BUFFERED_CHUNKS: usize = 16;
+
+struct Chunk {
+    samples: Vec<f32>,
+    channels: u16,
+    rate: u32,
+}
+
+/// copies the played samples to a named pipe for external visualizers like cava,
+/// as 16 bit little endian stereo pcm at 44100 Hz
+#[derive(Clone)]
+pub struct FifoSink {
+    tx: mpsc::SyncSender<Chunk>,
+}
+
+impl FifoSink {
+    pub fn open(path: PathBuf) -> Self {
+        let (tx, rx) = mpsc::sync_channel(BUFFERED_CHUNKS);
+
+        std::thread::Builder::new()
+            .name("fifo thread".to_string())
+            .spawn(move || write_loop(&path, rx))
+            .map_err(|e| warn!("Failed to spawn fifo thread: {e:?}"))
+            .ok();
+
+        Self { tx }
+    }
+
+    /// queue samples for the pipe, never blocks the audio stream
+    pub fn send(&self, samples: &[f32], channels: u16, rate: u32) {
+        self.tx
+            .try_send(Chunk {
+                samples: samples.to_vec(),
+                channels,
+                rate,
+            })
+            .ok();
+    }
+}
+
+fn write_loop(path: &Path, rx: mpsc::Receiver<Chunk>) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::FileTypeExt;
+
+        if !path.exists() {
+            match std::process::Command::new("mkfifo").arg(path).status() {
+                Ok(status) if status.success() => info!("Created fifo {}", path.display()),
+                result => warn!("Failed to create fifo {}: {result:?}", path.display()),
+            }
+        }
+
+        if !std::fs::metadata(path).is_ok_and(|m| m.file_type().is_fifo()) {
+            warn!("{} is not a fifo, not writing samples", path.display());
+            return;
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        warn!(
+            "Visualizer fifo {} is only supported on unix",
+            path.display()
+        );
+        return;
+    }
+
+    #[cfg_attr(not(unix), allow(unreachable_code))]
+    loop {
+        // blocks until a reader opens the pipe
+        let mut file = match OpenOptions::new().write(true).open(path) {
+            Ok(file) => file,
+            Err(e) => {
+                warn!("Failed to open fifo {}: {e:?}", path.display());
+                return;
+            }
+        };
+        debug!("Fifo reader connected");
+
+        // samples queued while waiting for a reader are stale
+        rx.try_iter().for_each(drop);
+
+        let mut position = 0.0;
+        loop {
+            let Ok(chunk) = rx.recv() else {
+                return;
+            };
+
+            let bytes = convert(&chunk, &mut position);
+            if let Err(e) = file.write_all(&bytes) {
+                debug!("Fifo reader disconnected: {e:?}");
+                break;
+            }
+        }
+    }
+}
+
+/// resample to stereo at `OUTPUT_RATE`, `position` carries the fractional frame over to the next chunk
+fn convert(chunk: &Chunk, position: &mut f64) -> Vec<u8> {
+    let channels = chunk.channels.max(1) as usize;
+    let frames = chunk.samples.len() / channels;
+    let step = chunk.rate as f64 / OUTPUT_RATE as f64;
+
+    let mut bytes = Vec::with_capacity((frames as f64 / step) as usize * 4 + 4);
+    while (*position as usize) < frames {
+        let i = *position as usize * channels;
+        let left = chunk.samples[i];
+        let right = if channels > 1 {
+            chunk.samples[i + 1]
+        } else {
+            left
+        };
+
+        for sample in [left, right] {
+            bytes.extend(((sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16).to_le_bytes());
+        }
+
+        *position += step;
+    }
+    *position -= frames as f64;
+
+    bytes
+}
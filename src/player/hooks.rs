@@ -0,0 +1,163 @@
+use std::{path::PathBuf, sync::Arc};
+
+use log::{debug, warn};
+
+use crate::{
+    config::Config,
+    song::{Song, StandardTagKey},
+};
+
+use super::{art, facade::PlayerFacade};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HookEvent {
+    Start,
+    Stop,
+    Pause,
+    Resume,
+}
+
+/// the song at the last update, kept for the stop hook once the player has let go of it
+struct Current {
+    song: Song,
+    paused: bool,
+    /// written when the song started, `None` without any hooks or without a cover
+    cover: Option<PathBuf>,
+}
+
+/// runs the configured hook commands when the playback state changes
+pub struct HookRunner {
+    config: Arc<Config>,
+    last: Option<Current>,
+}
+
+impl HookRunner {
+    pub fn new(config: Arc<Config>) -> Self {
+        Self { config, last: None }
+    }
+
+    pub fn update(&mut self, facade: &PlayerFacade) {
+        // the next song is still being opened, it starts once it is loaded
+        if facade.loading().is_some() {
+            return;
+        }
+
+        let current = facade.current_song();
+        let paused = facade.is_paused();
+
+        let event = match (&self.last, current) {
+            (None, None) => None,
+            (Some(_), None) => Some(HookEvent::Stop),
+            (None, Some(_)) => Some(HookEvent::Start),
+            (Some(last), Some(song)) if last.song.path != song.path => Some(HookEvent::Start),
+            (Some(last), Some(_)) if !last.paused && paused => Some(HookEvent::Pause),
+            (Some(last), Some(_)) if last.paused && !paused => Some(HookEvent::Resume),
+            (Some(_), Some(_)) => None,
+        };
+
+        match (event, current) {
+            // the stop hook is about the song that stopped
+            (Some(HookEvent::Stop), _) => {
+                if let Some(last) = self.last.take() {
+                    self.run(HookEvent::Stop, &last);
+                }
+            }
+            (Some(HookEvent::Start), Some(song)) => {
+                let current = Current {
+                    song: song.clone(),
+                    paused,
+                    cover: self.cover(facade),
+                };
+                self.run(HookEvent::Start, &current);
+                self.last = Some(current);
+            }
+            (event, _) => {
+                if let Some(last) = &mut self.last {
+                    last.paused = paused;
+                }
+                if let (Some(event), Some(last)) = (event, &self.last) {
+                    self.run(event, last);
+                }
+            }
+        }
+    }
+
+    /// write the cover of the current song for the hooks, if any are configured
+    fn cover(&self, facade: &PlayerFacade) -> Option<PathBuf> {
+        let hooks = &self.config.hooks;
+        [
+            &hooks.on_start,
+            &hooks.on_stop,
+            &hooks.on_pause,
+            &hooks.on_resume,
+        ]
+        .iter()
+        .any(|h| h.is_some())
+        .then(|| {
+            art::cached_cover(&self.config.art_cache_directory, facade)
+                .map_err(|e| warn!("Failed to write cover for hook: {e:?}"))
+                .ok()
+                .flatten()
+        })
+        .flatten()
+    }
+
+    fn run(&self, event: HookEvent, current: &Current) {
+        let hooks = &self.config.hooks;
+        let Some(command) = (match event {
+            HookEvent::Start => &hooks.on_start,
+            HookEvent::Stop => &hooks.on_stop,
+            HookEvent::Pause => &hooks.on_pause,
+            HookEvent::Resume => &hooks.on_resume,
+        }) else {
+            return;
+        };
+        debug!("Running {event:?} hook: {command}");
+
+        let tag = |key| {
+            current
+                .song
+                .standard_tags
+                .get(&key)
+                .map(|v| v.to_string())
+                .unwrap_or_default()
+        };
+
+        #[cfg(windows)]
+        let mut shell = std::process::Command::new("cmd");
+        #[cfg(windows)]
+        shell.arg("/C");
+        #[cfg(not(windows))]
+        let mut shell = std::process::Command::new("sh");
+        #[cfg(not(windows))]
+        shell.arg("-c");
+
+        let child = shell
+            .arg(command)
+            .env("RAMP_EVENT", format!("{event:?}").to_lowercase())
+            .env("RAMP_TITLE", tag(StandardTagKey::TrackTitle))
+            .env("RAMP_ARTIST", tag(StandardTagKey::Artist))
+            .env("RAMP_ALBUM", tag(StandardTagKey::Album))
+            .env("RAMP_PATH", &*current.song.path)
+            .env(
+                "RAMP_COVER",
+                current
+                    .cover
+                    .as_ref()
+                    .map(|p| p.as_os_str())
+                    .unwrap_or_default(),
+            )
+            .stdin(std::process::Stdio::null())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::null())
+            .spawn();
+
+        match child {
+            // reap the child in the background so hooks never block the player
+            Ok(mut child) => {
+                std::thread::spawn(move || child.wait());
+            }
+            Err(e) => warn!("Failed to run {event:?} hook: {e:?}"),
+        }
+    }
+}
@@ -1,61 +1,242 @@
+use std::time::Duration;
+
 use anyhow::Context;
 
-use log::{debug, trace};
+use log::{debug, trace, warn};
 use symphonia::core::{
-    audio::{SampleBuffer, SignalSpec},
-    codecs::{DecoderOptions, CODEC_TYPE_NULL},
+    audio::{AudioBuffer, AudioBufferRef, Signal, SignalSpec},
+    codecs::{self, DecoderOptions, CODEC_TYPE_NULL},
+    conv::IntoSample,
     errors::Error,
-    formats::FormatOptions,
-    io::{MediaSourceStream, MediaSourceStreamOptions},
-    meta::{MetadataOptions, MetadataRevision},
-    probe::Hint,
+    formats::{FormatReader, SeekMode, SeekTo},
+    meta::{MetadataRevision, StandardTagKey},
+    units::{Time, TimeBase},
 };
 
-use crate::song::Song;
+use crate::song::{probe, Chapter, Gapless, Song};
+
+use super::facade::TrackInfo;
+
+/// give up on files that fail to decode this many packets in a row
+const MAX_CONSECUTIVE_ERRORS: usize = 100;
+
+/// decodes the selected track of a song packet by packet
+pub struct SongDecoder {
+    format_reader: Box<dyn FormatReader>,
+    decoder: Box<dyn codecs::Decoder>,
+    track_id: u32,
+    signal_spec: SignalSpec,
+    /// encoder delay and padding to cut off, for codecs symphonia does not trim itself
+    trim: Option<Gapless>,
+    time_base: Option<TimeBase>,
+}
+
+impl SongDecoder {
+    /// decode the next packet, returning its interleaved samples if it belongs to the decoded track
+    /// and whether the end of the stream was reached
+    pub fn next(&mut self) -> anyhow::Result<(Option<Vec<f32>>, bool)> {
+        match self.format_reader.next_packet() {
+            Ok(packet) => {
+                if packet.track_id() == self.track_id {
+                    let data = match self.decoder.decode(&packet) {
+                        Ok(d) => d,
+                        Err(e) => {
+                            anyhow::bail!("Failed to decode packet {:?}", e);
+                        }
+                    };
+
+                    let samples = interleave(data, self.signal_spec.channels.count());
+                    let samples = self.trim(packet.ts(), &samples);
+
+                    trace!(
+                        "Decoded packet for track {} ({} bytes)",
+                        packet.track_id(),
+                        packet.data.len()
+                    );
+
+                    Ok((Some(samples), false))
+                } else {
+                    trace!(
+                        "Skipping packet for track {} ({} bytes)",
+                        packet.track_id(),
+                        packet.data.len()
+                    );
+                    Ok((None, false))
+                }
+            }
+            Err(Error::IoError(e)) if e.to_string() == "end of stream" => Ok((None, true)),
+            Err(e) => {
+                anyhow::bail!("Failed to read packet {:?}", e);
+            }
+        }
+    }
+
+    /// the interleaved samples of the remaining packets up to the end of the stream, for decoding a whole song,
+    /// packets that fail to decode are skipped until `MAX_CONSECUTIVE_ERRORS` fail in a row, which ends it with the error
+    pub fn buffers(&mut self) -> Buffers<'_> {
+        Buffers {
+            decoder: self,
+            done: false,
+        }
+    }
+
+    /// the samples of the packet starting at `ts` that are neither encoder delay nor padding
+    fn trim(&self, ts: u64, samples: &[f32]) -> Vec<f32> {
+        let (Some(trim), Some(time_base)) = (self.trim, self.time_base) else {
+            return samples.to_vec();
+        };
+
+        let channels = self.signal_spec.channels.count();
+        let start = (ts as u128 * self.signal_spec.rate as u128 * time_base.numer as u128
+            / time_base.denom as u128) as u64;
+        let end = start + (samples.len() / channels) as u64;
+
+        let from = trim.delay.clamp(start, end) - start;
+        let to = (trim.delay + trim.frames).clamp(start, end) - start;
+        samples[from as usize * channels..to as usize * channels].to_vec()
+    }
 
-pub type Decoder = dyn FnMut() -> anyhow::Result<(Option<SampleBuffer<f32>>, bool)> + Send;
+    /// continue decoding at `position`
+    pub fn seek(&mut self, position: Duration) -> anyhow::Result<()> {
+        // positions are within the audio, after the encoder delay
+        let position = match self.trim {
+            Some(trim) => {
+                position + Duration::from_secs_f64(trim.delay as f64 / self.signal_spec.rate as f64)
+            }
+            None => position,
+        };
+
+        self.format_reader
+            .seek(
+                SeekMode::Accurate,
+                SeekTo::Time {
+                    time: Time::from(position.as_secs_f64()),
+                    track_id: Some(self.track_id),
+                },
+            )
+            .context(format!("Failed to seek to {position:?}"))?;
+        self.decoder.reset();
+
+        Ok(())
+    }
+}
+
+/// the decoded packets of a `SongDecoder`, see `SongDecoder::buffers`
+pub struct Buffers<'a> {
+    decoder: &'a mut SongDecoder,
+    done: bool,
+}
+
+impl Iterator for Buffers<'_> {
+    type Item = anyhow::Result<Vec<f32>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut errors = 0;
+        while !self.done {
+            match self.decoder.next() {
+                Ok((Some(buffer), eof)) => {
+                    self.done = eof;
+                    return Some(Ok(buffer));
+                }
+                Ok((None, eof)) => (self.done, errors) = (eof, 0),
+                Err(e) => {
+                    errors += 1;
+                    if errors >= MAX_CONSECUTIVE_ERRORS {
+                        self.done = true;
+                        return Some(Err(e.context("Too many decoding errors")));
+                    }
+                    warn!("Skipping packet: {e:?}");
+                }
+            }
+        }
+
+        None
+    }
+}
 
 pub struct LoadedSong {
     pub song: Song,
     pub metadata: Option<MetadataRevision>,
     pub signal_spec: SignalSpec,
-    pub decoder: Box<Decoder>,
+    pub decoder: SongDecoder,
+    /// position the decoder starts at
+    pub start: Duration,
+    /// all audio tracks of the container
+    pub tracks: Vec<TrackInfo>,
+    /// id of the track being decoded
+    pub track_id: u32,
+    pub chapters: Vec<Chapter>,
 }
 
 impl LoadedSong {
-    pub fn load(song: Song) -> anyhow::Result<Self> {
-        let src = std::fs::File::open(song.path.as_ref()).context(format!(
-            "Failed to open file {}",
-            song.path.to_string_lossy()
-        ))?;
-
-        let mss = MediaSourceStream::new(Box::new(src), MediaSourceStreamOptions::default());
-        let mut probed = symphonia::default::get_probe().format(
-            &Hint::new(),
-            mss,
-            &FormatOptions::default(),
-            &MetadataOptions::default(),
-        )?;
+    /// load `song` starting at `start`, decoding the track with id `track`
+    /// or the first track matching `language` or else the first audio track
+    pub fn load(
+        song: Song,
+        start: Duration,
+        track: Option<u32>,
+        language: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        let mut probed = probe(&song.path)?;
 
         let metadata = {
             let mut meta = probed.format.metadata();
             meta.skip_to_latest().cloned()
         };
 
-        let mut format_reader = probed.format;
+        let format_reader = probed.format;
 
-        let track = format_reader
+        let audio_tracks = format_reader
             .tracks()
             .iter()
-            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
-            .ok_or(anyhow::anyhow!("No audio tracks found"))?;
+            .filter(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .collect::<Vec<_>>();
+
+        let track = match track {
+            Some(id) => audio_tracks
+                .iter()
+                .find(|t| t.id == id)
+                .ok_or(anyhow::anyhow!("Track {id} not found"))?,
+            None => language
+                .and_then(|l| {
+                    audio_tracks.iter().find(|t| {
+                        t.language
+                            .as_deref()
+                            .is_some_and(|tl| tl.eq_ignore_ascii_case(l))
+                    })
+                })
+                .or(audio_tracks.first())
+                .ok_or(anyhow::anyhow!("No audio tracks found"))?,
+        };
+
+        let tracks = audio_tracks
+            .iter()
+            .map(|t| TrackInfo {
+                id: t.id,
+                language: t.language.clone(),
+                codec: symphonia::default::get_codecs()
+                    .get_codec(t.codec_params.codec)
+                    .map(|d| d.short_name.to_string())
+                    .unwrap_or(format!("{}", t.codec_params.codec)),
+                channels: t.codec_params.channels.map(|c| c.count()),
+                sample_rate: t.codec_params.sample_rate,
+            })
+            .collect::<Vec<_>>();
 
         let codec_params = track.codec_params.clone();
         debug!("Codec params: {:?}", codec_params);
         let track_id = track.id;
 
-        let mut decoder = symphonia::default::get_codecs()
-            .make(&track.codec_params, &DecoderOptions::default())?;
+        let chapters = match codec_params.time_base {
+            Some(time_base) if !format_reader.cues().is_empty() => {
+                cue_chapters(format_reader.as_ref(), time_base)
+            }
+            _ => song.tag_chapters(),
+        };
+        debug!("Chapters: {:?}", chapters);
+
+        let decoder =
+            symphonia::default::get_codecs().make(&codec_params, &DecoderOptions::default())?;
 
         let signal_spec = SignalSpec::new(
             codec_params
@@ -67,46 +248,98 @@ impl LoadedSong {
         );
         debug!("Signal spec: {:?}", signal_spec);
 
-        let decoder = move || match format_reader.next_packet() {
-            Ok(packet) => {
-                if packet.track_id() == track_id {
-                    let data = match decoder.decode(&packet) {
-                        Ok(d) => d,
-                        Err(e) => {
-                            anyhow::bail!("Failed to decode packet {:?}", e);
-                        }
-                    };
+        // symphonia trims mp3 files with a lame header itself and reports the delay in the codec params
+        let trim = song.gapless().filter(|_| codec_params.delay.is_none());
+        debug!("Gapless: {:?}", trim);
 
-                    let mut sample_buffer = SampleBuffer::new(data.capacity() as u64, signal_spec);
-                    sample_buffer.copy_interleaved_ref(data);
-
-                    trace!(
-                        "Decoded packet for track {} ({} bytes)",
-                        packet.track_id(),
-                        packet.data.len()
-                    );
-
-                    Ok((Some(sample_buffer), false))
-                } else {
-                    trace!(
-                        "Skipping packet for track {} ({} bytes)",
-                        packet.track_id(),
-                        packet.data.len()
-                    );
-                    Ok((None, false))
-                }
-            }
-            Err(Error::IoError(e)) if e.to_string() == "end of stream" => Ok((None, true)),
-            Err(e) => {
-                anyhow::bail!("Failed to read packet {:?}", e);
-            }
+        let mut decoder = SongDecoder {
+            format_reader,
+            decoder,
+            track_id,
+            signal_spec,
+            trim,
+            time_base: codec_params.time_base,
         };
 
+        if !start.is_zero() {
+            decoder.seek(start)?;
+        }
+
         Ok(Self {
             song,
             metadata,
             signal_spec,
-            decoder: Box::new(decoder),
+            decoder,
+            start,
+            tracks,
+            track_id,
+            chapters,
         })
     }
 }
+
+/// the samples of a decoded packet of any sample format as interleaved f32 samples with `channels` channels,
+/// packets with fewer channels repeat their last channel and extra channels are dropped,
+/// which keeps the stream intact when a packet does not match the codec parameters
+fn interleave(data: AudioBufferRef, channels: usize) -> Vec<f32> {
+    match data {
+        AudioBufferRef::U8(b) => interleave_planes(&b, channels),
+        AudioBufferRef::U16(b) => interleave_planes(&b, channels),
+        AudioBufferRef::U24(b) => interleave_planes(&b, channels),
+        AudioBufferRef::U32(b) => interleave_planes(&b, channels),
+        AudioBufferRef::S8(b) => interleave_planes(&b, channels),
+        AudioBufferRef::S16(b) => interleave_planes(&b, channels),
+        AudioBufferRef::S24(b) => interleave_planes(&b, channels),
+        AudioBufferRef::S32(b) => interleave_planes(&b, channels),
+        AudioBufferRef::F32(b) => interleave_planes(&b, channels),
+        AudioBufferRef::F64(b) => interleave_planes(&b, channels),
+    }
+}
+
+fn interleave_planes<S>(buffer: &AudioBuffer<S>, channels: usize) -> Vec<f32>
+where
+    S: symphonia::core::sample::Sample + IntoSample<f32>,
+{
+    let planes = buffer.planes();
+    let planes = planes.planes();
+    if planes.is_empty() {
+        return vec![0.0; buffer.frames() * channels];
+    }
+
+    (0..buffer.frames())
+        .flat_map(|frame| {
+            (0..channels).map(move |channel| {
+                let sample: f32 = planes[channel.min(planes.len() - 1)][frame].into_sample();
+                // nan or infinity in float files would poison the dsp filters
+                if sample.is_finite() {
+                    sample
+                } else {
+                    0.0
+                }
+            })
+        })
+        .collect()
+}
+
+/// chapters from the cue points of the container, e.g. m4b chapters or flac cuesheets
+fn cue_chapters(format_reader: &dyn FormatReader, time_base: TimeBase) -> Vec<Chapter> {
+    let mut chapters = format_reader
+        .cues()
+        .iter()
+        .map(|cue| {
+            let time = time_base.calc_time(cue.start_ts);
+            Chapter {
+                title: cue
+                    .tags
+                    .iter()
+                    .find(|t| t.std_key == Some(StandardTagKey::TrackTitle))
+                    .map(|t| t.value.to_string())
+                    .unwrap_or(format!("Chapter {}", cue.index)),
+                start: Duration::from_secs_f64(time.seconds as f64 + time.frac),
+            }
+        })
+        .collect::<Vec<_>>();
+
+    chapters.sort_by_key(|c| c.start);
+    chapters
+}
@@ -1,24 +1,122 @@
 use crate::{
-    cache::Cache,
-    song::{Song, StandardTagKey},
+    album,
+    cache::{Cache, SharedCache},
+    config::Config,
+    notification::Notification,
+    song::{Chapter, Song, StandardTagKey},
+    stats::{Listen, Stats, MAX_RATING},
+    tags,
 };
 use anyhow::Context;
-use log::warn;
-use souvlaki::{MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig};
+use log::{info, warn};
 use std::{
-    collections::VecDeque,
-    io::Write,
-    sync::{mpsc, Arc, RwLock},
+    collections::{BTreeMap, VecDeque},
+    sync::{atomic::AtomicBool, mpsc, Arc, Mutex, PoisonError, RwLock},
+    thread::JoinHandle,
+    time::Duration,
 };
 use symphonia::core::meta::MetadataRevision;
-use tempfile::NamedTempFile;
 
-use self::{command::Command, facade::PlayerFacade, loader::LoadedSong, playback::Playback};
+pub use self::playback::set_media_role;
 
+use self::{
+    command::Command,
+    dsp::{ActivePreset, CrossfeedParams, DspPreset, SharedPreset},
+    error::PlayerError,
+    facade::{PlayerFacade, TrackInfo},
+    fifo::FifoSink,
+    hooks::HookRunner,
+    loader::LoadedSong,
+    mpris::Mpris,
+    playback::Playback,
+    sink::{AudioSink, DeviceSink, Zone, ZoneSink},
+    supervisor::Supervisor,
+};
+
+pub mod analysis;
+mod art;
 pub mod command;
+mod dither;
+pub mod dsp;
+mod encode;
+mod envelope;
+pub mod error;
+pub mod export;
 pub mod facade;
+mod fifo;
+mod hooks;
 mod loader;
+#[cfg(feature = "mpris")]
+mod mpris;
 mod playback;
+mod power;
+pub mod sink;
+pub mod supervisor;
+pub mod tempo;
+
+/// stand-in for the media controls when built without the `mpris` feature
+#[cfg(not(feature = "mpris"))]
+mod mpris {
+    use std::{path::PathBuf, sync::mpsc};
+
+    use super::{command::Command, facade::PlayerFacade};
+
+    pub struct Mpris;
+
+    impl Mpris {
+        pub fn new(_tx: mpsc::Sender<Command>, _art_cache: PathBuf) -> anyhow::Result<Self> {
+            Ok(Self)
+        }
+
+        pub fn update(&mut self, _facade: &PlayerFacade) {}
+    }
+}
+
+/// the song at `path` from the cache with all of its tags, songs outside of the library are loaded on the fly,
+/// the songs of continuous albums get their album gain, so that the level does not jump between their tracks
+fn lookup(cache: &Cache, path: &std::path::Path) -> Result<Song, PlayerError> {
+    match cache.get(path).context("Failed to get song from cache")? {
+        Some(entry) => {
+            let song = entry.as_file().context("Song is not a file")?;
+            let mut full = song.full().into_owned();
+            if let Some(gain) = song
+                .album_gain_factor()
+                .filter(|_| cache.is_continuous(song))
+            {
+                full.gain_factor = gain;
+            }
+            Ok(full)
+        }
+        None => Song::load(path).map_err(|source| PlayerError::NotInCache {
+            path: path.to_path_buf(),
+            source,
+        }),
+    }
+}
+
+/// going to the previous chapter restarts the current one if it played longer than this
+const PREVIOUS_CHAPTER_THRESHOLD: Duration = Duration::from_secs(3);
+
+/// a song counts as played once this fraction of it was played
+const PLAY_COUNT_THRESHOLD: f64 = 0.5;
+
+/// songs listened to for less than this are not added to the history
+const HISTORY_MIN_LISTENED: Duration = Duration::from_secs(10);
+
+/// a stream failing before playing this long is considered broken and skipped instead of restarted
+const RECOVERY_MIN_PLAYED: Duration = Duration::from_secs(1);
+
+/// interval the position shown by the media controls of windows and macOS is refreshed in
+const MEDIA_CONTROLS_INTERVAL: Duration = Duration::from_secs(1);
+
+/// how long the beginning of a song is previewed
+const PREVIEW_LENGTH: Duration = Duration::from_secs(15);
+
+/// factor the output is scaled by during a preview
+const PREVIEW_VOLUME: f32 = 0.5;
+
+/// the song opened by the loader thread, or why it could not be opened
+type LoadResult = Result<(Song, LoadedSong), PlayerError>;
 
 #[allow(clippy::large_enum_variant)]
 enum InternalPlayerStatus {
@@ -26,22 +124,85 @@ enum InternalPlayerStatus {
         song: Song,
         metadata: Option<MetadataRevision>,
         playback: Playback,
+        tracks: Vec<TrackInfo>,
+        track_id: u32,
+        chapters: Vec<Chapter>,
+    },
+    Loading {
+        path: Box<std::path::Path>,
+        /// filled by the loader thread before it sends `Command::SongLoaded`
+        result: Arc<Mutex<Option<LoadResult>>>,
+        /// start the song paused, e.g. when it was paused while loading
+        paused: bool,
     },
     Stopped,
 }
 
+/// what was playing before a preview, to continue with it afterwards
+struct Preview {
+    /// the song previewed
+    path: Box<std::path::Path>,
+    /// the song that was playing or loading with its position, track and whether it was paused
+    resume: Option<(Box<std::path::Path>, Duration, Option<u32>, bool)>,
+    /// the fade before the preview lowered it
+    fade: f32,
+}
+
+/// handles shared between the player thread and its supervisor
+#[derive(Clone)]
+struct PlayerContext {
+    cache: SharedCache,
+    config: Arc<Config>,
+    notifications: mpsc::Sender<Notification>,
+    tx: mpsc::Sender<Command>,
+    rx: Arc<Mutex<mpsc::Receiver<Command>>>,
+    facade: Arc<RwLock<PlayerFacade>>,
+    fifo: Option<FifoSink>,
+    dsp: SharedPreset,
+    stats: Arc<Mutex<Stats>>,
+    fade: Arc<RwLock<f32>>,
+    volume: Arc<RwLock<f32>>,
+    random_albums: Arc<AtomicBool>,
+    sink: Arc<dyn AudioSink>,
+}
+
 pub struct Player {
-    cache: Arc<Cache>,
+    cache: SharedCache,
+    config: Arc<Config>,
     status: InternalPlayerStatus,
     queue: VecDeque<Box<std::path::Path>>,
-    media_controls: MediaControls,
+    /// `None` when running without an audio device
+    mpris: Option<Mpris>,
     command_tx: mpsc::Sender<Command>,
+    fifo: Option<FifoSink>,
+    presets: BTreeMap<String, DspPreset>,
+    dsp: SharedPreset,
+    stats: Arc<Mutex<Stats>>,
+    /// the stats of the current song changed and need to be written to its tags
+    tags_dirty: bool,
+    /// factor the output is scaled by, shared with the audio stream
+    fade: Arc<RwLock<f32>>,
+    /// volume set by the user, shared with the audio stream
+    volume: Arc<RwLock<f32>>,
+    /// enqueue a random album when the queue runs out
+    random_albums: Arc<AtomicBool>,
+    sink: Arc<dyn AudioSink>,
+    /// the output device the current volume belongs to
+    device: Option<String>,
+    /// set while a song is previewed instead of the current one
+    preview: Option<Preview>,
+    facade: Arc<RwLock<PlayerFacade>>,
 }
 
 impl Player {
+    /// make the current state visible to the ui
+    fn publish(&self) {
+        *self.facade.write().unwrap() = PlayerFacade::from_player(self);
+    }
+
     /// command player to continue playing or start playing the next song
-    fn play(&mut self) -> anyhow::Result<()> {
-        match &self.status {
+    fn play(&mut self) -> Result<(), PlayerError> {
+        match &mut self.status {
             InternalPlayerStatus::PlayingOrPaused { playback, .. } => {
                 if playback.pause.load(std::sync::atomic::Ordering::Relaxed) {
                     playback
@@ -49,44 +210,420 @@ impl Player {
                         .store(false, std::sync::atomic::Ordering::Relaxed);
                 }
             }
+            InternalPlayerStatus::Loading { paused, .. } => *paused = false,
             InternalPlayerStatus::Stopped => {}
         }
 
         if matches!(self.status, InternalPlayerStatus::Stopped) {
+            if self.queue.is_empty()
+                && self
+                    .random_albums
+                    .load(std::sync::atomic::Ordering::Relaxed)
+            {
+                self.enqueue_random_album()?;
+            }
+
+            // the queue is kept for when a device is connected
+            if !self.queue.is_empty() && !self.sink.available() {
+                return Err(PlayerError::DeviceUnavailable);
+            }
             if let Some(path) = self.queue.pop_front() {
-                let song = self
-                    .cache
-                    .get(path)
-                    .context("Failed to get song from cache")?
-                    .ok_or(anyhow::anyhow!("Song not found in cache"))?
-                    .as_file()
-                    .context("Song is not a file")?
-                    .clone();
-
-                let loaded_song = LoadedSong::load(song.clone()).context("Failed to load song")?;
-
-                let metadata = loaded_song.metadata.clone();
-                let playback = Playback::new(self.command_tx.clone(), loaded_song)?;
-
-                self.status = InternalPlayerStatus::PlayingOrPaused {
-                    song,
-                    metadata,
-                    playback,
-                }
+                self.load(path, Duration::ZERO, None)?;
             }
         }
 
         Ok(())
     }
 
-    /// command player to pause
-    fn pause(&mut self) -> anyhow::Result<()> {
+    /// add all songs of a random album to the queue, playing it if the player is stopped
+    fn enqueue_random_album(&mut self) -> Result<(), PlayerError> {
+        let (key, tracks) = {
+            let stats = self.stats.lock().unwrap();
+            let history = stats.history();
+            let recent = &history[history.len().saturating_sub(self.config.shuffle_history)..];
+
+            album::random(&self.cache.load(), recent).ok_or(PlayerError::NoAlbums)?
+        };
+        info!("Enqueueing random album {}", key.album);
+
+        self.queue
+            .extend(tracks.into_iter().map(std::path::PathBuf::into_boxed_path));
+        if matches!(self.status, InternalPlayerStatus::Stopped) {
+            self.play()?;
+        }
+
+        Ok(())
+    }
+
+    fn toggle_random_albums(&mut self) -> Result<(), PlayerError> {
+        let enabled = !self
+            .random_albums
+            .fetch_xor(true, std::sync::atomic::Ordering::Relaxed);
+        info!("Random albums: {enabled}");
+
+        Ok(())
+    }
+
+    /// load a song in the background and start playing it at the given position once it is opened,
+    /// opening a file on network storage can take seconds, in which the player keeps handling commands
+    /// without an explicit `track`, the track is picked by the configured language preference
+    fn load<P: AsRef<std::path::Path>>(
+        &mut self,
+        path: P,
+        position: Duration,
+        track: Option<u32>,
+    ) -> Result<(), PlayerError> {
+        let path: Box<std::path::Path> = path.as_ref().into();
+        let result = Arc::new(Mutex::new(None));
+
+        let result2 = result.clone();
+        let path2 = path.clone();
+        let cache = self.cache.clone();
+        let config = self.config.clone();
+        let tx = self.command_tx.clone();
+        std::thread::Builder::new()
+            .name("loader thread".to_string())
+            .spawn(move || {
+                let loaded = lookup(&cache.load(), &path2).and_then(|song| {
+                    LoadedSong::load(
+                        song.clone(),
+                        position,
+                        track,
+                        config.preferred_track_language.as_deref(),
+                    )
+                    .map(|loaded| (song, loaded))
+                    .map_err(|source| PlayerError::DecodeFailed {
+                        path: path2.to_path_buf(),
+                        source,
+                    })
+                });
+
+                *result2.lock().unwrap() = Some(loaded);
+                tx.send(Command::SongLoaded)
+                    .unwrap_or_else(|e| warn!("Failed to send loaded song: {e:?}"));
+            })
+            .context("Failed to spawn loader thread")?;
+
+        // a song loading in the background is dropped along with its result
+        self.status = InternalPlayerStatus::Loading {
+            path,
+            result,
+            paused: false,
+        };
+
+        Ok(())
+    }
+
+    /// start playing the song opened by the loader thread, if it is still the one to be played
+    fn song_loaded(&mut self) -> Result<(), PlayerError> {
+        let InternalPlayerStatus::Loading { result, paused, .. } = &self.status else {
+            return Ok(());
+        };
+        let Some(result) = result.lock().unwrap().take() else {
+            return Ok(());
+        };
+        let paused = *paused;
+
+        match result {
+            Ok((song, loaded_song)) => self.start(song, loaded_song, paused),
+            Err(e) => {
+                self.status = InternalPlayerStatus::Stopped;
+                Err(e)
+            }
+        }
+    }
+
+    /// the peak of `song` after normalization and the preamp of the dsp preset, before the volume,
+    /// with the album peak for continuous albums played with their album gain
+    fn output_peak(&self, song: &Song) -> Option<f32> {
+        let peak = match self.cache.load().is_continuous(song) {
+            true => song.album_peak(),
+            false => song.peak(),
+        }?;
+        let preamp = 10_f32.powf(self.dsp.read().unwrap().preset.preamp / 20.0);
+
+        Some(peak * song.normalization(self.config.loudness_target.0) * preamp)
+    }
+
+    /// start playing `song` opened as `loaded_song`
+    fn start(
+        &mut self,
+        mut song: Song,
+        mut loaded_song: LoadedSong,
+        paused: bool,
+    ) -> Result<(), PlayerError> {
+        if let Some(peak) = self
+            .output_peak(&song)
+            .filter(|&p| self.config.prevent_clipping && p > 1.0)
+        {
+            info!(
+                "Lowering the gain of {} by {:.1} dB to prevent clipping",
+                song.path.display(),
+                20.0 * peak.log10()
+            );
+            song.gain_factor /= peak;
+            loaded_song.song.gain_factor = song.gain_factor;
+        }
+
+        let metadata = loaded_song.metadata.clone();
+        let tracks = loaded_song.tracks.clone();
+        let track_id = loaded_song.track_id;
+        let chapters = loaded_song.chapters.clone();
+        self.restore_device_volume();
+        let playback = Playback::new(
+            self.command_tx.clone(),
+            loaded_song,
+            self.config.loudness_target.0,
+            self.fifo.clone(),
+            self.dsp.clone(),
+            self.fade.clone(),
+            self.volume.clone(),
+            self.sink.as_ref(),
+            paused,
+        )
+        .inspect_err(|_| self.status = InternalPlayerStatus::Stopped)?;
+
+        self.compute_envelope(&song);
+
+        self.status = InternalPlayerStatus::PlayingOrPaused {
+            song,
+            metadata,
+            playback,
+            tracks,
+            track_id,
+            chapters,
+        };
+
+        Ok(())
+    }
+
+    /// restart the audio stream of the current song at its position after the stream failed,
+    /// the song is skipped if the stream fails again right after starting
+    fn recover_playback(&mut self, error: String) -> Result<(), PlayerError> {
+        let (path, position, start, track_id) = match &self.status {
+            InternalPlayerStatus::PlayingOrPaused {
+                song,
+                playback,
+                track_id,
+                ..
+            } => (
+                song.path.clone(),
+                *playback.played_duration.read().unwrap(),
+                playback.start,
+                *track_id,
+            ),
+            InternalPlayerStatus::Loading { .. } | InternalPlayerStatus::Stopped => return Ok(()),
+        };
+        warn!("Playback of {path:?} failed at {position:?}: {error}");
+
+        self.status = InternalPlayerStatus::Stopped;
+        if position.saturating_sub(start) > RECOVERY_MIN_PLAYED {
+            self.load(&path, position, Some(track_id))?;
+            Err(PlayerError::PlaybackRestarted(error))
+        } else {
+            self.play()?;
+            Err(PlayerError::PlaybackSkipped {
+                path: path.to_path_buf(),
+                error,
+            })
+        }
+    }
+
+    /// compute the amplitude envelope of `song` in the background if it is not cached yet
+    fn compute_envelope(&self, song: &Song) {
+        let cache = self.cache.load();
+        if !cache.claim_envelope(&song.path) {
+            return;
+        }
+
+        let song = song.clone();
+        std::thread::Builder::new()
+            .name("envelope thread".to_string())
+            .spawn(move || {
+                let envelope = envelope::compute(&song)
+                    .map_err(|e| {
+                        warn!(
+                            "Failed to compute envelope of {}: {e:?}",
+                            song.path.display()
+                        )
+                    })
+                    .ok();
+                cache.insert_envelope(&song.path, envelope);
+            })
+            .map_err(|e| warn!("Failed to spawn envelope thread: {e:?}"))
+            .ok();
+    }
+
+    /// switch the current song to another track of its container, keeping the position
+    fn select_track(&mut self, id: u32) -> Result<(), PlayerError> {
+        let (path, position, paused) = match &self.status {
+            InternalPlayerStatus::PlayingOrPaused { song, playback, .. } => (
+                song.path.clone(),
+                *playback.played_duration.read().unwrap(),
+                playback.pause.load(std::sync::atomic::Ordering::Relaxed),
+            ),
+            InternalPlayerStatus::Loading { .. } | InternalPlayerStatus::Stopped => {
+                return Err(PlayerError::NotPlaying)
+            }
+        };
+
+        self.load(path, position, Some(id))?;
+        if paused {
+            self.pause()?;
+        }
+
+        Ok(())
+    }
+
+    /// apply the preset `name`, reloading the presets so edited files take effect
+    fn select_preset(&mut self, name: String) -> Result<(), PlayerError> {
+        self.presets = dsp::load_presets(&self.config.presets_directory)?;
+
+        let preset = self
+            .presets
+            .get(&name)
+            .ok_or(PlayerError::PresetNotFound(name.clone()))?
+            .clone();
+        info!("Selected dsp preset {name}: {preset:?}");
+
+        let mut active = self.dsp.write().unwrap();
+        if let Some(crossfeed) = preset.crossfeed {
+            active.crossfeed = crossfeed.then(|| CrossfeedParams::from(&self.config.crossfeed));
+        }
+        active.name = Some(name);
+        active.preset = Arc::new(preset);
+
+        Ok(())
+    }
+
+    fn toggle_crossfeed(&mut self) -> Result<(), PlayerError> {
+        let mut active = self.dsp.write().unwrap();
+        active.crossfeed = match active.crossfeed {
+            Some(_) => None,
+            None => Some(CrossfeedParams::from(&self.config.crossfeed)),
+        };
+        info!("Crossfeed: {:?}", active.crossfeed);
+
+        Ok(())
+    }
+
+    /// save the active dsp settings as preset `name`
+    fn save_preset(&mut self, name: String) -> Result<(), PlayerError> {
+        let preset = self.dsp.read().unwrap().preset.clone();
+        preset
+            .save(&dsp::preset_path(&self.config.presets_directory, &name)?)
+            .context("Failed to save preset")?;
+
+        self.presets.insert(name.clone(), (*preset).clone());
+        self.dsp.write().unwrap().name = Some(name);
+
+        Ok(())
+    }
+
+    /// continue playing the current song at `position`
+    fn seek(&mut self, position: Duration) -> Result<(), PlayerError> {
         match &self.status {
+            InternalPlayerStatus::PlayingOrPaused { song, playback, .. } => {
+                playback.seek(position.min(song.duration));
+            }
+            InternalPlayerStatus::Loading { .. } | InternalPlayerStatus::Stopped => {
+                return Err(PlayerError::NotPlaying)
+            }
+        }
+
+        Ok(())
+    }
+
+    fn seek_by(&mut self, offset: Duration, forward: bool) -> Result<(), PlayerError> {
+        let position = match &self.status {
+            InternalPlayerStatus::PlayingOrPaused { playback, .. } => playback.target_position(),
+            _ => return Err(PlayerError::NotPlaying),
+        };
+
+        self.seek(if forward {
+            position + offset
+        } else {
+            position.saturating_sub(offset)
+        })
+    }
+
+    /// jump to the start of the next or previous chapter of the current song
+    fn skip_chapter(&mut self, forward: bool) -> Result<(), PlayerError> {
+        let (chapters, position) = match &self.status {
+            InternalPlayerStatus::PlayingOrPaused {
+                chapters, playback, ..
+            } => (chapters, *playback.played_duration.read().unwrap()),
+            InternalPlayerStatus::Loading { .. } | InternalPlayerStatus::Stopped => {
+                return Err(PlayerError::NotPlaying)
+            }
+        };
+
+        if chapters.is_empty() {
+            return Err(PlayerError::NoChapters);
+        }
+
+        let current = chapters.iter().rposition(|c| c.start <= position);
+        let target = if forward {
+            chapters
+                .get(current.map_or(0, |i| i + 1))
+                .ok_or(PlayerError::NoNextChapter)?
+                .start
+        } else {
+            match current {
+                Some(i) if position - chapters[i].start > PREVIOUS_CHAPTER_THRESHOLD => {
+                    chapters[i].start
+                }
+                Some(i) if i > 0 => chapters[i - 1].start,
+                _ => Duration::ZERO,
+            }
+        };
+
+        self.seek(target)
+    }
+
+    /// remember the position of the current song as cue point `number` in the cache
+    fn set_cue_point(&mut self, number: usize) -> Result<(), PlayerError> {
+        let InternalPlayerStatus::PlayingOrPaused { song, playback, .. } = &self.status else {
+            return Err(PlayerError::NotPlaying);
+        };
+
+        let position = playback.target_position();
+        info!(
+            "Setting cue point {number} of {} to {position:?}",
+            song.path.display()
+        );
+        self.cache
+            .load()
+            .set_cue_point(&song.path, number, Some(position))?;
+
+        Ok(())
+    }
+
+    fn jump_to_cue_point(&mut self, number: usize) -> Result<(), PlayerError> {
+        let InternalPlayerStatus::PlayingOrPaused { song, .. } = &self.status else {
+            return Err(PlayerError::NotPlaying);
+        };
+
+        let position = self
+            .cache
+            .load()
+            .cue_points(&song.path)
+            .get(number.wrapping_sub(1))
+            .copied()
+            .flatten()
+            .ok_or(PlayerError::NoCuePoint(number))?;
+        self.seek(position)
+    }
+
+    /// command player to pause
+    fn pause(&mut self) -> Result<(), PlayerError> {
+        match &mut self.status {
             InternalPlayerStatus::PlayingOrPaused { playback, .. } => {
                 playback
                     .pause
                     .store(true, std::sync::atomic::Ordering::Relaxed);
             }
+            InternalPlayerStatus::Loading { paused, .. } => *paused = true,
             InternalPlayerStatus::Stopped => {}
         }
 
@@ -94,13 +631,14 @@ impl Player {
     }
 
     /// command player to play if paused or pause if playing
-    fn play_pause(&mut self) -> anyhow::Result<()> {
-        match &self.status {
+    fn play_pause(&mut self) -> Result<(), PlayerError> {
+        match &mut self.status {
             InternalPlayerStatus::PlayingOrPaused { playback, .. } => {
                 playback
                     .pause
                     .fetch_xor(true, std::sync::atomic::Ordering::Relaxed);
             }
+            InternalPlayerStatus::Loading { paused, .. } => *paused = !*paused,
             InternalPlayerStatus::Stopped => {}
         }
 
@@ -108,23 +646,337 @@ impl Player {
     }
 
     /// command player to stop
-    fn stop(&mut self) -> anyhow::Result<()> {
+    fn stop(&mut self) -> Result<(), PlayerError> {
+        self.finish_song();
         self.status = InternalPlayerStatus::Stopped;
 
         Ok(())
     }
 
+    /// stop before ramp exits, the tags are written before the player reports it stopped
+    fn shutdown(&mut self) -> Result<(), PlayerError> {
+        info!("Shutting down the player");
+        let tag_thread = self.finish_song();
+        self.status = InternalPlayerStatus::Stopped;
+        self.mpris = None;
+
+        if let Some(thread) = tag_thread {
+            thread
+                .join()
+                .map_err(|_| anyhow::anyhow!("Failed to write the stats to the tags"))?;
+        }
+
+        Ok(())
+    }
+
+    /// count the play of the current song if enough of it was played, add it to the history
+    /// and write its stats to its tags if they changed in the returned thread
+    fn finish_song(&mut self) -> Option<JoinHandle<()>> {
+        let InternalPlayerStatus::PlayingOrPaused { song, playback, .. } = &self.status else {
+            return None;
+        };
+
+        let position = *playback.played_duration.read().unwrap();
+        let listened = *playback.listened.read().unwrap();
+        let mut stats = self.stats.lock().unwrap();
+
+        let counted = position.as_secs_f64() >= song.duration.as_secs_f64() * PLAY_COUNT_THRESHOLD;
+        if counted {
+            stats
+                .entry(&song.path, || tags::read_stats(song))
+                .play_count += 1;
+            self.tags_dirty = true;
+        }
+
+        let recorded = listened >= HISTORY_MIN_LISTENED;
+        if recorded {
+            let tag = |key| song.standard_tags.get(&key).map(|v| v.to_string());
+            stats.record(Listen {
+                time: chrono::Local::now(),
+                path: song.path.to_path_buf(),
+                listened,
+                title: tag(StandardTagKey::TrackTitle),
+                artist: tag(StandardTagKey::Artist),
+                album: tag(StandardTagKey::Album),
+            });
+        }
+
+        if counted || recorded {
+            stats
+                .save(&self.config.stats_path)
+                .unwrap_or_else(|e| warn!("Failed to save stats: {e:?}"));
+        }
+
+        let mut tag_thread = None;
+        if self.config.write_tags && self.tags_dirty {
+            let path = song.path.clone();
+            let song_stats = stats.get(&path, || tags::read_stats(song));
+            tag_thread = std::thread::Builder::new()
+                .name("tag thread".to_string())
+                .spawn(move || {
+                    tags::write_stats(&path, song_stats).unwrap_or_else(|e| {
+                        warn!("Failed to write stats to {}: {e:?}", path.display())
+                    })
+                })
+                .map_err(|e| warn!("Failed to spawn tag thread: {e:?}"))
+                .ok();
+        }
+        self.tags_dirty = false;
+
+        tag_thread
+    }
+
+    /// rate the current song, its tags are written once it stopped playing
+    fn rate(&mut self, rating: Option<u8>) -> Result<(), PlayerError> {
+        let InternalPlayerStatus::PlayingOrPaused { song, .. } = &self.status else {
+            return Err(PlayerError::NotPlaying);
+        };
+
+        let mut stats = self.stats.lock().unwrap();
+        stats.entry(&song.path, || tags::read_stats(song)).rating =
+            rating.map(|r| r.clamp(1, MAX_RATING));
+        stats
+            .save(&self.config.stats_path)
+            .context("Failed to save stats")?;
+        self.tags_dirty = true;
+
+        Ok(())
+    }
+
+    fn fade(&mut self, factor: f32) -> Result<(), PlayerError> {
+        *self.fade.write().unwrap() = factor.clamp(0.0, 1.0);
+
+        Ok(())
+    }
+
+    fn set_volume(&mut self, volume: f32) -> Result<(), PlayerError> {
+        let volume = volume.clamp(0.0, 1.0);
+        *self.volume.write().unwrap() = volume;
+
+        if let Some(device) = &self.device {
+            let mut stats = self.stats.lock().unwrap();
+            stats.set_device_volume(device, volume);
+            stats
+                .save(&self.config.stats_path)
+                .context("Failed to save stats")?;
+        }
+
+        Ok(())
+    }
+
+    fn set_zone_volume(&mut self, zone: String, volume: f32) -> Result<(), PlayerError> {
+        if !self.sink.set_zone_volume(&zone, volume) {
+            return Err(PlayerError::ZoneNotFound(zone));
+        }
+
+        let mut stats = self.stats.lock().unwrap();
+        stats.set_zone_volume(&zone, volume.clamp(0.0, 1.0));
+        stats
+            .save(&self.config.stats_path)
+            .context("Failed to save stats")?;
+
+        Ok(())
+    }
+
+    /// switch to the volume last used with the device the next song is played on when it changed,
+    /// so that switching from headphones to speakers does not blast them at the headphone volume
+    fn restore_device_volume(&mut self) {
+        let Some(device) = self.sink.device() else {
+            return;
+        };
+        if self.device.as_ref() == Some(&device) {
+            return;
+        }
+
+        let mut stats = self.stats.lock().unwrap();
+        match stats.device_volume(&device) {
+            Some(volume) => {
+                info!("Restoring volume {volume} of output device {device}");
+                *self.volume.write().unwrap() = volume;
+            }
+            None => stats.set_device_volume(&device, *self.volume.read().unwrap()),
+        }
+        self.device = Some(device);
+    }
+
+    /// set the zones to the volumes they were last used with
+    fn restore_zone_volumes(&self) {
+        let stats = self.stats.lock().unwrap();
+        for (zone, _) in self.sink.zones() {
+            if let Some(volume) = stats.zone_volume(&zone) {
+                self.sink.set_zone_volume(&zone, volume);
+            }
+        }
+    }
+
     /// command player to skip to next song
-    fn skip(&mut self) -> anyhow::Result<()> {
+    fn skip(&mut self) -> Result<(), PlayerError> {
         self.stop()?;
         self.play()?;
 
         Ok(())
     }
 
+    /// play the beginning of `path` quietly instead of the current song, without counting it as played
+    fn preview(&mut self, path: Box<std::path::Path>) -> Result<(), PlayerError> {
+        if self.preview.as_ref().is_some_and(|p| p.path == path) {
+            return self.end_preview();
+        }
+
+        let preview = match self.preview.take() {
+            // previewing another song continues with the song from before the first preview afterwards
+            Some(preview) => Preview { path, ..preview },
+            None => {
+                let resume = match &self.status {
+                    InternalPlayerStatus::PlayingOrPaused {
+                        song,
+                        playback,
+                        track_id,
+                        ..
+                    } => Some((
+                        song.path.clone(),
+                        *playback.played_duration.read().unwrap(),
+                        Some(*track_id),
+                        playback.pause.load(std::sync::atomic::Ordering::Relaxed),
+                    )),
+                    InternalPlayerStatus::Loading { path, paused, .. } => {
+                        Some((path.clone(), Duration::ZERO, None, *paused))
+                    }
+                    InternalPlayerStatus::Stopped => None,
+                };
+                let fade = *self.fade.read().unwrap();
+                *self.fade.write().unwrap() = fade * PREVIEW_VOLUME;
+
+                Preview { path, resume, fade }
+            }
+        };
+        info!("Previewing {}", preview.path.display());
+
+        self.status = InternalPlayerStatus::Stopped;
+        self.load(&preview.path, Duration::ZERO, None)?;
+        self.preview = Some(preview);
+
+        Ok(())
+    }
+
+    /// whether the preview played for `PREVIEW_LENGTH`
+    fn preview_elapsed(&self) -> bool {
+        match &self.status {
+            InternalPlayerStatus::PlayingOrPaused { playback, .. } if self.preview.is_some() => {
+                *playback.played_duration.read().unwrap() >= PREVIEW_LENGTH
+            }
+            _ => false,
+        }
+    }
+
+    /// continue with the song that was playing before the preview, as it was
+    fn end_preview(&mut self) -> Result<(), PlayerError> {
+        let Some(preview) = self.preview.take() else {
+            return Ok(());
+        };
+        *self.fade.write().unwrap() = preview.fade;
+        self.status = InternalPlayerStatus::Stopped;
+
+        if let Some((path, position, track, paused)) = preview.resume {
+            self.load(path, position, track)?;
+            if paused {
+                self.pause()?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// run a command received by the player thread
+    fn execute(&mut self, command: Command) -> Result<(), PlayerError> {
+        if self.preview.is_some() {
+            match &command {
+                // the preview ends early or with the end of its song, without playing the queue
+                Command::Skip | Command::Stop => return self.end_preview(),
+                // the song from before the preview is replaced anyway,
+                // the previewed song is dropped without counting it as played
+                Command::Jump(_) | Command::Replace(_) | Command::Clear | Command::Shutdown => {
+                    if let Some(preview) = self.preview.take() {
+                        *self.fade.write().unwrap() = preview.fade;
+                    }
+                    self.status = InternalPlayerStatus::Stopped;
+                }
+                _ => {}
+            }
+        }
+
+        match command {
+            Command::Play => self.play(),
+            Command::Pause => self.pause(),
+            Command::PlayPause => self.play_pause(),
+            Command::Skip => self.skip(),
+            Command::Stop => self.stop(),
+            Command::Shutdown => self.shutdown(),
+            Command::Clear => self.clear(),
+            Command::Enqueue(path) => self.enqueue(path),
+            Command::Replace(paths) => self.replace(paths),
+            Command::EnqueueRandomAlbum => self.enqueue_random_album(),
+            Command::ToggleRandomAlbums => self.toggle_random_albums(),
+            Command::Dequeue(index) => self.dequeue(index),
+            Command::Jump(index) => self.jump(index),
+            Command::PlaybackFailed(error) => self.recover_playback(error),
+            Command::SongLoaded => self.song_loaded(),
+            Command::SelectTrack(id) => self.select_track(id),
+            Command::Seek(position) => self.seek(position),
+            Command::SeekForward(offset) => self.seek_by(offset, true),
+            Command::SeekBackward(offset) => self.seek_by(offset, false),
+            Command::NextChapter => self.skip_chapter(true),
+            Command::PreviousChapter => self.skip_chapter(false),
+            Command::SetCuePoint(number) => self.set_cue_point(number),
+            Command::JumpToCuePoint(number) => self.jump_to_cue_point(number),
+            Command::SelectPreset(name) => self.select_preset(name),
+            Command::SavePreset(name) => self.save_preset(name),
+            Command::ToggleCrossfeed => self.toggle_crossfeed(),
+            Command::Rate(rating) => self.rate(rating),
+            Command::Fade(factor) => self.fade(factor),
+            Command::SetVolume(volume) => self.set_volume(volume),
+            Command::SetZoneVolume(zone, volume) => self.set_zone_volume(zone, volume),
+            Command::Preview(path) => self.preview(path),
+            Command::EndPreview => self.end_preview(),
+            Command::Batch(commands) => {
+                // every command is run, the first error is reported
+                let mut result = Ok(());
+                for command in commands {
+                    if let Err(e) = self.execute(command) {
+                        warn!("Failed to execute batched command: {e:?}");
+                        result = result.and(Err(e));
+                    }
+                }
+                result
+            }
+        }
+    }
+
     /// add a song to the queue
     /// if the player is stopped, the song will be played
-    fn enqueue<P: AsRef<std::path::Path>>(&mut self, path: P) -> anyhow::Result<()> {
+    fn enqueue<P: AsRef<std::path::Path>>(&mut self, path: P) -> Result<(), PlayerError> {
+        if self
+            .config
+            .max_queue_length
+            .is_some_and(|max| self.queue.len() >= max)
+        {
+            return Err(PlayerError::QueueFull(path.as_ref().to_path_buf()));
+        }
+
+        if self.config.deduplicate_queue {
+            if self.queue.iter().any(|p| **p == *path.as_ref()) {
+                return Err(PlayerError::AlreadyQueued(path.as_ref().to_path_buf()));
+            }
+            let current = match &self.status {
+                InternalPlayerStatus::PlayingOrPaused { song, .. } => Some(&song.path),
+                InternalPlayerStatus::Loading { path, .. } => Some(path),
+                InternalPlayerStatus::Stopped => None,
+            };
+            if current.is_some_and(|p| **p == *path.as_ref()) {
+                return Err(PlayerError::AlreadyPlaying(path.as_ref().to_path_buf()));
+            }
+        }
+
         self.queue.push_back(path.as_ref().into());
 
         if matches!(self.status, InternalPlayerStatus::Stopped) {
@@ -135,158 +987,261 @@ impl Player {
     }
 
     /// remove a song from the queue
-    fn dequeue(&mut self, index: usize) -> anyhow::Result<()> {
+    fn dequeue(&mut self, index: usize) -> Result<(), PlayerError> {
         self.queue
             .remove(index)
-            .ok_or(anyhow::anyhow!(format!("No song at index {}", index)))?;
+            .ok_or(PlayerError::NoSuchIndex(index))?;
 
         Ok(())
     }
 
+    /// play the song at `index` of the queue now, keeping the rest of the queue
+    fn jump(&mut self, index: usize) -> Result<(), PlayerError> {
+        let path = self
+            .queue
+            .remove(index)
+            .ok_or(PlayerError::NoSuchIndex(index))?;
+        self.queue.push_front(path);
+
+        self.skip()
+    }
+
     /// remove all songs from the queue and stop playing
-    fn clear(&mut self) -> anyhow::Result<()> {
+    fn clear(&mut self) -> Result<(), PlayerError> {
         self.queue.clear();
         self.stop()?;
 
         Ok(())
     }
 
+    /// replace the queue and the current song with `paths`
+    fn replace(&mut self, paths: Vec<Box<std::path::Path>>) -> Result<(), PlayerError> {
+        if paths.is_empty() {
+            return Err(PlayerError::NothingToPlay);
+        }
+
+        self.clear()?;
+        for path in paths {
+            self.enqueue(path)?;
+        }
+
+        Ok(())
+    }
+
     pub fn run(
-        cache: Arc<Cache>,
-    ) -> anyhow::Result<(mpsc::Sender<Command>, Arc<RwLock<PlayerFacade>>)> {
-        let media_controls = MediaControls::new(PlatformConfig {
-            display_name: "rcmp",
-            dbus_name: "rcmp",
-            hwnd: None,
-        })
-        .map_err(|e| anyhow::anyhow!(format!("{:?}", e)))
-        .context("Failed to create media controls")?;
+        cache: SharedCache,
+        config: Arc<Config>,
+        stats: Arc<Mutex<Stats>>,
+        notifications: mpsc::Sender<Notification>,
+    ) -> anyhow::Result<(mpsc::Sender<Command>, Arc<RwLock<PlayerFacade>>, Supervisor)> {
+        let sink: Arc<dyn AudioSink> = match config.zones.is_empty() {
+            true => Arc::new(DeviceSink::new(config.output_device.clone())),
+            false => Arc::new(ZoneSink::new(
+                config
+                    .zones
+                    .iter()
+                    .map(|z| {
+                        Zone::new(
+                            z.name.clone(),
+                            Box::new(DeviceSink::new(z.device.clone())),
+                            z.volume.0,
+                        )
+                    })
+                    .collect(),
+            )?),
+        };
+        Self::run_with_sink(cache, config, stats, notifications, sink)
+    }
 
+    /// like `run`, but sending the samples to `sink`
+    pub fn run_with_sink(
+        cache: SharedCache,
+        config: Arc<Config>,
+        stats: Arc<Mutex<Stats>>,
+        notifications: mpsc::Sender<Notification>,
+        sink: Arc<dyn AudioSink>,
+    ) -> anyhow::Result<(mpsc::Sender<Command>, Arc<RwLock<PlayerFacade>>, Supervisor)> {
         let (tx, rx) = mpsc::channel();
         let facade = Arc::new(RwLock::new(PlayerFacade::default()));
 
-        let tx2 = tx.clone();
-        let facade2 = facade.clone();
+        let fifo = config.visualizer_fifo.clone().map(FifoSink::open);
+
+        let presets = dsp::load_presets(&config.presets_directory).unwrap_or_else(|e| {
+            warn!("Failed to load dsp presets: {e:?}");
+            BTreeMap::new()
+        });
+        let mut active = config
+            .dsp_preset
+            .as_ref()
+            .and_then(|name| {
+                presets.get(name).cloned().map(|preset| ActivePreset {
+                    name: Some(name.clone()),
+                    preset: Arc::new(preset),
+                    crossfeed: None,
+                })
+            })
+            .unwrap_or_default();
+        if config.crossfeed.enabled {
+            active.crossfeed = Some(CrossfeedParams::from(&config.crossfeed));
+        }
+        let dsp = Arc::new(RwLock::new(active));
+
+        // browsing, searching and the apis keep working without an output device
+        if !sink.available() {
+            warn!("No audio output device available, playback is disabled");
+            notifications
+                .send(Notification::error(
+                    PlayerError::DeviceUnavailable.to_string(),
+                ))
+                .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+        }
+
+        let notifications2 = notifications.clone();
+        let headless = !sink.is_device() || !sink.available();
+        let supervisor = Supervisor::new(PlayerContext {
+            cache,
+            config,
+            notifications,
+            tx: tx.clone(),
+            rx: Arc::new(Mutex::new(rx)),
+            facade: facade.clone(),
+            fifo,
+            dsp,
+            stats,
+            fade: Arc::new(RwLock::new(1.0)),
+            volume: Arc::new(RwLock::new(1.0)),
+            random_albums: Arc::default(),
+            sink,
+        })?;
+
+        if !headless {
+            power::watch(tx.clone(), facade.clone(), notifications2);
+        }
+
+        Ok((tx, facade, supervisor))
+    }
+
+    /// spawn the player thread, starting with the given queue
+    /// if `resume` is set, that song is loaded at the given position before processing commands
+    fn spawn(
+        ctx: PlayerContext,
+        queue: VecDeque<Box<std::path::Path>>,
+        resume: Option<(Box<std::path::Path>, Duration)>,
+    ) -> anyhow::Result<JoinHandle<()>> {
+        let PlayerContext {
+            cache,
+            config,
+            notifications,
+            tx,
+            rx,
+            facade,
+            fifo,
+            dsp,
+            stats,
+            fade,
+            volume,
+            random_albums,
+            sink,
+        } = ctx;
+
+        let presets = dsp::load_presets(&config.presets_directory).unwrap_or_else(|e| {
+            warn!("Failed to load dsp presets: {e:?}");
+            BTreeMap::new()
+        });
+
+        let mpris = match sink.is_device() && sink.available() {
+            true => Mpris::new(tx.clone(), config.art_cache_directory.clone())
+                .map_err(|e| warn!("Failed to set up media controls: {e:?}"))
+                .ok(),
+            false => None,
+        };
+
+        let tx2 = tx;
+        let facade2 = facade;
         std::thread::Builder::new()
             .name("player thread".to_string())
             .spawn(move || {
+                // the receiver outlives a panicking player thread, so the lock may be poisoned
+                let rx = rx.lock().unwrap_or_else(PoisonError::into_inner);
+
                 let mut player = Player {
                     cache,
+                    config,
                     status: InternalPlayerStatus::Stopped,
-                    queue: VecDeque::new(),
-                    media_controls,
+                    queue,
+                    mpris,
                     command_tx: tx2.clone(),
+                    fifo,
+                    presets,
+                    dsp,
+                    stats,
+                    tags_dirty: false,
+                    fade,
+                    volume,
+                    random_albums,
+                    sink,
+                    device: None,
+                    preview: None,
+                    facade: facade2.clone(),
                 };
+                player.restore_zone_volumes();
 
-                let tx = tx2.clone();
-                player
-                    .media_controls
-                    .attach(move |event| match event {
-                        souvlaki::MediaControlEvent::Play => {
-                            tx.send(Command::Play).unwrap();
-                        }
-                        souvlaki::MediaControlEvent::Pause => {
-                            tx.send(Command::Pause).unwrap();
-                        }
-                        souvlaki::MediaControlEvent::Toggle => {
-                            tx.send(Command::PlayPause).unwrap();
-                        }
-                        souvlaki::MediaControlEvent::Next => {
-                            tx.send(Command::Skip).unwrap();
-                        }
-                        souvlaki::MediaControlEvent::Previous => warn!("Previous not implemented"),
-                        souvlaki::MediaControlEvent::Stop => {
-                            tx.send(Command::Stop).unwrap();
-                        }
-                        souvlaki::MediaControlEvent::Seek(dir) => {
-                            warn!("Seek {dir:?} not implemented")
-                        }
-                        souvlaki::MediaControlEvent::SeekBy(dir, dur) => {
-                            warn!("SeekBy {dir:?} {dur:?} not implemented")
-                        }
-                        souvlaki::MediaControlEvent::SetPosition(mp) => {
-                            warn!("SetPosition {mp:?} not implemented")
-                        }
-                        souvlaki::MediaControlEvent::OpenUri(uri) => {
-                            warn!("OpenUri {uri:?} not implemented")
+                if let Some((path, position)) = resume {
+                    player.load(path, position, None).unwrap_or_else(|e| {
+                        warn!("Failed to resume song: {e:?}");
+                        notifications
+                            .send(Notification::error(format!("{e:#}")))
+                            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+                    });
+                }
+                player.publish();
+                // desktop widgets would show the player as stopped until the first command otherwise
+                if let Some(mpris) = &mut player.mpris {
+                    mpris.update(&facade2.read().unwrap());
+                }
+
+                let mut hooks = HookRunner::new(player.config.clone());
+                loop {
+                    let command = match rx.recv_timeout(MEDIA_CONTROLS_INTERVAL) {
+                        Ok(command) => command,
+                        Err(mpsc::RecvTimeoutError::Timeout) if player.preview_elapsed() => {
+                            Command::EndPreview
                         }
-                        souvlaki::MediaControlEvent::Raise => {}
-                        souvlaki::MediaControlEvent::Quit => {
-                            warn!("Quit not implemented")
+                        // the media controls of windows and macOS do not advance the position by themselves,
+                        // MPRIS clients do and would get a signal every time
+                        Err(mpsc::RecvTimeoutError::Timeout) => {
+                            if let (Some(mpris), false) =
+                                (&mut player.mpris, cfg!(target_os = "linux"))
+                            {
+                                mpris.update(&facade2.read().unwrap());
+                            }
+                            continue;
                         }
-                    })
-                    .expect("Failed to attach media controls");
+                        Err(mpsc::RecvTimeoutError::Disconnected) => break,
+                    };
+                    let result = player.execute(command);
 
-                let mut cover_tempfile;
-                loop {
-                    match rx.recv().expect("Failed to receive Command") {
-                        Command::Play => player.play().unwrap(),
-                        Command::Pause => player.pause().unwrap(),
-                        Command::PlayPause => player.play_pause().unwrap(),
-                        Command::Skip => player.skip().unwrap(),
-                        Command::Stop => player.stop().unwrap(),
-                        Command::Clear => player.clear().unwrap(),
-                        Command::Enqueue(path) => player.enqueue(path).unwrap(),
-                        Command::Dequeue(index) => player.dequeue(index).unwrap(),
+                    if let Err(e) = result {
+                        let level = e.level();
+                        let e = anyhow::Error::from(e);
+                        warn!("Failed to execute command: {e:?}");
+                        notifications
+                            .send(Notification {
+                                level,
+                                message: format!("{e:#}"),
+                            })
+                            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
                     }
 
-                    *facade2.write().unwrap() = PlayerFacade::from_player(&player);
+                    player.publish();
 
                     let facade = facade2.read().unwrap();
-
-                    cover_tempfile = NamedTempFile::new().expect("Failed to create tempfile");
-                    cover_tempfile
-                        .write_all(facade.current_cover().unwrap_or(&[]))
-                        .expect("Failed to write cover to tempfile");
-
-                    player
-                        .media_controls
-                        .set_metadata(MediaMetadata {
-                            title: facade
-                                .current_song()
-                                .and_then(|s| s.tag_string(StandardTagKey::TrackTitle)),
-                            album: facade
-                                .current_song()
-                                .and_then(|s| s.tag_string(StandardTagKey::Album)),
-                            artist: facade
-                                .current_song()
-                                .and_then(|s| s.tag_string(StandardTagKey::Artist)),
-                            cover_url: Some(
-                                format!("file://{}", cover_tempfile.path().display()).as_str(),
-                            ),
-                            duration: facade.current_song().map(|s| s.duration),
-                        })
-                        .expect("Failed to set metadata");
-
-                    player
-                        .media_controls
-                        .set_playback(match &facade.status {
-                            facade::PlayerStatus::PlayingOrPaused {
-                                playing_duration,
-                                paused,
-                                ..
-                            } => {
-                                if paused.load(std::sync::atomic::Ordering::Relaxed) {
-                                    MediaPlayback::Paused {
-                                        progress: Some(MediaPosition(
-                                            *playing_duration.read().unwrap(),
-                                        )),
-                                    }
-                                } else {
-                                    MediaPlayback::Playing {
-                                        progress: Some(MediaPosition(
-                                            *playing_duration.read().unwrap(),
-                                        )),
-                                    }
-                                }
-                            }
-                            facade::PlayerStatus::Stopped => MediaPlayback::Stopped,
-                        })
-                        .expect("Failed to set playback");
+                    if let Some(mpris) = &mut player.mpris {
+                        mpris.update(&facade);
+                    }
+                    hooks.update(&facade);
                 }
             })
-            .context("Failed to create player thread")?;
-
-        Ok((tx, facade))
+            .context("Failed to create player thread")
     }
 }
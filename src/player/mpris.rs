@@ -0,0 +1,290 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+use anyhow::Context;
+use log::warn;
+use souvlaki::{
+    MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig, SeekDirection,
+};
+
+use crate::song::StandardTagKey;
+
+use super::{
+    art,
+    command::Command,
+    facade::{PlayerFacade, PlayerStatus},
+};
+
+/// offset of a seek without a given duration
+const SEEK_STEP: Duration = Duration::from_secs(5);
+
+fn seek_command(direction: SeekDirection, offset: Duration) -> Command {
+    match direction {
+        SeekDirection::Forward => Command::SeekForward(offset),
+        SeekDirection::Backward => Command::SeekBackward(offset),
+    }
+}
+
+/// the url of the cover at `path`, souvlaki turns it back into a path on windows
+fn file_url(path: &Path) -> String {
+    if cfg!(target_os = "windows") {
+        return format!("file://{}", path.display());
+    }
+
+    // macOS does not accept urls with spaces
+    let encoded = path
+        .to_string_lossy()
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect::<String>();
+    format!("file://{encoded}")
+}
+
+/// whether there is a D-Bus session bus, from the environment or at its default location
+#[cfg(target_os = "linux")]
+fn session_bus() -> bool {
+    std::env::var_os("DBUS_SESSION_BUS_ADDRESS").is_some()
+        || std::env::var_os("XDG_RUNTIME_DIR")
+            .is_some_and(|dir| Path::new(&dir).join("bus").exists())
+}
+
+/// desktop media controls, publishing the player state over MPRIS
+pub struct Mpris {
+    media_controls: MediaControls,
+    /// directory the covers are written to for desktops to read them from
+    art_cache: PathBuf,
+    /// path of the song the cover was last written for and the written cover
+    cover: Option<(Box<Path>, Option<PathBuf>)>,
+    /// the metadata is only sent when the song changed, windows and macOS load the cover every time
+    metadata_sent: bool,
+    /// the volume last sent, desktops are only told about changes
+    #[cfg(all(unix, not(target_os = "macos")))]
+    volume: Option<f32>,
+}
+
+impl Mpris {
+    pub fn new(tx: mpsc::Sender<Command>, art_cache: PathBuf) -> anyhow::Result<Self> {
+        // the media controls of windows belong to a window, which a terminal application does not have
+        #[cfg(target_os = "windows")]
+        let hwnd = Some(window::hidden().context("Failed to create window for media controls")?);
+        #[cfg(not(target_os = "windows"))]
+        let hwnd = None;
+
+        // souvlaki panics in its own thread without a session bus, e.g. on a headless server
+        #[cfg(target_os = "linux")]
+        anyhow::ensure!(
+            session_bus(),
+            "No D-Bus session bus to publish the media controls on"
+        );
+
+        let mut media_controls = MediaControls::new(PlatformConfig {
+            display_name: "rcmp",
+            dbus_name: "rcmp",
+            hwnd,
+        })
+        .map_err(|e| anyhow::anyhow!(format!("{:?}", e)))
+        .context("Failed to create media controls")?;
+
+        media_controls
+            .attach(move |event| match event {
+                souvlaki::MediaControlEvent::Play => {
+                    tx.send(Command::Play).unwrap();
+                }
+                souvlaki::MediaControlEvent::Pause => {
+                    tx.send(Command::Pause).unwrap();
+                }
+                souvlaki::MediaControlEvent::Toggle => {
+                    tx.send(Command::PlayPause).unwrap();
+                }
+                souvlaki::MediaControlEvent::Next => {
+                    tx.send(Command::Skip).unwrap();
+                }
+                souvlaki::MediaControlEvent::Previous => warn!("Previous not implemented"),
+                souvlaki::MediaControlEvent::Stop => {
+                    tx.send(Command::Stop).unwrap();
+                }
+                souvlaki::MediaControlEvent::Seek(dir) => {
+                    tx.send(seek_command(dir, SEEK_STEP)).unwrap();
+                }
+                souvlaki::MediaControlEvent::SeekBy(dir, dur) => {
+                    tx.send(seek_command(dir, dur)).unwrap();
+                }
+                souvlaki::MediaControlEvent::SetPosition(MediaPosition(position)) => {
+                    tx.send(Command::Seek(position)).unwrap();
+                }
+                souvlaki::MediaControlEvent::OpenUri(uri) => {
+                    warn!("OpenUri {uri:?} not implemented")
+                }
+                souvlaki::MediaControlEvent::SetVolume(volume) => {
+                    tx.send(Command::SetVolume(volume as f32)).unwrap();
+                }
+                souvlaki::MediaControlEvent::Raise => {}
+                souvlaki::MediaControlEvent::Quit => {
+                    warn!("Quit not implemented")
+                }
+            })
+            .map_err(|e| anyhow::anyhow!(format!("{:?}", e)))
+            .context("Failed to attach media controls")?;
+
+        Ok(Self {
+            media_controls,
+            art_cache,
+            cover: None,
+            metadata_sent: false,
+            #[cfg(all(unix, not(target_os = "macos")))]
+            volume: None,
+        })
+    }
+
+    pub fn update(&mut self, facade: &PlayerFacade) {
+        let path = facade.current_song().map(|s| s.path.clone());
+        if path != self.cover.as_ref().map(|(p, _)| p.clone()) {
+            self.cover = path.map(|path| {
+                let cover = art::cached_cover(&self.art_cache, facade)
+                    .map_err(|e| warn!("Failed to write cover to art cache: {e:?}"))
+                    .ok()
+                    .flatten();
+                (path, cover)
+            });
+            self.metadata_sent = false;
+        }
+
+        if !self.metadata_sent {
+            self.send_metadata(facade);
+            self.metadata_sent = true;
+        }
+
+        self.media_controls
+            .set_playback(match &facade.status {
+                PlayerStatus::PlayingOrPaused {
+                    playing_duration,
+                    paused,
+                    ..
+                } => {
+                    if paused.load(std::sync::atomic::Ordering::Relaxed) {
+                        MediaPlayback::Paused {
+                            progress: Some(MediaPosition(*playing_duration.read().unwrap())),
+                        }
+                    } else {
+                        MediaPlayback::Playing {
+                            progress: Some(MediaPosition(*playing_duration.read().unwrap())),
+                        }
+                    }
+                }
+                PlayerStatus::Loading { .. } | PlayerStatus::Stopped => MediaPlayback::Stopped,
+            })
+            .unwrap_or_else(|e| warn!("Failed to set playback: {e:?}"));
+
+        // only MPRIS has a volume, windows and macOS leave it to the system mixer
+        #[cfg(all(unix, not(target_os = "macos")))]
+        if self.volume != Some(facade.volume) {
+            self.media_controls
+                .set_volume(facade.volume as f64)
+                .unwrap_or_else(|e| warn!("Failed to set volume: {e:?}"));
+            self.volume = Some(facade.volume);
+        }
+    }
+
+    fn send_metadata(&mut self, facade: &PlayerFacade) {
+        self.media_controls
+            .set_metadata(MediaMetadata {
+                title: facade
+                    .current_song()
+                    .and_then(|s| s.tag_string(StandardTagKey::TrackTitle)),
+                album: facade
+                    .current_song()
+                    .and_then(|s| s.tag_string(StandardTagKey::Album)),
+                artist: facade
+                    .current_song()
+                    .and_then(|s| s.tag_string(StandardTagKey::Artist)),
+                cover_url: self
+                    .cover
+                    .as_ref()
+                    .and_then(|(_, cover)| cover.as_ref())
+                    .map(|p| file_url(p))
+                    .as_deref(),
+                duration: facade.current_song().map(|s| s.duration),
+            })
+            .unwrap_or_else(|e| warn!("Failed to set metadata: {e:?}"));
+    }
+}
+
+/// a hidden window for the media controls of windows, with a thread handling its messages
+#[cfg(target_os = "windows")]
+mod window {
+    use std::{ffi::c_void, sync::mpsc};
+
+    use windows_sys::Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, WPARAM},
+        System::LibraryLoader::GetModuleHandleW,
+        UI::WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, DispatchMessageW, GetMessageW, RegisterClassExW,
+            TranslateMessage, MSG, WNDCLASSEXW, WS_OVERLAPPEDWINDOW,
+        },
+    };
+
+    unsafe extern "system" fn window_proc(
+        hwnd: HWND,
+        message: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> LRESULT {
+        DefWindowProcW(hwnd, message, wparam, lparam)
+    }
+
+    pub fn hidden() -> anyhow::Result<*mut c_void> {
+        let (tx, rx) = mpsc::channel();
+        std::thread::Builder::new()
+            .name("media controls thread".to_string())
+            .spawn(move || unsafe {
+                let name = "ramp\0".encode_utf16().collect::<Vec<_>>();
+                let instance = GetModuleHandleW(std::ptr::null());
+                let class = WNDCLASSEXW {
+                    cbSize: std::mem::size_of::<WNDCLASSEXW>() as u32,
+                    lpfnWndProc: Some(window_proc),
+                    hInstance: instance,
+                    lpszClassName: name.as_ptr(),
+                    ..std::mem::zeroed()
+                };
+                RegisterClassExW(&class);
+
+                // the window is never shown
+                let hwnd = CreateWindowExW(
+                    0,
+                    name.as_ptr(),
+                    name.as_ptr(),
+                    WS_OVERLAPPEDWINDOW,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    instance,
+                    std::ptr::null(),
+                );
+                let _ = tx.send(hwnd);
+                if hwnd == 0 {
+                    return;
+                }
+
+                let mut message = std::mem::zeroed::<MSG>();
+                while GetMessageW(&mut message, 0, 0, 0) > 0 {
+                    TranslateMessage(&message);
+                    DispatchMessageW(&message);
+                }
+            })?;
+
+        let hwnd = rx.recv()?;
+        anyhow::ensure!(hwnd != 0, "Failed to create window");
+        Ok(hwnd as *mut c_void)
+    }
+}
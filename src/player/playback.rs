@@ -1,95 +1,216 @@
 use std::{
     collections::VecDeque,
-    sync::{atomic::AtomicBool, mpsc, Arc, RwLock},
+    panic::AssertUnwindSafe,
+    sync::{atomic::AtomicBool, mpsc, Arc, Mutex, RwLock},
     time::Duration,
 };
 
-use cpal::{
-    traits::{DeviceTrait, HostTrait},
-    StreamConfig,
-};
 use log::{debug, warn};
 
-use super::{command::Command, loader::LoadedSong};
+use super::{
+    command::Command,
+    dsp::{Dsp, SharedPreset},
+    error::PlayerError,
+    fifo::FifoSink,
+    loader::LoadedSong,
+    sink::{AudioSink, SinkStream, StreamSpec},
+};
 
 pub struct Playback {
-    _stream: cpal::Stream,
+    _stream: SinkStream,
     pub pause: Arc<AtomicBool>,
     pub played_duration: Arc<RwLock<Duration>>,
+    /// time actually played, unlike the position not affected by seeking
+    pub listened: Arc<RwLock<Duration>>,
+    /// audio decoded ahead of the position, waiting to be played
+    pub buffered: Arc<RwLock<Duration>>,
+    /// position the playback started at
+    pub start: Duration,
+    /// whether the sink plays the samples unchanged and the song needs no normalization,
+    /// so that the output is bit-perfect as long as no volume or dsp is applied either
+    pub bit_perfect: bool,
+    seek_request: Arc<Mutex<Option<Duration>>>,
 }
 
 impl Playback {
-    pub fn new(cmd: mpsc::Sender<Command>, mut song: LoadedSong) -> anyhow::Result<Self> {
-        let config = StreamConfig {
+    /// start playing `song` normalized to `loudness_target` LUFS on `sink`, or start paused if `paused`
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        cmd: mpsc::Sender<Command>,
+        mut song: LoadedSong,
+        loudness_target: f32,
+        fifo: Option<FifoSink>,
+        dsp: SharedPreset,
+        fade: Arc<RwLock<f32>>,
+        volume: Arc<RwLock<f32>>,
+        sink: &dyn AudioSink,
+        paused: bool,
+    ) -> Result<Self, PlayerError> {
+        let spec = StreamSpec {
             channels: song.signal_spec.channels.count() as u16,
-            sample_rate: cpal::SampleRate(song.signal_spec.rate),
-            buffer_size: cpal::BufferSize::Default,
+            sample_rate: song.signal_spec.rate,
         };
-        debug!("Stream config: {:?}", config);
+        debug!("Stream spec: {:?}", spec);
 
         let mut buffer = VecDeque::<f32>::new();
+        let mut dsp = Dsp::new(dsp, spec.channels, spec.sample_rate);
 
-        let pause = Arc::new(AtomicBool::new(false));
-        let playing_duration = Arc::new(RwLock::new(Duration::from_secs(0)));
+        let pause = Arc::new(AtomicBool::new(paused));
+        let playing_duration = Arc::new(RwLock::new(song.start));
+        let listened = Arc::new(RwLock::new(Duration::ZERO));
+        let buffered = Arc::new(RwLock::new(Duration::ZERO));
+        let failed = Arc::new(AtomicBool::new(false));
+        let seek_request = Arc::new(Mutex::new(None));
 
-        let gain_factor = song.song.gain_factor;
+        let start = song.start;
+        let gain_factor = song.song.normalization(loudness_target);
         let pause_stream2 = pause.clone();
         let playing_duration2 = playing_duration.clone();
+        let listened2 = listened.clone();
+        let buffered2 = buffered.clone();
+        let failed2 = failed.clone();
+        let seek_request2 = seek_request.clone();
+        let cmd2 = cmd.clone();
+        let mut ended = false;
 
-        let stream = cpal::default_host()
-            .default_output_device()
-            .expect("Failed to get default output device")
-            .build_output_stream::<f32, _, _>(
-                &config,
-                move |dest, _info| {
-                    if pause_stream2.load(std::sync::atomic::Ordering::Relaxed) {
-                        dest.fill(0.0);
-                        return;
+        let render = move |dest: &mut [f32]| {
+            if let Some(position) = seek_request2.lock().unwrap().take() {
+                match song.decoder.seek(position) {
+                    Ok(()) => {
+                        buffer.clear();
+                        *playing_duration2.write().unwrap() = position;
                     }
+                    Err(e) => warn!("Failed to seek: {e:?}"),
+                }
+            }
 
-                    let mut duration = playing_duration2.write().unwrap();
-
-                    let mut byte_count = 0;
-                    while byte_count < dest.len() {
-                        if buffer.len() < dest.len() {
-                            let (sample_buffer, eof) = (song.decoder)().unwrap_or_else(|e| {
-                                warn!("Error in decoder: {:?}", e);
-                                (None, false)
-                            });
+            if pause_stream2.load(std::sync::atomic::Ordering::Relaxed)
+                || failed2.load(std::sync::atomic::Ordering::Relaxed)
+                || ended
+            {
+                dest.fill(0.0);
+                return 0;
+            }
 
-                            if let Some(s) = sample_buffer {
-                                buffer.extend(s.samples());
-                            }
+            // a panicking decoder must not take down the audio thread silently
+            let result = std::panic::catch_unwind(AssertUnwindSafe(|| {
+                let gain_factor = gain_factor * *fade.read().unwrap() * *volume.read().unwrap();
+                let mut byte_count = 0;
+                while byte_count < dest.len() {
+                    if buffer.len() < dest.len() {
+                        let (sample_buffer, eof) = song.decoder.next().unwrap_or_else(|e| {
+                            warn!("Error in decoder: {:?}", e);
+                            (None, false)
+                        });
 
-                            if eof && buffer.is_empty() {
-                                cmd.send(Command::Skip).unwrap();
-                                break;
-                            }
+                        if let Some(s) = sample_buffer {
+                            buffer.extend(s);
                         }
 
-                        buffer
-                            .drain(..(dest.len() - byte_count).min(buffer.len()))
-                            .for_each(|sample| {
-                                dest[byte_count] = sample * gain_factor;
-                                byte_count += 1;
-                            });
+                        // the stream keeps rendering until the player dropped it,
+                        // which must not skip the following songs as well
+                        if eof && buffer.is_empty() {
+                            ended = true;
+                            cmd.send(Command::Skip).unwrap();
+                            dest[byte_count..].fill(0.0);
+                            break;
+                        }
                     }
 
-                    *duration += Duration::from_secs_f64(
-                        dest.len() as f64 / config.channels as f64 / config.sample_rate.0 as f64,
-                    );
-                },
-                |e| {
-                    warn!("Error in playback stream: {:?}", e);
-                },
-                None,
-            )
-            .expect("Failed to build output stream");
+                    buffer
+                        .drain(..(dest.len() - byte_count).min(buffer.len()))
+                        .for_each(|sample| {
+                            dest[byte_count] = sample * gain_factor;
+                            byte_count += 1;
+                        });
+                }
+
+                dsp.process(dest);
+
+                if let Some(fifo) = &fifo {
+                    fifo.send(dest, spec.channels, spec.sample_rate);
+                }
+
+                let played = Duration::from_secs_f64(
+                    byte_count as f64 / spec.channels as f64 / spec.sample_rate as f64,
+                );
+                *playing_duration2.write().unwrap() += played;
+                *listened2.write().unwrap() += played;
+                *buffered2.write().unwrap() = Duration::from_secs_f64(
+                    buffer.len() as f64 / spec.channels as f64 / spec.sample_rate as f64,
+                );
+
+                byte_count
+            }));
+
+            result.unwrap_or_else(|e| {
+                dest.fill(0.0);
+                failed2.store(true, std::sync::atomic::Ordering::Relaxed);
+
+                let message = e
+                    .downcast_ref::<&str>()
+                    .map(|s| s.to_string())
+                    .or_else(|| e.downcast_ref::<String>().cloned())
+                    .unwrap_or("decoder panicked".to_string());
+                cmd.send(Command::PlaybackFailed(message))
+                    .unwrap_or_else(|e| warn!("Failed to report playback failure: {e:?}"));
+
+                0
+            })
+        };
+
+        let error = move |e: String| {
+            warn!("Error in playback stream: {e}");
+
+            if !failed.swap(true, std::sync::atomic::Ordering::Relaxed) {
+                cmd2.send(Command::PlaybackFailed(e))
+                    .unwrap_or_else(|e| warn!("Failed to report playback failure: {e:?}"));
+            }
+        };
+
+        let bit_perfect = gain_factor == 1.0 && sink.bit_perfect(spec);
+        let stream = sink.open(spec, pause.clone(), Box::new(render), Box::new(error))?;
 
         Ok(Self {
             _stream: stream,
             pause,
             played_duration: playing_duration,
+            listened,
+            buffered,
+            start,
+            bit_perfect,
+            seek_request,
         })
     }
+
+    /// continue playing at `position`, the seek is performed by the audio stream
+    pub fn seek(&self, position: Duration) {
+        *self.seek_request.lock().unwrap() = Some(position);
+    }
+
+    /// the position of a seek not yet performed by the audio stream, otherwise the current position,
+    /// so that consecutive relative seeks add up
+    pub fn target_position(&self) -> Duration {
+        self.seek_request
+            .lock()
+            .unwrap()
+            .unwrap_or_else(|| *self.played_duration.read().unwrap())
+    }
+}
+
+/// mark the output as music for PulseAudio and PipeWire, which the alsa default device usually goes through,
+/// so that their role based ducking and corking during calls applies to it like to other players,
+/// has to be called before other threads are started and keeps properties set by the user
+pub fn set_media_role() {
+    for (key, value) in [
+        ("PULSE_PROP", "media.role=music application.name=ramp"),
+        (
+            "PIPEWIRE_PROPS",
+            "{ media.role=Music application.name=ramp }",
+        ),
+    ] {
+        if std::env::var_os(key).is_none() {
+            std::env::set_var(key, value);
+        }
+    }
 }
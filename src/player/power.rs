@@ -0,0 +1,120 @@
+use std::{
+    sync::{mpsc, Arc, RwLock},
+    time::Duration,
+};
+
+use cpal::traits::{DeviceTrait, HostTrait};
+use log::{info, warn};
+
+use crate::notification::Notification;
+
+use super::{command::Command, facade::PlayerFacade};
+
+/// interval the default output device is checked at
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// pauses playing songs, playback has to be resumed manually
+#[derive(Clone)]
+struct Pauser {
+    cmd: mpsc::Sender<Command>,
+    player: Arc<RwLock<PlayerFacade>>,
+    notifications: mpsc::Sender<Notification>,
+}
+
+impl Pauser {
+    fn pause(&self, reason: &str) {
+        let playing = {
+            let player = self.player.read().unwrap();
+            player.current_song().is_some() && !player.is_paused()
+        };
+        if !playing {
+            return;
+        }
+
+        info!("Pausing, {reason}");
+        self.cmd
+            .send(Command::Pause)
+            .unwrap_or_else(|e| warn!("Failed to send pause: {e:?}"));
+        self.notifications
+            .send(Notification::info(format!("⏸️ Paused, {reason}")))
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+    }
+}
+
+/// pause before the system suspends and when the default output device changes,
+/// e.g. when headphones are unplugged, so that music does not suddenly play on the speakers
+pub fn watch(
+    cmd: mpsc::Sender<Command>,
+    player: Arc<RwLock<PlayerFacade>>,
+    notifications: mpsc::Sender<Notification>,
+) {
+    let pauser = Pauser {
+        cmd,
+        player,
+        notifications,
+    };
+
+    let device_pauser = pauser.clone();
+    std::thread::Builder::new()
+        .name("device watcher thread".to_string())
+        .spawn(move || watch_device(device_pauser))
+        .map_err(|e| warn!("Failed to spawn device watcher thread: {e:?}"))
+        .ok();
+
+    #[cfg(all(feature = "mpris", target_os = "linux"))]
+    std::thread::Builder::new()
+        .name("sleep watcher thread".to_string())
+        .spawn(move || {
+            watch_sleep(pauser).unwrap_or_else(|e| warn!("Failed to watch for suspend: {e:?}"))
+        })
+        .map_err(|e| warn!("Failed to spawn sleep watcher thread: {e:?}"))
+        .ok();
+}
+
+fn default_device() -> Option<String> {
+    cpal::default_host()
+        .default_output_device()
+        .and_then(|d| d.name().ok())
+}
+
+/// the alsa "default" device keeps its name when the sound server switches outputs,
+/// so this mostly catches changes on hosts that name the actual device
+fn watch_device(pauser: Pauser) {
+    let mut device = default_device();
+    loop {
+        std::thread::sleep(DEVICE_POLL_INTERVAL);
+
+        let current = default_device();
+        if current != device {
+            info!("Default output device changed from {device:?} to {current:?}");
+            pauser.pause("the output device changed");
+            device = current;
+        }
+    }
+}
+
+/// pause on the PrepareForSleep signal of logind
+#[cfg(all(feature = "mpris", target_os = "linux"))]
+fn watch_sleep(pauser: Pauser) -> anyhow::Result<()> {
+    use anyhow::Context;
+    use dbus::{blocking::Connection, message::MatchRule};
+
+    let connection = Connection::new_system().context("Failed to connect to the system bus")?;
+    connection
+        .add_match(
+            MatchRule::new_signal("org.freedesktop.login1.Manager", "PrepareForSleep"),
+            move |(start,): (bool,), _, _| {
+                if start {
+                    pauser.pause("the system is suspending");
+                }
+                true
+            },
+        )
+        .context("Failed to listen for PrepareForSleep")?;
+
+    loop {
+        connection
+            .process(Duration::from_secs(60))
+            .context("Failed to process D-Bus messages")?;
+    }
+}
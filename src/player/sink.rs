@@ -0,0 +1,607 @@
+use std::{
+    collections::VecDeque,
+    path::Path,
+    sync::{atomic::AtomicBool, Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use cpal::{
+    traits::{DeviceTrait, HostTrait},
+    SampleFormat, StreamConfig,
+};
+use log::{debug, warn};
+
+use super::{dither::Dither, encode::Encoder, error::PlayerError};
+
+/// frames rendered at once by sinks without an audio device
+const BLOCK_FRAMES: usize = 1024;
+
+/// interval a paused sink without an audio device checks whether it was resumed at
+const PAUSE_POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// zones playing copies of the samples drop the oldest ones when they fall further behind than this
+const MAX_ZONE_LAG: Duration = Duration::from_millis(250);
+
+/// the format of the samples of a song
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamSpec {
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+/// fills the buffer with the next interleaved samples of the playback, silence while paused,
+/// returns the number of samples rendered before the end of the song, the rest of the buffer is silence
+pub type Render = Box<dyn FnMut(&mut [f32]) -> usize + Send>;
+
+/// called with the error when the sink fails while playing
+pub type OnError = Box<dyn FnMut(String) + Send>;
+
+/// keeps the samples flowing to the sink until dropped
+pub type SinkStream = Box<dyn std::any::Any>;
+
+/// where the player sends its samples
+pub trait AudioSink: Send + Sync {
+    /// start sending the samples of `render` to the sink until the returned stream is dropped
+    fn open(
+        &self,
+        spec: StreamSpec,
+        pause: Arc<AtomicBool>,
+        render: Render,
+        error: OnError,
+    ) -> Result<SinkStream, PlayerError>;
+
+    /// whether the samples are played on the audio device of the system,
+    /// media controls and pausing on suspend are only set up then
+    fn is_device(&self) -> bool {
+        false
+    }
+
+    /// whether there is an output to open a stream on, without one the player refuses to play,
+    /// e.g. on a headless server
+    fn available(&self) -> bool {
+        true
+    }
+
+    /// the name of the output device the next stream is opened on, `None` without a single device
+    fn device(&self) -> Option<String> {
+        None
+    }
+
+    /// whether samples of `spec` reach the output unchanged, without being resampled or dithered,
+    /// the volume of the zones aside
+    fn bit_perfect(&self, _spec: StreamSpec) -> bool {
+        false
+    }
+
+    /// the names of the zones played to and their volume from 0.0 to 1.0, empty for a single output
+    fn zones(&self) -> Vec<(String, f32)> {
+        vec![]
+    }
+
+    /// set the volume of the zone `name` from 0.0 to 1.0, false if there is no such zone
+    fn set_zone_volume(&self, _name: &str, _volume: f32) -> bool {
+        false
+    }
+}
+
+/// an output device of the system
+pub struct DeviceSink {
+    /// name of the device, the default device if `None` or if there is no such device
+    name: Option<String>,
+}
+
+impl DeviceSink {
+    pub fn new(name: Option<String>) -> Self {
+        Self { name }
+    }
+
+    fn resolve(&self) -> Option<cpal::Device> {
+        output_device(self.name.as_deref()).or_else(|| {
+            warn!(
+                "Output device {:?} not found, using the default device",
+                self.name
+            );
+            output_device(None)
+        })
+    }
+}
+
+/// the names of the output devices of the system
+pub fn output_devices() -> Vec<String> {
+    cpal::default_host()
+        .output_devices()
+        .map(|devices| devices.filter_map(|d| d.name().ok()).collect())
+        .unwrap_or_else(|e| {
+            warn!("Failed to list output devices: {e:?}");
+            vec![]
+        })
+}
+
+/// the output device called `name`, or the default device if `name` is `None`
+pub fn output_device(name: Option<&str>) -> Option<cpal::Device> {
+    let host = cpal::default_host();
+    match name {
+        Some(name) => host
+            .output_devices()
+            .ok()?
+            .find(|d| d.name().is_ok_and(|n| n == name)),
+        None => host.default_output_device(),
+    }
+}
+
+impl AudioSink for DeviceSink {
+    fn open(
+        &self,
+        spec: StreamSpec,
+        _pause: Arc<AtomicBool>,
+        mut render: Render,
+        mut error: OnError,
+    ) -> Result<SinkStream, PlayerError> {
+        let config = StreamConfig {
+            channels: spec.channels,
+            sample_rate: cpal::SampleRate(spec.sample_rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+        debug!("Stream config: {:?}", config);
+
+        let device = self.resolve().ok_or(PlayerError::DeviceUnavailable)?;
+        let format = sample_format(&device, &config);
+        debug!("Sample format: {:?}", format);
+
+        let error = move |e: cpal::StreamError| error(e.to_string());
+
+        // devices without float support get 16 bit samples, dithered to hide the quantization
+        let mut samples = Vec::new();
+        let mut dither = Dither::default();
+        let stream = match format {
+            SampleFormat::I16 => device.build_output_stream::<i16, _, _>(
+                &config,
+                move |dest, _info| {
+                    samples.resize(dest.len(), 0.0);
+                    render(&mut samples);
+                    for (d, s) in dest.iter_mut().zip(&samples) {
+                        *d = dither.quantize_i16(*s);
+                    }
+                },
+                error,
+                None,
+            ),
+            SampleFormat::U16 => device.build_output_stream::<u16, _, _>(
+                &config,
+                move |dest, _info| {
+                    samples.resize(dest.len(), 0.0);
+                    render(&mut samples);
+                    for (d, s) in dest.iter_mut().zip(&samples) {
+                        *d = dither.quantize_u16(*s);
+                    }
+                },
+                error,
+                None,
+            ),
+            _ => device.build_output_stream::<f32, _, _>(
+                &config,
+                move |dest, _info| {
+                    render(dest);
+                },
+                error,
+                None,
+            ),
+        }
+        .map_err(|e| PlayerError::Stream(e.into()))?;
+
+        Ok(Box::new(stream))
+    }
+
+    fn is_device(&self) -> bool {
+        true
+    }
+
+    fn available(&self) -> bool {
+        self.resolve().is_some()
+    }
+
+    fn device(&self) -> Option<String> {
+        self.resolve().and_then(|d| d.name().ok())
+    }
+
+    /// float samples at the rate of the song are passed to the device as they are
+    fn bit_perfect(&self, spec: StreamSpec) -> bool {
+        let config = StreamConfig {
+            channels: spec.channels,
+            sample_rate: cpal::SampleRate(spec.sample_rate),
+            buffer_size: cpal::BufferSize::Default,
+        };
+        self.resolve()
+            .is_some_and(|d| supported_formats(&d, &config).contains(&SampleFormat::F32))
+    }
+}
+
+/// the best sample format the device supports for `config`, f32 if the device does not tell
+fn sample_format(device: &cpal::Device, config: &StreamConfig) -> SampleFormat {
+    let formats = supported_formats(device, config);
+
+    [SampleFormat::F32, SampleFormat::I16, SampleFormat::U16]
+        .into_iter()
+        .find(|f| formats.contains(f))
+        .unwrap_or(SampleFormat::F32)
+}
+
+/// the sample formats the device supports with the channels and the sample rate of `config`
+fn supported_formats(device: &cpal::Device, config: &StreamConfig) -> Vec<SampleFormat> {
+    match device.supported_output_configs() {
+        Ok(configs) => configs
+            .filter(|c| {
+                c.channels() == config.channels
+                    && c.min_sample_rate() <= config.sample_rate
+                    && c.max_sample_rate() >= config.sample_rate
+            })
+            .map(|c| c.sample_format())
+            .collect::<Vec<_>>(),
+        Err(e) => {
+            warn!("Failed to query supported output configs: {e:?}");
+            vec![]
+        }
+    }
+}
+
+/// a named output of a `ZoneSink` with its own volume
+pub struct Zone {
+    name: String,
+    sink: Box<dyn AudioSink>,
+    volume: Arc<RwLock<f32>>,
+}
+
+impl Zone {
+    pub fn new(name: String, sink: Box<dyn AudioSink>, volume: f32) -> Self {
+        Self {
+            name,
+            sink,
+            volume: Arc::new(RwLock::new(volume.clamp(0.0, 1.0))),
+        }
+    }
+}
+
+/// plays to several sinks at once with independent volumes, e.g. speakers and headphones,
+/// the first zone pulls the samples from the playback and the others play copies of them
+pub struct ZoneSink {
+    zones: Vec<Zone>,
+}
+
+impl ZoneSink {
+    pub fn new(zones: Vec<Zone>) -> anyhow::Result<Self> {
+        anyhow::ensure!(!zones.is_empty(), "No zones to play to");
+        Ok(Self { zones })
+    }
+}
+
+impl AudioSink for ZoneSink {
+    fn open(
+        &self,
+        spec: StreamSpec,
+        pause: Arc<AtomicBool>,
+        mut render: Render,
+        error: OnError,
+    ) -> Result<SinkStream, PlayerError> {
+        let channels = spec.channels.max(1) as usize;
+        let max_lag = (MAX_ZONE_LAG.as_secs_f64() * spec.sample_rate as f64) as usize * channels;
+        let error = Arc::new(Mutex::new(error));
+        let zone_error = |name: &str| -> OnError {
+            let (name, error) = (name.to_string(), error.clone());
+            Box::new(move |e| (error.lock().unwrap())(format!("{name}: {e}")))
+        };
+
+        let (first, others) = self.zones.split_first().expect("Zones are not empty");
+        let queues = others
+            .iter()
+            .map(|_| Arc::new(Mutex::new(VecDeque::<f32>::new())))
+            .collect::<Vec<_>>();
+
+        let mut streams = vec![];
+        for (zone, queue) in others.iter().zip(&queues) {
+            let (queue, volume) = (queue.clone(), zone.volume.clone());
+            let render: Render = Box::new(move |buffer| {
+                let volume = *volume.read().unwrap();
+                let mut queue = queue.lock().unwrap();
+                let available = queue.len().min(buffer.len());
+                for (d, s) in buffer.iter_mut().zip(queue.drain(..available)) {
+                    *d = s * volume;
+                }
+                buffer[available..].fill(0.0);
+                available
+            });
+            streams.push(
+                zone.sink
+                    .open(spec, pause.clone(), render, zone_error(&zone.name))?,
+            );
+        }
+
+        let volume = first.volume.clone();
+        let zone_queues = queues.clone();
+        let render: Render = Box::new(move |buffer| {
+            let rendered = render(buffer);
+            for queue in &zone_queues {
+                let mut queue = queue.lock().unwrap();
+                queue.extend(&buffer[..rendered]);
+                let behind = queue
+                    .len()
+                    .saturating_sub(max_lag)
+                    .next_multiple_of(channels)
+                    .min(queue.len());
+                queue.drain(..behind);
+            }
+
+            let volume = *volume.read().unwrap();
+            for s in &mut buffer[..rendered] {
+                *s *= volume;
+            }
+            rendered
+        });
+        let first = first
+            .sink
+            .open(spec, pause.clone(), render, zone_error(&first.name))?;
+
+        Ok(Box::new(ZoneStream {
+            first: Some(first),
+            others: streams,
+            queues,
+            pause,
+        }))
+    }
+
+    fn is_device(&self) -> bool {
+        self.zones[0].sink.is_device()
+    }
+
+    fn available(&self) -> bool {
+        self.zones.iter().all(|z| z.sink.available())
+    }
+
+    fn bit_perfect(&self, spec: StreamSpec) -> bool {
+        self.zones.iter().all(|z| z.sink.bit_perfect(spec))
+    }
+
+    fn zones(&self) -> Vec<(String, f32)> {
+        self.zones
+            .iter()
+            .map(|z| (z.name.clone(), *z.volume.read().unwrap()))
+            .collect()
+    }
+
+    fn set_zone_volume(&self, name: &str, volume: f32) -> bool {
+        match self.zones.iter().find(|z| z.name == name) {
+            Some(zone) => {
+                *zone.volume.write().unwrap() = volume.clamp(0.0, 1.0);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// the streams of the zones, the other zones play what they are behind before they stop
+struct ZoneStream {
+    first: Option<SinkStream>,
+    others: Vec<SinkStream>,
+    queues: Vec<Arc<Mutex<VecDeque<f32>>>>,
+    pause: Arc<AtomicBool>,
+}
+
+impl Drop for ZoneStream {
+    fn drop(&mut self) {
+        drop(self.first.take());
+
+        // the other zones get the rest of the song as long as they keep playing it
+        let queued = || {
+            self.queues
+                .iter()
+                .map(|q| q.lock().unwrap().len())
+                .sum::<usize>()
+        };
+        let (mut left, mut progress) = (queued(), Instant::now());
+        while left > 0
+            && progress.elapsed() < MAX_ZONE_LAG
+            && !self.pause.load(std::sync::atomic::Ordering::Relaxed)
+        {
+            std::thread::sleep(PAUSE_POLL_INTERVAL);
+            let now = queued();
+            if now < left {
+                progress = Instant::now();
+            }
+            left = now;
+        }
+        self.others.clear();
+    }
+}
+
+/// discards the samples at the pace they would be played at, for running without an audio device
+pub struct NullSink;
+
+impl AudioSink for NullSink {
+    fn open(
+        &self,
+        spec: StreamSpec,
+        pause: Arc<AtomicBool>,
+        render: Render,
+        _error: OnError,
+    ) -> Result<SinkStream, PlayerError> {
+        let mut buffer = Vec::new();
+        spawn_renderer(spec, pause, true, render, move |render, len| {
+            buffer.resize(len, 0.0);
+            render(&mut buffer);
+        })
+    }
+}
+
+/// writes the samples to a 32 bit float wav or 24 bit flac file as fast as they can be rendered,
+/// all songs have to have the format of the first one
+#[derive(Clone)]
+pub struct FileSink {
+    encoder: Arc<Mutex<Option<Encoder>>>,
+    path: Arc<Path>,
+}
+
+impl FileSink {
+    /// the file is created once the first song is played, its format is picked by the extension of `path`
+    pub fn new(path: &Path) -> anyhow::Result<Self> {
+        Encoder::format_supported(path)?;
+
+        Ok(Self {
+            encoder: Arc::default(),
+            path: path.into(),
+        })
+    }
+
+    /// wait for the block being written, the file is complete once the last song ended
+    pub fn finish(&self) {
+        drop(self.encoder.lock().unwrap());
+    }
+}
+
+impl AudioSink for FileSink {
+    fn open(
+        &self,
+        spec: StreamSpec,
+        pause: Arc<AtomicBool>,
+        render: Render,
+        mut error: OnError,
+    ) -> Result<SinkStream, PlayerError> {
+        {
+            let mut encoder = self.encoder.lock().unwrap();
+            match encoder.as_ref() {
+                Some(e) if e.spec != spec => {
+                    return Err(PlayerError::Stream(anyhow::anyhow!(
+                        "Format {spec:?} differs from the format {:?} of {}",
+                        e.spec,
+                        self.path.display()
+                    )))
+                }
+                Some(_) => {}
+                None => {
+                    *encoder = Some(Encoder::create(&self.path, spec).map_err(PlayerError::Stream)?)
+                }
+            }
+        }
+
+        let encoder = self.encoder.clone();
+        let mut buffer = Vec::new();
+        let mut failed = false;
+        spawn_renderer(spec, pause, false, render, move |render, len| {
+            // rendering while holding the lock keeps the samples of consecutive songs in order
+            let mut encoder = encoder.lock().unwrap();
+            buffer.resize(len, 0.0);
+            let rendered = render(&mut buffer);
+
+            if let Some(Err(e)) = encoder.as_mut().map(|e| e.write(&buffer[..rendered])) {
+                if !std::mem::replace(&mut failed, true) {
+                    error(format!("{e:#}"));
+                }
+            }
+        })
+    }
+}
+
+/// keeps the samples rendered so far in memory, rendering as fast as possible, e.g. for tests
+#[derive(Clone, Default)]
+pub struct Capture {
+    samples: Arc<Mutex<Vec<f32>>>,
+}
+
+impl Capture {
+    /// all samples rendered so far
+    pub fn samples(&self) -> Vec<f32> {
+        self.samples.lock().unwrap().clone()
+    }
+
+    /// the number of samples rendered so far
+    pub fn len(&self) -> usize {
+        self.samples.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// forget the samples rendered so far
+    pub fn clear(&self) {
+        self.samples.lock().unwrap().clear();
+    }
+}
+
+impl AudioSink for Capture {
+    fn open(
+        &self,
+        spec: StreamSpec,
+        pause: Arc<AtomicBool>,
+        render: Render,
+        _error: OnError,
+    ) -> Result<SinkStream, PlayerError> {
+        let samples = self.samples.clone();
+        spawn_renderer(spec, pause, false, render, move |render, len| {
+            // rendering while holding the lock keeps the samples of consecutive songs in order,
+            // the end of a song starts the next one while its last block is rendered
+            let mut samples = samples.lock().unwrap();
+            let end = samples.len();
+            samples.resize(end + len, 0.0);
+            let rendered = render(&mut samples[end..]);
+            samples.truncate(end + rendered);
+        })
+    }
+
+    fn bit_perfect(&self, _spec: StreamSpec) -> bool {
+        true
+    }
+}
+
+/// stops its renderer when dropped, like a dropped `cpal::Stream` stops playing
+struct RendererStream {
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for RendererStream {
+    fn drop(&mut self) {
+        self.stopped
+            .store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+}
+
+/// render blocks of samples in a thread for sinks without an audio device, until the returned stream is dropped,
+/// `write` is called with the render function and the number of samples to render
+/// if `paced`, blocks are rendered at the pace they would be played at, otherwise as fast as possible
+fn spawn_renderer(
+    spec: StreamSpec,
+    pause: Arc<AtomicBool>,
+    paced: bool,
+    mut render: Render,
+    mut write: impl FnMut(&mut Render, usize) + Send + 'static,
+) -> Result<SinkStream, PlayerError> {
+    let stopped = Arc::new(AtomicBool::new(false));
+    let stopped2 = stopped.clone();
+    let block = BLOCK_FRAMES * spec.channels as usize;
+    let block_duration = Duration::from_secs_f64(BLOCK_FRAMES as f64 / spec.sample_rate as f64);
+
+    std::thread::Builder::new()
+        .name("renderer thread".to_string())
+        .spawn(move || {
+            let mut next = Instant::now();
+            while !stopped2.load(std::sync::atomic::Ordering::Relaxed) {
+                if pause.load(std::sync::atomic::Ordering::Relaxed) {
+                    std::thread::sleep(PAUSE_POLL_INTERVAL);
+                    next = Instant::now();
+                    continue;
+                }
+
+                write(&mut render, block);
+
+                if paced {
+                    next += block_duration;
+                    std::thread::sleep(next.saturating_duration_since(Instant::now()));
+                } else {
+                    // give the player thread a chance to react to the end of a song
+                    std::thread::yield_now();
+                }
+            }
+        })
+        .map_err(|e| PlayerError::Stream(e.into()))?;
+
+    Ok(Box::new(RendererStream { stopped }))
+}
@@ -0,0 +1,84 @@
+use std::{
+    collections::VecDeque,
+    sync::PoisonError,
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+use log::{error, info, warn};
+
+use crate::notification::Notification;
+
+use super::{Player, PlayerContext};
+
+/// minimum time between two restart attempts
+const RESTART_INTERVAL: Duration = Duration::from_secs(5);
+
+/// watches the player thread and restarts it with the previous queue and position if it died
+pub struct Supervisor {
+    ctx: PlayerContext,
+    handle: JoinHandle<()>,
+    last_restart: Option<Instant>,
+}
+
+impl Supervisor {
+    pub(super) fn new(ctx: PlayerContext) -> anyhow::Result<Self> {
+        let handle = Player::spawn(ctx.clone(), VecDeque::new(), None)?;
+
+        Ok(Self {
+            ctx,
+            handle,
+            last_restart: None,
+        })
+    }
+
+    /// restart the player thread if it is no longer running
+    pub fn check(&mut self) {
+        if !self.handle.is_finished()
+            || self
+                .last_restart
+                .is_some_and(|t| t.elapsed() < RESTART_INTERVAL)
+        {
+            return;
+        }
+        self.last_restart = Some(Instant::now());
+
+        error!("Player thread died, restarting");
+
+        let (queue, resume) = {
+            let facade = self
+                .ctx
+                .facade
+                .read()
+                .unwrap_or_else(PoisonError::into_inner);
+            let resume = facade
+                .current_song()
+                .map(|s| {
+                    (
+                        s.path.clone(),
+                        facade.playing_duration().unwrap_or_default(),
+                    )
+                })
+                .or(facade.loading().map(|p| (p.into(), Duration::ZERO)));
+            (facade.queue.iter().cloned().collect(), resume)
+        };
+        self.ctx.facade.clear_poison();
+        info!("Restoring queue {queue:?} at {resume:?}");
+
+        let message = match Player::spawn(self.ctx.clone(), queue, resume) {
+            Ok(handle) => {
+                self.handle = handle;
+                Notification::error("Player crashed and was restarted")
+            }
+            Err(e) => {
+                error!("Failed to restart player thread: {e:?}");
+                Notification::error(format!("Player crashed, restarting failed: {e:#}"))
+            }
+        };
+
+        self.ctx
+            .notifications
+            .send(message)
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+    }
+}
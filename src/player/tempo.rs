@@ -0,0 +1,85 @@
+//! beat detection for songs without a bpm tag, from the autocorrelation of their onsets
+
+use std::time::Duration;
+
+use crate::song::Song;
+
+use super::loader::LoadedSong;
+
+/// samples of the downmixed signal per value of the onset envelope
+const HOP: usize = 128;
+
+/// the tempo is searched in this range, halves and doubles of it are hard to tell apart
+const MIN_BPM: f64 = 70.0;
+const MAX_BPM: f64 = 180.0;
+
+/// decode the whole song and estimate its tempo in beats per minute,
+/// `None` if it has no discernible beat, e.g. silence
+pub fn detect(song: &Song) -> anyhow::Result<Option<f64>> {
+    let mut loaded = LoadedSong::load(song.clone(), Duration::ZERO, None, None)?;
+    let channels = loaded.signal_spec.channels.count().max(1);
+    let rate = loaded.signal_spec.rate as f64;
+
+    // the energy of each hop, compressed so that quiet onsets count as well
+    let mut energies = vec![];
+    let (mut energy, mut samples) = (0.0f64, 0);
+    for buffer in loaded.decoder.buffers() {
+        let buffer = buffer?;
+        for frame in buffer.chunks(channels) {
+            let mono = frame.iter().sum::<f32>() as f64 / channels as f64;
+            energy += mono * mono;
+            samples += 1;
+            if samples == HOP {
+                energies.push((1.0 + 1000.0 * energy / HOP as f64).ln());
+                (energy, samples) = (0.0, 0);
+            }
+        }
+    }
+
+    // onsets are rises in energy
+    let onsets = energies
+        .windows(2)
+        .map(|w| (w[1] - w[0]).max(0.0))
+        .collect::<Vec<_>>();
+
+    let hops_per_minute = 60.0 * rate / HOP as f64;
+    let min_lag = (hops_per_minute / MAX_BPM).floor() as usize;
+    let max_lag = (hops_per_minute / MIN_BPM).ceil() as usize;
+    if onsets.len() <= max_lag + 1 {
+        return Ok(None);
+    }
+
+    let correlation = |lag: usize| {
+        onsets
+            .iter()
+            .zip(&onsets[lag..])
+            .map(|(a, b)| a * b)
+            .sum::<f64>()
+    };
+    let correlations = (min_lag - 1..=max_lag + 1)
+        .map(correlation)
+        .collect::<Vec<_>>();
+
+    let Some((i, &best)) = correlations[1..correlations.len() - 1]
+        .iter()
+        .enumerate()
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(i, c)| (i + 1, c))
+    else {
+        return Ok(None);
+    };
+    if best <= 0.0 {
+        return Ok(None);
+    }
+
+    // the peak between the lags, from the parabola through it and its neighbours
+    let (before, after) = (correlations[i - 1], correlations[i + 1]);
+    let curvature = before - 2.0 * best + after;
+    let shift = match curvature < 0.0 {
+        true => 0.5 * (before - after) / curvature,
+        false => 0.0,
+    };
+    let lag = (min_lag - 1 + i) as f64 + shift;
+
+    Ok(Some(hops_per_minute / lag))
+}
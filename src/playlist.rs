@@ -0,0 +1,263 @@
+use std::{
+    collections::HashMap,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, RwLock, Weak},
+    time::{Duration, SystemTime},
+};
+
+use anyhow::Context;
+use log::{info, warn};
+use walkdir::WalkDir;
+
+use crate::{config::Config, notification::Notification};
+
+const PLAYLIST_EXTENSION: &str = "m3u";
+
+/// extensions of the playlists shown in the playlists tab
+const PLAYLIST_EXTENSIONS: [&str; 3] = ["m3u", "m3u8", "xspf"];
+
+/// time between two checks of the playlist directories for changed playlists
+const SCAN_INTERVAL: Duration = Duration::from_secs(5);
+
+/// path of the playlist with the given name
+pub fn playlist_path(dir: &Path, name: &str) -> PathBuf {
+    // `with_extension` would cut names like "mix 1.5" at their dot
+    dir.join(format!("{name}.{PLAYLIST_EXTENSION}"))
+}
+
+/// write `songs` as an m3u playlist named `name`, replacing a playlist of the same name
+pub fn save<P: AsRef<Path>>(dir: &Path, name: &str, songs: &[P]) -> anyhow::Result<PathBuf> {
+    let name = name.trim();
+    anyhow::ensure!(!name.is_empty(), "The playlist needs a name");
+    anyhow::ensure!(
+        !name.contains(['/', '\\']) && !name.starts_with('.'),
+        "Invalid playlist name {name}"
+    );
+    anyhow::ensure!(!songs.is_empty(), "Nothing to save");
+
+    std::fs::create_dir_all(dir).context("Failed to create playlists directory")?;
+
+    let path = playlist_path(dir, name);
+    let mut file =
+        std::io::BufWriter::new(std::fs::File::create(&path).context("Failed to create playlist")?);
+    writeln!(file, "#EXTM3U")?;
+    for song in songs {
+        writeln!(file, "{}", song.as_ref().display())?;
+    }
+    file.flush().context("Failed to write playlist")?;
+    info!("Saved {} songs to {}", songs.len(), path.display());
+
+    Ok(path)
+}
+
+/// a playlist read from one of the playlist directories
+#[derive(Debug, Clone)]
+pub struct Playlist {
+    pub path: PathBuf,
+    pub songs: Vec<PathBuf>,
+    /// entries that are not files, e.g. streams or deleted songs
+    pub missing: usize,
+    /// the playlist is read again when it changes
+    modified: Option<SystemTime>,
+}
+
+impl Playlist {
+    pub fn name(&self) -> String {
+        self.path
+            .file_stem()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default()
+    }
+}
+
+/// read an m3u or xspf playlist, relative entries are relative to the directory of the playlist
+pub fn load(path: &Path) -> anyhow::Result<Playlist> {
+    let contents = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    let modified = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+
+    let entries = match path.extension().and_then(|e| e.to_str()) {
+        Some("xspf") => xspf_entries(&contents)?,
+        _ => contents
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+    };
+
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let (songs, missing): (Vec<_>, Vec<_>) = entries
+        .iter()
+        .map(|entry| match entry.strip_prefix("file://") {
+            Some(url) => PathBuf::from(percent_decode(url)),
+            // streams are not files
+            None if entry.contains("://") => PathBuf::new(),
+            None => dir.join(entry),
+        })
+        .partition(|p| p.is_file());
+
+    Ok(Playlist {
+        path: path.to_path_buf(),
+        songs,
+        missing: missing.len(),
+        modified,
+    })
+}
+
+/// the `<location>` of the tracks of an xspf playlist
+fn xspf_entries(contents: &str) -> anyhow::Result<Vec<String>> {
+    let location = regex::Regex::new(r"<location>\s*([^<]*?)\s*</location>")?;
+    Ok(location
+        .captures_iter(contents)
+        .map(|c| {
+            c[1].replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&amp;", "&")
+        })
+        .collect())
+}
+
+/// `%20` and the like in file urls
+fn percent_decode(url: &str) -> String {
+    let mut bytes = vec![];
+    let mut rest = url.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let hex = tail
+            .get(..2)
+            .and_then(|h| std::str::from_utf8(h).ok())
+            .and_then(|h| u8::from_str_radix(h, 16).ok());
+        match (byte, hex) {
+            (b'%', Some(decoded)) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            _ => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// the playlists of `playlists_directory` and `playlist_directories`, read on startup and again when they change
+#[derive(Clone)]
+pub struct Playlists {
+    playlists: Arc<RwLock<Vec<Playlist>>>,
+}
+
+impl Playlists {
+    /// read the playlists in a thread that keeps checking them for changes
+    pub fn watch(config: &Config, notifications: mpsc::Sender<Notification>) -> Self {
+        let playlists = Self {
+            playlists: Arc::default(),
+        };
+
+        let mut dirs = vec![config.playlists_directory.clone()];
+        dirs.extend(config.playlist_directories.iter().cloned());
+        for dir in dirs.iter().skip(1).filter(|d| !d.is_dir()) {
+            warn!("Playlist directory {} is not a directory", dir.display());
+            notifications
+                .send(Notification::error(format!(
+                    "Playlist directory {} is not a directory",
+                    dir.display()
+                )))
+                .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+        }
+
+        let weak = Arc::downgrade(&playlists.playlists);
+        std::thread::Builder::new()
+            .name("playlists thread".to_string())
+            .spawn(move || scan_loop(&dirs, weak, notifications))
+            .map_err(|e| warn!("Failed to spawn playlists thread: {e:?}"))
+            .ok();
+
+        playlists
+    }
+
+    pub fn playlists(&self) -> Vec<Playlist> {
+        self.playlists.read().unwrap().clone()
+    }
+}
+
+fn scan_loop(
+    dirs: &[PathBuf],
+    playlists: Weak<RwLock<Vec<Playlist>>>,
+    notifications: mpsc::Sender<Notification>,
+) {
+    // modification times of playlists that failed to load, they are retried once they change
+    let mut failed = HashMap::new();
+
+    loop {
+        let Some(playlists) = playlists.upgrade() else {
+            return;
+        };
+
+        let files = dirs
+            .iter()
+            .flat_map(WalkDir::new)
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .filter(|e| {
+                e.path()
+                    .extension()
+                    .and_then(|e| e.to_str())
+                    .is_some_and(|e| PLAYLIST_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+            })
+            .map(|e| e.into_path())
+            .collect::<Vec<_>>();
+
+        let known = playlists
+            .read()
+            .unwrap()
+            .iter()
+            .map(|p| (p.path.clone(), p.clone()))
+            .collect::<HashMap<_, _>>();
+        failed.retain(|p, _| files.contains(p));
+
+        let mut changed = false;
+        let mut current = vec![];
+        for path in files {
+            let modified = std::fs::metadata(&path).and_then(|m| m.modified()).ok();
+            match known.get(&path) {
+                Some(playlist) if playlist.modified == modified => {
+                    current.push(playlist.clone());
+                    continue;
+                }
+                _ if failed.get(&path) == Some(&modified) => continue,
+                _ => changed = true,
+            }
+
+            match load(&path) {
+                Ok(playlist) => {
+                    failed.remove(&path);
+                    current.push(playlist);
+                }
+                Err(e) => {
+                    warn!("Failed to load playlist {}: {e:?}", path.display());
+                    notifications
+                        .send(Notification::error(format!(
+                            "Failed to load playlist {}: {e:#}",
+                            path.display()
+                        )))
+                        .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+                    failed.insert(path, modified);
+                }
+            }
+        }
+
+        if changed || current.len() != known.len() {
+            info!("Loaded {} playlists", current.len());
+            current.sort_by_key(|p| p.name().to_lowercase());
+            *playlists.write().unwrap() = current;
+        }
+        drop(playlists);
+
+        std::thread::sleep(SCAN_INTERVAL);
+    }
+}
@@ -0,0 +1,179 @@
+use std::path::{Path, PathBuf};
+
+use regex::Regex;
+
+use crate::{
+    cache::Cache,
+    song::{Song, StandardTagKey},
+};
+
+/// a numeric property of a song
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    /// in seconds
+    Duration,
+    /// in kbit/s
+    Bitrate,
+    Year,
+    /// in beats per minute
+    Bpm,
+}
+
+impl Field {
+    fn value(self, song: &Song) -> Option<f64> {
+        match self {
+            Field::Duration => Some(song.duration.as_secs_f64()),
+            Field::Bitrate => song.bitrate.map(f64::from),
+            Field::Bpm => song.bpm(),
+            Field::Year => [
+                StandardTagKey::Date,
+                StandardTagKey::OriginalDate,
+                StandardTagKey::ReleaseDate,
+            ]
+            .iter()
+            .filter_map(|k| song.standard_tags.get(k))
+            // dates are "1997", "1997-05-21" or similar, the year comes first
+            .find_map(|v| v.to_string().get(..4)?.parse().ok()),
+        }
+    }
+
+    /// `value` in the unit of the field, durations also as "m:ss" or "h:mm:ss"
+    fn parse(self, value: &str) -> anyhow::Result<f64> {
+        let number = match self {
+            Field::Duration => value
+                .split(':')
+                .try_fold(0.0, |acc, x| x.parse::<f64>().map(|x| acc * 60.0 + x))
+                .ok(),
+            _ => value.parse().ok(),
+        };
+
+        number.ok_or(anyhow::anyhow!("Invalid value for {self:?}: {value}"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Comparison {
+    Less(f64),
+    LessOrEqual(f64),
+    Greater(f64),
+    GreaterOrEqual(f64),
+    Equal(f64),
+    /// inclusive
+    Range(f64, f64),
+}
+
+impl Comparison {
+    fn holds(self, x: f64) -> bool {
+        match self {
+            Comparison::Less(v) => x < v,
+            Comparison::LessOrEqual(v) => x <= v,
+            Comparison::Greater(v) => x > v,
+            Comparison::GreaterOrEqual(v) => x >= v,
+            Comparison::Equal(v) => x == v,
+            Comparison::Range(from, to) => (from..=to).contains(&x),
+        }
+    }
+}
+
+/// a search query, text looked up in the search index and predicates on the cached metadata,
+/// e.g. `beatles duration > 3:00 bitrate < 192 year in 1965..1969 bpm in 120..130 genre = rock`
+#[derive(Debug, Clone)]
+pub struct Query {
+    /// the query without the predicates
+    pub text: String,
+    predicates: Vec<(Field, Comparison)>,
+    /// lowercase genres the song needs to have, `genre = "hip hop"`
+    genres: Vec<String>,
+}
+
+impl Query {
+    pub fn parse(query: &str) -> anyhow::Result<Self> {
+        let regex = Regex::new(r"(?i)\b(duration|bitrate|year|bpm)\s*(<=|>=|<|>|=|\bin\b)\s*(\S+)")
+            .expect("Failed to compile predicate regex");
+
+        let genre_regex = Regex::new(r#"(?i)\bgenre\s*[:=]\s*("[^"]*"|\S+)"#)
+            .expect("Failed to compile genre regex");
+        let genres = genre_regex
+            .captures_iter(query)
+            .map(|c| c[1].trim_matches('"').to_lowercase())
+            .collect();
+        let query = genre_regex.replace_all(query, " ");
+
+        let mut predicates = vec![];
+        for captures in regex.captures_iter(&query) {
+            let field = match captures[1].to_lowercase().as_str() {
+                "duration" => Field::Duration,
+                "bitrate" => Field::Bitrate,
+                "bpm" => Field::Bpm,
+                _ => Field::Year,
+            };
+
+            let value = &captures[3];
+            let comparison = match captures[2].to_lowercase().as_str() {
+                "<" => Comparison::Less(field.parse(value)?),
+                "<=" => Comparison::LessOrEqual(field.parse(value)?),
+                ">" => Comparison::Greater(field.parse(value)?),
+                ">=" => Comparison::GreaterOrEqual(field.parse(value)?),
+                "=" => Comparison::Equal(field.parse(value)?),
+                _ => {
+                    let (from, to) = value
+                        .split_once("..")
+                        .ok_or(anyhow::anyhow!("Expected a range like 1990..1999: {value}"))?;
+                    Comparison::Range(field.parse(from)?, field.parse(to)?)
+                }
+            };
+            predicates.push((field, comparison));
+        }
+
+        Ok(Self {
+            text: regex.replace_all(&query, " ").trim().to_string(),
+            predicates,
+            genres,
+        })
+    }
+
+    /// whether the song fulfills all predicates, songs without the property never do
+    pub fn matches(&self, song: &Song) -> bool {
+        self.predicates
+            .iter()
+            .all(|(field, comparison)| field.value(song).is_some_and(|x| comparison.holds(x)))
+            && self
+                .genres
+                .iter()
+                .all(|g| song.genres.iter().any(|s| s.to_lowercase() == *g))
+    }
+
+    /// the songs matching the text and the predicates, all songs matching the predicates without text
+    #[cfg_attr(
+        not(any(feature = "scripting", all(feature = "mpris", target_os = "linux"))),
+        allow(dead_code)
+    )]
+    pub fn lookup(&self, cache: &Cache) -> Vec<PathBuf> {
+        if self.text.is_empty() {
+            if self.predicates.is_empty() && self.genres.is_empty() {
+                return vec![];
+            }
+
+            return cache
+                .songs()
+                .filter(|(s, _)| self.matches(s))
+                .map(|(_, p)| p)
+                .collect();
+        }
+
+        cache
+            .index()
+            .lookup(&self.text)
+            .into_iter()
+            .filter(|p| {
+                cache
+                    .get(p)
+                    .ok()
+                    .flatten()
+                    .and_then(|e| e.as_file().ok())
+                    .is_some_and(|s| self.matches(s))
+            })
+            .map(Path::to_path_buf)
+            .collect()
+    }
+}
@@ -0,0 +1,158 @@
+use std::{collections::HashMap, fmt::Write, time::Duration};
+
+use chrono::{Datelike, Local, NaiveDate, TimeDelta};
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::stats::Listen;
+
+/// number of days in the daily listening time
+const DAYS: usize = 14;
+
+/// number of weeks in the weekly listening time
+const WEEKS: usize = 8;
+
+/// entries of the top lists
+const TOP: usize = 10;
+
+/// listening time in a day or a week
+#[derive(Debug, Clone, Serialize)]
+pub struct Period {
+    /// first day of the period, weeks start on monday
+    pub start: NaiveDate,
+    pub seconds: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TopEntry {
+    pub name: String,
+    pub plays: usize,
+    pub seconds: u64,
+}
+
+/// listening statistics aggregated from the history
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub total_seconds: u64,
+    /// the last `DAYS` days, oldest first
+    pub days: Vec<Period>,
+    /// the last `WEEKS` weeks, oldest first
+    pub weeks: Vec<Period>,
+    /// by listening time
+    pub top_artists: Vec<TopEntry>,
+    pub top_albums: Vec<TopEntry>,
+    pub top_tracks: Vec<TopEntry>,
+}
+
+impl Report {
+    pub fn new(history: &[Listen]) -> Self {
+        let today = Local::now().date_naive();
+        let this_week = today - TimeDelta::days(today.weekday().num_days_from_monday() as i64);
+
+        let period = |start: NaiveDate, days: i64| {
+            let listened = history
+                .iter()
+                .filter(|l| {
+                    let date = l.time.date_naive();
+                    date >= start && date < start + TimeDelta::days(days)
+                })
+                .map(|l| l.listened)
+                .sum::<Duration>();
+
+            Period {
+                start,
+                seconds: listened.as_secs(),
+            }
+        };
+
+        Self {
+            total_seconds: history
+                .iter()
+                .map(|l| l.listened)
+                .sum::<Duration>()
+                .as_secs(),
+            days: (0..DAYS as i64)
+                .rev()
+                .map(|d| period(today - TimeDelta::days(d), 1))
+                .collect(),
+            weeks: (0..WEEKS as i64)
+                .rev()
+                .map(|w| period(this_week - TimeDelta::weeks(w), 7))
+                .collect(),
+            top_artists: top(history, |l| l.artist.clone()),
+            top_albums: top(history, |l| l.album.clone()),
+            top_tracks: top(history, |l| {
+                let title = l
+                    .title
+                    .clone()
+                    .or_else(|| l.path.file_name().map(|f| f.to_string_lossy().to_string()))?;
+
+                Some(match &l.artist {
+                    Some(artist) => format!("{artist} - {title}"),
+                    None => title,
+                })
+            }),
+        }
+    }
+
+    /// one row per period and top entry, as `kind,name,plays,seconds`
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("kind,name,plays,seconds\n");
+
+        for (kind, periods) in [("day", &self.days), ("week", &self.weeks)] {
+            for p in periods {
+                writeln!(csv, "{kind},{},,{}", p.start, p.seconds).unwrap();
+            }
+        }
+
+        for (kind, entries) in [
+            ("artist", &self.top_artists),
+            ("album", &self.top_albums),
+            ("track", &self.top_tracks),
+        ] {
+            for e in entries {
+                writeln!(
+                    csv,
+                    "{kind},{},{},{}",
+                    csv_field(&e.name),
+                    e.plays,
+                    e.seconds
+                )
+                .unwrap();
+            }
+        }
+
+        csv
+    }
+}
+
+/// the `TOP` names by listening time, listens without a name are ignored
+fn top(history: &[Listen], name: impl Fn(&Listen) -> Option<String>) -> Vec<TopEntry> {
+    let mut entries = HashMap::<String, (usize, Duration)>::new();
+    for listen in history {
+        if let Some(name) = name(listen) {
+            let entry = entries.entry(name).or_default();
+            entry.0 += 1;
+            entry.1 += listen.listened;
+        }
+    }
+
+    entries
+        .into_iter()
+        .map(|(name, (plays, listened))| TopEntry {
+            name,
+            plays,
+            seconds: listened.as_secs(),
+        })
+        .sorted_by(|a, b| b.seconds.cmp(&a.seconds).then(a.name.cmp(&b.name)))
+        .take(TOP)
+        .collect()
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
@@ -0,0 +1,174 @@
+//! rescanning the library while the player runs
+
+use std::sync::{
+    atomic::{AtomicBool, AtomicUsize},
+    mpsc, Arc,
+};
+
+use log::{info, warn};
+
+use crate::{
+    cache::{Rescan, SharedCache},
+    config::Config,
+    notification::Notification,
+    player::tempo,
+    song::{StandardTagKey, Value},
+};
+
+/// songs whose detected tempo is applied to the cache at once, as every update rebuilds the search index
+const TEMPO_BATCH: usize = 50;
+
+/// runs rescans of the library in the background, one at a time
+#[derive(Clone)]
+pub struct Scanner {
+    config: Arc<Config>,
+    cache: SharedCache,
+    notifications: mpsc::Sender<Notification>,
+    running: Arc<AtomicBool>,
+    /// files looked at by the running rescan
+    progress: Arc<AtomicUsize>,
+}
+
+impl Scanner {
+    pub fn new(
+        config: Arc<Config>,
+        cache: SharedCache,
+        notifications: mpsc::Sender<Notification>,
+    ) -> Self {
+        Self {
+            config,
+            cache,
+            notifications,
+            running: Arc::default(),
+            progress: Arc::default(),
+        }
+    }
+
+    /// the number of files looked at so far while a rescan is running
+    pub fn progress(&self) -> Option<usize> {
+        self.running
+            .load(std::sync::atomic::Ordering::Relaxed)
+            .then(|| self.progress.load(std::sync::atomic::Ordering::Relaxed))
+    }
+
+    /// add new songs to the cache and remove the ones that are gone in a thread,
+    /// with `full` all songs are read again, e.g. after retagging, does nothing while a rescan is running
+    pub fn start(&self, full: bool) {
+        self.spawn("Scanning the library in the background", move |scanner| {
+            scanner.rescan(full);
+            if scanner.config.detect_tempo {
+                scanner.detect_tempo();
+            }
+        });
+    }
+
+    /// detect the tempo of the songs without a bpm tag in a thread, does nothing while a rescan is running
+    pub fn start_tempo_detection(&self) {
+        self.spawn(
+            "Detecting the tempo of songs in the background",
+            Scanner::detect_tempo,
+        );
+    }
+
+    /// run `job` in the scan thread and save the cache afterwards
+    fn spawn(&self, message: &str, job: impl FnOnce(&Scanner) + Send + 'static) {
+        if self
+            .running
+            .swap(true, std::sync::atomic::Ordering::Relaxed)
+        {
+            self.notify(Notification::info("The library is already being scanned"));
+            return;
+        }
+        self.notify(Notification::info(message));
+
+        let scanner = self.clone();
+        std::thread::Builder::new()
+            .name("scan thread".to_string())
+            .spawn(move || {
+                job(&scanner);
+                scanner
+                    .cache
+                    .load()
+                    .save(&scanner.config)
+                    .unwrap_or_else(|e| warn!("Failed to save cache {e:?}"));
+                scanner
+                    .running
+                    .store(false, std::sync::atomic::Ordering::Relaxed);
+            })
+            .map_err(|e| {
+                warn!("Failed to spawn scan thread: {e:?}");
+                self.running
+                    .store(false, std::sync::atomic::Ordering::Relaxed);
+            })
+            .ok();
+    }
+
+    fn rescan(&self, full: bool) {
+        self.progress.store(0, std::sync::atomic::Ordering::Relaxed);
+        let rescan = self.cache.load().rescan(&self.config, full, &self.progress);
+        let (read, removed) = (rescan.songs.len(), rescan.removed.len());
+        self.cache.update(|c| c.apply(rescan));
+
+        info!("Rescan read {read} songs and removed {removed}");
+        self.notify(Notification::info(match full {
+            true => format!("Scanned the library: read {read} songs, removed {removed}"),
+            false => format!("Scanned the library: {read} new songs, removed {removed}"),
+        }));
+    }
+
+    /// store the detected tempo of the available songs without a bpm tag in the cache,
+    /// 0 if a song has no discernible beat or failed to decode so that it is not analyzed again until it changes
+    fn detect_tempo(&self) {
+        self.progress.store(0, std::sync::atomic::Ordering::Relaxed);
+        let cache = self.cache.load();
+        let songs = cache
+            .songs()
+            .filter(|(s, p)| {
+                !s.standard_tags.contains_key(&StandardTagKey::Bpm) && cache.is_available(p)
+            })
+            .map(|(s, _)| s.clone())
+            .collect::<Vec<_>>();
+
+        let mut detected = 0;
+        for batch in songs.chunks(TEMPO_BATCH) {
+            let songs = batch
+                .iter()
+                .map(|song| {
+                    self.progress
+                        .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                    let bpm = tempo::detect(song).unwrap_or_else(|e| {
+                        warn!("Failed to detect tempo of {}: {e:?}", song.path.display());
+                        None
+                    });
+                    detected += bpm.is_some() as usize;
+
+                    let mut song = song.clone();
+                    song.standard_tags.insert(
+                        StandardTagKey::Bpm,
+                        Value::UnsignedInt(bpm.map_or(0, |b| b.round() as u64)),
+                    );
+                    (song.path.to_path_buf(), song)
+                })
+                .collect();
+            self.cache.update(|c| {
+                c.apply(Rescan {
+                    songs,
+                    ..Rescan::default()
+                })
+            });
+        }
+
+        info!("Detected the tempo of {detected} of {} songs", songs.len());
+        if !songs.is_empty() {
+            self.notify(Notification::info(format!(
+                "Detected the tempo of {detected} songs"
+            )));
+        }
+    }
+
+    fn notify(&self, notification: Notification) {
+        self.notifications
+            .send(notification)
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+    }
+}
@@ -0,0 +1,215 @@
+use std::{
+    path::PathBuf,
+    sync::{mpsc, Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use chrono::{Local, NaiveDate, TimeDelta};
+use log::{info, warn};
+
+use crate::{
+    cache::SharedCache,
+    config::{Alarm, Config},
+    notification::Notification,
+    player::command::Command,
+};
+
+/// interval of the scheduler thread, also the step size of the fades
+const TICK: Duration = Duration::from_millis(250);
+
+/// the sleep timer fades out over this long before pausing
+const SLEEP_FADE_OUT: Duration = Duration::from_secs(30);
+
+/// durations in minutes the sleep timer cycles through
+const SLEEP_DURATIONS: [u64; 4] = [15, 30, 45, 60];
+
+/// an alarm missed by more than this, e.g. because the player was started later, does not go off
+const ALARM_WINDOW: TimeDelta = TimeDelta::minutes(1);
+
+#[derive(Debug, Clone, Copy)]
+struct SleepTimer {
+    duration: Duration,
+    deadline: Instant,
+}
+
+/// pauses playback when the sleep timer runs out and starts the configured alarm,
+/// fading the volume out and in
+#[derive(Clone)]
+pub struct Scheduler {
+    sleep: Arc<Mutex<Option<SleepTimer>>>,
+}
+
+impl Scheduler {
+    pub fn start(
+        config: Arc<Config>,
+        cache: SharedCache,
+        cmd: mpsc::Sender<Command>,
+        notifications: mpsc::Sender<Notification>,
+    ) -> Self {
+        let scheduler = Self {
+            sleep: Arc::default(),
+        };
+
+        let mut state = State {
+            sleep: scheduler.sleep.clone(),
+            config,
+            cache,
+            cmd,
+            notifications,
+            fade: 1.0,
+            alarm_fired: None,
+            ramp_start: None,
+        };
+        std::thread::Builder::new()
+            .name("scheduler thread".to_string())
+            .spawn(move || loop {
+                if let Err(e) = state.tick() {
+                    warn!("Scheduler stopped: {e:?}");
+                    return;
+                }
+                std::thread::sleep(TICK);
+            })
+            .map_err(|e| warn!("Failed to spawn scheduler thread: {e:?}"))
+            .ok();
+
+        scheduler
+    }
+
+    /// time until the sleep timer pauses playback, `None` if it is off
+    pub fn sleep_remaining(&self) -> Option<Duration> {
+        self.sleep
+            .lock()
+            .unwrap()
+            .map(|t| t.deadline.saturating_duration_since(Instant::now()))
+    }
+
+    /// set the sleep timer to the next of `SLEEP_DURATIONS`, turning it off after the longest
+    pub fn cycle_sleep_timer(&self) {
+        let mut sleep = self.sleep.lock().unwrap();
+        let next = SLEEP_DURATIONS
+            .iter()
+            .map(|&m| Duration::from_secs(m * 60))
+            .find(|&d| sleep.is_none_or(|t| d > t.duration));
+
+        *sleep = next.map(|duration| SleepTimer {
+            duration,
+            deadline: Instant::now() + duration,
+        });
+        info!("Sleep timer: {:?}", next);
+    }
+}
+
+struct State {
+    sleep: Arc<Mutex<Option<SleepTimer>>>,
+    config: Arc<Config>,
+    cache: SharedCache,
+    cmd: mpsc::Sender<Command>,
+    notifications: mpsc::Sender<Notification>,
+    /// fade factor last sent to the player
+    fade: f32,
+    /// day the alarm last went off
+    alarm_fired: Option<NaiveDate>,
+    /// start of the volume ramp of the alarm
+    ramp_start: Option<Instant>,
+}
+
+impl State {
+    fn tick(&mut self) -> anyhow::Result<()> {
+        let timer = *self.sleep.lock().unwrap();
+        let sleep_fade = match timer {
+            Some(t) if t.deadline <= Instant::now() => {
+                *self.sleep.lock().unwrap() = None;
+                self.cmd.send(Command::Pause)?;
+                self.notify(Notification::info("Sleep timer expired, paused playback"));
+                1.0
+            }
+            Some(t) => (t
+                .deadline
+                .saturating_duration_since(Instant::now())
+                .as_secs_f32()
+                / SLEEP_FADE_OUT.as_secs_f32())
+            .min(1.0),
+            None => 1.0,
+        };
+
+        let config = self.config.clone();
+        if let Some(alarm) = &config.alarm {
+            let now = Local::now();
+            let since = now.time().signed_duration_since(alarm.time);
+            if self.alarm_fired != Some(now.date_naive())
+                && since >= TimeDelta::zero()
+                && since < ALARM_WINDOW
+            {
+                self.alarm_fired = Some(now.date_naive());
+                self.fire(alarm)?;
+            }
+        }
+
+        let ramp_up = config
+            .alarm
+            .as_ref()
+            .map_or(Duration::ZERO, |a| Duration::from_secs(a.ramp_up));
+        let ramp_fade = match self.ramp_start {
+            Some(start) if start.elapsed() < ramp_up => {
+                start.elapsed().as_secs_f32() / ramp_up.as_secs_f32()
+            }
+            _ => {
+                self.ramp_start = None;
+                1.0
+            }
+        };
+
+        let fade = sleep_fade * ramp_fade;
+        if fade != self.fade {
+            self.cmd.send(Command::Fade(fade))?;
+            self.fade = fade;
+        }
+
+        Ok(())
+    }
+
+    /// replace the queue with the songs of the alarm, starting silently
+    fn fire(&mut self, alarm: &Alarm) -> anyhow::Result<()> {
+        let mut songs = if alarm.path.is_dir() {
+            self.cache
+                .load()
+                .songs()
+                .map(|(_, p)| p)
+                .filter(|p| p.starts_with(&alarm.path))
+                .collect::<Vec<PathBuf>>()
+        } else {
+            vec![alarm.path.clone()]
+        };
+        songs.sort();
+
+        if songs.is_empty() {
+            warn!("No songs for alarm in {}", alarm.path.display());
+            self.notify(Notification::error(format!(
+                "Alarm went off, but there are no songs in {}",
+                alarm.path.display()
+            )));
+            return Ok(());
+        }
+        info!("Alarm went off, playing {}", alarm.path.display());
+
+        self.cmd.send(Command::Fade(0.0))?;
+        self.fade = 0.0;
+        self.ramp_start = Some(Instant::now());
+
+        self.cmd.send(Command::Replace(
+            songs.into_iter().map(|p| p.into_boxed_path()).collect(),
+        ))?;
+        self.notify(Notification::info(format!(
+            "⏰ Alarm, playing {}",
+            alarm.path.display()
+        )));
+
+        Ok(())
+    }
+
+    fn notify(&self, notification: Notification) {
+        self.notifications
+            .send(notification)
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+    }
+}
@@ -0,0 +1,423 @@
+use std::{
+    collections::HashSet,
+    sync::{mpsc, Arc, RwLock},
+};
+
+#[cfg(not(feature = "scripting"))]
+use log::warn;
+
+use crate::{
+    cache::SharedCache,
+    config::Config,
+    notification::Notification,
+    player::{command::Command, facade::PlayerFacade},
+};
+
+#[cfg_attr(not(feature = "scripting"), allow(dead_code))]
+enum ScriptEvent {
+    Key(String),
+}
+
+/// handle used by the tui to forward key presses bound by scripts
+#[derive(Clone, Default)]
+pub struct ScriptHandle {
+    keys: Arc<RwLock<HashSet<String>>>,
+    tx: Option<mpsc::Sender<ScriptEvent>>,
+}
+
+impl ScriptHandle {
+    /// forward a key press to the scripts, returns whether a script has bound the key
+    pub fn key(&self, key: &str) -> bool {
+        if !self.keys.read().unwrap().contains(key) {
+            return false;
+        }
+
+        self.tx
+            .as_ref()
+            .is_some_and(|tx| tx.send(ScriptEvent::Key(key.to_string())).is_ok())
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+pub fn start(
+    config: Arc<Config>,
+    _cache: SharedCache,
+    _cmd: mpsc::Sender<Command>,
+    _player: Arc<RwLock<PlayerFacade>>,
+    _notifications: mpsc::Sender<Notification>,
+) -> ScriptHandle {
+    if !config.scripts.is_empty() {
+        warn!("Scripts are configured, but ramp was built without the scripting feature");
+    }
+
+    ScriptHandle::default()
+}
+
+/// load the configured scripts and run them on their own thread
+#[cfg(feature = "scripting")]
+pub fn start(
+    config: Arc<Config>,
+    cache: SharedCache,
+    cmd: mpsc::Sender<Command>,
+    player: Arc<RwLock<PlayerFacade>>,
+    notifications: mpsc::Sender<Notification>,
+) -> ScriptHandle {
+    if config.scripts.is_empty() {
+        return ScriptHandle::default();
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let handle = ScriptHandle {
+        keys: Arc::default(),
+        tx: Some(tx),
+    };
+
+    let keys = handle.keys.clone();
+    std::thread::Builder::new()
+        .name("script thread".to_string())
+        .spawn(move || engine::run(config, cache, cmd, player, notifications, keys, rx))
+        .map_err(|e| log::warn!("Failed to spawn script thread: {e:?}"))
+        .ok();
+
+    handle
+}
+
+#[cfg(feature = "scripting")]
+mod engine {
+    use std::{
+        cell::RefCell,
+        collections::{HashMap, HashSet},
+        path::Path,
+        rc::Rc,
+        sync::{mpsc, Arc, RwLock},
+        time::Duration,
+    };
+
+    use log::{debug, info, warn};
+    use rhai::{Array, Dynamic, Engine, Map, Scope, AST};
+
+    use crate::{
+        cache::SharedCache,
+        config::Config,
+        notification::Notification,
+        player::{command::Command, facade::PlayerFacade},
+        query::Query,
+        song::{Song, StandardTagKey},
+    };
+
+    use super::ScriptEvent;
+
+    /// how often the scripts check the player state for changes
+    const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+    struct Script {
+        ast: AST,
+        scope: Scope<'static>,
+    }
+
+    impl Script {
+        fn call(&mut self, engine: &Engine, name: &str, args: impl rhai::FuncArgs) {
+            if !self.ast.iter_functions().any(|f| f.name == name) {
+                return;
+            }
+
+            engine
+                .call_fn::<Dynamic>(&mut self.scope, &self.ast, name, args)
+                .map_err(|e| warn!("Script function {name} failed: {e}"))
+                .ok();
+        }
+    }
+
+    pub fn run(
+        config: Arc<Config>,
+        cache: SharedCache,
+        cmd: mpsc::Sender<Command>,
+        player: Arc<RwLock<PlayerFacade>>,
+        notifications: mpsc::Sender<Notification>,
+        keys: Arc<RwLock<HashSet<String>>>,
+        rx: mpsc::Receiver<ScriptEvent>,
+    ) {
+        let bindings = Rc::new(RefCell::new(Vec::<(String, String)>::new()));
+        let engine = build_engine(cache, cmd, player.clone(), notifications, bindings.clone());
+
+        let mut scripts = vec![];
+        let mut key_bindings = HashMap::new();
+        for path in &config.scripts {
+            let script = engine.compile_file(path.clone()).and_then(|ast| {
+                let mut scope = Scope::new();
+                engine.run_ast_with_scope(&mut scope, &ast)?;
+                Ok(Script { ast, scope })
+            });
+
+            match script {
+                Ok(script) => {
+                    info!("Loaded script {}", path.display());
+                    for (key, function) in bindings.borrow_mut().drain(..) {
+                        key_bindings.insert(key, (scripts.len(), function));
+                    }
+                    scripts.push(script);
+                }
+                Err(e) => warn!("Failed to load script {}: {e}", path.display()),
+            }
+        }
+        *keys.write().unwrap() = key_bindings.keys().cloned().collect();
+        debug!("Script key bindings: {key_bindings:?}");
+
+        let mut last_song = None;
+        loop {
+            match rx.recv_timeout(POLL_INTERVAL) {
+                Ok(ScriptEvent::Key(key)) => {
+                    if let Some((i, function)) = key_bindings.get(&key) {
+                        scripts[*i].call(&engine, function, ());
+                    }
+                }
+                Err(mpsc::RecvTimeoutError::Timeout) => {}
+                Err(mpsc::RecvTimeoutError::Disconnected) => break,
+            }
+
+            let (song, current) = {
+                let player = player.read().unwrap();
+                let song = player.current_song().cloned();
+                let path = song.as_ref().map(|s| s.path.clone());
+                (song, path)
+            };
+
+            if current != last_song {
+                for script in &mut scripts {
+                    match &song {
+                        Some(song) => script.call(&engine, "on_start", (song_map(song),)),
+                        None => script.call(&engine, "on_stop", ()),
+                    }
+                }
+                last_song = current;
+            }
+        }
+    }
+
+    fn song_map(song: &Song) -> Map {
+        let tag = |key| {
+            song.standard_tags
+                .get(&key)
+                .map(|v| Dynamic::from(v.to_string()))
+                .unwrap_or(Dynamic::UNIT)
+        };
+
+        let mut map = Map::new();
+        map.insert("path".into(), song.path.display().to_string().into());
+        map.insert("duration".into(), song.duration.as_secs_f64().into());
+        map.insert("title".into(), tag(StandardTagKey::TrackTitle));
+        map.insert("artist".into(), tag(StandardTagKey::Artist));
+        map.insert("album".into(), tag(StandardTagKey::Album));
+        map.insert("genre".into(), tag(StandardTagKey::Genre));
+        map.insert(
+            "bpm".into(),
+            song.bpm().map(Dynamic::from).unwrap_or(Dynamic::UNIT),
+        );
+        map.insert(
+            "genres".into(),
+            song.genres
+                .iter()
+                .map(|g| Dynamic::from(g.clone()))
+                .collect::<Array>()
+                .into(),
+        );
+        map
+    }
+
+    fn build_engine(
+        cache: SharedCache,
+        cmd: mpsc::Sender<Command>,
+        player: Arc<RwLock<PlayerFacade>>,
+        notifications: mpsc::Sender<Notification>,
+        bindings: Rc<RefCell<Vec<(String, String)>>>,
+    ) -> Engine {
+        let mut engine = Engine::new();
+
+        engine.on_print(|s| info!("[script] {s}"));
+        engine.on_debug(|s, _, pos| debug!("[script] {pos:?} {s}"));
+
+        for (name, command) in [
+            ("play", (|| Command::Play) as fn() -> Command),
+            ("pause", || Command::Pause),
+            ("play_pause", || Command::PlayPause),
+            ("skip", || Command::Skip),
+            ("stop", || Command::Stop),
+            ("clear", || Command::Clear),
+            ("toggle_crossfeed", || Command::ToggleCrossfeed),
+        ] {
+            let cmd = cmd.clone();
+            engine.register_fn(name, move || {
+                cmd.send(command()).ok();
+            });
+        }
+
+        let cmd2 = cmd.clone();
+        engine.register_fn("enqueue", move |path: &str| {
+            cmd2.send(Command::Enqueue(Path::new(path).into())).ok();
+        });
+        let cmd2 = cmd.clone();
+        engine.register_fn("seek", move |seconds: f64| {
+            cmd2.send(Command::Seek(Duration::from_secs_f64(seconds.max(0.0))))
+                .ok();
+        });
+
+        let cmd2 = cmd.clone();
+        engine.register_fn("preset", move |name: &str| {
+            cmd2.send(Command::SelectPreset(name.to_string())).ok();
+        });
+        let cmd2 = cmd.clone();
+        engine.register_fn("save_preset", move |name: &str| {
+            cmd2.send(Command::SavePreset(name.to_string())).ok();
+        });
+
+        let cmd2 = cmd.clone();
+        engine.register_fn("rate", move |rating: i64| {
+            let rating = u8::try_from(rating).ok().filter(|&r| r > 0);
+            cmd2.send(Command::Rate(rating)).ok();
+        });
+
+        engine.register_fn("notify", move |message: &str| {
+            notifications.send(Notification::info(message)).ok();
+        });
+
+        let player2 = player.clone();
+        engine.register_fn("queue", move || {
+            player2
+                .read()
+                .unwrap()
+                .queue
+                .iter()
+                .map(|p| Dynamic::from(p.display().to_string()))
+                .collect::<Array>()
+        });
+        let player2 = player.clone();
+        engine.register_fn("current_song", move || {
+            player2
+                .read()
+                .unwrap()
+                .current_song()
+                .map(|s| Dynamic::from(song_map(s)))
+                .unwrap_or(Dynamic::UNIT)
+        });
+        engine.register_fn("position", move || {
+            player
+                .read()
+                .unwrap()
+                .playing_duration()
+                .map(|d| Dynamic::from(d.as_secs_f64()))
+                .unwrap_or(Dynamic::UNIT)
+        });
+
+        engine.register_fn("search", move |query: &str| {
+            Query::parse(query)
+                .map(|q| q.lookup(&cache.load()))
+                .unwrap_or_else(|e| {
+                    warn!("Invalid search query {query:?}: {e}");
+                    vec![]
+                })
+                .into_iter()
+                .map(|p| Dynamic::from(p.display().to_string()))
+                .collect::<Array>()
+        });
+
+        engine.register_fn("bind_key", move |key: &str, function: &str| {
+            bindings
+                .borrow_mut()
+                .push((key_name(key), function.to_string()));
+        });
+
+        engine
+    }
+
+    /// the key of a binding as the tui names it, modifiers and named keys like "F5" are lowercased
+    /// but single characters keep their case, so that "X" is bound to `Shift-X` and not to `x`
+    fn key_name(key: &str) -> String {
+        // "-" on its own or after a modifier is the minus key
+        fn strip<'a>(key: &'a str, prefix: &str) -> Option<&'a str> {
+            key.get(..prefix.len())
+                .filter(|p| p.eq_ignore_ascii_case(prefix))
+                .and_then(|_| key.get(prefix.len()..))
+                .filter(|rest| !rest.is_empty())
+        }
+
+        let (mut ctrl, mut alt, mut rest) = (false, false, key);
+        loop {
+            if let Some(after) = strip(rest, "ctrl-") {
+                (ctrl, rest) = (true, after);
+            } else if let Some(after) = strip(rest, "alt-") {
+                (alt, rest) = (true, after);
+            } else {
+                break;
+            }
+        }
+
+        let mut name = String::new();
+        if ctrl {
+            name.push_str("ctrl-");
+        }
+        if alt {
+            name.push_str("alt-");
+        }
+        match rest.chars().count() {
+            1 => name.push_str(rest),
+            _ => name.push_str(&rest.to_lowercase()),
+        }
+        name
+    }
+}
+
+#[cfg(all(test, feature = "scripting"))]
+mod tests {
+    use std::{
+        sync::{mpsc, Arc, RwLock},
+        time::{Duration, Instant},
+    };
+
+    use crate::{
+        cache::{Cache, SharedCache},
+        config::Config,
+    };
+
+    #[test]
+    fn bound_characters_keep_their_case() {
+        let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let script = dir.path().join("keys.rhai");
+        std::fs::write(
+            &script,
+            r#"
+            bind_key("X", "shout");
+            bind_key("Ctrl-Alt-F5", "rescan");
+            fn shout() {}
+            fn rescan() {}
+            "#,
+        )
+        .expect("Failed to write script");
+        let config = Arc::new(Config {
+            scripts: vec![script],
+            ..Config::default_from_config_dir(dir.path())
+        });
+
+        let (cmd, _) = mpsc::channel();
+        let (notifications, _) = mpsc::channel();
+        let handle = super::start(
+            config,
+            SharedCache::new(Cache::default()),
+            cmd,
+            Arc::new(RwLock::new(Default::default())),
+            notifications,
+        );
+
+        let start = Instant::now();
+        while handle.keys.read().unwrap().is_empty() {
+            assert!(
+                start.elapsed() < Duration::from_secs(5),
+                "Script was not loaded"
+            );
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        assert!(!handle.key("x"));
+        assert!(handle.key("X"));
+        assert!(handle.key("ctrl-alt-f5"));
+    }
+}
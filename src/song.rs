@@ -1,4 +1,4 @@
-use std::{collections::HashMap, fmt::Debug, num::NonZeroU32, time::Duration};
+use std::{borrow::Cow, collections::HashMap, fmt::Debug, num::NonZeroU32, time::Duration};
 
 use anyhow::Context;
 use log::warn;
@@ -7,7 +7,7 @@ use symphonia::core::{
     formats::FormatOptions,
     io::{MediaSourceStream, MediaSourceStreamOptions},
     meta::MetadataOptions,
-    probe::Hint,
+    probe::{Hint, ProbeResult},
 };
 
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
@@ -49,6 +49,7 @@ impl std::fmt::Display for Value {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Visual {
     pub media_type: String,
@@ -74,6 +75,7 @@ impl From<symphonia::core::meta::Visual> for Visual {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
 pub struct Size {
     pub width: u32,
@@ -89,6 +91,7 @@ impl From<symphonia::core::meta::Size> for Size {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Copy, serde::Deserialize, serde::Serialize)]
 pub enum ColorMode {
     Discrete,
@@ -104,6 +107,7 @@ impl From<symphonia::core::meta::ColorMode> for ColorMode {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
 pub enum StandardVisualKey {
     FileIcon,
@@ -171,6 +175,7 @@ impl From<symphonia::core::meta::StandardVisualKey> for StandardVisualKey {
     }
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Tag {
     std_key: Option<StandardTagKey>,
@@ -475,16 +480,124 @@ impl From<symphonia::core::meta::StandardTagKey> for StandardTagKey {
     }
 }
 
+/// a named position inside a song, e.g. an audiobook chapter
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct Chapter {
+    pub title: String,
+    pub start: Duration,
+}
+
+/// encoder delay and length of the audio from gapless metadata, the rest after the audio is padding
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gapless {
+    /// frames of silence the encoder inserted before the audio
+    pub delay: u64,
+    pub frames: u64,
+}
+
+/// loudness in LUFS the ReplayGain 2 tags bring songs to
+pub const REPLAY_GAIN_REFERENCE: f32 = -18.0;
+
+/// loudness in LUFS the R128 gain tags of opus files bring songs to
+const R128_REFERENCE: f32 = -23.0;
+
+/// version of what `Song::load` reads from files, songs cached by an older version are read again on startup,
+/// increase it when the tags read change, e.g. after updating symphonia
+pub const SCANNER_VERSION: u32 = 1;
+
+/// standard tags with long texts that `Song::strip` drops, they are only shown for a single song
+const STRIPPED_TAGS: [StandardTagKey; 2] = [StandardTagKey::Lyrics, StandardTagKey::Comment];
+
+/// other tags `Song::strip` keeps, the classical tab groups songs by their work
+/// and the album gain and rip markers tell continuous albums apart, see `album::continuous`
+const KEPT_OTHER_TAGS: [&str; 5] = [
+    "WORK",
+    "R128_ALBUM_GAIN",
+    "ITUNPGAP",
+    "ACCURATERIPRESULT",
+    "CDTOC",
+];
+
 #[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
 pub struct Song {
     pub path: Box<std::path::Path>,
     pub duration: Duration,
+    /// average bitrate in kbit/s, from the file size and the duration
+    pub bitrate: Option<u32>,
+    /// the track gain bringing the song to `REPLAY_GAIN_REFERENCE`, 1.0 without replay gain tags
     pub gain_factor: f32,
     pub standard_tags: HashMap<StandardTagKey, Value>,
     pub other_tags: HashMap<String, Value>,
+    /// all genre tags, split into separate genres at separators like "Rock; Indie"
+    pub genres: Vec<String>,
+    /// the `SCANNER_VERSION` the song was read with, 0 for songs cached before it was recorded
+    #[serde(default)]
+    pub scanner_version: u32,
+    /// whether lyrics, comments and most other tags were dropped to save memory, see `Song::full`
+    #[serde(default)]
+    pub stripped: bool,
 }
 
 impl Song {
+    /// track or disc number, also of "3/12" style values scanned before they were normalized
+    pub fn number(&self, key: StandardTagKey) -> Option<u32> {
+        self.standard_tags
+            .get(&key)?
+            .to_string()
+            .split('/')
+            .next()?
+            .trim()
+            .parse()
+            .ok()
+    }
+
+    /// the factor bringing the song from `REPLAY_GAIN_REFERENCE` to a loudness of `target` LUFS
+    pub fn normalization(&self, target: f32) -> f32 {
+        self.gain_factor * 10_f32.powf((target - REPLAY_GAIN_REFERENCE) / 20.0)
+    }
+
+    /// the other tag called `name` ignoring case, also with a prefix like `TXXX:` or `com.apple.iTunes:`
+    pub fn other_tag(&self, name: &str) -> Option<&Value> {
+        self.other_tags
+            .iter()
+            .find(|(k, _)| other_tag_is(k, name))
+            .map(|(_, v)| v)
+    }
+
+    /// the album gain factor bringing the album of the song to `REPLAY_GAIN_REFERENCE`,
+    /// like `gain_factor` for tracks
+    pub fn album_gain_factor(&self) -> Option<f32> {
+        replay_gain(
+            &self.standard_tags,
+            &self.other_tags,
+            StandardTagKey::ReplayGainAlbumGain,
+            "R128_ALBUM_GAIN",
+        )
+        .ok()
+        .flatten()
+        .map(|x| 10_f32.powf(x / 20.0))
+    }
+
+    /// the highest sample of the song from its ReplayGain track peak tag, 1.0 is full scale
+    pub fn peak(&self) -> Option<f32> {
+        self.number_tag(StandardTagKey::ReplayGainTrackPeak)
+    }
+
+    /// the highest sample of the album of the song, like `peak`
+    pub fn album_peak(&self) -> Option<f32> {
+        self.number_tag(StandardTagKey::ReplayGainAlbumPeak)
+    }
+
+    fn number_tag(&self, key: StandardTagKey) -> Option<f32> {
+        self.standard_tags
+            .get(&key)?
+            .to_string()
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&peak: &f32| peak > 0.0)
+    }
+
     pub fn tag_string(&self, key: StandardTagKey) -> Option<&str> {
         self.standard_tags.get(&key).and_then(|v| match v {
             Value::String(s) => Some(s.as_str()),
@@ -492,32 +605,85 @@ impl Song {
         })
     }
 
-    pub fn load<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
-        let src = std::fs::File::open(&path)
-            .context(format!("Failed to open file {}", path.as_ref().display()))?;
+    /// the tempo from the bpm tag, also of "128.00" style values
+    pub fn bpm(&self) -> Option<f64> {
+        self.standard_tags
+            .get(&StandardTagKey::Bpm)?
+            .to_string()
+            .trim()
+            .parse()
+            .ok()
+            .filter(|&bpm: &f64| bpm > 0.0)
+    }
 
-        let source = MediaSourceStream::new(Box::new(src), MediaSourceStreamOptions::default());
+    /// the gapless metadata of files encoded by iTunes, which symphonia does not apply itself
+    pub fn gapless(&self) -> Option<Gapless> {
+        gapless(&self.standard_tags, &self.other_tags)
+    }
 
-        let extension = path
-            .as_ref()
-            .extension()
-            .unwrap()
-            .to_str()
-            .ok_or(anyhow::anyhow!(
-                "Failed to get extension for file {}",
-                path.as_ref().display()
-            ))?;
+    /// chapters from vorbis-comment style tags, `CHAPTER001=00:00:00.000` and `CHAPTER001NAME=...`
+    pub fn tag_chapters(&self) -> Vec<Chapter> {
+        let tags = self
+            .other_tags
+            .iter()
+            .map(|(k, v)| (k.to_uppercase(), v))
+            .collect::<HashMap<_, _>>();
 
-        let mut probed = symphonia::default::get_probe().format(
-            Hint::new().with_extension(extension),
-            source,
-            &FormatOptions {
-                prebuild_seek_index: false,
-                seek_index_fill_rate: 0,
-                enable_gapless: true,
-            },
-            &MetadataOptions::default(),
-        )?;
+        let mut chapters = tags
+            .iter()
+            .filter_map(|(k, v)| {
+                let number = k.strip_prefix("CHAPTER")?;
+                if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+                    return None;
+                }
+
+                let start = v
+                    .to_string()
+                    .split(':')
+                    .try_fold(0.0, |acc, x| x.parse::<f64>().map(|x| acc * 60.0 + x))
+                    .ok()?;
+
+                Some(Chapter {
+                    title: tags
+                        .get(&format!("{k}NAME"))
+                        .map(|n| n.to_string())
+                        .unwrap_or(format!("Chapter {}", number.trim_start_matches('0'))),
+                    start: Duration::from_secs_f64(start),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        chapters.sort_by_key(|c| c.start);
+        chapters
+    }
+
+    /// drop the tags lists and searches do not need, for keeping large libraries in memory,
+    /// `Song::full` reads them from the file again
+    pub fn strip(&mut self) {
+        self.standard_tags.retain(|k, _| !STRIPPED_TAGS.contains(k));
+        self.other_tags
+            .retain(|k, _| KEPT_OTHER_TAGS.iter().any(|t| other_tag_is(k, t)));
+        self.stripped = true;
+    }
+
+    /// the song with all of its tags, read from the file again if they were stripped,
+    /// the stripped song if that fails
+    pub fn full(&self) -> Cow<'_, Song> {
+        if !self.stripped {
+            return Cow::Borrowed(self);
+        }
+
+        match Song::load(&self.path) {
+            Ok(song) => Cow::Owned(song),
+            Err(e) => {
+                warn!("Failed to read the tags of {}: {e:?}", self.path.display());
+                Cow::Borrowed(self)
+            }
+        }
+    }
+
+    pub fn load<P: AsRef<std::path::Path>>(path: P) -> anyhow::Result<Self> {
+        let mut probed = probe(path.as_ref())?;
 
         let mut metadata = probed.format.metadata();
         let metadata = metadata.skip_to_latest().cloned();
@@ -529,6 +695,8 @@ impl Song {
             .find(|t| t.codec_params.codec != codecs::CODEC_TYPE_NULL)
             .ok_or(anyhow::anyhow!("No audio tracks found"))?;
 
+        let delay = track.codec_params.delay;
+        let sample_rate = track.codec_params.sample_rate;
         let duration = track
             .codec_params
             .time_base
@@ -541,9 +709,26 @@ impl Song {
                 track.id
             ))?);
 
-        let duration = std::time::Duration::from_secs_f64(duration.seconds as f64 + duration.frac);
+        let mut duration =
+            std::time::Duration::from_secs_f64(duration.seconds as f64 + duration.frac);
+        let bitrate = std::fs::metadata(&path)
+            .ok()
+            .filter(|_| !duration.is_zero())
+            .map(|m| (m.len() as f64 * 8.0 / 1000.0 / duration.as_secs_f64()).round() as u32);
 
-        let (standard_tags, other_tags) = metadata
+        let genres = metadata
+            .as_ref()
+            .map(|m| {
+                genres(
+                    m.tags()
+                        .iter()
+                        .filter(|t| t.std_key == Some(symphonia::core::meta::StandardTagKey::Genre))
+                        .map(|t| t.value.to_string()),
+                )
+            })
+            .unwrap_or_default();
+
+        let (mut standard_tags, other_tags) = metadata
             .map(|m| {
                 let s = m
                     .tags()
@@ -562,40 +747,184 @@ impl Song {
             })
             .unwrap_or_default();
 
-        let replay_gain = standard_tags
-            .get(&StandardTagKey::ReplayGainTrackGain)
-            .ok_or(anyhow::anyhow!(
+        normalize_numbers(&mut standard_tags);
+
+        // the frame count of the container includes the encoder delay and padding
+        if let (None, Some(gapless), Some(rate)) =
+            (delay, gapless(&standard_tags, &other_tags), sample_rate)
+        {
+            duration = std::time::Duration::from_secs_f64(gapless.frames as f64 / rate as f64);
+        }
+
+        let replay_gain = replay_gain(
+            &standard_tags,
+            &other_tags,
+            StandardTagKey::ReplayGainTrackGain,
+            "R128_TRACK_GAIN",
+        )
+        .context(format!(
+            "Failed to parse replay gain for {}",
+            path.as_ref().display()
+        ))
+        .and_then(|gain| {
+            gain.ok_or(anyhow::anyhow!(
                 "No replay gain found for {}",
                 path.as_ref().display()
             ))
-            .and_then(|v| match v {
-                Value::String(s) => {
-                    s.strip_suffix(" dB")
-                        .unwrap_or(s)
-                        .parse::<f32>()
-                        .context(format!(
-                            "Failed to parse replay gain for {}",
-                            path.as_ref().display()
-                        ))
-                }
-                v => anyhow::bail!("Expected string, got {:?}", v),
-            })
-            .map(|x| 10_f32.powf(x / 20.0))
-            .unwrap_or_else(|e| {
-                warn!(
-                    "Failed to get replay gain for {}: {}",
-                    path.as_ref().display(),
-                    e
-                );
-                1.0
-            });
+        })
+        .map(|x| 10_f32.powf(x / 20.0))
+        .unwrap_or_else(|e| {
+            warn!(
+                "Failed to get replay gain for {}: {}",
+                path.as_ref().display(),
+                e
+            );
+            1.0
+        });
 
         Ok(Song {
             path: path.as_ref().into(),
             duration,
+            bitrate,
             standard_tags,
             other_tags,
+            genres,
             gain_factor: replay_gain,
+            scanner_version: SCANNER_VERSION,
+            stripped: false,
+        })
+    }
+}
+
+/// split "03/12" style track and disc numbers into the number and the total, so that they sort and display
+/// like plain numbers, a total tag present in the file is kept
+/// open the file at `path` for decoding, scanning and playback have to use the same options
+/// so that the duration of a song matches what is played
+pub fn probe(path: &std::path::Path) -> anyhow::Result<ProbeResult> {
+    let src =
+        std::fs::File::open(path).context(format!("Failed to open file {}", path.display()))?;
+    let source = MediaSourceStream::new(Box::new(src), MediaSourceStreamOptions::default());
+
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+
+    symphonia::default::get_probe()
+        .format(
+            &hint,
+            source,
+            &FormatOptions {
+                enable_gapless: true,
+                ..Default::default()
+            },
+            &MetadataOptions::default(),
+        )
+        .context(format!("Failed to probe {}", path.display()))
+}
+
+/// whether the other tag `key` is called `name`, ignoring case and a prefix like `TXXX:`
+fn other_tag_is(key: &str, name: &str) -> bool {
+    key.eq_ignore_ascii_case(name)
+        || key
+            .rsplit_once(':')
+            .is_some_and(|(_, k)| k.eq_ignore_ascii_case(name))
+}
+
+/// the track or album gain in dB to bring the song to `REPLAY_GAIN_REFERENCE`, from the ReplayGain tag `key`
+/// or the `r128` tag of opus files, e.g. `R128_TRACK_GAIN`, which is relative to -23 LUFS in 1/256 dB
+fn replay_gain(
+    standard_tags: &HashMap<StandardTagKey, Value>,
+    other_tags: &HashMap<String, Value>,
+    key: StandardTagKey,
+    r128: &str,
+) -> anyhow::Result<Option<f32>> {
+    if let Some(value) = standard_tags.get(&key) {
+        let Value::String(s) = value else {
+            anyhow::bail!("Expected string, got {:?}", value);
+        };
+        return Ok(Some(s.strip_suffix(" dB").unwrap_or(s).trim().parse()?));
+    }
+
+    other_tags
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(r128))
+        .map(|(_, v)| {
+            let gain = v.to_string().trim().parse::<i16>()?;
+            Ok(gain as f32 / 256.0 + REPLAY_GAIN_REFERENCE - R128_REFERENCE)
         })
+        .transpose()
+}
+
+/// parse the iTunSMPB tag, which mp4 files store as "com.apple.iTunes:iTunSMPB"
+/// and mp3 files as a comment, " 00000000 00000840 000001CA 00000000003F31F6 ..."
+/// with the delay, the padding and the length of the audio in frames
+fn gapless(
+    standard_tags: &HashMap<StandardTagKey, Value>,
+    other_tags: &HashMap<String, Value>,
+) -> Option<Gapless> {
+    let parse = |value: &Value| {
+        let fields = value.to_string();
+        let fields = fields.split_whitespace().collect::<Vec<_>>();
+        let [zero, delay, _padding, frames, ..] = fields[..] else {
+            return None;
+        };
+        if zero.len() != 8 || delay.len() != 8 || frames.len() != 16 {
+            return None;
+        }
+
+        let gapless = Gapless {
+            delay: u64::from_str_radix(delay, 16).ok()?,
+            frames: u64::from_str_radix(frames, 16).ok()?,
+        };
+        (gapless.frames > 0).then_some(gapless)
+    };
+
+    other_tags
+        .iter()
+        .find(|(k, _)| k.to_lowercase().ends_with("itunsmpb"))
+        .map(|(_, v)| v)
+        .or(standard_tags.get(&StandardTagKey::Comment))
+        .and_then(parse)
+}
+
+fn normalize_numbers(tags: &mut HashMap<StandardTagKey, Value>) {
+    for (number, total) in [
+        (StandardTagKey::TrackNumber, StandardTagKey::TrackTotal),
+        (StandardTagKey::DiscNumber, StandardTagKey::DiscTotal),
+    ] {
+        let Some(value) = tags.get(&number).map(Value::to_string) else {
+            continue;
+        };
+        let (n, t) = match value.split_once('/') {
+            Some((n, t)) => (n, Some(t)),
+            None => (value.as_str(), None),
+        };
+
+        if let Ok(n) = n.trim().parse::<u64>() {
+            tags.insert(number, Value::UnsignedInt(n));
+        }
+        if let Some(Ok(t)) = t.map(|t| t.trim().parse::<u64>()) {
+            tags.entry(total).or_insert(Value::UnsignedInt(t));
+        }
     }
 }
+
+/// separators of multiple genres in one tag
+const GENRE_SEPARATORS: [char; 5] = [';', '/', ',', '|', '\0'];
+
+/// the distinct genres of the tags, in order, e.g. "Rock", "Indie" for "Rock; Indie"
+fn genres(tags: impl Iterator<Item = String>) -> Vec<String> {
+    let mut genres = Vec::<String>::new();
+    for genre in tags.flat_map(|t| {
+        t.split(GENRE_SEPARATORS)
+            .map(|g| g.trim().to_string())
+            .collect::<Vec<_>>()
+    }) {
+        if !genre.is_empty() && !genres.iter().any(|g| g.eq_ignore_ascii_case(&genre)) {
+            genres.push(genre);
+        }
+    }
+
+    genres
+}
@@ -0,0 +1,95 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+
+/// highest rating, ratings go from 1 to `MAX_RATING` stars
+pub const MAX_RATING: u8 = 5;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SongStats {
+    pub play_count: u32,
+    /// 1 to `MAX_RATING` stars, `None` if unrated
+    pub rating: Option<u8>,
+}
+
+/// a song that was listened to, with the tags needed for reports
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Listen {
+    pub time: DateTime<Local>,
+    pub path: PathBuf,
+    /// time actually played, without pauses and skipped parts
+    pub listened: Duration,
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+}
+
+/// play counts, ratings and listening history of the library, stored next to the config
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Stats {
+    songs: HashMap<PathBuf, SongStats>,
+    #[serde(default)]
+    history: Vec<Listen>,
+    /// the volume last used with each output device
+    #[serde(default)]
+    device_volumes: HashMap<String, f32>,
+    /// the volume last used with each zone
+    #[serde(default)]
+    zone_volumes: HashMap<String, f32>,
+}
+
+impl Stats {
+    pub fn load<P: AsRef<Path>>(path: P) -> anyhow::Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let stats = serde_json::from_str(&contents)?;
+
+        Ok(stats)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> anyhow::Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer(file, self)?;
+
+        Ok(())
+    }
+
+    /// stats of `path`, or `seed` if the song has none yet
+    pub fn get(&self, path: &Path, seed: impl FnOnce() -> SongStats) -> SongStats {
+        self.songs.get(path).copied().unwrap_or_else(seed)
+    }
+
+    /// stats of `path` for modification, starting from `seed` if the song has none yet
+    pub fn entry(&mut self, path: &Path, seed: impl FnOnce() -> SongStats) -> &mut SongStats {
+        self.songs.entry(path.to_path_buf()).or_insert_with(seed)
+    }
+
+    /// listening history, oldest first
+    pub fn history(&self) -> &[Listen] {
+        &self.history
+    }
+
+    pub fn record(&mut self, listen: Listen) {
+        self.history.push(listen);
+    }
+
+    pub fn device_volume(&self, device: &str) -> Option<f32> {
+        self.device_volumes.get(device).copied()
+    }
+
+    pub fn set_device_volume(&mut self, device: &str, volume: f32) {
+        self.device_volumes.insert(device.to_string(), volume);
+    }
+
+    pub fn zone_volume(&self, zone: &str) -> Option<f32> {
+        self.zone_volumes.get(zone).copied()
+    }
+
+    pub fn set_zone_volume(&mut self, zone: &str, volume: f32) {
+        self.zone_volumes.insert(zone.to_string(), volume);
+    }
+}
@@ -0,0 +1,124 @@
+use std::{fs::File, path::Path};
+
+use lofty::{
+    config::{ParseOptions, WriteOptions},
+    file::AudioFile,
+    flac::FlacFile,
+    id3::v2::{Frame, Id3v2Tag, PopularimeterFrame},
+    mpeg::MpegFile,
+    ogg::{OpusFile, VorbisComments, VorbisFile},
+};
+
+use crate::{
+    song::{Song, StandardTagKey, Value},
+    stats::{SongStats, MAX_RATING},
+};
+
+/// email of the POPM frame written to ID3v2 tags
+const POPM_EMAIL: &str = "ramp";
+
+/// POPM ratings for 1 to 5 stars, as used by most players
+const POPM_RATINGS: [u8; MAX_RATING as usize] = [1, 64, 128, 196, 255];
+
+/// play count and rating from the tags of `song`, to start from what other players recorded
+pub fn read_stats(song: &Song) -> SongStats {
+    let rating = song
+        .standard_tags
+        .get(&StandardTagKey::Rating)
+        .and_then(|v| match v {
+            // POPM, 1 to 255
+            Value::UnsignedInt(0) => None,
+            Value::UnsignedInt(r) => {
+                Some(POPM_RATINGS.iter().position(|&p| *r <= p as u64)? as u8 + 1)
+            }
+            // RATING comment, 0 to 100
+            v => v
+                .to_string()
+                .parse::<u8>()
+                .ok()
+                .filter(|&r| r > 0)
+                .map(|r| r.div_ceil(100 / MAX_RATING)),
+        });
+
+    let play_count = song
+        .other_tags
+        .iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case("PLAYCOUNT"))
+        .and_then(|(_, v)| v.to_string().parse().ok())
+        .unwrap_or(0);
+
+    SongStats { play_count, rating }
+}
+
+/// write play count and rating into the tags of the file at `path`,
+/// as a POPM frame for mp3 and as PLAYCOUNT and RATING comments for flac, ogg and opus
+pub fn write_stats(path: &Path, stats: SongStats) -> anyhow::Result<()> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+    let parse_options = ParseOptions::new();
+
+    match extension.as_deref() {
+        Some("mp3") => {
+            let mut file = MpegFile::read_from(&mut File::open(path)?, parse_options)?;
+            if file.id3v2_mut().is_none() {
+                file.set_id3v2(Id3v2Tag::new());
+            }
+
+            let rating = stats
+                .rating
+                .map(|r| POPM_RATINGS[r as usize - 1])
+                .unwrap_or(0);
+            file.id3v2_mut()
+                .expect("ID3v2 tag was just inserted")
+                .insert(Frame::Popularimeter(PopularimeterFrame::new(
+                    POPM_EMAIL.to_string(),
+                    rating,
+                    stats.play_count as u64,
+                )));
+
+            file.save_to_path(path, WriteOptions::default())?;
+        }
+        Some("flac") => {
+            let mut file = FlacFile::read_from(&mut File::open(path)?, parse_options)?;
+            if file.vorbis_comments_mut().is_none() {
+                file.set_vorbis_comments(VorbisComments::default());
+            }
+
+            set_comments(
+                file.vorbis_comments_mut()
+                    .expect("Vorbis comments were just inserted"),
+                stats,
+            );
+            file.save_to_path(path, WriteOptions::default())?;
+        }
+        Some("ogg") => {
+            let mut file = VorbisFile::read_from(&mut File::open(path)?, parse_options)?;
+            set_comments(file.vorbis_comments_mut(), stats);
+            file.save_to_path(path, WriteOptions::default())?;
+        }
+        Some("opus") => {
+            let mut file = OpusFile::read_from(&mut File::open(path)?, parse_options)?;
+            set_comments(file.vorbis_comments_mut(), stats);
+            file.save_to_path(path, WriteOptions::default())?;
+        }
+        _ => anyhow::bail!("Writing tags is not supported for {}", path.display()),
+    }
+
+    Ok(())
+}
+
+fn set_comments(comments: &mut VorbisComments, stats: SongStats) {
+    comments.insert("PLAYCOUNT".to_string(), stats.play_count.to_string());
+
+    match stats.rating {
+        Some(r) => comments.insert(
+            "RATING".to_string(),
+            (r as u32 * 100 / MAX_RATING as u32).to_string(),
+        ),
+        None => {
+            comments.remove("RATING").for_each(drop);
+        }
+    }
+}
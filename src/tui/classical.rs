@@ -0,0 +1,339 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    path::PathBuf,
+    rc::Rc,
+    sync::{mpsc, Arc, Weak},
+};
+
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    prelude::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Paragraph, Row, Table},
+    Frame,
+};
+
+use crate::{
+    cache::{Cache, SharedCache},
+    collation,
+    player::command::Command,
+    song::{Song, StandardTagKey, Value},
+};
+
+use super::{format_duration, list::ListController, song_table, Tui, UNKNOWN_STRING};
+
+/// a piece made of movements, e.g. a symphony
+struct Work {
+    name: String,
+    /// in movement order
+    movements: Vec<PathBuf>,
+}
+
+struct Composer {
+    name: String,
+    works: Vec<Work>,
+}
+
+/// the songs of the library with a composer tag, by composer and work
+fn composers(cache: &Cache) -> Vec<Composer> {
+    let mut composers = HashMap::<String, HashMap<String, Vec<(&Song, PathBuf)>>>::new();
+    for (song, path) in cache.songs() {
+        let Some(composer) = song
+            .tag_string(StandardTagKey::Composer)
+            .map(str::trim)
+            .filter(|c| !c.is_empty())
+        else {
+            continue;
+        };
+
+        composers
+            .entry(composer.to_string())
+            .or_default()
+            .entry(work(song))
+            .or_default()
+            .push((song, path));
+    }
+
+    let mut composers = composers
+        .into_iter()
+        .map(|(name, works)| {
+            let mut works = works
+                .into_iter()
+                .map(|(name, mut movements)| {
+                    movements.sort_by_key(|(s, p)| {
+                        (
+                            s.number(StandardTagKey::MovementNumber),
+                            s.number(StandardTagKey::DiscNumber),
+                            s.number(StandardTagKey::TrackNumber),
+                            p.clone(),
+                        )
+                    });
+                    Work {
+                        name,
+                        movements: movements.into_iter().map(|(_, p)| p).collect(),
+                    }
+                })
+                .collect::<Vec<_>>();
+            works.sort_by_cached_key(|w| collation::key(&w.name, &[]));
+
+            Composer { name, works }
+        })
+        .collect::<Vec<_>>();
+    composers.sort_by_cached_key(|c| collation::key(&c.name, &[]));
+
+    composers
+}
+
+/// the work tag, which is stored as content group in id3 and as "WORK" in vorbis comments,
+/// the album for songs without one
+fn work(song: &Song) -> String {
+    song.tag_string(StandardTagKey::ContentGroup)
+        .or_else(|| {
+            song.other_tags
+                .iter()
+                .find(|(k, _)| k.eq_ignore_ascii_case("work"))
+                .and_then(|(_, v)| match v {
+                    Value::String(s) => Some(s.as_str()),
+                    _ => None,
+                })
+        })
+        .or_else(|| song.tag_string(StandardTagKey::Album))
+        .map(str::trim)
+        .filter(|w| !w.is_empty())
+        .unwrap_or(UNKNOWN_STRING)
+        .to_string()
+}
+
+/// the composers grouped from a cache snapshot, grouping the whole library on every frame is slow
+struct Grouped {
+    cache: Weak<Cache>,
+    composers: Rc<Vec<Composer>>,
+}
+
+/// browsing by composer, work and movement, which fits classical music better than artists and albums
+pub struct Classical {
+    cache: SharedCache,
+    cmd: mpsc::Sender<Command>,
+    /// the selected composer, work and movement, as far as they were opened
+    selected: Vec<ListController>,
+    /// single steps past either end of a list wrap around
+    wrap: bool,
+    grouped: RefCell<Option<Grouped>>,
+}
+
+impl Classical {
+    pub fn new(cache: SharedCache, cmd: mpsc::Sender<Command>, wrap: bool) -> Self {
+        Self {
+            cache,
+            cmd,
+            selected: vec![ListController::new(wrap)],
+            wrap,
+            grouped: RefCell::new(None),
+        }
+    }
+
+    fn composers(&self, cache: &Arc<Cache>) -> Rc<Vec<Composer>> {
+        let mut grouped = self.grouped.borrow_mut();
+        match grouped.as_ref() {
+            Some(g) if Weak::as_ptr(&g.cache) == Arc::as_ptr(cache) => g.composers.clone(),
+            _ => {
+                let composers = Rc::new(self::composers(cache));
+                *grouped = Some(Grouped {
+                    cache: Arc::downgrade(cache),
+                    composers: composers.clone(),
+                });
+                composers
+            }
+        }
+    }
+
+    /// the indices of the selected composer, work and movement, as far as they were opened
+    fn opened(&self) -> Vec<usize> {
+        self.selected.iter().map(ListController::selected).collect()
+    }
+
+    /// the rows of the opened level
+    fn rows<'a>(&self, composers: &'a [Composer], cache: &'a Cache) -> Vec<Row<'a>> {
+        match self.opened()[..] {
+            [_] => composers
+                .iter()
+                .map(|c| Row::new(vec![c.name.clone(), c.works.len().to_string()]))
+                .collect(),
+            [composer, _] => composers
+                .get(composer)
+                .map(|c| c.works.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .map(|w| Row::new(vec![w.name.clone(), w.movements.len().to_string()]))
+                .collect(),
+            [composer, work, ..] => composers
+                .get(composer)
+                .and_then(|c| c.works.get(work))
+                .map(|w| w.movements.as_slice())
+                .unwrap_or_default()
+                .iter()
+                .map(|path| {
+                    let song = cache
+                        .get(path)
+                        .ok()
+                        .flatten()
+                        .and_then(|e| e.as_file().ok());
+                    let name = song
+                        .and_then(|s| {
+                            s.tag_string(StandardTagKey::MovementName)
+                                .or(s.tag_string(StandardTagKey::TrackTitle))
+                        })
+                        .map(str::to_string)
+                        .or(path.file_name().map(|f| f.to_string_lossy().to_string()))
+                        .unwrap_or(UNKNOWN_STRING.to_string());
+                    let duration = song
+                        .map(|s| format_duration(s.duration))
+                        .unwrap_or_default();
+
+                    song_table::availability(
+                        Row::new(vec![name, duration]),
+                        cache.is_available(path),
+                    )
+                })
+                .collect(),
+            [] => vec![],
+        }
+    }
+
+    /// the songs of the `index`th row of the opened level, for movements the movements from it on
+    fn songs(&self, composers: &[Composer], index: usize) -> Vec<PathBuf> {
+        match self.opened()[..] {
+            [_] => composers
+                .get(index)
+                .map(|c| c.works.iter().flat_map(|w| w.movements.clone()).collect())
+                .unwrap_or_default(),
+            [composer, _] => composers
+                .get(composer)
+                .and_then(|c| c.works.get(index))
+                .map(|w| w.movements.clone())
+                .unwrap_or_default(),
+            [composer, work, ..] => composers
+                .get(composer)
+                .and_then(|c| c.works.get(work))
+                .map(|w| w.movements.iter().skip(index).cloned().collect())
+                .unwrap_or_default(),
+            [] => vec![],
+        }
+    }
+}
+
+impl Tui for Classical {
+    fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+
+        let cache = self.cache.load();
+        let composers = self.composers(&cache);
+        let rows = self.rows(&composers, &cache);
+        let (offset, mut state) = self
+            .selected
+            .last()
+            .copied()
+            .unwrap_or_default()
+            .window(rows.len(), layout[0].height);
+
+        let header = match self.selected.len() {
+            1 => ["Composer", "Works"],
+            2 => ["Work", "Movements"],
+            _ => ["Movement", "Duration"],
+        };
+        let table = Table::new(
+            rows.into_iter()
+                .skip(offset)
+                .take(layout[0].height as usize)
+                .collect::<Vec<_>>(),
+        )
+        .header(Row::new(header.to_vec()).light_blue().bold())
+        .fg(Color::Rgb(210, 210, 210))
+        .highlight_style(Style::default().light_yellow().bold())
+        .highlight_symbol("⏯️  ")
+        .column_spacing(4)
+        .widths(&[Constraint::Percentage(80), Constraint::Percentage(20)]);
+
+        // the opened composer and work
+        let opened = self.opened();
+        let location = opened
+            .iter()
+            .take(opened.len() - 1)
+            .enumerate()
+            .filter_map(|(level, &i)| match level {
+                0 => composers.get(i).map(|c| c.name.as_str()),
+                _ => composers
+                    .get(opened[0])
+                    .and_then(|c| c.works.get(i))
+                    .map(|w| w.name.as_str()),
+            })
+            .collect::<Vec<_>>()
+            .join(" › ");
+        let help = Paragraph::new(Line::from(vec![
+            Span::from(location).bold(),
+            Span::from("  "),
+            Span::from("Enter").bold(),
+            Span::from(" open / enqueue  "),
+            Span::from("a").bold(),
+            Span::from(" enqueue all  "),
+            Span::from("Backspace").bold(),
+            Span::from(" back"),
+        ]))
+        .fg(Color::DarkGray);
+
+        f.render_stateful_widget(table, layout[0], &mut state);
+        f.render_widget(help, layout[1]);
+
+        Ok(())
+    }
+
+    fn input(&mut self, event: &Event) -> anyhow::Result<()> {
+        let cache = self.cache.load();
+        let composers = self.composers(&cache);
+
+        if let Event::Key(KeyEvent { code, .. }) = event {
+            let len = self.rows(&composers, &cache).len();
+            let selected = self.selected.last().map_or(0, ListController::selected);
+            let songs = self.songs(&composers, selected);
+
+            match code {
+                code if self
+                    .selected
+                    .last_mut()
+                    .is_some_and(|list| list.input(code, len)) => {}
+                KeyCode::Enter if self.selected.len() == 3 => {
+                    if let Some(song) = songs.first() {
+                        self.cmd.send(Command::Enqueue(song.as_path().into()))?;
+                    }
+                }
+                KeyCode::Enter if !songs.is_empty() => {
+                    self.selected.push(ListController::new(self.wrap))
+                }
+                KeyCode::Char('a') => {
+                    self.cmd.send(Command::Batch(
+                        songs
+                            .into_iter()
+                            .map(|s| Command::Enqueue(s.into_boxed_path()))
+                            .collect(),
+                    ))?;
+                }
+                KeyCode::Backspace if self.selected.len() > 1 => {
+                    self.selected.pop();
+                }
+                _ => {}
+            }
+        }
+
+        let len = self.rows(&composers, &cache).len();
+        if let Some(list) = self.selected.last_mut() {
+            list.clamp(len);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,57 @@
+use std::io::Write;
+
+use log::{info, warn};
+
+use crate::song::{Song, StandardTagKey};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+/// copy `text` to the system clipboard with an OSC 52 escape sequence,
+/// which the terminal forwards to the clipboard, also over ssh
+pub fn copy(text: &str) {
+    let mut stdout = std::io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", base64(text.as_bytes()))
+        .and_then(|_| stdout.flush())
+        .map(|_| info!("Copied to clipboard: {text}"))
+        .unwrap_or_else(|e| warn!("Failed to copy to clipboard: {e:?}"));
+}
+
+/// "artist - title" of the song, the file name if the tags are missing
+pub fn describe(song: &Song) -> String {
+    let file_name = || {
+        song.path
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default()
+    };
+
+    match (
+        song.tag_string(StandardTagKey::Artist),
+        song.tag_string(StandardTagKey::TrackTitle),
+    ) {
+        (Some(artist), Some(title)) => format!("{artist} - {title}"),
+        (None, Some(title)) => title.to_string(),
+        _ => file_name(),
+    }
+}
+
+fn base64(data: &[u8]) -> String {
+    let mut encoded = String::with_capacity(data.len().div_ceil(3) * 4);
+
+    for chunk in data.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
@@ -1,26 +1,424 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    cell::RefCell,
+    path::Path,
+    sync::{mpsc, Arc, RwLock},
+    time::Duration,
+};
 
-use crossterm::event::Event;
+use crossterm::event::{Event, KeyCode, KeyEvent};
+#[cfg(feature = "cover-art")]
 use image::imageops::FilterType;
 use ratatui::{
     prelude::{Alignment, Constraint, Direction, Layout, Rect},
-    style::{Color, Style, Stylize},
-    text::{Line, Span},
-    widgets::{Block, BorderType, Borders, Cell, Padding, Paragraph, Row, Table},
+    style::{Style, Stylize},
+    text::Line,
+    widgets::{
+        Block, BorderType, Borders, Cell, Clear, Padding, Paragraph, Row, Table, TableState,
+    },
     Frame,
 };
+#[cfg(feature = "cover-art")]
+use ratatui::{style::Color, text::Span};
+
+use crate::{
+    config::{Config, CoverStyle},
+    lyrics::Lyrics,
+    player::{command::Command, facade::PlayerFacade},
+    scheduler::Scheduler,
+    song::{Song, StandardTagKey},
+};
+
+use super::{format_duration, popup_area, reveal, tabs::VOLUME_STEP, Tui, UNKNOWN_STRING};
+
+/// width of the volume bars of the zones popup
+const ZONE_BAR_WIDTH: usize = 20;
+
+/// file names of artist images, next to the songs or in a directory above them, e.g. `Artist/artist.jpg`
+const ARTIST_IMAGES: [&str; 3] = ["artist.jpg", "artist.jpeg", "artist.png"];
 
-use crate::player::facade::PlayerFacade;
+/// milliseconds `<` and `>` shift synced lyrics by
+const LYRICS_OFFSET_STEP: i64 = 100;
 
-use super::Tui;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Popup {
+    Tracks,
+    Chapters,
+    Presets,
+    Zones,
+    Lyrics,
+}
+
+/// the rendered cover of a song for an area, decoding and resizing it on every frame is expensive
+struct RenderedCover {
+    path: Box<Path>,
+    /// the cover arrives after the song when attached to a daemon
+    cover_len: Option<usize>,
+    area: Rect,
+    lines: Option<Vec<Line<'static>>>,
+}
+
+/// the rendered artist image of a song, read from disk once per song
+struct RenderedArtist {
+    path: Box<Path>,
+    image: Option<Vec<u8>>,
+    area: Option<Rect>,
+    lines: Option<Vec<Line<'static>>>,
+}
+
+/// the lyrics of a song, read once per song
+struct SongLyrics {
+    path: Box<Path>,
+    lyrics: Option<Arc<Lyrics>>,
+}
 
 pub struct Fancy {
     player: Arc<RwLock<PlayerFacade>>,
+    cmd: mpsc::Sender<Command>,
+    scheduler: Scheduler,
+    config: Arc<Config>,
+    /// open popup and its selected row
+    popup: Option<(Popup, usize)>,
+    cover: RefCell<Option<RenderedCover>>,
+    artist: RefCell<Option<RenderedArtist>>,
+    lyrics: RefCell<Option<SongLyrics>>,
+    /// milliseconds synced lyrics are shown earlier, starting at `lyrics_offset`
+    lyrics_offset: i64,
 }
 
 impl Fancy {
-    pub fn new(player: Arc<RwLock<PlayerFacade>>) -> Self {
-        Self { player }
+    pub fn new(
+        player: Arc<RwLock<PlayerFacade>>,
+        cmd: mpsc::Sender<Command>,
+        scheduler: Scheduler,
+        config: Arc<Config>,
+    ) -> Self {
+        Self {
+            player,
+            cmd,
+            scheduler,
+            popup: None,
+            cover: RefCell::new(None),
+            artist: RefCell::new(None),
+            lyrics: RefCell::new(None),
+            lyrics_offset: config.lyrics_offset,
+            config,
+        }
+    }
+
+    /// whether the current song has an artist image, read when the song changed
+    fn has_artist_image(&self, song: &Song) -> bool {
+        let mut rendered = self.artist.borrow_mut();
+        let outdated = rendered.as_ref().is_none_or(|r| r.path != song.path);
+        if outdated {
+            *rendered = Some(RenderedArtist {
+                path: song.path.clone(),
+                image: artist_image(&self.config, song),
+                area: None,
+                lines: None,
+            });
+        }
+
+        rendered.as_ref().is_some_and(|r| r.image.is_some())
+    }
+
+    /// the artist image of the current song rendered for `area`, see `has_artist_image`
+    fn artist_lines(&self, area: Rect) -> Option<Vec<Line<'static>>> {
+        let mut rendered = self.artist.borrow_mut();
+        let rendered = rendered.as_mut()?;
+        if rendered.area != Some(area) {
+            rendered.area = Some(area);
+            rendered.lines = rendered
+                .image
+                .as_ref()
+                .and_then(|i| cover_lines(i, area, self.config.theme.cover));
+        }
+
+        rendered.lines.clone()
+    }
+
+    /// the cover of the current song rendered for `area`, only rendered again when one of them changed
+    fn cover_lines(&self, player: &PlayerFacade, area: Rect) -> Option<Vec<Line<'static>>> {
+        let song = player.current_song()?;
+        let cover = player.current_cover();
+
+        let mut rendered = self.cover.borrow_mut();
+        let outdated = !rendered.as_ref().is_some_and(|r| {
+            r.path == song.path && r.cover_len == cover.map(<[u8]>::len) && r.area == area
+        });
+        if outdated {
+            *rendered = Some(RenderedCover {
+                path: song.path.clone(),
+                cover_len: cover.map(<[u8]>::len),
+                area,
+                lines: cover.and_then(|c| cover_lines(c, area, self.config.theme.cover)),
+            });
+        }
+
+        rendered.as_ref().and_then(|r| r.lines.clone())
+    }
+
+    /// the lyrics of the current song, read when the song changed
+    fn lyrics(&self, player: &PlayerFacade) -> Option<Arc<Lyrics>> {
+        let song = player.current_song()?;
+        let mut lyrics = self.lyrics.borrow_mut();
+        let outdated = lyrics.as_ref().is_none_or(|l| l.path != song.path);
+        if outdated {
+            *lyrics = Some(SongLyrics {
+                path: song.path.clone(),
+                lyrics: Lyrics::of(song).map(Arc::new),
+            });
+        }
+
+        lyrics.as_ref().and_then(|l| l.lyrics.clone())
+    }
+
+    fn popup_len(&self, popup: Popup) -> usize {
+        let player = self.player.read().expect("Failed to lock player");
+        match popup {
+            Popup::Tracks => player.tracks().map(|(t, _)| t.len()).unwrap_or(0),
+            Popup::Chapters => player.chapters().len(),
+            Popup::Presets => player.presets.len(),
+            Popup::Zones => player.zones.len(),
+            Popup::Lyrics => self.lyrics(&player).map(|l| l.lines.len()).unwrap_or(0),
+        }
+    }
+
+    fn draw_chapter_popup(&self, selected: usize, area: Rect, f: &mut Frame) {
+        let player = self.player.read().expect("Failed to lock player");
+        let current = player.current_chapter();
+
+        let rows = player
+            .chapters()
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                Row::new(vec![
+                    if Some(i) == current { "▶" } else { "" }.to_string(),
+                    format_duration(c.start),
+                    c.title.clone(),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let len = rows.len();
+
+        let table = Table::new(rows)
+            .header(Row::new(["", "Start", "Title"]).light_blue().bold())
+            .highlight_style(Style::default().light_yellow().bold())
+            .widths(&[
+                Constraint::Length(2),
+                Constraint::Length(9),
+                Constraint::Min(10),
+            ])
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(" Chapters ")
+                    .title_style(Style::default().light_blue().bold()),
+            );
+
+        let area = popup_area(area, 70, len as u16 + 3);
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(
+            table,
+            area,
+            &mut TableState::default().with_selected(Some(selected)),
+        );
+    }
+
+    fn draw_preset_popup(&self, selected: usize, area: Rect, f: &mut Frame) {
+        let player = self.player.read().expect("Failed to lock player");
+
+        let rows = player
+            .presets
+            .iter()
+            .map(|name| {
+                Row::new(vec![
+                    if Some(name) == player.preset.as_ref() {
+                        "▶"
+                    } else {
+                        ""
+                    }
+                    .to_string(),
+                    name.clone(),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let len = rows.len();
+
+        let table = Table::new(rows)
+            .highlight_style(Style::default().light_yellow().bold())
+            .widths(&[Constraint::Length(2), Constraint::Min(10)])
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(" DSP presets ")
+                    .title_style(Style::default().light_blue().bold()),
+            );
+
+        let area = popup_area(area, 40, len as u16 + 2);
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(
+            table,
+            area,
+            &mut TableState::default().with_selected(Some(selected)),
+        );
+    }
+
+    fn draw_zone_popup(&self, selected: usize, area: Rect, f: &mut Frame) {
+        let player = self.player.read().expect("Failed to lock player");
+
+        let rows = player
+            .zones
+            .iter()
+            .map(|(name, volume)| {
+                let filled = (volume * ZONE_BAR_WIDTH as f32).round() as usize;
+                Row::new(vec![
+                    name.clone(),
+                    format!(
+                        "{}{} {:>3.0}%",
+                        "█".repeat(filled),
+                        "░".repeat(ZONE_BAR_WIDTH - filled),
+                        volume * 100.0
+                    ),
+                ])
+            })
+            .collect::<Vec<_>>();
+        let len = rows.len();
+
+        let table = Table::new(rows)
+            .highlight_style(Style::default().light_yellow().bold())
+            .widths(&[
+                Constraint::Min(10),
+                Constraint::Length(ZONE_BAR_WIDTH as u16 + 5),
+            ])
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(" Zones, + and - change the volume ")
+                    .title_style(Style::default().light_blue().bold()),
+            );
+
+        let area = popup_area(area, 60, len as u16 + 2);
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(
+            table,
+            area,
+            &mut TableState::default().with_selected(Some(selected)),
+        );
+    }
+
+    /// synced lyrics follow the song with the current line highlighted, plain lyrics are scrolled from `selected`
+    fn draw_lyrics_popup(&self, selected: usize, area: Rect, f: &mut Frame) {
+        let player = self.player.read().expect("Failed to lock player");
+        let Some(lyrics) = self.lyrics(&player) else {
+            return;
+        };
+
+        let area = popup_area(area, 70, lyrics.lines.len() as u16 + 2);
+        let current = match lyrics.synced() {
+            true => lyrics.current(
+                player.playing_duration().unwrap_or(Duration::ZERO),
+                self.lyrics_offset,
+            ),
+            false => None,
+        };
+        // the current line is kept in the middle
+        let top = match current {
+            Some(current) => current.saturating_sub(area.height.saturating_sub(2) as usize / 2),
+            None if lyrics.synced() => 0,
+            None => selected,
+        };
+
+        let lines = lyrics
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(i, l)| {
+                let style = match current.map(|c| i.cmp(&c)) {
+                    Some(std::cmp::Ordering::Equal) => Style::default().light_yellow().bold(),
+                    Some(std::cmp::Ordering::Less) => Style::default().gray(),
+                    _ => Style::default(),
+                };
+                Line::styled(l.text.clone(), style)
+            })
+            .collect::<Vec<_>>();
+
+        let title = match lyrics.synced() {
+            true => format!(" Lyrics ({:+.1}s) ", self.lyrics_offset as f64 / 1000.0),
+            false => " Lyrics ".to_string(),
+        };
+        let paragraph = Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .scroll((top as u16, 0))
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(title)
+                    .title_style(Style::default().light_blue().bold()),
+            );
+
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
+    }
+
+    fn draw_track_popup(&self, selected: usize, area: Rect, f: &mut Frame) {
+        let player = self.player.read().expect("Failed to lock player");
+        let Some((tracks, track_id)) = player.tracks() else {
+            return;
+        };
+
+        let rows = tracks
+            .iter()
+            .map(|t| {
+                Row::new(vec![
+                    if t.id == track_id { "▶" } else { "" }.to_string(),
+                    t.id.to_string(),
+                    t.language.clone().unwrap_or(UNKNOWN_STRING.to_string()),
+                    t.codec.clone(),
+                    t.channels
+                        .map(|c| c.to_string())
+                        .unwrap_or(UNKNOWN_STRING.to_string()),
+                    t.sample_rate
+                        .map(|r| format!("{r} Hz"))
+                        .unwrap_or(UNKNOWN_STRING.to_string()),
+                ])
+            })
+            .collect::<Vec<_>>();
+
+        let table = Table::new(rows)
+            .header(
+                Row::new(["", "Id", "Language", "Codec", "Channels", "Sample rate"])
+                    .light_blue()
+                    .bold(),
+            )
+            .highlight_style(Style::default().light_yellow().bold())
+            .widths(&[
+                Constraint::Length(2),
+                Constraint::Length(4),
+                Constraint::Length(10),
+                Constraint::Length(10),
+                Constraint::Length(9),
+                Constraint::Length(12),
+            ])
+            .block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(" Tracks ")
+                    .title_style(Style::default().light_blue().bold()),
+            );
+
+        let area = popup_area(area, 60, tracks.len() as u16 + 3);
+        f.render_widget(Clear, area);
+        f.render_stateful_widget(
+            table,
+            area,
+            &mut TableState::default().with_selected(Some(selected)),
+        );
     }
 }
 
@@ -64,71 +462,334 @@ impl Tui for Fancy {
                 .title_style(Style::default().bold().light_blue()),
         );
 
-        let layout = Layout::new()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Percentage(50),
-                Constraint::Length(1),
-                Constraint::Percentage(50),
-            ])
-            .split(area);
-
-        let (left, _seperator, right) = (layout[0], layout[1], layout[2]);
-
-        if let Some(image) = player
-            .current_cover()
-            .and_then(|x| image::load_from_memory(x).ok())
-        {
-            let resized = image.resize(
-                (right.width as u32 - 1) * 2,
-                (right.height as u32 - 1) * 2,
-                FilterType::CatmullRom,
-            );
-
-            let rgb = resized
-                .as_flat_samples_u8()
-                .expect("Failed to convert image")
-                .samples
-                .chunks(3)
-                .collect::<Vec<_>>();
+        // the cover and the artist image share the right half, one of them takes all of it
+        let artist = player
+            .current_song()
+            .is_some_and(|s| self.has_artist_image(s));
+        let cover_area = match artist {
+            true => Rect {
+                height: area.height / 2,
+                ..area
+            },
+            false => area,
+        };
+        let cover = self.cover_lines(&player, cover_area);
+        let artist_area = match cover {
+            Some(_) => Rect {
+                height: area.height - area.height / 2,
+                ..area
+            },
+            None => area,
+        };
+        let images = [
+            (" Album Art ", cover),
+            (
+                " Artist ",
+                self.artist_lines(artist_area).filter(|_| artist),
+            ),
+        ]
+        .into_iter()
+        .filter_map(|(title, lines)| Some((title, lines?)))
+        .collect::<Vec<_>>();
 
-            let mut lines = vec![];
-            for y in (0..resized.height()).step_by(2) {
-                let mut line = vec![];
-                for x in 0..resized.width() {
-                    let [r1, g1, b1] = rgb
-                        .get((y * resized.width() + x) as usize)
-                        .and_then(|&x| x.try_into().ok())
-                        .unwrap_or([0, 0, 0]);
-                    let [r2, g2, b2] = rgb
-                        .get((y * resized.width() + x + resized.width()) as usize)
-                        .and_then(|&x| x.try_into().ok())
-                        .unwrap_or([0, 0, 0]);
-                    line.push(
-                        Span::from("▀")
-                            .fg(Color::Rgb(r1, g1, b1))
-                            .bg(Color::Rgb(r2, g2, b2)),
-                    );
-                }
-                lines.push(Line::from(line));
-            }
+        if images.is_empty() {
+            f.render_widget(standard_tags, area);
+        } else {
+            let layout = Layout::new()
+                .direction(Direction::Horizontal)
+                .constraints([
+                    Constraint::Percentage(50),
+                    Constraint::Length(1),
+                    Constraint::Percentage(50),
+                ])
+                .split(area);
 
-            let image = Paragraph::new(lines).alignment(Alignment::Center).block(
-                Block::new()
-                    .border_type(BorderType::Rounded)
-                    .borders(Borders::ALL)
-                    .title(" Album Art ")
-                    .title_style(Style::default().light_blue().bold()),
-            );
+            let (left, _seperator, right) = (layout[0], layout[1], layout[2]);
+            let rows = Layout::new()
+                .direction(Direction::Vertical)
+                .constraints(vec![
+                    Constraint::Ratio(1, images.len() as u32);
+                    images.len()
+                ])
+                .split(right);
 
-            f.render_widget(image, right);
+            for ((title, lines), row) in images.into_iter().zip(rows.iter()) {
+                let image = Paragraph::new(lines).alignment(Alignment::Center).block(
+                    Block::new()
+                        .border_type(BorderType::Rounded)
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .title_style(Style::default().light_blue().bold()),
+                );
+                f.render_widget(image, *row);
+            }
             f.render_widget(standard_tags, left);
         }
+        drop(player);
+
+        match self.popup {
+            Some((Popup::Tracks, selected)) => self.draw_track_popup(selected, area, f),
+            Some((Popup::Chapters, selected)) => self.draw_chapter_popup(selected, area, f),
+            Some((Popup::Presets, selected)) => self.draw_preset_popup(selected, area, f),
+            Some((Popup::Zones, selected)) => self.draw_zone_popup(selected, area, f),
+            Some((Popup::Lyrics, selected)) => self.draw_lyrics_popup(selected, area, f),
+            None => {}
+        }
 
         Ok(())
     }
 
-    fn input(&mut self, _event: &Event) -> anyhow::Result<()> {
+    fn captures_keys(&self) -> bool {
+        matches!(self.popup, Some((Popup::Zones, _)))
+    }
+
+    fn input(&mut self, event: &Event) -> anyhow::Result<()> {
+        if let Event::Resize(..) = event {
+            self.cover.get_mut().take();
+            self.artist.get_mut().take();
+        }
+
+        if let Event::Key(KeyEvent { code, .. }) = event {
+            match (self.popup, code) {
+                (None, KeyCode::Char('t')) if self.popup_len(Popup::Tracks) > 0 => {
+                    let player = self.player.read().expect("Failed to lock player");
+                    self.popup = player
+                        .tracks()
+                        .and_then(|(tracks, id)| tracks.iter().position(|t| t.id == id))
+                        .map(|i| (Popup::Tracks, i));
+                }
+                (None, KeyCode::Char('h')) if self.popup_len(Popup::Chapters) > 0 => {
+                    let player = self.player.read().expect("Failed to lock player");
+                    self.popup = Some((Popup::Chapters, player.current_chapter().unwrap_or(0)));
+                }
+                (None, KeyCode::Char(c @ '0'..='5')) => {
+                    let rating = c.to_digit(10).filter(|&r| r > 0).map(|r| r as u8);
+                    self.cmd.send(Command::Rate(rating))?;
+                }
+                (None, KeyCode::Char('x')) => {
+                    self.cmd.send(Command::ToggleCrossfeed)?;
+                }
+                (None, KeyCode::Char('a')) => {
+                    self.cmd.send(Command::ToggleRandomAlbums)?;
+                }
+                (None, KeyCode::Char('o')) => {
+                    let player = self.player.read().expect("Failed to lock player");
+                    if let Some(song) = player.current_song() {
+                        reveal::reveal(&song.path);
+                    }
+                }
+                (None, KeyCode::Char('z')) => {
+                    self.scheduler.cycle_sleep_timer();
+                }
+                (None, KeyCode::Char('e')) if self.popup_len(Popup::Presets) > 0 => {
+                    let player = self.player.read().expect("Failed to lock player");
+                    let selected = player
+                        .preset
+                        .as_ref()
+                        .and_then(|p| player.presets.iter().position(|n| n == p))
+                        .unwrap_or(0);
+                    self.popup = Some((Popup::Presets, selected));
+                }
+                (None, KeyCode::Char('v')) if self.popup_len(Popup::Zones) > 0 => {
+                    self.popup = Some((Popup::Zones, 0));
+                }
+                (None, KeyCode::Char('l')) if self.popup_len(Popup::Lyrics) > 0 => {
+                    self.popup = Some((Popup::Lyrics, 0));
+                }
+                (Some(_), KeyCode::Esc)
+                | (Some((Popup::Tracks, _)), KeyCode::Char('t'))
+                | (Some((Popup::Chapters, _)), KeyCode::Char('h'))
+                | (Some((Popup::Presets, _)), KeyCode::Char('e'))
+                | (Some((Popup::Zones, _)), KeyCode::Char('v') | KeyCode::Enter)
+                | (Some((Popup::Lyrics, _)), KeyCode::Char('l')) => {
+                    self.popup = None;
+                }
+                (Some((Popup::Zones, i)), KeyCode::Char(c @ ('+' | '-'))) => {
+                    let player = self.player.read().expect("Failed to lock player");
+                    if let Some((name, volume)) = player.zones.get(i) {
+                        let step = if *c == '+' { VOLUME_STEP } else { -VOLUME_STEP };
+                        self.cmd
+                            .send(Command::SetZoneVolume(name.clone(), volume + step))?;
+                    }
+                }
+                (Some((Popup::Lyrics, _)), KeyCode::Char(c @ ('<' | '>'))) => {
+                    let step = if *c == '>' {
+                        LYRICS_OFFSET_STEP
+                    } else {
+                        -LYRICS_OFFSET_STEP
+                    };
+                    self.lyrics_offset += step;
+                }
+                (Some((popup, i)), KeyCode::Up) => {
+                    self.popup = Some((popup, i.saturating_sub(1)));
+                }
+                (Some((popup, i)), KeyCode::Down) => {
+                    self.popup =
+                        Some((popup, (i + 1).min(self.popup_len(popup).saturating_sub(1))));
+                }
+                (Some((popup, i)), KeyCode::Enter) => {
+                    let player = self.player.read().expect("Failed to lock player");
+                    let command = match popup {
+                        Popup::Tracks => player
+                            .tracks()
+                            .and_then(|(tracks, _)| tracks.get(i))
+                            .map(|t| Command::SelectTrack(t.id)),
+                        Popup::Chapters => player.chapters().get(i).map(|c| Command::Seek(c.start)),
+                        Popup::Presets => player
+                            .presets
+                            .get(i)
+                            .map(|name| Command::SelectPreset(name.clone())),
+                        Popup::Zones | Popup::Lyrics => None,
+                    };
+
+                    if let Some(command) = command {
+                        self.cmd.send(command)?;
+                    }
+                    drop(player);
+                    self.popup = None;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some((popup, _)) = self.popup {
+            if self.popup_len(popup) == 0 {
+                self.popup = None;
+            }
+        }
+
         Ok(())
     }
 }
+
+/// the image of the artist of `song`: a file like `artist.jpg` in its directory or one above it,
+/// up to its search directory, or `artists/<artist>.jpg` in `art_cache_directory`, e.g. downloaded by a hook
+fn artist_image(config: &Config, song: &Song) -> Option<Vec<u8>> {
+    let directories = song.path.ancestors().skip(1).enumerate();
+    let local = directories
+        .take_while(|(i, d)| *i == 0 || config.search_directories.iter().any(|s| d.starts_with(s)))
+        .flat_map(|(_, d)| ARTIST_IMAGES.map(|name| d.join(name)));
+
+    let artist = song
+        .tag_string(StandardTagKey::AlbumArtist)
+        .or(song.tag_string(StandardTagKey::Artist))
+        .map(|a| a.replace(['/', '\\', '\0'], "_"));
+    let cached = artist.map(|a| {
+        config
+            .art_cache_directory
+            .join("artists")
+            .join(format!("{a}.jpg"))
+    });
+
+    local
+        .chain(cached)
+        .find(|p| p.is_file())
+        .and_then(|p| std::fs::read(p).ok())
+}
+
+/// render the cover fitting into the right half of `area`
+#[cfg(feature = "cover-art")]
+fn cover_lines(cover: &[u8], area: Rect, style: CoverStyle) -> Option<Vec<Line<'static>>> {
+    let image = image::load_from_memory(cover).ok()?;
+    if style == CoverStyle::Braille {
+        return Some(braille_lines(&image, area));
+    }
+
+    let right_width = area.width.saturating_sub(1) / 2;
+    let resized = image.resize(
+        (right_width as u32).saturating_sub(1) * 2,
+        (area.height as u32).saturating_sub(1) * 2,
+        FilterType::CatmullRom,
+    );
+
+    let rgb = resized
+        .as_flat_samples_u8()
+        .expect("Failed to convert image")
+        .samples
+        .chunks(3)
+        .collect::<Vec<_>>();
+
+    let mut lines = vec![];
+    for y in (0..resized.height()).step_by(2) {
+        let mut line = vec![];
+        for x in 0..resized.width() {
+            let [r1, g1, b1] = rgb
+                .get((y * resized.width() + x) as usize)
+                .and_then(|&x| x.try_into().ok())
+                .unwrap_or([0, 0, 0]);
+            let [r2, g2, b2] = rgb
+                .get((y * resized.width() + x + resized.width()) as usize)
+                .and_then(|&x| x.try_into().ok())
+                .unwrap_or([0, 0, 0]);
+            line.push(
+                Span::from("▀")
+                    .fg(Color::Rgb(r1, g1, b1))
+                    .bg(Color::Rgb(r2, g2, b2)),
+            );
+        }
+        lines.push(Line::from(line));
+    }
+
+    Some(lines)
+}
+
+/// render the cover as braille characters, each cell shows the brighter of its 2x4 pixels as dots
+/// in their average color on the average color of the darker ones
+#[cfg(feature = "cover-art")]
+fn braille_lines(image: &image::DynamicImage, area: Rect) -> Vec<Line<'static>> {
+    // bits of the dots of a braille character, by row and column
+    const DOTS: [[u32; 2]; 4] = [[0x01, 0x08], [0x02, 0x10], [0x04, 0x20], [0x40, 0x80]];
+
+    let columns = (area.width.saturating_sub(1) / 2).saturating_sub(2) as u32;
+    let rows = area.height.saturating_sub(2) as u32;
+    let resized = image
+        .resize(columns * 2, rows * 4, FilterType::CatmullRom)
+        .to_rgb8();
+
+    let mut lines = vec![];
+    for row in 0..resized.height().div_ceil(4) {
+        let mut line = vec![];
+        for column in 0..resized.width().div_ceil(2) {
+            let pixels = DOTS
+                .iter()
+                .enumerate()
+                .flat_map(|(y, bits)| bits.iter().enumerate().map(move |(x, &bit)| (x, y, bit)))
+                .filter_map(|(x, y, bit)| {
+                    resized
+                        .get_pixel_checked(column * 2 + x as u32, row * 4 + y as u32)
+                        .map(|p| (p.0, bit))
+                })
+                .collect::<Vec<_>>();
+
+            let luma = |[r, g, b]: [u8; 3]| 299 * r as u32 + 587 * g as u32 + 114 * b as u32;
+            let threshold =
+                pixels.iter().map(|(p, _)| luma(*p)).sum::<u32>() / pixels.len().max(1) as u32;
+            let (bright, dark): (Vec<_>, Vec<_>) =
+                pixels.iter().partition(|(p, _)| luma(*p) > threshold);
+
+            let average = |pixels: &[([u8; 3], u32)]| {
+                let n = pixels.len().max(1) as u32;
+                let sum = pixels.iter().fold([0u32; 3], |mut sum, (p, _)| {
+                    sum.iter_mut().zip(p).for_each(|(s, &c)| *s += c as u32);
+                    sum
+                });
+                Color::Rgb((sum[0] / n) as u8, (sum[1] / n) as u8, (sum[2] / n) as u8)
+            };
+
+            let bits = bright.iter().fold(0, |bits, (_, bit)| bits | bit);
+            line.push(
+                Span::from(char::from_u32(0x2800 + bits).unwrap_or(' ').to_string())
+                    .fg(average(&bright))
+                    .bg(average(&dark)),
+            );
+        }
+        lines.push(Line::from(line));
+    }
+
+    lines
+}
+
+#[cfg(not(feature = "cover-art"))]
+fn cover_lines(_cover: &[u8], _area: Rect, _style: CoverStyle) -> Option<Vec<Line<'static>>> {
+    None
+}
@@ -1,68 +1,346 @@
 use std::{
-    cmp::Ordering,
-    path::PathBuf,
-    sync::{mpsc, Arc},
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{mpsc, Arc, RwLock, Weak},
+    time::Duration,
 };
 
 use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use itertools::Itertools;
-use log::trace;
+use log::{trace, warn};
 use ratatui::{
     prelude::{Constraint, Direction, Layout, Rect},
     style::{Color, Style, Stylize},
     text::{Line, Span},
-    widgets::{Paragraph, Table, TableState},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Table, Wrap},
     Frame,
 };
 
 use crate::{
-    cache::{Cache, CacheEntry},
-    player::command::Command,
-    song::StandardTagKey,
-    tui::song_table,
+    album::{self, AlbumKey},
+    cache::{Cache, CacheEntry, SharedCache},
+    collation,
+    config::Config,
+    notification::Notification,
+    organize, paths,
+    player::{analysis, command::Command, facade::PlayerFacade},
+    song::{Song, StandardTagKey},
+    tui::{clipboard, list::ListController, reveal, song_table},
 };
 
-use super::Tui;
+use super::{format_duration, popup_area, Tui, UNKNOWN_STRING};
+
+/// a file operation waiting for confirmation
+#[derive(Debug, PartialEq, Eq)]
+enum FileAction {
+    Trash(PathBuf),
+    Rename { from: PathBuf, to: PathBuf },
+}
 
 #[derive(Debug, PartialEq, Eq)]
 enum FilterState {
     Disabled,
-    Active { input: String, selected: bool },
+    Active {
+        input: String,
+        selected: bool,
+        matches: HashSet<PathBuf>,
+        /// whether the songs of all subdirectories are listed by their relative path
+        recursive: bool,
+    },
+}
+
+/// the sorted entries of a directory, sorting directories with tens of thousands of files
+/// on every key press and frame is slow
+struct Listing {
+    path: PathBuf,
+    /// the cache snapshot the entries were sorted from
+    cache: Weak<Cache>,
+    /// the filter input `names` was filtered with and whether it was recursive
+    filter: Option<(String, bool)>,
+    sorted: Rc<[String]>,
+    names: Rc<[String]>,
+}
+
+/// the number of songs below a directory and their total duration
+#[derive(Debug, Clone, Copy, Default)]
+struct Summary {
+    songs: usize,
+    duration: Duration,
+}
+
+/// the summaries of the directories shown so far, computed when a directory is first shown
+/// as adding up large directories on every frame is slow
+struct Summaries {
+    /// the cache snapshot the summaries were computed from
+    cache: Weak<Cache>,
+    directories: HashMap<PathBuf, Summary>,
+}
+
+impl Summaries {
+    /// the summary of `entry` at `path`, storing the ones of all directories below it on the way
+    fn get(&mut self, path: &Path, entry: &CacheEntry) -> Summary {
+        let children = match entry {
+            CacheEntry::File { song } => {
+                return Summary {
+                    songs: 1,
+                    duration: song.duration,
+                }
+            }
+            CacheEntry::Directory { children } => children,
+        };
+        if let Some(summary) = self.directories.get(path) {
+            return *summary;
+        }
+
+        let summary = children
+            .iter()
+            .map(|(name, child)| self.get(&path.join(name), child))
+            .fold(Summary::default(), |a, b| Summary {
+                songs: a.songs + b.songs,
+                duration: a.duration + b.duration,
+            });
+        self.directories.insert(path.to_path_buf(), summary);
+        summary
+    }
+}
+
+/// the shown entries of the current directory, or the songs of its subdirectories by their relative path
+struct Items<'a> {
+    directory: Option<&'a HashMap<String, CacheEntry>>,
+    names: Rc<[String]>,
+}
+
+impl<'a> Items<'a> {
+    fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    fn get(&self, index: usize) -> Option<(String, &'a CacheEntry)> {
+        let name = self.names.get(index)?;
+        Some((name.clone(), self.entry(name)?))
+    }
+
+    /// the entry `name` of the directory, or of one of its subdirectories for relative paths
+    fn entry(&self, name: &str) -> Option<&'a CacheEntry> {
+        let components = paths::components(Path::new(name)).ok()?;
+        let (first, rest) = components.split_first()?;
+        rest.iter()
+            .try_fold(self.directory?.get(first)?, |entry, c| {
+                entry.as_directory().ok()?.get(c)
+            })
+    }
+
+    /// at most `count` entries from `start` on
+    fn window(
+        &self,
+        start: usize,
+        count: usize,
+    ) -> impl Iterator<Item = (&String, &'a CacheEntry)> + '_ {
+        self.names
+            .iter()
+            .skip(start)
+            .take(count)
+            .filter_map(|f| Some((f, self.entry(f)?)))
+    }
 }
 
 pub struct Files {
-    cache: Arc<Cache>,
+    cache: SharedCache,
+    player: Arc<RwLock<PlayerFacade>>,
     path: PathBuf,
-    selected: Vec<usize>,
+    /// the selection in every directory down to the current one
+    selected: Vec<ListController>,
     player_tx: mpsc::Sender<Command>,
     filter: FilterState,
+    config: Arc<Config>,
+    notifications: mpsc::Sender<Notification>,
+    confirm: Option<FileAction>,
+    /// the song whose info popup is open, with all of its tags
+    info: Option<Song>,
+    listing: RefCell<Option<Listing>>,
+    summaries: RefCell<Option<Summaries>>,
 }
 
 impl Files {
-    pub fn new(cache: Arc<Cache>, cmd: mpsc::Sender<Command>) -> Self {
+    pub fn new(
+        cache: SharedCache,
+        player: Arc<RwLock<PlayerFacade>>,
+        cmd: mpsc::Sender<Command>,
+        config: Arc<Config>,
+        notifications: mpsc::Sender<Notification>,
+    ) -> Self {
         Self {
-            path: std::path::Path::new("/")
-                .canonicalize()
-                .expect("Failed to get directory")
-                .components()
-                .map(|c| {
-                    c.as_os_str()
-                        .to_str()
-                        .expect("Failed to convert path to string")
-                        .to_string()
-                })
-                .collect(),
-            selected: vec![0],
+            path: paths::root(),
+            // the roots of the library and the root of the current directory
+            selected: vec![
+                ListController::new(config.wrap_selection),
+                ListController::new(config.wrap_selection),
+            ],
             cache,
+            player,
             player_tx: cmd,
             filter: FilterState::Disabled,
+            config,
+            notifications,
+            confirm: None,
+            info: None,
+            listing: RefCell::new(None),
+            summaries: RefCell::new(None),
         }
     }
 
+    fn notify(&self, notification: Notification) {
+        self.notifications
+            .send(notification)
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+    }
+
+    /// the song at `path` and the songs following it on its album,
+    /// `None` with an error notification if it has no album tag
+    fn album_from(&self, cache: &Cache, path: PathBuf, song: &Song) -> Option<Vec<PathBuf>> {
+        let Some(key) = AlbumKey::new(song) else {
+            self.notify(Notification::error(format!(
+                "{} has no album tag",
+                path.display()
+            )));
+            return None;
+        };
+
+        let mut tracks = album::tracks(cache, &key);
+        let start = tracks.iter().position(|p| *p == path).unwrap_or(0);
+        Some(tracks.split_off(start))
+    }
+
+    fn run_action(&self, action: FileAction) {
+        let result = match &action {
+            FileAction::Trash(path) => organize::trash(&self.cache, path),
+            FileAction::Rename { from, .. } => {
+                organize::rename(&self.cache, &self.config, from).map(|_| ())
+            }
+        };
+
+        self.notify(match (result, action) {
+            (Ok(()), FileAction::Trash(path)) => {
+                Notification::info(format!("Moved {} to the trash", path.display()))
+            }
+            (Ok(()), FileAction::Rename { to, .. }) => {
+                Notification::info(format!("Moved to {}", to.display()))
+            }
+            (Err(e), _) => {
+                warn!("File operation failed: {e:?}");
+                Notification::error(format!("{e:#}"))
+            }
+        });
+    }
+
+    fn draw_confirm_popup(&self, action: &FileAction, area: Rect, f: &mut Frame) {
+        let text = match action {
+            FileAction::Trash(path) => format!("Move {} to the trash?", path.display()),
+            FileAction::Rename { from, to } => {
+                format!("Move {}\nto {}?", from.display(), to.display())
+            }
+        };
+
+        let area = popup_area(area, 80, 6);
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(text).wrap(Wrap { trim: false }).block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(" Confirm with y, cancel with any other key ")
+                    .title_style(Style::default().light_red().bold()),
+            ),
+            area,
+        );
+    }
+
+    fn draw_info_popup(&self, song: &Song, area: Rect, f: &mut Frame) {
+        let cache = self.cache.load();
+        let path = song.path.as_ref();
+
+        let row = |name: &str, value: String| {
+            Line::from(vec![
+                Span::from(format!("{name:<15}")).light_blue(),
+                Span::from(value),
+            ])
+        };
+        let tag = |key| song.tag_string(key).unwrap_or(UNKNOWN_STRING).to_string();
+        let mut lines = vec![
+            row("Title", tag(StandardTagKey::TrackTitle)),
+            row("Artist", tag(StandardTagKey::Artist)),
+            row("Album", tag(StandardTagKey::Album)),
+            row("Duration", format_duration(song.duration)),
+            row(
+                "Bitrate",
+                song.bitrate
+                    .map_or(UNKNOWN_STRING.to_string(), |b| format!("{b} kbit/s")),
+            ),
+            row(
+                "Replay gain",
+                format!("{:+.1} dB", 20.0 * song.gain_factor.log10()),
+            ),
+            row(
+                "Comment",
+                song.tag_string(StandardTagKey::Comment)
+                    .and_then(|c| c.lines().next())
+                    .unwrap_or(UNKNOWN_STRING)
+                    .to_string(),
+            ),
+            row(
+                "Lyrics",
+                song.tag_string(StandardTagKey::Lyrics)
+                    .map_or("None".to_string(), |l| {
+                        format!("{} lines", l.lines().count())
+                    }),
+            ),
+            Line::default(),
+        ];
+        match cache.analysis(path) {
+            Some(analysis) => lines.extend([
+                row("True peak", format!("{:.1} dBTP", analysis.true_peak)),
+                row("RMS", format!("{:.1} dBFS", analysis.rms)),
+                row(
+                    "Dynamic range",
+                    format!(
+                        "DR{:.0} ({:.1} dB)",
+                        analysis.dynamic_range, analysis.dynamic_range
+                    ),
+                ),
+            ]),
+            None if cache.analyzing(path) => {
+                lines.push(Line::from(Span::from("Analyzing…").fg(Color::DarkGray)))
+            }
+            None => lines.push(Line::from(
+                Span::from("The analysis failed").fg(Color::DarkGray),
+            )),
+        }
+
+        let area = popup_area(area, 60, lines.len() as u16 + 2);
+        f.render_widget(Clear, area);
+        f.render_widget(
+            Paragraph::new(lines).block(
+                Block::new()
+                    .borders(Borders::ALL)
+                    .border_type(BorderType::Rounded)
+                    .title(format!(
+                        " {} ",
+                        path.file_name().unwrap_or_default().to_string_lossy()
+                    ))
+                    .title_style(Style::default().light_blue().bold()),
+            ),
+            area,
+        );
+    }
+
     fn input_files(&mut self, event: &Event) -> anyhow::Result<()> {
         trace!("input_files: {:?}", event);
 
-        let l = self.items()?.count();
+        let cache = self.cache.load();
+
+        let l = self.items(&cache)?.len();
 
         if let Event::Key(KeyEvent {
             code, modifiers, ..
@@ -73,61 +351,125 @@ impl Files {
                     self.filter = FilterState::Active {
                         input: String::new(),
                         selected: true,
+                        matches: HashSet::new(),
+                        recursive: false,
                     };
                 }
-                KeyCode::Char(' ') => {
-                    self.player_tx
-                        .send(Command::PlayPause)
-                        .expect("Failed to send play/pause");
-                }
-                KeyCode::Char('n') => {
-                    self.player_tx
-                        .send(Command::Skip)
-                        .expect("Failed to send skip");
-                }
-                KeyCode::Char('s') => {
-                    self.player_tx
-                        .send(Command::Stop)
-                        .expect("Failed to send stop");
-                }
                 KeyCode::Char('c') => {
                     self.player_tx
                         .send(Command::Clear)
                         .expect("Failed to send clear");
                 }
-                KeyCode::Up => {
-                    if let Some(i) = self.selected.last_mut() {
-                        *i = i.checked_sub(1).unwrap_or(0);
+                KeyCode::Char(c @ ('y' | 'Y')) => {
+                    let selected = self.selection();
+                    if let Some((f, entry)) = self.items(&cache)?.get(selected) {
+                        match (c, entry) {
+                            ('y', _) => clipboard::copy(&self.path.join(f).to_string_lossy()),
+                            (_, CacheEntry::File { song, .. }) => {
+                                clipboard::copy(&clipboard::describe(song))
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                KeyCode::Char('i') => {
+                    let selected = self.selection();
+                    if let Some((_, CacheEntry::File { song })) = self.items(&cache)?.get(selected)
+                    {
+                        analysis::start(&self.cache, song, self.notifications.clone());
+                        self.info = Some(song.full().into_owned());
                     }
                 }
-                KeyCode::Down => {
-                    if let Some(i) = self.selected.last_mut() {
-                        *i = (*i + 1).min(l - 1);
+                KeyCode::Char('o') => {
+                    let selected = self.selection();
+                    if let Some((f, _)) = self.items(&cache)?.get(selected) {
+                        reveal::reveal(&self.path.join(f));
                     }
                 }
-                KeyCode::PageUp => {
-                    if let Some(i) = self.selected.last_mut() {
-                        *i = i.checked_sub(25).unwrap_or(0);
+                KeyCode::Char('a') => {
+                    let selected = self.selection();
+                    if let Some((f, CacheEntry::File { song })) = self.items(&cache)?.get(selected)
+                    {
+                        let tracks = self
+                            .album_from(&cache, self.path.join(f), song)
+                            .unwrap_or_default();
+                        self.player_tx.send(Command::Batch(
+                            tracks
+                                .into_iter()
+                                .map(|t| Command::Enqueue(t.into_boxed_path()))
+                                .collect(),
+                        ))?;
                     }
                 }
-                KeyCode::PageDown => {
-                    if let Some(i) = self.selected.last_mut() {
-                        *i = (*i + 25).min(l - 1);
+                KeyCode::Char(c @ ('p' | 'P')) => {
+                    let selected = self.selection();
+                    let paths = match self.items(&cache)?.get(selected) {
+                        Some((f, CacheEntry::File { song })) if *c == 'P' => {
+                            self.album_from(&cache, self.path.join(f), song)
+                        }
+                        Some((f, CacheEntry::File { .. })) => Some(vec![self.path.join(f)]),
+                        // all songs below the directory, in path order
+                        Some((f, CacheEntry::Directory { .. })) => {
+                            let dir = self.path.join(f);
+                            Some(
+                                cache
+                                    .songs()
+                                    .map(|(_, p)| p)
+                                    .filter(|p| p.starts_with(&dir))
+                                    .sorted()
+                                    .collect(),
+                            )
+                        }
+                        None => None,
+                    };
+
+                    if let Some(paths) = paths {
+                        self.player_tx.send(Command::Replace(
+                            paths.into_iter().map(PathBuf::into_boxed_path).collect(),
+                        ))?;
                     }
                 }
-                KeyCode::End => {
-                    if let Some(i) = self.selected.last_mut() {
-                        *i = l - 1;
+                KeyCode::Char('v') => {
+                    let selected = self.selection();
+                    if let Some((f, CacheEntry::File { .. })) = self.items(&cache)?.get(selected) {
+                        self.player_tx
+                            .send(Command::Preview(self.path.join(f).into_boxed_path()))?;
                     }
                 }
-                KeyCode::Home => {
-                    if let Some(i) = self.selected.last_mut() {
-                        *i = 0;
+                KeyCode::Char('A') => {
+                    self.player_tx
+                        .send(Command::EnqueueRandomAlbum)
+                        .expect("Failed to send enqueue random album");
+                }
+                KeyCode::Char(c @ ('D' | 'R')) => {
+                    let selected = self.selection();
+                    let action = match self.items(&cache)?.get(selected) {
+                        Some((f, CacheEntry::File { song })) => {
+                            let path = self.path.join(f);
+                            Some(match c {
+                                'D' => Ok(FileAction::Trash(path)),
+                                _ => organize::destination(&self.config, song)
+                                    .map(|to| FileAction::Rename { from: path, to }),
+                            })
+                        }
+                        _ => None,
+                    };
+
+                    match action {
+                        Some(Ok(action)) => self.confirm = Some(action),
+                        Some(Err(e)) => self.notify(Notification::error(format!("{e:#}"))),
+                        None => {}
                     }
                 }
+                code if self
+                    .selected
+                    .last_mut()
+                    .is_some_and(|list| list.input(code, l)) => {}
                 KeyCode::Enter => {
-                    let selected = *self.selected.last().expect("Failed to get selected index");
-                    let (f, c) = self.items()?.nth(selected).expect("Failed to get item");
+                    let selected = self.selection();
+                    let Some((f, c)) = self.items(&cache)?.get(selected) else {
+                        return Ok(());
+                    };
 
                     match c {
                         CacheEntry::File { .. } => {
@@ -138,16 +480,19 @@ impl Files {
                         }
                         CacheEntry::Directory { .. } => {
                             self.path.push(f.clone());
-                            self.selected.push(0);
+                            self.selected
+                                .push(ListController::new(self.config.wrap_selection));
                         }
                     }
 
                     trace!("unlock player");
                 }
-                KeyCode::Backspace => {
-                    if self.path.pop() {
-                        self.selected.pop();
+                // going up from a root lists the roots, e.g. the drives on windows
+                KeyCode::Backspace if !self.path.as_os_str().is_empty() => {
+                    if !self.path.pop() {
+                        self.path = PathBuf::new();
                     }
+                    self.selected.pop();
                 }
                 _ => {}
             }
@@ -156,67 +501,144 @@ impl Files {
         Ok(())
     }
 
-    fn items<'a>(
-        &'a self,
-    ) -> anyhow::Result<Box<dyn Iterator<Item = (&'a String, &'a CacheEntry)> + 'a>> {
-        let d = self.cache.get(&self.path)?;
+    /// index of the selected entry of the current directory
+    fn selection(&self) -> usize {
+        self.selected.last().map_or(0, ListController::selected)
+    }
+
+    /// the summary of the directory `entry` at `path`, computed again only when the cache changed
+    fn summary(&self, cache: &Arc<Cache>, path: &Path, entry: &CacheEntry) -> Summary {
+        let mut summaries = self.summaries.borrow_mut();
+        let summaries = match summaries.as_mut() {
+            Some(s) if Weak::as_ptr(&s.cache) == Arc::as_ptr(cache) => s,
+            _ => summaries.insert(Summaries {
+                cache: Arc::downgrade(cache),
+                directories: HashMap::new(),
+            }),
+        };
+        summaries.get(path, entry)
+    }
+
+    fn filter_matches(cache: &Cache, input: &str) -> HashSet<PathBuf> {
+        cache
+            .index()
+            .lookup(input)
+            .into_iter()
+            .map(|p| p.to_path_buf())
+            .collect()
+    }
 
-        Ok(d.map_or(Box::new(std::iter::empty()), |d| {
-            Box::new(
-                d.as_directory()
-                    .unwrap()
+    /// whether the entry is shown with the current filter
+    fn shown(&self, name: &str, entry: &CacheEntry) -> bool {
+        match &self.filter {
+            FilterState::Disabled => true,
+            FilterState::Active { input, matches, .. } => match entry {
+                CacheEntry::File { .. } => {
+                    input.is_empty() || matches.contains(&self.path.join(name))
+                }
+                CacheEntry::Directory { .. } => name.to_lowercase().contains(&input.to_lowercase()),
+            },
+        }
+    }
+
+    /// the entries of the current directory in display order, sorted again only when the directory
+    /// or the cache changed and filtered again only when the filter changed
+    fn items<'a>(&self, cache: &'a Arc<Cache>) -> anyhow::Result<Items<'a>> {
+        let directory = match self.path.as_os_str().is_empty() {
+            true => Some(cache.roots()),
+            false => cache
+                .get(&self.path)?
+                .map(CacheEntry::as_directory)
+                .transpose()?,
+        };
+        let filter = match &self.filter {
+            FilterState::Disabled => None,
+            FilterState::Active {
+                input, recursive, ..
+            } => Some((input.clone(), *recursive)),
+        };
+
+        let mut listing = self.listing.borrow_mut();
+        let current = listing
+            .as_ref()
+            .is_some_and(|l| l.path == self.path && Weak::as_ptr(&l.cache) == Arc::as_ptr(cache));
+        if !current {
+            let mut entries = directory.into_iter().flatten().collect::<Vec<_>>();
+            entries.sort_by_cached_key(|(f, c)| sort_key(f, c, &self.config.sort_articles));
+            let sorted = entries
+                .into_iter()
+                .map(|(f, _)| f.clone())
+                .collect::<Rc<[_]>>();
+            *listing = Some(Listing {
+                path: self.path.clone(),
+                cache: Arc::downgrade(cache),
+                filter: None,
+                names: sorted.clone(),
+                sorted,
+            });
+        }
+
+        let listing = listing.as_mut().expect("listing was just created");
+        if !current || listing.filter != filter {
+            listing.names = match (&self.filter, directory) {
+                // the matching songs below the current directory, sorted by their relative path
+                (
+                    FilterState::Active {
+                        input,
+                        matches,
+                        recursive: true,
+                        ..
+                    },
+                    Some(_),
+                ) if !input.is_empty() => matches
                     .iter()
-                    .filter(|(f, c)| match &self.filter {
-                        FilterState::Disabled => true,
-                        FilterState::Active { input, .. } => match c {
-                            CacheEntry::File { song } => {
-                                song.standard_tags.iter().any(|(_, v)| {
-                                    v.to_string().to_lowercase().contains(&input.to_lowercase())
-                                }) || f.to_lowercase().contains(&input.to_lowercase())
-                            }
-                            CacheEntry::Directory { .. } => {
-                                f.to_lowercase().contains(&input.to_lowercase())
-                            }
-                        },
-                    })
-                    .sorted_by(|(f1, c1), (f2, c2)| match (c1, c2) {
-                        (
-                            CacheEntry::File { song: song1, .. },
-                            CacheEntry::File { song: song2, .. },
-                        ) => {
-                            let t1 = song1
-                                .standard_tags
-                                .get(&StandardTagKey::TrackNumber)
-                                .map(|v| v.to_string())
-                                .and_then(|v| v.parse::<u32>().ok());
-                            let t2 = song2
-                                .standard_tags
-                                .get(&StandardTagKey::TrackNumber)
-                                .map(|v| v.to_string())
-                                .and_then(|v| v.parse::<u32>().ok());
-
-                            match (t1, t2) {
-                                (None, None) => f1.cmp(f2),
-                                (None, Some(_)) => Ordering::Less,
-                                (Some(_), None) => Ordering::Greater,
-                                (Some(a), Some(b)) => a.cmp(&b),
-                            }
-                        }
-                        (CacheEntry::File { .. }, CacheEntry::Directory { .. }) => Ordering::Less,
-                        (CacheEntry::Directory { .. }, CacheEntry::File { .. }) => {
-                            Ordering::Greater
-                        }
-                        (CacheEntry::Directory { .. }, CacheEntry::Directory { .. }) => f1.cmp(f2),
-                    }),
-            )
-        }))
+                    .filter_map(|p| p.strip_prefix(&self.path).ok())
+                    .map(|p| p.to_string_lossy().to_string())
+                    .sorted_by_cached_key(|p| collation::key(p, &self.config.sort_articles))
+                    .collect(),
+                (FilterState::Active { .. }, Some(directory)) => listing
+                    .sorted
+                    .iter()
+                    .filter(|f| directory.get(*f).is_some_and(|c| self.shown(f, c)))
+                    .cloned()
+                    .collect(),
+                _ => listing.sorted.clone(),
+            };
+            listing.filter = filter;
+        }
+
+        Ok(Items {
+            directory,
+            names: listing.names.clone(),
+        })
     }
 }
 
+/// songs first, by track number or name, then directories by name, names in the order of `collation`
+fn sort_key(
+    name: &str,
+    entry: &CacheEntry,
+    articles: &[String],
+) -> (bool, Option<u32>, String, String) {
+    let track = match entry {
+        CacheEntry::File { song } => song.number(StandardTagKey::TrackNumber),
+        CacheEntry::Directory { .. } => None,
+    };
+
+    (
+        matches!(entry, CacheEntry::Directory { .. }),
+        track,
+        collation::key(name, articles),
+        name.to_string(),
+    )
+}
+
 impl Tui for Files {
     fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
         trace!("drawing files");
 
+        let cache = self.cache.load();
+
         let (inner_area, filter_area) = match self.filter {
             FilterState::Disabled => (area, None),
             FilterState::Active { .. } => {
@@ -232,57 +654,60 @@ impl Tui for Files {
             FilterState::Disabled => vec![],
             FilterState::Active {
                 input,
-                selected: true,
-            } => vec![
-                Span::from("Filter: ").bold(),
-                Span::from(input.clone()).light_yellow(),
-                Span::from("_").light_yellow().slow_blink(),
-            ],
-            FilterState::Active {
-                input,
-                selected: false,
-            } => vec![
-                Span::from("Filter: ").bold(),
-                Span::from(input.clone()).light_yellow(),
-            ],
+                selected,
+                recursive,
+                ..
+            } => {
+                let mut spans = vec![
+                    Span::from(match recursive {
+                        true => "Filter (subdirectories): ",
+                        false => "Filter: ",
+                    })
+                    .bold(),
+                    Span::from(input.clone()).light_yellow(),
+                ];
+                if *selected {
+                    spans.push(Span::from("_").light_yellow().slow_blink());
+                }
+                spans
+            }
         }));
 
-        let items = self
-            .items()?
-            .map(|(f, c)| song_table::cache_row(f, c))
-            .collect::<Vec<_>>();
-
+        let items = self.items(&cache)?;
         let len = items.len();
 
-        let table = Table::new(items)
+        let (offset, mut table_state) = self
+            .selected
+            .last()
+            .copied()
+            .unwrap_or_default()
+            .window(len, area.height);
+
+        // only the visible rows are built, directories can have tens of thousands of entries
+        let player = self.player.read().expect("Failed to lock player");
+        let markers = song_table::QueueMarkers::new(&player);
+        let rows = items
+            .window(offset, area.height as usize)
+            .map(|(f, c)| {
+                let path = self.path.join(f);
+                let row = match c {
+                    CacheEntry::File { .. } => song_table::cache_row(f, c, markers.marker(&path)),
+                    CacheEntry::Directory { .. } => {
+                        let summary = self.summary(&cache, &path, c);
+                        song_table::directory_row(f, summary.songs, summary.duration)
+                    }
+                };
+                song_table::availability(row, cache.is_available(&path))
+            })
+            .collect::<Vec<_>>();
+
+        let table = Table::new(rows)
             .header(song_table::HEADER().light_blue().bold())
             .fg(Color::Rgb(210, 210, 210))
             .highlight_style(Style::default().light_yellow().bold())
             .highlight_symbol("⏯️  ")
             .column_spacing(4)
-            .widths(&[
-                Constraint::Percentage(5),
-                Constraint::Percentage(15),
-                Constraint::Percentage(40),
-                Constraint::Percentage(30),
-            ]);
-
-        let selected = *self.selected.last().expect("Failed to get selected index");
-        let mut table_state = TableState::default()
-            .with_selected(Some((selected).min(len - 1).max(0)))
-            .with_offset({
-                if len <= area.height as usize {
-                    0
-                } else if selected > area.height as usize / 2 {
-                    if selected < len + 1 - area.height as usize / 2 {
-                        selected - area.height as usize / 2
-                    } else {
-                        len + 1 - area.height as usize
-                    }
-                } else {
-                    0
-                }
-            });
+            .widths(&song_table::WIDTHS);
 
         f.render_stateful_widget(table, inner_area, &mut table_state);
 
@@ -290,13 +715,33 @@ impl Tui for Files {
             f.render_widget(search_bar, search_bar_area);
         }
 
+        if let Some(action) = &self.confirm {
+            self.draw_confirm_popup(action, area, f);
+        }
+
+        if let Some(song) = &self.info {
+            self.draw_info_popup(song, area, f);
+        }
+
         Ok(())
     }
 
+    fn captures_keys(&self) -> bool {
+        self.confirm.is_some()
+            || self.info.is_some()
+            || matches!(self.filter, FilterState::Active { selected: true, .. })
+    }
+
     fn input(&mut self, event: &Event) -> anyhow::Result<()> {
         trace!("input: {:?}", event);
 
-        if let Event::Key(KeyEvent {
+        if let (Some(_), Event::Key(_)) = (&self.info, event) {
+            self.info = None;
+        } else if let (Some(_), Event::Key(KeyEvent { code, .. })) = (&self.confirm, event) {
+            if let (Some(action), KeyCode::Char('y')) = (self.confirm.take(), code) {
+                self.run_action(action);
+            }
+        } else if let Event::Key(KeyEvent {
             code, modifiers, ..
         }) = event
         {
@@ -304,10 +749,21 @@ impl Tui for Files {
                 FilterState::Disabled => {
                     self.input_files(event)?;
                 }
-                FilterState::Active { input, selected } => match code {
+                FilterState::Active {
+                    input,
+                    selected,
+                    matches,
+                    recursive,
+                } => match code {
                     KeyCode::Esc => {
                         self.filter = FilterState::Disabled;
                     }
+                    KeyCode::Char('r') if modifiers.contains(KeyModifiers::CONTROL) => {
+                        *recursive = !*recursive;
+                        if let Some(list) = self.selected.last_mut() {
+                            *list = ListController::new(self.config.wrap_selection);
+                        }
+                    }
                     KeyCode::Enter if *selected => {
                         *selected = false;
                     }
@@ -318,9 +774,11 @@ impl Tui for Files {
                     }
                     KeyCode::Char(c) if *selected => {
                         input.push(*c);
+                        *matches = Self::filter_matches(&self.cache.load(), input);
                     }
                     KeyCode::Backspace if *selected => {
                         input.pop();
+                        *matches = Self::filter_matches(&self.cache.load(), input);
                     }
                     _ if !*selected => {
                         self.input_files(event)?;
@@ -330,10 +788,10 @@ impl Tui for Files {
             }
         }
 
-        let l = self.items()?.count();
+        let l = self.items(&self.cache.load())?.len();
 
-        if let Some(i) = self.selected.last_mut().filter(|i| **i >= l) {
-            *i = l - 1;
+        if let Some(list) = self.selected.last_mut() {
+            list.clamp(l);
         }
 
         Ok(())
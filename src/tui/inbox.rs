@@ -0,0 +1,125 @@
+use std::sync::mpsc;
+
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    prelude::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Paragraph, Table},
+    Frame,
+};
+
+use crate::{
+    cache::CacheEntry, inbox::Inbox as InboxSongs, notification::Notification,
+    player::command::Command,
+};
+
+use super::{list::ListController, song_table, Tui};
+
+/// triage of the songs in the inbox directory
+pub struct Inbox {
+    inbox: InboxSongs,
+    cmd: mpsc::Sender<Command>,
+    list: ListController,
+}
+
+impl Inbox {
+    /// single steps past either end of the list wrap around if `wrap`
+    pub fn new(inbox: InboxSongs, cmd: mpsc::Sender<Command>, wrap: bool) -> Self {
+        Self {
+            inbox,
+            cmd,
+            list: ListController::new(wrap),
+        }
+    }
+}
+
+impl Tui for Inbox {
+    fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+
+        let songs = self.inbox.songs();
+        let (offset, mut state) = self.list.window(songs.len(), layout[0].height);
+        let table = Table::new(
+            songs
+                .iter()
+                .skip(offset)
+                .take(layout[0].height as usize)
+                .map(|(p, s)| {
+                    let filename = p.file_name().and_then(|f| f.to_str()).unwrap_or_default();
+                    song_table::cache_row(filename, &CacheEntry::File { song: s.clone() }, "")
+                })
+                .collect::<Vec<_>>(),
+        )
+        .header(
+            song_table::HEADER()
+                .fg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        )
+        .fg(Color::Rgb(210, 210, 210))
+        .highlight_style(
+            Style::default()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("⏯️  ")
+        .column_spacing(4)
+        .widths(&song_table::WIDTHS);
+
+        let help = Paragraph::new(Line::from(vec![
+            Span::from("Enter").bold(),
+            Span::from(" enqueue  "),
+            Span::from("m").bold(),
+            Span::from(" move into library  "),
+            Span::from("D").bold(),
+            Span::from(" delete"),
+        ]))
+        .fg(Color::DarkGray);
+
+        f.render_stateful_widget(table, layout[0], &mut state);
+        f.render_widget(help, layout[1]);
+
+        Ok(())
+    }
+
+    fn input(&mut self, event: &Event) -> anyhow::Result<()> {
+        let songs = self.inbox.songs();
+
+        if let Event::Key(KeyEvent { code, .. }) = event {
+            let selected = songs.get(self.list.selected()).map(|(p, _)| p);
+
+            match (code, selected) {
+                (code, _) if self.list.input(code, songs.len()) => {}
+                (KeyCode::Enter, Some(path)) => {
+                    self.cmd.send(Command::Enqueue(path.as_path().into()))?;
+                }
+                (KeyCode::Char('m'), Some(path)) => match self.inbox.move_to_library(path) {
+                    Ok(destination) => self.inbox.notify(Notification::info(format!(
+                        "Moved to {}",
+                        destination.display()
+                    ))),
+                    Err(e) => self.inbox.notify(Notification::error(format!(
+                        "Failed to move {}: {e:#}",
+                        path.display()
+                    ))),
+                },
+                (KeyCode::Char('D'), Some(path)) => {
+                    if let Err(e) = self.inbox.delete(path) {
+                        self.inbox.notify(Notification::error(format!(
+                            "Failed to delete {}: {e:#}",
+                            path.display()
+                        )));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        self.list.clamp(self.inbox.songs().len());
+
+        Ok(())
+    }
+}
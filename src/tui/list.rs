@@ -0,0 +1,82 @@
+//! selection and scrolling of the song lists of the tabs
+
+use crossterm::event::KeyCode;
+use ratatui::widgets::TableState;
+
+/// rows `PageUp` and `PageDown` move the selection by
+const PAGE: isize = 25;
+
+/// the selected row of a list, moved by the navigation keys and kept centered when scrolling
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ListController {
+    selected: usize,
+    /// single steps past either end wrap around to the other
+    wrap: bool,
+}
+
+impl ListController {
+    pub fn new(wrap: bool) -> Self {
+        Self { selected: 0, wrap }
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    /// move the selection for `Up`, `Down`, `PageUp`, `PageDown`, `Home` and `End` in a list of `len` rows,
+    /// returns whether the key was one of them
+    pub fn input(&mut self, code: &KeyCode, len: usize) -> bool {
+        self.selected = match code {
+            KeyCode::Up => step(self.selected, len, -1, self.wrap),
+            KeyCode::Down => step(self.selected, len, 1, self.wrap),
+            KeyCode::PageUp => step(self.selected, len, -PAGE, false),
+            KeyCode::PageDown => step(self.selected, len, PAGE, false),
+            KeyCode::Home => 0,
+            KeyCode::End => len.saturating_sub(1),
+            _ => return false,
+        };
+
+        true
+    }
+
+    /// keep the selection inside a list of `len` rows, e.g. after the list changed
+    pub fn clamp(&mut self, len: usize) {
+        self.selected = self.selected.min(len.saturating_sub(1));
+    }
+
+    /// the first row shown of a table of `len` rows below a header in `height` lines,
+    /// and the state of a table of the rows shown from there
+    pub fn window(&self, len: usize, height: u16) -> (usize, TableState) {
+        let selected = self.selected.min(len.saturating_sub(1));
+        let offset = offset(selected, len, height);
+
+        (
+            offset,
+            TableState::default().with_selected(Some(selected - offset)),
+        )
+    }
+}
+
+/// move `selected` by `delta` rows in a list of `len` rows, stopping at the first and last row,
+/// single steps past either end wrap around to the other if `wrap`
+fn step(selected: usize, len: usize, delta: isize, wrap: bool) -> usize {
+    let last = len.saturating_sub(1);
+    match delta {
+        -1 if wrap && selected == 0 => last,
+        1 if wrap && selected >= last => 0,
+        _ => selected.saturating_add_signed(delta).min(last),
+    }
+}
+
+/// the first row shown of a table of `len` rows below a header in `height` lines,
+/// scrolled so that `selected` stays centered
+fn offset(selected: usize, len: usize, height: u16) -> usize {
+    let height = height as usize;
+    if len <= height || selected <= height / 2 {
+        0
+    } else if selected < len + 1 - height / 2 {
+        selected - height / 2
+    } else {
+        len + 1 - height
+    }
+}
@@ -1,13 +1,23 @@
+mod classical;
+mod clipboard;
 mod fancy;
 mod files;
+mod inbox;
+mod list;
+mod notifications;
+mod party;
+mod playlists;
 mod queue;
+mod reveal;
 mod search;
+mod setup;
 mod song_table;
+mod stats;
 mod status;
 mod tabs;
 
 use std::{
-    sync::{atomic::AtomicBool, mpsc, Arc, RwLock},
+    sync::{atomic::AtomicBool, mpsc, Arc, Mutex, RwLock},
     time::Duration,
 };
 
@@ -23,12 +33,25 @@ use ratatui::{
 };
 
 use crate::{
-    cache::Cache,
+    cache::SharedCache,
     config::Config,
-    player::{command::Command, facade::PlayerFacade},
+    inbox::Inbox,
+    notification::Notification,
+    party::Party,
+    player::{command::Command, facade::PlayerFacade, supervisor::Supervisor},
+    playlist::Playlists,
+    scan::Scanner,
+    scheduler::Scheduler,
+    scripting::ScriptHandle,
+    stats::Stats,
 };
 
-use self::{fancy::Fancy, files::Files, queue::Queue, search::Search, status::Status, tabs::Tabs};
+use self::{
+    fancy::Fancy, files::Files, notifications::Notifications, queue::Queue, search::Search,
+    stats::Statistics, status::Status, tabs::Tabs,
+};
+
+pub use self::setup::setup;
 
 pub const UNKNOWN_STRING: &str = "<unknown>";
 
@@ -44,16 +67,47 @@ pub fn format_duration(duration: Duration) -> String {
     }
 }
 
+/// a centered area of at most `width` x `height` inside `area`
+pub fn popup_area(area: Rect, width: u16, height: u16) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
 pub trait Tui {
     fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()>;
     fn input(&mut self, event: &Event) -> anyhow::Result<()>;
+
+    /// whether text is being typed or a prompt is open, character keys then go to the tab
+    /// and the transport keys need `Alt`
+    fn captures_keys(&self) -> bool {
+        false
+    }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn tui(
-    _config: Arc<Config>,
-    cache: Arc<Cache>,
+    config: Arc<Config>,
+    cache: SharedCache,
     cmd: mpsc::Sender<Command>,
     player: Arc<RwLock<PlayerFacade>>,
+    notification_tx: mpsc::Sender<Notification>,
+    notifications: mpsc::Receiver<Notification>,
+    mut supervisor: Option<Supervisor>,
+    scripts: ScriptHandle,
+    inbox: Option<Inbox>,
+    playlists: Playlists,
+    party: Option<Party>,
+    scheduler: Scheduler,
+    stats: Arc<Mutex<Stats>>,
+    scanner: Scanner,
+    terminate: Arc<AtomicBool>,
 ) -> anyhow::Result<()> {
     let stdout = std::io::stdout();
     let backend = CrosstermBackend::new(stdout);
@@ -63,49 +117,126 @@ pub fn tui(
     terminal.clear()?;
 
     let running = Arc::new(AtomicBool::new(true));
+    let mut tabs: Vec<(&str, Box<dyn Tui>)> = vec![
+        (
+            " Files 🗃️ ",
+            Box::new(Files::new(
+                cache.clone(),
+                player.clone(),
+                cmd.clone(),
+                config.clone(),
+                notification_tx.clone(),
+            )),
+        ),
+        (
+            "Queue 🕰️ ",
+            Box::new(Queue::new(
+                cache.clone(),
+                player.clone(),
+                cmd.clone(),
+                config.clone(),
+                notification_tx,
+                stats.clone(),
+            )),
+        ),
+        (
+            "Playlists 📜 ",
+            Box::new(playlists::Playlists::new(
+                playlists,
+                cmd.clone(),
+                config.wrap_selection,
+            )),
+        ),
+        (
+            "Search 🔎",
+            Box::new(Search::new(
+                cache.clone(),
+                player.clone(),
+                cmd.clone(),
+                config.clone(),
+            )),
+        ),
+        (
+            "Classical 🎻 ",
+            Box::new(classical::Classical::new(
+                cache.clone(),
+                cmd.clone(),
+                config.wrap_selection,
+            )),
+        ),
+        (
+            "Fancy stuff ✨ ",
+            Box::new(Fancy::new(
+                player.clone(),
+                cmd.clone(),
+                scheduler.clone(),
+                config.clone(),
+            )),
+        ),
+        ("Stats 📊 ", Box::new(Statistics::new(stats))),
+    ];
+    if let Some(inbox) = inbox {
+        tabs.push((
+            "Inbox 📥 ",
+            Box::new(inbox::Inbox::new(inbox, cmd.clone(), config.wrap_selection)),
+        ));
+    }
+    if let Some(party) = party {
+        tabs.push((
+            "Requests 🎉 ",
+            Box::new(party::Requests::new(
+                party,
+                cmd.clone(),
+                config.wrap_selection,
+            )),
+        ));
+    }
     let mut tabs = Tabs::new(
-        vec![
-            (
-                " Files 🗃️ ",
-                Box::new(Files::new(cache.clone(), cmd.clone())),
-            ),
-            (
-                "Queue 🕰️ ",
-                Box::new(Queue::new(cache.clone(), player.clone())),
-            ),
-            (
-                "Search 🔎",
-                Box::new(Search::new(cache.clone(), cmd.clone())),
-            ),
-            ("Fancy stuff ✨ ", Box::new(Fancy::new(player.clone()))),
-        ],
+        tabs,
         running.clone(),
+        scripts,
+        cmd.clone(),
+        player.clone(),
+        scanner.clone(),
     );
 
-    let usage = Status::new(player.clone());
-
-    loop {
-        terminal.draw(|f| {
-            let main_area = Layout::new()
-                .constraints([Constraint::Min(1), Constraint::Length(4)])
-                .direction(Direction::Vertical)
-                .split(f.size());
-
-            tabs.draw(main_area[0], f).expect("Failed to draw tabs");
-            usage.draw(main_area[1], f).expect("Failed to draw usage");
-        })?;
-
-        if event::poll(Duration::from_secs_f32(0.2))? {
-            tabs.input(&event::read()?)?;
+    let usage = Status::new(player.clone(), cache.clone(), scheduler, scanner);
+    let mut notifications = Notifications::new(notifications);
+
+    let mut run = || -> anyhow::Result<()> {
+        while running.load(std::sync::atomic::Ordering::Relaxed)
+            && !terminate.load(std::sync::atomic::Ordering::Relaxed)
+        {
+            if let Some(supervisor) = &mut supervisor {
+                supervisor.check();
+            }
+            notifications.update();
+
+            terminal.draw(|f| {
+                let main_area = Layout::new()
+                    .constraints([Constraint::Min(1), Constraint::Length(4)])
+                    .direction(Direction::Vertical)
+                    .split(f.size());
+
+                tabs.draw(main_area[0], f).expect("Failed to draw tabs");
+                usage.draw(main_area[1], f).expect("Failed to draw usage");
+                notifications
+                    .draw(main_area[0], f)
+                    .expect("Failed to draw notifications");
+            })?;
+
+            if event::poll(Duration::from_secs_f32(0.2))? {
+                tabs.input(&event::read()?)?;
+            }
         }
 
-        if !running.load(std::sync::atomic::Ordering::Relaxed) {
-            break;
-        }
-    }
+        Ok(())
+    };
+    // the terminal is restored even if drawing failed, e.g. after it was closed
+    let result = run();
 
     disable_raw_mode()?;
     terminal.clear()?;
 
-    Ok(())
+    result
 }
@@ -0,0 +1,102 @@
+use std::{
+    collections::VecDeque,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use crossterm::event::Event;
+use ratatui::{
+    prelude::Rect,
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Clear, Paragraph, Wrap},
+    Frame,
+};
+
+use crate::notification::{Level, Notification};
+
+use super::Tui;
+
+const DISPLAY_DURATION: Duration = Duration::from_secs(5);
+const MAX_SHOWN: usize = 5;
+const WIDTH: u16 = 60;
+
+pub struct Notifications {
+    rx: mpsc::Receiver<Notification>,
+    shown: VecDeque<(Notification, Instant)>,
+}
+
+impl Notifications {
+    pub fn new(rx: mpsc::Receiver<Notification>) -> Self {
+        Self {
+            rx,
+            shown: VecDeque::new(),
+        }
+    }
+
+    /// receive new notifications and drop expired ones
+    pub fn update(&mut self) {
+        self.shown
+            .extend(self.rx.try_iter().map(|n| (n, Instant::now())));
+
+        self.shown.retain(|(_, t)| t.elapsed() < DISPLAY_DURATION);
+
+        while self.shown.len() > MAX_SHOWN {
+            self.shown.pop_front();
+        }
+    }
+}
+
+impl Tui for Notifications {
+    fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
+        if self.shown.is_empty() {
+            return Ok(());
+        }
+
+        let width = WIDTH.min(area.width);
+        let inner_width = width.saturating_sub(2).max(1) as usize;
+        let lines = self
+            .shown
+            .iter()
+            .map(|(n, _)| n.message.chars().count().max(1).div_ceil(inner_width) as u16)
+            .sum::<u16>();
+        let height = (lines + 2).min(area.height);
+        let area = Rect::new(
+            area.x + area.width - width,
+            area.y + area.height - height,
+            width,
+            height,
+        );
+
+        let lines = self
+            .shown
+            .iter()
+            .map(|(n, _)| {
+                Line::from(Span::styled(
+                    n.message.clone(),
+                    Style::default().fg(match n.level {
+                        Level::Info => Color::White,
+                        Level::Error => Color::LightRed,
+                    }),
+                ))
+            })
+            .collect::<Vec<_>>();
+
+        let paragraph = Paragraph::new(lines).wrap(Wrap { trim: true }).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_type(BorderType::Rounded)
+                .title(" Notifications ")
+                .title_style(Style::default().light_blue().bold()),
+        );
+
+        f.render_widget(Clear, area);
+        f.render_widget(paragraph, area);
+
+        Ok(())
+    }
+
+    fn input(&mut self, _event: &Event) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
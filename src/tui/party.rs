@@ -0,0 +1,114 @@
+use std::sync::mpsc;
+
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    prelude::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Paragraph, Row, Table},
+    Frame,
+};
+
+use crate::{party::Party, player::command::Command, song::StandardTagKey};
+
+use super::{list::ListController, Tui, UNKNOWN_STRING};
+
+/// the songs requested by guests on the party page, waiting for the host
+pub struct Requests {
+    party: Party,
+    cmd: mpsc::Sender<Command>,
+    list: ListController,
+}
+
+impl Requests {
+    /// single steps past either end of the list wrap around if `wrap`
+    pub fn new(party: Party, cmd: mpsc::Sender<Command>, wrap: bool) -> Self {
+        Self {
+            party,
+            cmd,
+            list: ListController::new(wrap),
+        }
+    }
+}
+
+impl Tui for Requests {
+    fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+
+        let requests = self.party.requests();
+        let (offset, mut state) = self.list.window(requests.len(), layout[0].height);
+        let table = Table::new(
+            requests
+                .iter()
+                .skip(offset)
+                .take(layout[0].height as usize)
+                .map(|r| {
+                    let tag = |key| r.song.tag_string(key).unwrap_or(UNKNOWN_STRING).to_string();
+                    Row::new([
+                        r.guest.clone(),
+                        tag(StandardTagKey::Artist),
+                        tag(StandardTagKey::TrackTitle),
+                        tag(StandardTagKey::Album),
+                    ])
+                })
+                .collect::<Vec<_>>(),
+        )
+        .header(
+            Row::new(["Guest 🙋 ", "Artist 🧑‍🎤 ", "Title 🎶 ", "Album 🖼️ "])
+                .fg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        )
+        .fg(Color::Rgb(210, 210, 210))
+        .highlight_style(
+            Style::default()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("⏯️  ")
+        .column_spacing(4)
+        .widths(&[
+            Constraint::Percentage(15),
+            Constraint::Percentage(20),
+            Constraint::Percentage(35),
+            Constraint::Percentage(30),
+        ]);
+
+        let help = Paragraph::new(Line::from(vec![
+            Span::from("Enter").bold(),
+            Span::from(" approve and enqueue  "),
+            Span::from("d").bold(),
+            Span::from(" reject"),
+        ]))
+        .fg(Color::DarkGray);
+
+        f.render_stateful_widget(table, layout[0], &mut state);
+        f.render_widget(help, layout[1]);
+
+        Ok(())
+    }
+
+    fn input(&mut self, event: &Event) -> anyhow::Result<()> {
+        let requests = self.party.requests();
+
+        if let Event::Key(KeyEvent { code, .. }) = event {
+            let selected = requests.get(self.list.selected()).map(|r| &r.path);
+
+            match (code, selected) {
+                (code, _) if self.list.input(code, requests.len()) => {}
+                (KeyCode::Enter, Some(path)) => {
+                    self.cmd.send(Command::Enqueue(path.as_path().into()))?;
+                    self.party.dismiss(path);
+                }
+                (KeyCode::Char('d'), Some(path)) => self.party.dismiss(path),
+                _ => {}
+            }
+        }
+
+        self.list.clamp(self.party.requests().len());
+
+        Ok(())
+    }
+}
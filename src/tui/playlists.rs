@@ -0,0 +1,125 @@
+use std::sync::mpsc;
+
+use crossterm::event::{Event, KeyCode, KeyEvent};
+use ratatui::{
+    prelude::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Paragraph, Row, Table},
+    Frame,
+};
+
+use crate::{player::command::Command, playlist::Playlists as PlaylistFiles};
+
+use super::{list::ListController, Tui};
+
+/// the saved queues and the playlists of the playlist directories
+pub struct Playlists {
+    playlists: PlaylistFiles,
+    cmd: mpsc::Sender<Command>,
+    list: ListController,
+}
+
+impl Playlists {
+    /// single steps past either end of the list wrap around if `wrap`
+    pub fn new(playlists: PlaylistFiles, cmd: mpsc::Sender<Command>, wrap: bool) -> Self {
+        Self {
+            playlists,
+            cmd,
+            list: ListController::new(wrap),
+        }
+    }
+}
+
+impl Tui for Playlists {
+    fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(area);
+
+        let playlists = self.playlists.playlists();
+        let (offset, mut state) = self.list.window(playlists.len(), layout[0].height);
+        let table = Table::new(
+            playlists
+                .iter()
+                .skip(offset)
+                .take(layout[0].height as usize)
+                .map(|p| {
+                    Row::new(vec![
+                        p.name(),
+                        match p.missing {
+                            0 => p.songs.len().to_string(),
+                            missing => format!("{} ({missing} missing)", p.songs.len()),
+                        },
+                        p.path
+                            .parent()
+                            .map(|d| d.display().to_string())
+                            .unwrap_or_default(),
+                    ])
+                })
+                .collect::<Vec<_>>(),
+        )
+        .header(
+            Row::new(["Name", "Songs", "Directory"])
+                .fg(Color::LightBlue)
+                .add_modifier(Modifier::BOLD),
+        )
+        .fg(Color::Rgb(210, 210, 210))
+        .highlight_style(
+            Style::default()
+                .fg(Color::LightYellow)
+                .add_modifier(Modifier::BOLD),
+        )
+        .highlight_symbol("⏯️  ")
+        .column_spacing(4)
+        .widths(&[
+            Constraint::Percentage(40),
+            Constraint::Percentage(20),
+            Constraint::Percentage(40),
+        ]);
+
+        let help = Paragraph::new(Line::from(vec![
+            Span::from("Enter").bold(),
+            Span::from(" enqueue  "),
+            Span::from("p").bold(),
+            Span::from(" play instead of the queue"),
+        ]))
+        .fg(Color::DarkGray);
+
+        f.render_stateful_widget(table, layout[0], &mut state);
+        f.render_widget(help, layout[1]);
+
+        Ok(())
+    }
+
+    fn input(&mut self, event: &Event) -> anyhow::Result<()> {
+        let playlists = self.playlists.playlists();
+
+        if let Event::Key(KeyEvent { code, .. }) = event {
+            let songs = playlists.get(self.list.selected()).map(|p| {
+                p.songs
+                    .iter()
+                    .map(|s| s.as_path().into())
+                    .collect::<Vec<_>>()
+            });
+
+            match (code, songs) {
+                (code, _) if self.list.input(code, playlists.len()) => {}
+                (KeyCode::Enter, Some(songs)) => {
+                    self.cmd.send(Command::Batch(
+                        songs.into_iter().map(Command::Enqueue).collect(),
+                    ))?;
+                }
+                (KeyCode::Char('p'), Some(songs)) => {
+                    self.cmd.send(Command::Replace(songs))?;
+                }
+                _ => {}
+            }
+        }
+
+        self.list.clamp(self.playlists.playlists().len());
+
+        Ok(())
+    }
+}
@@ -1,25 +1,286 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex, RwLock},
+};
 
-use crossterm::event::Event;
-use log::trace;
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
+use log::{trace, warn};
 use ratatui::{
-    prelude::Constraint,
-    style::{Color, Modifier, Stylize},
-    widgets::{Table, TableState},
+    prelude::{Constraint, Direction, Layout},
+    style::{Color, Modifier, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Paragraph, Row, Table},
 };
 
-use crate::{cache::Cache, player::facade::PlayerFacade, tui::song_table};
+use crate::{
+    cache::{Cache, CacheEntry, SharedCache},
+    config::Config,
+    notification::Notification,
+    player::{command::Command, export, facade::PlayerFacade},
+    playlist,
+    stats::Stats,
+    tui::{clipboard, list::ListController, song_table},
+};
 
 use super::Tui;
 
+/// songs shown in the recently played list
+const RECENT_SONGS: usize = 50;
+
+#[derive(Debug, PartialEq, Eq)]
+enum FilterState {
+    Disabled,
+    Active {
+        input: String,
+        selected: bool,
+        matches: HashSet<PathBuf>,
+    },
+}
+
+/// text entered in the bottom line
+enum Prompt {
+    /// name to save the queue as a playlist
+    Playlist(String),
+    /// file to export the queue to
+    Export(String),
+}
+
 pub struct Queue {
-    cache: Arc<Cache>,
+    cache: SharedCache,
     player: Arc<RwLock<PlayerFacade>>,
+    cmd: mpsc::Sender<Command>,
+    /// index into the shown, possibly filtered, songs
+    list: ListController,
+    filter: FilterState,
+    config: Arc<Config>,
+    notifications: mpsc::Sender<Notification>,
+    prompt: Option<Prompt>,
+    stats: Arc<Mutex<Stats>>,
+    /// show the recently played songs instead of the queue
+    recent: bool,
 }
 
 impl Queue {
-    pub fn new(cache: Arc<Cache>, player: Arc<RwLock<PlayerFacade>>) -> Self {
-        Queue { cache, player }
+    pub fn new(
+        cache: SharedCache,
+        player: Arc<RwLock<PlayerFacade>>,
+        cmd: mpsc::Sender<Command>,
+        config: Arc<Config>,
+        notifications: mpsc::Sender<Notification>,
+        stats: Arc<Mutex<Stats>>,
+    ) -> Self {
+        Queue {
+            cache,
+            player,
+            cmd,
+            list: ListController::new(config.wrap_selection),
+            filter: FilterState::Disabled,
+            config,
+            notifications,
+            prompt: None,
+            stats,
+            recent: false,
+        }
+    }
+
+    /// the last played songs from the history, most recent first, each song once
+    fn recent_songs(&self) -> Vec<PathBuf> {
+        let stats = self.stats.lock().unwrap();
+        let mut seen = HashSet::new();
+        stats
+            .history()
+            .iter()
+            .rev()
+            .filter(|l| seen.insert(&l.path))
+            .take(RECENT_SONGS)
+            .map(|l| l.path.clone())
+            .collect()
+    }
+
+    /// play a recently played song again right away, jumping to it if it is still queued
+    fn play_again(&self, path: &Path) -> anyhow::Result<()> {
+        let queue = self.player.read().unwrap().queue.clone();
+        let command = match queue.iter().position(|p| p.as_ref() == path) {
+            Some(index) => Command::Jump(index),
+            None => Command::Batch(vec![
+                Command::Enqueue(path.into()),
+                Command::Jump(queue.len()),
+            ]),
+        };
+        trace!("playing {} again", path.display());
+        self.cmd.send(command)?;
+
+        Ok(())
+    }
+
+    fn input_recent(&mut self, code: &KeyCode) -> anyhow::Result<()> {
+        let songs = self.recent_songs();
+
+        match code {
+            code if self.list.input(code, songs.len()) => {}
+            KeyCode::Enter => {
+                if let Some(path) = songs.get(self.list.selected()) {
+                    self.play_again(path)?;
+                }
+            }
+            KeyCode::Char('h') | KeyCode::Esc => {
+                self.recent = false;
+                self.list = ListController::new(self.config.wrap_selection);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// the current song followed by the queue
+    fn songs(&self) -> Vec<Box<std::path::Path>> {
+        let player = self.player.read().unwrap();
+        player
+            .current_song()
+            .map(|s| s.path.clone())
+            .into_iter()
+            .chain(player.queue.iter().cloned())
+            .collect()
+    }
+
+    /// save the current song and the queue as a playlist
+    fn save_playlist(&self, name: &str) {
+        let songs = self.songs();
+
+        let notification = match playlist::save(&self.config.playlists_directory, name, &songs) {
+            Ok(path) => {
+                Notification::info(format!("Saved {} songs to {}", songs.len(), path.display()))
+            }
+            Err(e) => Notification::error(format!("{e:#}")),
+        };
+        self.notifications
+            .send(notification)
+            .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+    }
+
+    /// render the current song and the queue into `output` in the background,
+    /// with the active dsp preset and crossfeed
+    fn export(&self, output: &str) {
+        let songs = self.songs();
+        let (preset, crossfeed) = {
+            let player = self.player.read().unwrap();
+            (player.preset.clone(), player.crossfeed)
+        };
+        let output = PathBuf::from(output.trim());
+        let cache = self.cache.load();
+        let config = self.config.clone();
+        let notifications = self.notifications.clone();
+
+        std::thread::Builder::new()
+            .name("export thread".to_string())
+            .spawn(move || {
+                let notification = match export::export(
+                    &cache,
+                    &config,
+                    &songs,
+                    &output,
+                    preset.as_deref(),
+                    crossfeed,
+                ) {
+                    Ok(()) => Notification::info(format!(
+                        "Exported {} songs to {}",
+                        songs.len(),
+                        output.display()
+                    )),
+                    Err(e) => Notification::error(format!("{e:#}")),
+                };
+                notifications
+                    .send(notification)
+                    .unwrap_or_else(|e| warn!("Failed to send notification: {e:?}"));
+            })
+            .map_err(|e| warn!("Failed to spawn export thread: {e:?}"))
+            .ok();
+    }
+
+    /// queue indices of the songs matching the filter
+    fn items(&self) -> Vec<usize> {
+        let player = self.player.read().unwrap();
+
+        player
+            .queue
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| match &self.filter {
+                FilterState::Disabled => true,
+                FilterState::Active { input, matches, .. } => {
+                    input.is_empty() || matches.contains(p.as_ref())
+                }
+            })
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    fn input_queue(&mut self, code: &KeyCode, modifiers: &KeyModifiers) -> anyhow::Result<()> {
+        let items = self.items();
+
+        match code {
+            KeyCode::Char('f') if modifiers.contains(KeyModifiers::CONTROL) => {
+                self.filter = FilterState::Active {
+                    input: String::new(),
+                    selected: true,
+                    matches: HashSet::new(),
+                };
+            }
+            code if self.list.input(code, items.len()) => {}
+            KeyCode::Enter => {
+                if let Some(&index) = items.get(self.list.selected()) {
+                    trace!("jumping to queue index {index}");
+                    self.cmd.send(Command::Jump(index))?;
+                }
+            }
+            KeyCode::Char(c @ ('y' | 'Y')) => {
+                if let Some(&index) = items.get(self.list.selected()) {
+                    let path = self.player.read().unwrap().queue[index].clone();
+                    match (c, self.cache.load().get(&path).ok().flatten()) {
+                        ('Y', Some(CacheEntry::File { song, .. })) => {
+                            clipboard::copy(&clipboard::describe(song))
+                        }
+                        _ => clipboard::copy(&path.to_string_lossy()),
+                    }
+                }
+            }
+            KeyCode::Char('h') => {
+                self.recent = true;
+                self.list = ListController::new(self.config.wrap_selection);
+            }
+            KeyCode::Char('S') => self.prompt = Some(Prompt::Playlist(String::new())),
+            KeyCode::Char('E') => self.prompt = Some(Prompt::Export(String::new())),
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(&index) = items.get(self.list.selected()) {
+                    trace!("removing queue index {index}");
+                    self.cmd.send(Command::Dequeue(index))?;
+                }
+            }
+            KeyCode::Char('x') => {
+                let cache = self.cache.load();
+                // from the back, so that the indices of the remaining songs stay valid
+                let missing = self
+                    .player
+                    .read()
+                    .unwrap()
+                    .queue
+                    .iter()
+                    .enumerate()
+                    .rev()
+                    .filter(|(_, p)| missing(&cache, p))
+                    .map(|(i, _)| Command::Dequeue(i))
+                    .collect::<Vec<_>>();
+                trace!("removing {} missing songs", missing.len());
+                if !missing.is_empty() {
+                    self.cmd.send(Command::Batch(missing))?;
+                }
+            }
+            _ => {}
+        }
+
+        Ok(())
     }
 }
 
@@ -27,42 +288,205 @@ impl Tui for Queue {
     fn draw(&self, area: ratatui::prelude::Rect, f: &mut ratatui::Frame) -> anyhow::Result<()> {
         trace!("drawing queue");
 
-        trace!("lock player");
-        let player = self.player.read().unwrap();
+        let (inner_area, filter_area) = match (&self.filter, &self.prompt) {
+            (FilterState::Disabled, None) if !self.recent => (area, None),
+            _ => {
+                let layout = Layout::new()
+                    .direction(Direction::Vertical)
+                    .constraints([Constraint::Min(1), Constraint::Length(1)])
+                    .split(area);
+                (layout[0], Some(layout[1]))
+            }
+        };
 
-        let items = player
-            .queue
+        let cache = self.cache.load();
+        let songs = match self.recent {
+            true => self.recent_songs(),
+            false => {
+                let indices = self.items();
+                trace!("lock player");
+                let player = self.player.read().unwrap();
+                indices
+                    .iter()
+                    .filter_map(|&i| player.queue.get(i))
+                    .map(|p| p.to_path_buf())
+                    .collect()
+            }
+        };
+
+        let (offset, mut state) = self.list.window(songs.len(), inner_area.height);
+        let items = songs
             .iter()
-            .map(|p| self.cache.get(p).unwrap().unwrap().as_file().unwrap())
-            .map(song_table::song_row)
+            .skip(offset)
+            .take(inner_area.height as usize)
+            .map(|p| row(&cache, p))
             .collect::<Vec<_>>();
 
-        let table = Table::new(items.clone())
+        let table = Table::new(items)
             .header(
                 song_table::HEADER()
                     .fg(Color::LightBlue)
                     .add_modifier(Modifier::BOLD),
             )
             .fg(Color::Rgb(210, 210, 210))
-            .highlight_symbol("   ")
+            .highlight_style(Style::default().light_yellow().bold())
+            .highlight_symbol("⏯️  ")
             .column_spacing(4)
-            .widths(&[
-                Constraint::Percentage(5),
-                Constraint::Percentage(15),
-                Constraint::Percentage(40),
-                Constraint::Percentage(30),
-            ]);
-
-        f.render_stateful_widget(
-            table,
-            area,
-            &mut TableState::default().with_selected(Some(0)),
-        );
+            .widths(&song_table::WIDTHS);
+
+        f.render_stateful_widget(table, inner_area, &mut state);
+
+        if let (Some(filter_area), true) = (filter_area, self.recent) {
+            let spans = vec![
+                Span::from("Recently played").bold(),
+                Span::from(", Enter plays a song again, h returns to the queue").dark_gray(),
+            ];
+            f.render_widget(Paragraph::new(Line::from(spans)), filter_area);
+        } else if let (Some(filter_area), Some(prompt)) = (filter_area, &self.prompt) {
+            let (label, text) = match prompt {
+                Prompt::Playlist(name) => ("Save queue as: ", name),
+                Prompt::Export(path) => ("Export queue to (.wav or .flac): ", path),
+            };
+            let spans = vec![
+                Span::from(label).bold(),
+                Span::from(text.clone()).light_yellow(),
+                Span::from("_").light_yellow().slow_blink(),
+            ];
+            f.render_widget(Paragraph::new(Line::from(spans)), filter_area);
+        } else if let (
+            Some(filter_area),
+            FilterState::Active {
+                input, selected, ..
+            },
+        ) = (filter_area, &self.filter)
+        {
+            let mut spans = vec![
+                Span::from("Filter: ").bold(),
+                Span::from(input.clone()).light_yellow(),
+            ];
+            if *selected {
+                spans.push(Span::from("_").light_yellow().slow_blink());
+            }
+            f.render_widget(Paragraph::new(Line::from(spans)), filter_area);
+        }
 
         Ok(())
     }
 
-    fn input(&mut self, _event: &Event) -> anyhow::Result<()> {
+    fn captures_keys(&self) -> bool {
+        self.prompt.is_some() || matches!(self.filter, FilterState::Active { selected: true, .. })
+    }
+
+    fn input(&mut self, event: &Event) -> anyhow::Result<()> {
+        if let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = event
+        {
+            if let Some(prompt) = &mut self.prompt {
+                let (Prompt::Playlist(text) | Prompt::Export(text)) = prompt;
+                match code {
+                    KeyCode::Esc => self.prompt = None,
+                    KeyCode::Enter => match self.prompt.take() {
+                        Some(Prompt::Playlist(name)) => self.save_playlist(&name),
+                        Some(Prompt::Export(path)) => self.export(&path),
+                        None => {}
+                    },
+                    KeyCode::Char(c) => text.push(*c),
+                    KeyCode::Backspace => {
+                        text.pop();
+                    }
+                    _ => {}
+                }
+
+                return Ok(());
+            }
+
+            if self.recent {
+                self.input_recent(code)?;
+                self.list.clamp(self.recent_songs().len());
+                return Ok(());
+            }
+
+            match &mut self.filter {
+                FilterState::Disabled => self.input_queue(code, modifiers)?,
+                FilterState::Active {
+                    input,
+                    selected,
+                    matches,
+                } => match code {
+                    KeyCode::Esc => {
+                        self.filter = FilterState::Disabled;
+                    }
+                    KeyCode::Enter if *selected => {
+                        *selected = false;
+                    }
+                    KeyCode::Char('f')
+                        if modifiers.contains(KeyModifiers::CONTROL) && !*selected =>
+                    {
+                        *selected = true;
+                    }
+                    KeyCode::Char(c) if *selected => {
+                        input.push(*c);
+                        *matches = filter_matches(&self.cache.load(), input);
+                        self.list = ListController::new(self.config.wrap_selection);
+                    }
+                    KeyCode::Backspace if *selected => {
+                        input.pop();
+                        *matches = filter_matches(&self.cache.load(), input);
+                        self.list = ListController::new(self.config.wrap_selection);
+                    }
+                    _ if !*selected => self.input_queue(code, modifiers)?,
+                    _ => {}
+                },
+            }
+        }
+
+        self.list.clamp(self.items().len());
+
         Ok(())
     }
 }
+
+/// the row of a queued or recently played song
+fn row<'a>(cache: &Cache, path: &Path) -> Row<'a> {
+    match cache.get(path).ok().flatten() {
+        Some(CacheEntry::File { song }) => {
+            song_table::availability(song_table::song_row(song), cache.is_available(path))
+        }
+        // songs played outside of the library are not in the cache,
+        // neither are songs removed from disk, which show their whole path
+        _ => {
+            let available = path.exists();
+            let name = match available {
+                true => path.file_name().unwrap_or_default().to_string_lossy(),
+                false => path.to_string_lossy(),
+            };
+            song_table::availability(
+                Row::new([
+                    String::new(),
+                    String::new(),
+                    name.to_string(),
+                    String::new(),
+                ]),
+                available,
+            )
+        }
+    }
+}
+
+/// whether the file of a queued song is gone, songs of the library are checked when scanning
+fn missing(cache: &Cache, path: &std::path::Path) -> bool {
+    match cache.get(path).ok().flatten() {
+        Some(CacheEntry::File { .. }) => !cache.is_available(path),
+        _ => !path.exists(),
+    }
+}
+
+fn filter_matches(cache: &Cache, input: &str) -> HashSet<PathBuf> {
+    cache
+        .index()
+        .lookup(input)
+        .into_iter()
+        .map(|p| p.to_path_buf())
+        .collect()
+}
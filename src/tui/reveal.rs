@@ -0,0 +1,34 @@
+use std::path::Path;
+
+use log::{info, warn};
+
+/// the command opening a directory in the system file manager
+#[cfg(target_os = "macos")]
+const OPEN: &str = "open";
+#[cfg(not(target_os = "macos"))]
+const OPEN: &str = "xdg-open";
+
+/// show `path` in the system file manager, the directory containing it for files
+pub fn reveal(path: &Path) {
+    let directory = if path.is_dir() {
+        path
+    } else {
+        path.parent().unwrap_or(path)
+    };
+
+    let child = std::process::Command::new(OPEN)
+        .arg(directory)
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn();
+
+    match child {
+        // reap the child in the background, some file managers keep running in the foreground
+        Ok(mut child) => {
+            info!("Opened {} in the file manager", directory.display());
+            std::thread::spawn(move || child.wait());
+        }
+        Err(e) => warn!("Failed to run {OPEN} on {}: {e:?}", directory.display()),
+    }
+}
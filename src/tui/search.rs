@@ -1,88 +1,227 @@
 use std::{
-    path::PathBuf,
-    sync::{mpsc, Arc},
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, RwLock},
 };
 
-use crossterm::event::{Event, KeyCode, KeyEvent};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use itertools::Itertools;
 use ordered_float::OrderedFloat;
 use ratatui::{
-    prelude::{Constraint, Direction, Layout, Rect},
+    prelude::{Direction, Layout, Rect},
     style::{Color, Modifier, Style, Stylize},
     text::{Line, Span},
-    widgets::{Paragraph, Table, TableState},
+    widgets::{Paragraph, Table},
     Frame,
 };
+use regex::RegexBuilder;
 use strsim::jaro_winkler;
 
 use crate::{
-    cache::{Cache, CacheEntry},
-    player::command::Command,
+    cache::{CacheEntry, SharedCache},
+    config::{Config, SearchField},
+    player::{command::Command, facade::PlayerFacade},
+    query::Query,
     song::{Song, StandardTagKey},
 };
 
-use super::{song_table, Tui, UNKNOWN_STRING};
+use super::{clipboard, list::ListController, song_table, Tui};
+
+/// how the keyword is matched against title, artist, album and file name
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchMode {
+    /// jaro-winkler similarity, best matches first
+    Fuzzy,
+    /// case insensitive substring
+    Substring,
+    /// case insensitive regular expression
+    Regex,
+}
+
+impl SearchMode {
+    fn next(self) -> Self {
+        match self {
+            SearchMode::Fuzzy => SearchMode::Substring,
+            SearchMode::Substring => SearchMode::Regex,
+            SearchMode::Regex => SearchMode::Fuzzy,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            SearchMode::Fuzzy => "fuzzy",
+            SearchMode::Substring => "substring",
+            SearchMode::Regex => "regex",
+        }
+    }
+}
+
+/// fields of a song the exact modes match against
+const MATCHED_TAGS: [StandardTagKey; 3] = [
+    StandardTagKey::TrackTitle,
+    StandardTagKey::Artist,
+    StandardTagKey::Album,
+];
 
 pub struct Search {
     keyword: String,
-    cache: Arc<Cache>,
-    selected: usize,
+    mode: SearchMode,
+    /// the keyword is not a valid regex
+    error: Option<String>,
+    cache: SharedCache,
+    player: Arc<RwLock<PlayerFacade>>,
+    list: ListController,
     cmd: mpsc::Sender<Command>,
-    items: Vec<(Song, PathBuf)>,
+    config: Arc<Config>,
+    /// sort the matches by tempo instead of similarity or path
+    by_tempo: bool,
+    /// matching songs with their similarity to the keyword in fuzzy mode
+    items: Vec<(Song, PathBuf, Option<f64>)>,
 }
 
 impl Search {
-    pub fn new(cache: Arc<Cache>, cmd: mpsc::Sender<Command>) -> Self {
+    pub fn new(
+        cache: SharedCache,
+        player: Arc<RwLock<PlayerFacade>>,
+        cmd: mpsc::Sender<Command>,
+        config: Arc<Config>,
+    ) -> Self {
         Self {
             keyword: String::new(),
+            mode: SearchMode::Fuzzy,
+            error: None,
             cache,
-            selected: 0,
+            player,
+            list: ListController::new(config.wrap_selection),
             cmd,
+            config,
+            by_tempo: false,
             items: vec![],
         }
     }
 
     fn update_items(&mut self) {
+        self.error = None;
+        if self.keyword.is_empty() {
+            self.items.clear();
+            return;
+        }
+
+        let query = match Query::parse(&self.keyword) {
+            Ok(query) => query,
+            Err(e) => {
+                self.error = Some(e.to_string());
+                self.items.clear();
+                return;
+            }
+        };
+
+        match self.mode {
+            // only predicates, e.g. "duration > 10:00"
+            _ if query.text.is_empty() => self.update_exact(&query, |_| true),
+            SearchMode::Fuzzy => self.update_fuzzy(&query),
+            SearchMode::Substring => {
+                let keyword = query.text.to_lowercase();
+                self.update_exact(&query, |s| s.to_lowercase().contains(&keyword));
+            }
+            SearchMode::Regex => {
+                match RegexBuilder::new(&query.text)
+                    .case_insensitive(true)
+                    .build()
+                {
+                    Ok(regex) => self.update_exact(&query, |s| regex.is_match(s)),
+                    Err(e) => {
+                        self.error = Some(e.to_string().lines().last().unwrap_or("").to_string());
+                        self.items.clear();
+                    }
+                }
+            }
+        }
+
+        // slowest first, songs without a tempo last
+        if self.by_tempo {
+            self.items
+                .sort_by_key(|(s, _, _)| (s.bpm().is_none(), s.bpm().map(OrderedFloat)));
+        }
+    }
+
+    /// all songs fulfilling the predicates of `query` with a tag or file name matching `matches`, sorted by path
+    fn update_exact(&mut self, query: &Query, matches: impl Fn(&str) -> bool) {
         self.items = self
             .cache
+            .load()
             .songs()
+            .filter(|(s, _)| query.matches(s))
+            .filter(|(s, p)| {
+                MATCHED_TAGS
+                    .iter()
+                    .filter_map(|k| s.standard_tags.get(k))
+                    .any(|v| matches(&v.to_string()))
+                    || p.file_name().and_then(|f| f.to_str()).is_some_and(&matches)
+            })
+            .map(|(s, p)| (s.clone(), p, None))
+            .sorted_unstable_by(|(_, a, _), (_, b, _)| a.cmp(b))
+            .collect();
+    }
+
+    fn update_fuzzy(&mut self, query: &Query) {
+        let cache = self.cache.load();
+        let keyword = query.text.to_lowercase();
+        let threshold = OrderedFloat(-self.config.search_threshold.0);
+
+        let candidates = cache.index().lookup(&keyword);
+        let songs: Box<dyn Iterator<Item = (&Song, PathBuf)>> = if candidates.is_empty() {
+            // no exact token matches, fall back to fuzzy scoring the whole library
+            Box::new(cache.songs())
+        } else {
+            Box::new(candidates.into_iter().filter_map(|p| {
+                cache
+                    .get(p)
+                    .ok()
+                    .flatten()
+                    .and_then(|e| e.as_file().ok())
+                    .map(|s| (s, p.to_path_buf()))
+            }))
+        };
+
+        self.items = songs
+            .filter(|(s, _)| query.matches(s))
             .map(|(s, p)| {
-                let l = p
-                    .file_name()
-                    .and_then(|s| s.to_str())
-                    .map(|s| s.to_lowercase());
-                (
-                    s,
-                    p,
-                    OrderedFloat(-jaro_winkler(
-                        self.keyword.to_lowercase().as_str(),
-                        s.standard_tags
-                            .get(&StandardTagKey::TrackTitle)
-                            .map(|s| s.to_string().to_lowercase())
-                            .or(l.clone())
-                            .unwrap_or(UNKNOWN_STRING.to_string())
-                            .to_lowercase()
-                            .as_str(),
-                    )),
-                    OrderedFloat(-jaro_winkler(
-                        self.keyword.to_lowercase().as_str(),
-                        s.standard_tags
-                            .get(&StandardTagKey::Artist)
-                            .map(|s| s.to_string().to_lowercase())
-                            .or(l)
-                            .unwrap_or(UNKNOWN_STRING.to_string())
-                            .to_lowercase()
-                            .as_str(),
-                    )),
-                )
+                let score = self
+                    .config
+                    .search_weights
+                    .iter()
+                    .filter_map(|(field, weight)| {
+                        let value = field_value(s, &p, *field)?;
+                        Some(OrderedFloat(
+                            -weight.0 * jaro_winkler(&keyword, &value.to_lowercase()),
+                        ))
+                    })
+                    .min()
+                    .unwrap_or(OrderedFloat(0.0));
+                (s, p, score)
             })
-            .sorted_unstable_by_key(|&(_, _, x, y)| x.min(y))
-            .take_while(|&(_, _, x, y)| x.min(y) <= OrderedFloat(0.0))
-            .map(|(s, p, _, _)| (s.clone(), p))
+            .sorted_unstable_by_key(|&(_, _, score)| score)
+            .take_while(|&(_, _, score)| score <= threshold)
+            .map(|(s, p, score)| (s.clone(), p, Some(-score.0)))
             .collect::<Vec<_>>();
     }
 }
 
+/// the text of `field` the fuzzy search scores, none if the song has no such tag
+fn field_value(song: &Song, path: &Path, field: SearchField) -> Option<String> {
+    let file_name = || path.file_name().map(|f| f.to_string_lossy().to_string());
+    let tag = |key| song.standard_tags.get(&key).map(|v| v.to_string());
+
+    match field {
+        // untagged songs are scored by their file name
+        SearchField::Title => tag(StandardTagKey::TrackTitle).or_else(file_name),
+        SearchField::Artist => tag(StandardTagKey::Artist).or_else(file_name),
+        SearchField::Album => tag(StandardTagKey::Album),
+        SearchField::AlbumArtist => tag(StandardTagKey::AlbumArtist),
+        SearchField::Filename => file_name(),
+    }
+}
+
 impl Tui for Search {
     fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
         let layout = Layout::default()
@@ -93,26 +232,54 @@ impl Tui for Search {
             ])
             .split(area);
 
-        let input = Paragraph::new(Line::from(vec![
-            Span::from("Search: ")
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
+        let mut input = vec![
+            Span::from(match self.by_tempo {
+                true => format!("Search ({}, by tempo): ", self.mode.name()),
+                false => format!("Search ({}): ", self.mode.name()),
+            })
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
             Span::from(self.keyword.clone()).add_modifier(Modifier::ITALIC),
             Span::from("_").add_modifier(Modifier::SLOW_BLINK),
-        ]));
+        ];
+        if let Some(error) = &self.error {
+            input.push(Span::from(format!("  {error}")).fg(Color::LightRed));
+        } else if !self.keyword.is_empty() {
+            let score = self
+                .items
+                .get(self.list.selected())
+                .and_then(|(_, _, score)| *score)
+                .filter(|_| self.config.show_search_score)
+                .map(|score| format!(", score {score:.2}"))
+                .unwrap_or_default();
+            input.push(
+                Span::from(format!("  {} matches{score}", self.items.len())).fg(Color::DarkGray),
+            );
+        }
+        let input = Paragraph::new(Line::from(input));
 
+        let cache = self.cache.load();
+        let player = self.player.read().expect("Failed to lock player");
+        let markers = song_table::QueueMarkers::new(&player);
+        let (offset, mut state) = self.list.window(self.items.len(), layout[0].height);
         let table = Table::new(
             self.items
                 .iter()
-                .map(|(s, p)| {
+                .skip(offset)
+                .take(layout[0].height as usize)
+                .map(|(s, p, _)| {
                     let filename = p
                         .file_name()
                         .ok_or(anyhow::anyhow!("Failed to get filename from path {:?}", p))?
                         .to_str()
                         .ok_or(anyhow::anyhow!("Failed to convert OsString to str {:?}", p))?;
-                    Ok(song_table::cache_row(
-                        filename,
-                        &CacheEntry::File { song: s.clone() },
+                    Ok(song_table::availability(
+                        song_table::cache_row(
+                            filename,
+                            &CacheEntry::File { song: s.clone() },
+                            markers.marker(p),
+                        ),
+                        cache.is_available(p),
                     ))
                 })
                 .collect::<anyhow::Result<Vec<_>>>()?,
@@ -130,51 +297,62 @@ impl Tui for Search {
         )
         .highlight_symbol("⏯️  ")
         .column_spacing(4)
-        .widths(&[
-            Constraint::Percentage(5),
-            Constraint::Percentage(15),
-            Constraint::Percentage(40),
-            Constraint::Percentage(30),
-        ]);
-
-        f.render_stateful_widget(
-            table,
-            layout[0],
-            &mut TableState::default().with_selected(Some(self.selected)),
-        );
+        .widths(&song_table::WIDTHS);
+
+        f.render_stateful_widget(table, layout[0], &mut state);
         f.render_widget(input, layout[1]);
 
         Ok(())
     }
 
+    /// the keyword is always being typed
+    fn captures_keys(&self) -> bool {
+        true
+    }
+
     fn input(&mut self, event: &Event) -> anyhow::Result<()> {
-        if let Event::Key(KeyEvent { code, .. }) = event {
+        if let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = event
+        {
             match code {
-                KeyCode::Char(c) => {
-                    self.keyword.push(*c);
+                KeyCode::Char('t') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.mode = self.mode.next();
                     self.update_items();
                 }
-                KeyCode::Backspace => {
-                    if self.keyword.pop().is_some() {
-                        self.update_items();
+                KeyCode::Char('b') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    self.by_tempo = !self.by_tempo;
+                    self.update_items();
+                }
+                KeyCode::Char(c @ ('y' | 'u')) if modifiers.contains(KeyModifiers::CONTROL) => {
+                    if let Some((song, path, _)) = self.items.get(self.list.selected()) {
+                        match c {
+                            'y' => clipboard::copy(&path.to_string_lossy()),
+                            _ => clipboard::copy(&clipboard::describe(song)),
+                        }
                     }
-
-                    if self.keyword.is_empty() {
-                        self.items.clear();
+                }
+                KeyCode::Char('p') if modifiers.contains(KeyModifiers::CONTROL) => {
+                    if let Some((_, path, _)) = self.items.get(self.list.selected()) {
+                        self.cmd.send(Command::Preview(path.as_path().into()))?;
                     }
                 }
+                KeyCode::Char(c) => {
+                    self.keyword.push(*c);
+                    self.update_items();
+                }
+                KeyCode::Backspace if self.keyword.pop().is_some() => {
+                    self.update_items();
+                }
                 KeyCode::Esc => {
                     self.keyword.clear();
-                    self.items.clear();
-                }
-                KeyCode::Down => {
-                    self.selected += 1;
+                    self.update_items();
                 }
-                KeyCode::Up => self.selected = self.selected.saturating_sub(1),
+                code if self.list.input(code, self.items.len()) => {}
                 KeyCode::Enter => {
-                    let (_, path) = self
+                    let (_, path, _) = self
                         .items
-                        .get(self.selected)
+                        .get(self.list.selected())
                         .ok_or(anyhow::anyhow!("Failed to get selected Song"))?
                         .clone();
 
@@ -184,7 +362,7 @@ impl Tui for Search {
             }
         }
 
-        self.selected = self.selected.clamp(0, self.items.len());
+        self.list.clamp(self.items.len());
 
         Ok(())
     }
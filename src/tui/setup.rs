@@ -0,0 +1,253 @@
+use std::{path::PathBuf, time::Duration};
+
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEvent},
+    terminal::{disable_raw_mode, enable_raw_mode},
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    prelude::{Constraint, Direction, Layout, Rect},
+    style::{Color, Style, Stylize},
+    text::{Line, Span},
+    widgets::{Block, BorderType, Borders, Paragraph, Row, Table},
+    Frame, Terminal,
+};
+
+use crate::{config::Config, paths, player::sink::output_devices};
+
+use super::{list::ListController, popup_area, Tui};
+
+/// extensions suggested for the library
+const DEFAULT_EXTENSIONS: &str = "mp3 flac ogg opus wav m4a";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Step {
+    Directories,
+    Extensions,
+    Device,
+    Done,
+}
+
+/// the setup shown on the first run, asking for the music directories, extensions and output device
+struct Setup {
+    config: Config,
+    step: Step,
+    input: String,
+    /// why the last input was rejected
+    error: Option<String>,
+    devices: Vec<String>,
+    /// the selected device, the first row is the default device of the system
+    list: ListController,
+    skipped: bool,
+}
+
+impl Setup {
+    fn new(config: Config) -> Self {
+        let input = dirs::audio_dir()
+            .filter(|d| d.is_dir())
+            .map(|d| d.display().to_string())
+            .unwrap_or_default();
+
+        Self {
+            list: ListController::new(config.wrap_selection),
+            config,
+            step: Step::Directories,
+            input,
+            error: None,
+            devices: vec![],
+            skipped: false,
+        }
+    }
+
+    /// the directory typed in, with a leading `~` expanded to the home directory
+    fn directory(&self) -> PathBuf {
+        match (self.input.trim().strip_prefix('~'), dirs::home_dir()) {
+            (Some(rest), Some(home)) => home.join(rest.trim_start_matches(['/', '\\'])),
+            _ => PathBuf::from(self.input.trim()),
+        }
+    }
+
+    fn submit(&mut self) {
+        self.error = None;
+        match self.step {
+            Step::Directories if self.input.trim().is_empty() => {
+                match self.config.search_directories.is_empty() {
+                    true => self.error = Some("Add at least one directory".to_string()),
+                    false => {
+                        self.step = Step::Extensions;
+                        self.input = DEFAULT_EXTENSIONS.to_string();
+                    }
+                }
+            }
+            Step::Directories => match paths::canonicalize(&self.directory()) {
+                Ok(directory) if directory.is_dir() => {
+                    if !self.config.search_directories.contains(&directory) {
+                        self.config.search_directories.push(directory);
+                    }
+                    self.input.clear();
+                }
+                Ok(directory) => {
+                    self.error = Some(format!("{} is not a directory", directory.display()))
+                }
+                Err(e) => self.error = Some(format!("{}: {e}", self.directory().display())),
+            },
+            Step::Extensions => {
+                let extensions = self
+                    .input
+                    .split(|c: char| c.is_whitespace() || c == ',')
+                    .map(|e| e.trim_start_matches('.').to_lowercase())
+                    .filter(|e| !e.is_empty())
+                    .collect::<std::collections::HashSet<_>>();
+                match extensions.is_empty() {
+                    true => self.error = Some("Enter at least one extension".to_string()),
+                    false => {
+                        self.config.extensions = extensions;
+                        self.devices = output_devices();
+                        self.step = Step::Device;
+                    }
+                }
+            }
+            Step::Device => {
+                self.config.output_device = match self.list.selected() {
+                    0 => None,
+                    i => self.devices.get(i - 1).cloned(),
+                };
+                self.step = Step::Done;
+            }
+            Step::Done => {}
+        }
+    }
+}
+
+impl Tui for Setup {
+    fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
+        let area = popup_area(area, 80, 20);
+        let block = Block::new()
+            .borders(Borders::ALL)
+            .border_type(BorderType::Rounded)
+            .title(" Welcome to ramp ")
+            .title_style(Style::default().light_blue().bold());
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(2),
+                Constraint::Min(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+            ])
+            .split(inner);
+
+        let (question, help) = match self.step {
+            Step::Directories => (
+                "Which directories is your music in?",
+                "Enter adds a directory, Enter on an empty line continues, Esc skips the setup",
+            ),
+            Step::Extensions => (
+                "Which file extensions should be played?",
+                "Enter continues, Esc skips the setup",
+            ),
+            Step::Device | Step::Done => (
+                "Which output device should be played on?",
+                "Enter starts scanning the library, Esc skips the setup",
+            ),
+        };
+        f.render_widget(
+            Paragraph::new(Line::from(Span::from(question).bold())),
+            layout[0],
+        );
+
+        match self.step {
+            Step::Directories => f.render_widget(
+                Paragraph::new(
+                    self.config
+                        .search_directories
+                        .iter()
+                        .map(|d| Line::from(Span::from(format!("📁 {}", d.display())).light_cyan()))
+                        .collect::<Vec<_>>(),
+                ),
+                layout[1],
+            ),
+            Step::Extensions => {}
+            Step::Device | Step::Done => {
+                let rows = std::iter::once("System default")
+                    .chain(self.devices.iter().map(String::as_str))
+                    .map(|d| Row::new(vec![d.to_string()]))
+                    .collect::<Vec<_>>();
+                let (offset, mut state) = self.list.window(rows.len(), layout[1].height);
+                let table = Table::new(rows.into_iter().skip(offset).collect::<Vec<_>>())
+                    .fg(Color::Rgb(210, 210, 210))
+                    .highlight_style(Style::default().light_yellow().bold())
+                    .highlight_symbol("🔊 ")
+                    .widths(&[Constraint::Percentage(100)]);
+                f.render_stateful_widget(table, layout[1], &mut state);
+            }
+        }
+
+        if matches!(self.step, Step::Directories | Step::Extensions) {
+            let spans = vec![
+                Span::from("> ").bold(),
+                Span::from(self.input.clone()).light_yellow(),
+                Span::from("_").light_yellow().slow_blink(),
+            ];
+            f.render_widget(Paragraph::new(Line::from(spans)), layout[2]);
+        }
+
+        let help = match &self.error {
+            Some(error) => Line::from(Span::from(error.as_str()).light_red()),
+            None => Line::from(Span::from(help).fg(Color::DarkGray)),
+        };
+        f.render_widget(Paragraph::new(help), layout[3]);
+
+        Ok(())
+    }
+
+    fn input(&mut self, event: &Event) -> anyhow::Result<()> {
+        let Event::Key(KeyEvent { code, .. }) = event else {
+            return Ok(());
+        };
+
+        match code {
+            KeyCode::Esc => self.skipped = true,
+            KeyCode::Enter => self.submit(),
+            code if self.step == Step::Device => {
+                self.list.input(code, self.devices.len() + 1);
+            }
+            KeyCode::Char(c) => self.input.push(*c),
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn captures_keys(&self) -> bool {
+        true
+    }
+}
+
+/// ask for the music directories, extensions and output device in the terminal,
+/// returns `None` if the setup was skipped
+pub fn setup(config: Config) -> anyhow::Result<Option<Config>> {
+    let mut terminal = Terminal::new(CrosstermBackend::new(std::io::stdout()))?;
+    enable_raw_mode()?;
+    terminal.clear()?;
+
+    let mut setup = Setup::new(config);
+    while !setup.skipped && setup.step != Step::Done {
+        terminal.draw(|f| setup.draw(f.size(), f).expect("Failed to draw setup"))?;
+
+        if event::poll(Duration::from_secs_f32(0.2))? {
+            setup.input(&event::read()?)?;
+        }
+    }
+
+    disable_raw_mode()?;
+    terminal.clear()?;
+
+    Ok((!setup.skipped).then_some(setup.config))
+}
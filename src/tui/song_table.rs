@@ -1,20 +1,43 @@
+use std::{
+    collections::HashSet,
+    path::{Path, MAIN_SEPARATOR},
+    time::Duration,
+};
+
 use ratatui::{
+    prelude::Constraint,
     style::{Modifier, Stylize},
     widgets::Row,
 };
 
 use crate::{
     cache::CacheEntry,
+    player::facade::PlayerFacade,
     song::{Song, StandardTagKey},
 };
 
-use super::UNKNOWN_STRING;
+use super::{format_duration, UNKNOWN_STRING};
 
 pub const HEADER: fn() -> Row<'static> = || {
-    Row::new(["Track #️⃣ ", "Artist 🧑‍🎤 ", "Title / File 🎶 ", "Album 🖼️ "])
-        .add_modifier(Modifier::BOLD)
+    Row::new([
+        "Track #️⃣ ",
+        "Artist 🧑‍🎤 ",
+        "Title / File 🎶 ",
+        "Album 🖼️ ",
+        "BPM 🥁 ",
+    ])
+    .add_modifier(Modifier::BOLD)
 };
 
+/// widths of the columns of `HEADER`
+pub const WIDTHS: [Constraint; 5] = [
+    Constraint::Percentage(5),
+    Constraint::Percentage(15),
+    Constraint::Percentage(35),
+    Constraint::Percentage(30),
+    Constraint::Percentage(5),
+];
+
 const KEYS: [StandardTagKey; 4] = [
     StandardTagKey::TrackNumber,
     StandardTagKey::Artist,
@@ -22,7 +45,38 @@ const KEYS: [StandardTagKey; 4] = [
     StandardTagKey::Album,
 ];
 
-pub fn cache_row<'a>(key: &str, value: &CacheEntry) -> Row<'a> {
+/// the tempo rounded to whole beats, empty if unknown
+fn bpm(song: &Song) -> String {
+    song.bpm().map(|b| format!("{b:.0}")).unwrap_or_default()
+}
+
+/// the playing and the queued songs, marked in front of the track number in song tables
+pub struct QueueMarkers<'a> {
+    playing: Option<&'a Path>,
+    queued: HashSet<&'a Path>,
+}
+
+impl<'a> QueueMarkers<'a> {
+    pub fn new(player: &'a PlayerFacade) -> Self {
+        Self {
+            playing: player.current_song().map(|s| &*s.path),
+            queued: player.queue.iter().map(|p| &**p).collect(),
+        }
+    }
+
+    pub fn marker(&self, path: &Path) -> &'static str {
+        if self.playing == Some(path) {
+            "▶ "
+        } else if self.queued.contains(path) {
+            "• "
+        } else {
+            ""
+        }
+    }
+}
+
+/// the row of a file or directory, `key` is its name or its path relative to the listed directory, `marker` is put in front of the track number, see `QueueMarkers`
+pub fn cache_row<'a>(key: &str, value: &CacheEntry, marker: &str) -> Row<'a> {
     Row::new(match value {
         CacheEntry::File { ref song, .. } => {
             let track = song
@@ -30,6 +84,7 @@ pub fn cache_row<'a>(key: &str, value: &CacheEntry) -> Row<'a> {
                 .get(&StandardTagKey::TrackNumber)
                 .map(|s| s.to_string())
                 .unwrap_or(UNKNOWN_STRING.to_string());
+            let track = format!("{marker}{track}");
 
             let artist = song
                 .standard_tags
@@ -41,7 +96,18 @@ pub fn cache_row<'a>(key: &str, value: &CacheEntry) -> Row<'a> {
                 .standard_tags
                 .get(&StandardTagKey::TrackTitle)
                 .map(|s| s.to_string())
-                .unwrap_or(key.to_string());
+                .unwrap_or_else(|| {
+                    Path::new(key)
+                        .file_name()
+                        .map_or(key.to_string(), |f| f.to_string_lossy().to_string())
+                });
+            // songs listed by their path relative to the directory show where they are
+            let title = match Path::new(key).parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => {
+                    format!("{}{MAIN_SEPARATOR}{title}", dir.display())
+                }
+                _ => title,
+            };
 
             let album = song
                 .standard_tags
@@ -49,17 +115,38 @@ pub fn cache_row<'a>(key: &str, value: &CacheEntry) -> Row<'a> {
                 .map(|s| s.to_string())
                 .unwrap_or(UNKNOWN_STRING.to_string());
 
-            [track, artist, title, album]
+            [track, artist, title, album, bpm(song)]
         }
-        CacheEntry::Directory { .. } => ["", "", key, ""].map(|s| s.to_string()),
+        CacheEntry::Directory { .. } => ["", "", key, "", ""].map(|s| s.to_string()),
     })
 }
 
+/// the row of a directory with the number of songs below it and their total duration in the album column
+pub fn directory_row<'a>(name: &str, songs: usize, duration: Duration) -> Row<'a> {
+    let summary = match songs {
+        1 => format!("1 song, {}", format_duration(duration)),
+        n => format!("{n} songs, {}", format_duration(duration)),
+    };
+    Row::new(["", "", name, &summary, ""].map(|s| s.to_string()))
+}
+
+/// grey out the row of a song whose file is missing, see `Cache::is_available`
+pub fn availability<'a>(row: Row<'a>, available: bool) -> Row<'a> {
+    match available {
+        true => row,
+        false => row.dark_gray().italic(),
+    }
+}
+
 pub fn song_row<'a>(song: &Song) -> Row<'a> {
-    Row::new(KEYS.map(|k| {
-        song.standard_tags
-            .get(&k)
-            .map(|v| v.to_string())
-            .unwrap_or(UNKNOWN_STRING.to_string())
-    }))
+    Row::new(
+        KEYS.map(|k| {
+            song.standard_tags
+                .get(&k)
+                .map(|v| v.to_string())
+                .unwrap_or(UNKNOWN_STRING.to_string())
+        })
+        .into_iter()
+        .chain([bpm(song)]),
+    )
 }
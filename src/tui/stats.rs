@@ -0,0 +1,163 @@
+use std::{
+    cell::RefCell,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::{Local, NaiveDate};
+use crossterm::event::Event;
+use ratatui::{
+    prelude::{Constraint, Direction, Layout, Rect},
+    style::{Color, Modifier, Style, Stylize},
+    widgets::{BarChart, Block, BorderType, Borders, Row, Table},
+    Frame,
+};
+
+use crate::{
+    report::{Period, Report, TopEntry},
+    stats::Stats,
+};
+
+use super::{format_duration, Tui};
+
+/// the report of the history as it was, aggregating the whole history on every frame is slow
+struct CachedReport {
+    listens: usize,
+    /// the days and weeks of the report move on at midnight
+    day: NaiveDate,
+    report: Rc<Report>,
+}
+
+/// listening time per day and week and the most listened artists, albums and tracks
+pub struct Statistics {
+    stats: Arc<Mutex<Stats>>,
+    report: RefCell<Option<CachedReport>>,
+}
+
+impl Statistics {
+    pub fn new(stats: Arc<Mutex<Stats>>) -> Self {
+        Self {
+            stats,
+            report: RefCell::new(None),
+        }
+    }
+
+    /// the report of the history, only built again when songs were listened to or the day changed
+    fn report(&self) -> Rc<Report> {
+        let stats = self.stats.lock().unwrap();
+        let listens = stats.history().len();
+        let day = Local::now().date_naive();
+
+        let mut cached = self.report.borrow_mut();
+        match cached.as_ref() {
+            Some(c) if c.listens == listens && c.day == day => c.report.clone(),
+            _ => {
+                let report = Rc::new(Report::new(stats.history()));
+                *cached = Some(CachedReport {
+                    listens,
+                    day,
+                    report: report.clone(),
+                });
+                report
+            }
+        }
+    }
+}
+
+fn block(title: String) -> Block<'static> {
+    Block::default()
+        .borders(Borders::ALL)
+        .border_type(BorderType::Rounded)
+        .title(title)
+        .title_style(Style::default().add_modifier(Modifier::BOLD))
+}
+
+/// bar chart of the listening time in minutes
+fn draw_periods(periods: &[Period], title: &str, area: Rect, f: &mut Frame) {
+    let labels = periods
+        .iter()
+        .map(|p| p.start.format("%m-%d").to_string())
+        .collect::<Vec<_>>();
+    let data = labels
+        .iter()
+        .zip(periods)
+        .map(|(l, p)| (l.as_str(), p.seconds / 60))
+        .collect::<Vec<_>>();
+
+    let chart = BarChart::default()
+        .block(block(format!(" {title} (minutes) ")))
+        .data(&data)
+        .bar_width(5)
+        .bar_gap(1)
+        .bar_style(Style::default().fg(Color::LightBlue))
+        .value_style(Style::default().fg(Color::Black).bg(Color::LightBlue))
+        .label_style(Style::default().fg(Color::Rgb(210, 210, 210)));
+
+    f.render_widget(chart, area);
+}
+
+fn draw_top(entries: &[TopEntry], title: &str, area: Rect, f: &mut Frame) {
+    let table = Table::new(entries.iter().map(|e| {
+        Row::new([
+            e.name.clone(),
+            e.plays.to_string(),
+            format_duration(Duration::from_secs(e.seconds)),
+        ])
+    }))
+    .header(
+        Row::new(["Name", "Plays", "Time"])
+            .fg(Color::LightBlue)
+            .bold(),
+    )
+    .fg(Color::Rgb(210, 210, 210))
+    .column_spacing(2)
+    .widths(&[
+        Constraint::Min(10),
+        Constraint::Length(5),
+        Constraint::Length(8),
+    ])
+    .block(block(format!(" {title} ")));
+
+    f.render_widget(table, area);
+}
+
+impl Tui for Statistics {
+    fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
+        let report = self.report();
+
+        let layout = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(12), Constraint::Min(1)])
+            .split(area);
+        let charts = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .split(layout[0]);
+        let tops = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Ratio(1, 3); 3])
+            .split(layout[1]);
+
+        draw_periods(&report.days, "Days", charts[0], f);
+        draw_periods(&report.weeks, "Weeks", charts[1], f);
+
+        draw_top(&report.top_artists, "Top artists", tops[0], f);
+        draw_top(&report.top_albums, "Top albums", tops[1], f);
+        draw_top(
+            &report.top_tracks,
+            &format!(
+                "Top tracks, {} total",
+                format_duration(Duration::from_secs(report.total_seconds))
+            ),
+            tops[2],
+            f,
+        );
+
+        Ok(())
+    }
+
+    fn input(&mut self, _event: &Event) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
@@ -1,28 +1,116 @@
-use std::sync::{Arc, RwLock};
+use std::{
+    sync::{Arc, RwLock},
+    time::Duration,
+};
 
 use itertools::Itertools;
 use ratatui::{
     prelude::{Constraint, Direction, Layout, Margin, Rect},
-    style::{Color, Style, Stylize},
+    style::{Color, Stylize},
     text::{Line, Span, Text},
-    widgets::{LineGauge, Paragraph},
+    widgets::Paragraph,
     Frame,
 };
 
-use crate::{player::facade::PlayerFacade, song::StandardTagKey, tui::format_duration};
+use crate::{
+    cache::SharedCache, player::facade::PlayerFacade, scan::Scanner, scheduler::Scheduler,
+    song::StandardTagKey, stats::MAX_RATING, tui::format_duration,
+};
 
 use super::{Tui, UNKNOWN_STRING};
 
+/// bar characters of increasing height for the envelope
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// frames of the spinner shown while a song is loading
+const SPINNER: [char; 10] = ['⠋', '⠙', '⠹', '⠸', '⠼', '⠴', '⠦', '⠧', '⠇', '⠏'];
+
 pub struct Status {
     player: Arc<RwLock<PlayerFacade>>,
+    cache: SharedCache,
+    scheduler: Scheduler,
+    scanner: Scanner,
 }
 
 impl Status {
-    pub fn new(player: Arc<RwLock<PlayerFacade>>) -> Self {
-        Self { player }
+    pub fn new(
+        player: Arc<RwLock<PlayerFacade>>,
+        cache: SharedCache,
+        scheduler: Scheduler,
+        scanner: Scanner,
+    ) -> Self {
+        Self {
+            player,
+            cache,
+            scheduler,
+            scanner,
+        }
     }
 }
 
+/// the color of column `x` of a progress bar `width` wide,
+/// highlighting the played part and, more subtly, the part decoded ahead of it
+fn progress_color(x: usize, width: u16, ratio: f64, buffered: f64) -> Color {
+    if x < (width as f64 * ratio) as usize {
+        Color::LightBlue
+    } else if x < (width as f64 * buffered).ceil() as usize {
+        Color::Gray
+    } else {
+        Color::DarkGray
+    }
+}
+
+/// a double line `width` wide filled up to `ratio`, with the part decoded ahead up to `buffered`
+fn progress_line(width: u16, ratio: f64, buffered: f64) -> Line<'static> {
+    Line::from(
+        (0..width as usize)
+            .map(|x| Span::from("═").fg(progress_color(x, width, ratio, buffered)))
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// the song's amplitude envelope resampled to `width` bars, the played and buffered parts highlighted
+fn envelope_line(envelope: &[u8], width: u16, ratio: f64, buffered: f64) -> Line<'static> {
+    Line::from(
+        (0..width as usize)
+            .map(|x| {
+                let start = x * envelope.len() / width as usize;
+                let end = ((x + 1) * envelope.len() / width as usize).max(start + 1);
+                let peak = envelope[start..end.min(envelope.len())]
+                    .iter()
+                    .max()
+                    .copied()
+                    .unwrap_or(0);
+                let bar = BARS[peak as usize * (BARS.len() - 1) / u8::MAX as usize];
+
+                Span::from(bar.to_string()).fg(progress_color(x, width, ratio, buffered))
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// `line` of a progress bar with the cue points of a song of `duration` marked by their number
+fn mark_cue_points(
+    mut line: Line<'static>,
+    cue_points: &[Option<Duration>],
+    duration: Duration,
+) -> Line<'static> {
+    let width = line.spans.len();
+    if width == 0 || duration.is_zero() {
+        return line;
+    }
+
+    for (i, position) in cue_points.iter().enumerate() {
+        if let Some(position) = position {
+            let x = ((position.as_secs_f64() / duration.as_secs_f64() * width as f64) as usize)
+                .min(width - 1);
+            line.spans[x] = Span::from((i + 1).to_string()).fg(Color::LightMagenta);
+        }
+    }
+
+    line
+}
+
 impl Tui for Status {
     fn draw(&self, area: Rect, f: &mut Frame) -> anyhow::Result<()> {
         let layout = Layout::default()
@@ -33,54 +121,115 @@ impl Tui for Status {
                 horizontal: 1,
             }));
 
-        let playing = Paragraph::new(
-            if let Some(song) = self.player.read().unwrap().current_song() {
-                let title = song
-                    .standard_tags
-                    .get(&StandardTagKey::TrackTitle)
-                    .map(|s| s.to_string())
-                    .or(song
-                        .path
-                        .components()
-                        .last()
-                        .map(|s| s.as_os_str().to_string_lossy().to_string()))
-                    .unwrap_or(UNKNOWN_STRING.to_string());
-
-                let artist = song
-                    .standard_tags
-                    .get(&StandardTagKey::Artist)
-                    .map(|s| s.to_string());
-
-                let mut elems = vec![Span::from(" ")];
-
-                if let Some(artist) = artist {
-                    elems.push(
-                        Span::from(artist)
-                            .fg(Color::LightYellow)
-                            .add_modifier(ratatui::style::Modifier::BOLD),
-                    );
-                    elems.push(Span::from(" - ").fg(Color::White));
-                }
+        let player = self.player.read().unwrap();
+        let playing = Paragraph::new(if let Some(song) = player.current_song() {
+            let title = song
+                .standard_tags
+                .get(&StandardTagKey::TrackTitle)
+                .map(|s| s.to_string())
+                .or(song
+                    .path
+                    .components()
+                    .next_back()
+                    .map(|s| s.as_os_str().to_string_lossy().to_string()))
+                .unwrap_or(UNKNOWN_STRING.to_string());
 
-                elems.extend([
-                    Span::from(title)
+            let artist = song
+                .standard_tags
+                .get(&StandardTagKey::Artist)
+                .map(|s| s.to_string());
+
+            let mut elems = vec![Span::from(" ")];
+
+            if let Some(artist) = artist {
+                elems.push(
+                    Span::from(artist)
                         .fg(Color::LightYellow)
                         .add_modifier(ratatui::style::Modifier::BOLD),
-                    Span::from(format!(" ({})", format_duration(song.duration)))
-                        .fg(Color::LightGreen),
+                );
+                elems.push(Span::from(" - ").fg(Color::White));
+            }
+
+            elems.extend([
+                Span::from(title)
+                    .fg(Color::LightYellow)
+                    .add_modifier(ratatui::style::Modifier::BOLD),
+                Span::from(format!(" ({})", format_duration(song.duration))).fg(Color::LightGreen),
+                Span::from(" "),
+            ]);
+
+            if let Some(chapter) = player
+                .current_chapter()
+                .and_then(|i| player.chapters().get(i))
+            {
+                elems.extend([
+                    Span::from("📖 ").fg(Color::White),
+                    Span::from(chapter.title.clone()).fg(Color::LightCyan),
                     Span::from(" "),
                 ]);
+            }
 
-                Line::from(elems)
-            } else {
-                Line::from(vec![
-                    Span::from(" - ").add_modifier(ratatui::style::Modifier::BOLD)
-                ])
-            },
-        )
+            if let Some(rating) = player.stats.and_then(|s| s.rating) {
+                elems.push(
+                    Span::from(format!(
+                        "{}{} ",
+                        "★".repeat(rating as usize),
+                        "☆".repeat((MAX_RATING - rating) as usize)
+                    ))
+                    .fg(Color::LightYellow),
+                );
+            }
+
+            if player.crossfeed {
+                elems.push(Span::from("🎧 ").fg(Color::White));
+            }
+
+            if player.random_albums {
+                elems.push(Span::from("💿 ").fg(Color::White));
+            }
+
+            if player.clipping {
+                elems.push(Span::from("⚠ clipping ").fg(Color::LightRed));
+            }
+
+            if player.preview {
+                elems.push(Span::from("preview ").fg(Color::LightMagenta));
+            }
+
+            if player.bit_perfect {
+                elems.push(Span::from("bit-perfect ").fg(Color::LightGreen));
+            }
+
+            if player.volume < 1.0 {
+                elems.push(
+                    Span::from(format!("🔉 {:.0}% ", player.volume * 100.0)).fg(Color::White),
+                );
+            }
+
+            Line::from(elems)
+        } else if let Some(path) = player.loading() {
+            let frame = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_millis()
+                / 100;
+
+            Line::from(vec![
+                Span::from(format!(" {} ", SPINNER[frame as usize % SPINNER.len()]))
+                    .fg(Color::LightCyan),
+                Span::from(format!(
+                    "Loading {} ",
+                    path.file_name().unwrap_or_default().to_string_lossy()
+                ))
+                .fg(Color::White),
+            ])
+        } else {
+            Line::from(vec![
+                Span::from(" - ").add_modifier(ratatui::style::Modifier::BOLD)
+            ])
+        })
         .alignment(ratatui::prelude::Alignment::Center);
 
-        let player = self.player.read().unwrap();
         let ratio = if let (Some(song), Some(current_time)) =
             (player.current_song(), player.playing_duration())
         {
@@ -89,12 +238,13 @@ impl Tui for Status {
             0.0
         }
         .clamp(0.0, 1.0);
-
-        let progress = LineGauge::default()
-            .ratio(ratio)
-            .line_set(ratatui::symbols::line::DOUBLE)
-            .label("")
-            .gauge_style(Style::default().fg(Color::LightBlue).bg(Color::DarkGray));
+        let buffered =
+            if let (Some(song), Some(buffered)) = (player.current_song(), player.buffered()) {
+                ratio + buffered.as_secs_f64() / song.duration.as_secs_f64()
+            } else {
+                ratio
+            }
+            .clamp(0.0, 1.0);
         let elapsed = format_duration(
             player
                 .playing_duration()
@@ -124,21 +274,39 @@ impl Tui for Status {
                 horizontal: 1,
             }));
 
+        let mut keys = vec![
+            Span::from("⏯️  Space"),
+            Span::from("⏭️  n"),
+            Span::from("⏹️  s"),
+            Span::from("⛔ q"),
+        ];
+        if let Some(remaining) = self.scheduler.sleep_remaining() {
+            keys.push(
+                Span::from(format!("💤 {}", format_duration(remaining))).fg(Color::LightCyan),
+            );
+        }
+        if let Some(files) = self.scanner.progress() {
+            keys.push(Span::from(format!("🔄 {files} files scanned")).fg(Color::LightCyan));
+        }
+
         let usage = Paragraph::new(Text::from(vec![Line::from(
-            vec![
-                Span::from("⏯️  Space"),
-                Span::from("⏭️  n"),
-                Span::from("⏹️  s"),
-                Span::from("⛔ q"),
-            ]
-            .into_iter()
-            .interleave_shortest(std::iter::repeat(Span::from(" - ")))
-            .collect::<Vec<_>>(),
+            keys.into_iter()
+                .interleave_shortest(std::iter::repeat(Span::from(" - ")))
+                .collect::<Vec<_>>(),
         )
         .alignment(ratatui::prelude::Alignment::Center)]));
 
         f.render_widget(Paragraph::new(Line::from(elapsed)), progress_layout[0]);
-        f.render_widget(progress, progress_layout[1]);
+        let cache = self.cache.load();
+        let progress = match player.current_song().and_then(|s| cache.envelope(&s.path)) {
+            Some(envelope) => envelope_line(&envelope, progress_layout[1].width, ratio, buffered),
+            None => progress_line(progress_layout[1].width, ratio, buffered),
+        };
+        let progress = match player.current_song() {
+            Some(song) => mark_cue_points(progress, &cache.cue_points(&song.path), song.duration),
+            None => progress,
+        };
+        f.render_widget(Paragraph::new(progress), progress_layout[1]);
         f.render_widget(playing, progress_layout[1]);
         f.render_widget(Paragraph::new(Line::from(duration)), progress_layout[2]);
 
@@ -1,6 +1,9 @@
-use std::sync::{atomic::AtomicBool, Arc};
+use std::{
+    sync::{atomic::AtomicBool, mpsc, Arc, RwLock},
+    time::{Duration, Instant},
+};
 
-use crossterm::event::{Event, KeyCode, KeyEvent};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use log::trace;
 use ratatui::{
     prelude::{Margin, Rect},
@@ -10,22 +13,162 @@ use ratatui::{
     Frame,
 };
 
+use crate::{
+    player::{command::Command, facade::PlayerFacade},
+    scan::Scanner,
+    scripting::ScriptHandle,
+};
+
 use super::Tui;
 
+/// seek steps of left and right, with shift and with ctrl
+const SEEK_STEPS: [Duration; 3] = [
+    Duration::from_secs(5),
+    Duration::from_secs(30),
+    Duration::from_secs(60),
+];
+
+/// presses of the same seek key closer than this count as a held key and accelerate the seek
+const SEEK_REPEAT: Duration = Duration::from_millis(300);
+
+/// repeated presses after which the seek step doubles, up to 8 times the step
+const SEEK_ACCELERATION: u32 = 4;
+
+/// change of the volume per key press
+pub(super) const VOLUME_STEP: f32 = 0.05;
+
+/// the last seek key press, for accelerating held keys
+struct SeekRepeat {
+    key: (KeyCode, KeyModifiers),
+    time: Instant,
+    count: u32,
+}
+
 pub struct Tabs<'a> {
     pub selected: usize,
     pub tabs: Vec<(&'static str, Box<dyn Tui + 'a>)>,
     running: Arc<AtomicBool>,
+    scripts: ScriptHandle,
+    cmd: mpsc::Sender<Command>,
+    player: Arc<RwLock<PlayerFacade>>,
+    scanner: Scanner,
+    seek_repeat: Option<SeekRepeat>,
 }
 
 impl<'a> Tabs<'a> {
-    pub fn new(tabs: Vec<(&'static str, Box<dyn Tui + 'a>)>, running: Arc<AtomicBool>) -> Self {
+    pub fn new(
+        tabs: Vec<(&'static str, Box<dyn Tui + 'a>)>,
+        running: Arc<AtomicBool>,
+        scripts: ScriptHandle,
+        cmd: mpsc::Sender<Command>,
+        player: Arc<RwLock<PlayerFacade>>,
+        scanner: Scanner,
+    ) -> Self {
         Self {
             selected: 0,
             tabs,
             running,
+            scripts,
+            cmd,
+            player,
+            scanner,
+            seek_repeat: None,
         }
     }
+
+    /// whether the selected tab takes the key as text input, `Alt` always passes it through
+    fn captured(&self, modifiers: &KeyModifiers) -> bool {
+        !modifiers.contains(KeyModifiers::ALT)
+            && self
+                .tabs
+                .get(self.selected)
+                .is_some_and(|(_, tab)| tab.captures_keys())
+    }
+
+    /// the player command of a transport key, which work in every tab
+    fn transport(&self, code: &KeyCode, modifiers: &KeyModifiers) -> Option<Command> {
+        if self.captured(modifiers) {
+            return None;
+        }
+
+        match code {
+            KeyCode::Char(' ') => Some(Command::PlayPause),
+            KeyCode::Char('n') => Some(Command::Skip),
+            KeyCode::Char('s') => Some(Command::Stop),
+            KeyCode::Char(']') => Some(Command::NextChapter),
+            KeyCode::Char('[') => Some(Command::PreviousChapter),
+            KeyCode::Char(c @ ('+' | '-')) => {
+                let volume = self.player.read().unwrap().volume;
+                let step = if *c == '+' { VOLUME_STEP } else { -VOLUME_STEP };
+                Some(Command::SetVolume(volume + step))
+            }
+            _ => None,
+        }
+    }
+
+    /// seek by the step of the modifiers, doubled every `SEEK_ACCELERATION` repeats while the key is held
+    fn seek(&mut self, code: KeyCode, modifiers: KeyModifiers) -> anyhow::Result<()> {
+        let key = (code, modifiers);
+        let count = match &self.seek_repeat {
+            Some(repeat) if repeat.key == key && repeat.time.elapsed() < SEEK_REPEAT => {
+                repeat.count + 1
+            }
+            _ => 0,
+        };
+        self.seek_repeat = Some(SeekRepeat {
+            key,
+            time: Instant::now(),
+            count,
+        });
+
+        let step = if modifiers.contains(KeyModifiers::CONTROL) {
+            SEEK_STEPS[2]
+        } else if modifiers.contains(KeyModifiers::SHIFT) {
+            SEEK_STEPS[1]
+        } else {
+            SEEK_STEPS[0]
+        };
+        let offset = step * (1 << (count / SEEK_ACCELERATION).min(3));
+
+        self.cmd.send(match code {
+            KeyCode::Left => Command::SeekBackward(offset),
+            _ => Command::SeekForward(offset),
+        })?;
+
+        Ok(())
+    }
+}
+
+/// name of a key as used by scripts, e.g. "x", "X", "ctrl-r" or "f5"
+fn key_name(code: &KeyCode, modifiers: &KeyModifiers) -> Option<String> {
+    let key = match code {
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("f{n}"),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        KeyCode::Delete => "delete".to_string(),
+        KeyCode::Insert => "insert".to_string(),
+        _ => return None,
+    };
+
+    let mut name = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        name.push_str("ctrl-");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        name.push_str("alt-");
+    }
+    name.push_str(&key);
+
+    Some(name)
 }
 
 impl Tui for Tabs<'_> {
@@ -77,7 +220,21 @@ impl Tui for Tabs<'_> {
 
     fn input(&mut self, event: &Event) -> anyhow::Result<()> {
         trace!("Tabs input: {:?}", event);
-        if let Event::Key(KeyEvent { code, .. }) = event {
+        if let Event::Key(KeyEvent {
+            code, modifiers, ..
+        }) = event
+        {
+            if !self.captured(modifiers)
+                && key_name(code, modifiers).is_some_and(|name| self.scripts.key(&name))
+            {
+                return Ok(());
+            }
+
+            if let Some(command) = self.transport(code, modifiers) {
+                self.cmd.send(command)?;
+                return Ok(());
+            }
+
             match code {
                 KeyCode::Tab => {
                     self.selected = (self.selected + 1) % self.tabs.len();
@@ -89,11 +246,28 @@ impl Tui for Tabs<'_> {
                     self.running
                         .store(false, std::sync::atomic::Ordering::Relaxed);
                 }
+                KeyCode::Left | KeyCode::Right => self.seek(*code, *modifiers)?,
+                KeyCode::F(5) => self.scanner.start(modifiers.contains(KeyModifiers::SHIFT)),
+                KeyCode::F(n @ 1..=4) if modifiers.contains(KeyModifiers::SHIFT) => {
+                    self.cmd.send(Command::SetCuePoint(*n as usize))?;
+                }
+                // some terminals send `Shift-F1` as `F13`
+                KeyCode::F(n @ 13..=16) => {
+                    self.cmd.send(Command::SetCuePoint(*n as usize - 12))?;
+                }
+                KeyCode::F(n @ 1..=4) => {
+                    self.cmd.send(Command::JumpToCuePoint(*n as usize))?;
+                }
                 _ => {
                     let content = self.tabs.get_mut(self.selected).expect("Tab not found");
                     content.1.input(event)?;
                 }
             }
+        } else if let Event::Resize(..) = event {
+            // hidden tabs must not keep layouts for the old size either
+            for (_, tab) in &mut self.tabs {
+                tab.input(event)?;
+            }
         }
 
         Ok(())
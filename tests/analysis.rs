@@ -0,0 +1,81 @@
+//! the technical analysis of songs shown in the info popup
+
+mod common;
+
+use common::{click_track, sine, wav, SAMPLE_RATE};
+use ramp_core::{
+    player::{analysis, tempo},
+    song::Song,
+};
+
+fn analyze(samples: &[i16]) -> analysis::Analysis {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let path = wav(&dir.path().join("song.wav"), samples);
+    let song = Song::load(&path).expect("Failed to load song");
+    analysis::compute(&song).expect("Failed to analyze song")
+}
+
+#[test]
+fn sine_has_an_rms_3_db_below_its_peak() {
+    // whole periods, so the song ends at a zero crossing
+    let analysis = analyze(&sine(SAMPLE_RATE as usize * 3, 0.5, 100.0, 0.0));
+
+    assert!((analysis.true_peak - -6.02).abs() < 0.1, "{analysis:?}");
+    assert!((analysis.rms - -9.03).abs() < 0.1, "{analysis:?}");
+    assert!(analysis.dynamic_range.abs() < 0.1, "{analysis:?}");
+}
+
+#[test]
+fn true_peak_includes_peaks_between_samples() {
+    // a quarter of the sample rate sampled at 45°, so every sample is at 70.7% of the peak
+    let analysis = analyze(&sine(
+        SAMPLE_RATE as usize * 3,
+        0.5,
+        4.0,
+        std::f64::consts::FRAC_PI_4,
+    ));
+
+    assert!((analysis.true_peak - -6.02).abs() < 0.5, "{analysis:?}");
+}
+
+#[test]
+fn transients_raise_the_dynamic_range() {
+    // a quiet sine with a loud click every 3 second block
+    let mut samples = sine(SAMPLE_RATE as usize * 15, 0.05, 100.0, 0.0);
+    for block in 0..5 {
+        let click = (block * 3 + 1) * SAMPLE_RATE as usize * 2;
+        samples[click..click + 2].fill(i16::MAX / 2);
+    }
+    let analysis = analyze(&samples);
+
+    // the click is 20 dB above the peak of the sine, which is 3 dB above its rms
+    assert!((analysis.dynamic_range - 20.0).abs() < 0.5, "{analysis:?}");
+    assert!((analysis.true_peak - -6.02).abs() < 0.5, "{analysis:?}");
+}
+
+#[test]
+fn tempo_of_a_click_track_is_detected() {
+    for bpm in [90.0, 120.0, 128.0, 174.0] {
+        let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let path = wav(&dir.path().join("song.wav"), &click_track(bpm, 20));
+        let song = Song::load(&path).expect("Failed to load song");
+        let detected = tempo::detect(&song).expect("Failed to detect tempo");
+
+        assert!(
+            detected.is_some_and(|d| (d - bpm).abs() < 0.5),
+            "{bpm}: {detected:?}"
+        );
+    }
+}
+
+#[test]
+fn silence_has_no_tempo() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let path = wav(
+        &dir.path().join("song.wav"),
+        &common::constant(SAMPLE_RATE as usize * 10, 0),
+    );
+    let song = Song::load(&path).expect("Failed to load song");
+
+    assert_eq!(tempo::detect(&song).expect("Failed to detect tempo"), None);
+}
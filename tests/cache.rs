@@ -0,0 +1,350 @@
+//! scanning the library into the cache and rescanning it at runtime
+
+mod common;
+
+use std::{
+    collections::HashSet,
+    sync::{atomic::AtomicUsize, mpsc, Arc},
+    time::{Duration, Instant},
+};
+
+use common::{click_track, constant, flac, wav};
+use ramp_core::{
+    cache::{Cache, CacheEntry, Rescan, SharedCache},
+    config::Config,
+    paths,
+    query::Query,
+    scan::Scanner,
+    song::{Song, StandardTagKey},
+};
+
+/// a config with `dir` as the only search directory
+fn library(dir: &std::path::Path) -> Config {
+    Config {
+        search_directories: vec![dir.to_path_buf()],
+        extensions: HashSet::from(["wav".to_string(), "flac".to_string()]),
+        ..Config::default_from_config_dir(dir.join("config"))
+    }
+}
+
+#[test]
+fn rescan_adds_new_and_removes_deleted_songs() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = library(dir.path());
+    let a = wav(&dir.path().join("a.wav"), &constant(1000, 1000));
+    let b = wav(&dir.path().join("b.wav"), &constant(1000, 1000));
+    let mut cache = Cache::build_from_config(&config);
+
+    std::fs::remove_file(&a).expect("Failed to remove song");
+    std::fs::create_dir(dir.path().join("sub")).expect("Failed to create directory");
+    let c = flac(
+        &dir.path().join("sub").join("c.flac"),
+        &constant(1000, 1000),
+        &[],
+    );
+
+    let progress = AtomicUsize::new(0);
+    let rescan = cache.rescan(&config, false, &progress);
+    assert_eq!(
+        rescan.songs.iter().map(|(p, _)| p).collect::<Vec<_>>(),
+        vec![&c]
+    );
+    assert_eq!(rescan.removed, vec![a.clone()]);
+    assert_eq!(progress.load(std::sync::atomic::Ordering::Relaxed), 2);
+
+    cache.apply(rescan);
+    let mut songs = cache.songs().map(|(_, p)| p).collect::<Vec<_>>();
+    songs.sort();
+    assert_eq!(songs, vec![b.clone(), c]);
+    assert!(matches!(cache.get(&a), Ok(None)));
+
+    // a full rescan reads the songs again
+    let rescan = cache.rescan(&config, true, &progress);
+    assert_eq!(rescan.songs.len(), 2);
+    assert!(rescan.removed.is_empty());
+    assert!(matches!(cache.get(&b), Ok(Some(CacheEntry::File { .. }))));
+}
+
+#[test]
+fn tempo_detection_fills_in_missing_bpm() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = Arc::new(Config {
+        detect_tempo: true,
+        ..library(dir.path())
+    });
+    std::fs::create_dir_all(
+        config
+            .cache_path
+            .parent()
+            .expect("Cache path has no parent"),
+    )
+    .expect("Failed to create config directory");
+    let beat = wav(&dir.path().join("beat.wav"), &click_track(120.0, 10));
+    let tagged = flac(
+        &dir.path().join("tagged.flac"),
+        &click_track(120.0, 10),
+        &[("BPM", "90")],
+    );
+    let broken = wav(&dir.path().join("broken.wav"), &click_track(120.0, 10));
+
+    let cache = SharedCache::new(Cache::build_from_config(&config));
+    // the file became unreadable after it was scanned
+    std::fs::write(&broken, b"not a wav file").expect("Failed to overwrite song");
+    let (notifications, _rx) = mpsc::channel();
+    let scanner = Scanner::new(config.clone(), cache.clone(), notifications);
+    scanner.start_tempo_detection();
+
+    let start = Instant::now();
+    while scanner.progress().is_some() {
+        assert!(
+            start.elapsed() < Duration::from_secs(30),
+            "Tempo detection timed out"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    let bpm = |path| match cache.load().get(path) {
+        Ok(Some(CacheEntry::File { song })) => song.bpm(),
+        _ => None,
+    };
+    assert_eq!(bpm(&beat), Some(120.0));
+    // tags are not overwritten
+    assert_eq!(bpm(&tagged), Some(90.0));
+    // failures are remembered like songs without a beat, so that they are not decoded again
+    assert_eq!(bpm(&broken), None);
+    assert!(matches!(
+        cache.load().get(&broken),
+        Ok(Some(CacheEntry::File { song })) if song.standard_tags.contains_key(&StandardTagKey::Bpm)
+    ));
+
+    let query = Query::parse("bpm in 115..125").expect("Failed to parse query");
+    assert_eq!(query.lookup(&cache.load()), vec![beat]);
+}
+
+#[test]
+fn paths_are_keyed_by_their_root() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = library(dir.path());
+    let a = wav(&dir.path().join("a.wav"), &constant(1000, 1000));
+    let cache = Cache::build_from_config(&config);
+
+    let root = paths::components(&a).expect("Failed to split path")[0].clone();
+    assert_eq!(cache.roots().keys().collect::<Vec<_>>(), vec![&root]);
+    assert_eq!(cache.songs().map(|(_, p)| p).collect::<Vec<_>>(), vec![a]);
+
+    #[cfg(unix)]
+    assert_eq!(
+        paths::components(std::path::Path::new("/music/a.flac")).expect("Failed to split path"),
+        vec!["/", "music", "a.flac"]
+    );
+}
+
+#[cfg(windows)]
+#[test]
+fn windows_drives_and_shares_are_one_component() {
+    use std::path::Path;
+
+    let split = |p: &str| paths::components(Path::new(p)).expect("Failed to split path");
+    assert_eq!(split(r"C:\Music\a.flac"), vec![r"C:\", "Music", "a.flac"]);
+    assert_eq!(
+        split(r"\\?\C:\Music\a.flac"),
+        vec![r"C:\", "Music", "a.flac"]
+    );
+    assert_eq!(
+        split(r"\\server\share\a.flac"),
+        vec![r"\\server\share\", "a.flac"]
+    );
+    assert_eq!(
+        split(r"\\?\UNC\server\share\a.flac"),
+        vec![r"\\server\share\", "a.flac"]
+    );
+
+    assert_eq!(
+        paths::normalize(Path::new(r"\\?\C:\Music\a.flac")),
+        Path::new(r"C:\Music\a.flac")
+    );
+    assert_eq!(
+        paths::normalize(Path::new(r"\\?\UNC\server\share\a.flac")),
+        Path::new(r"\\server\share\a.flac")
+    );
+}
+
+#[test]
+fn songs_of_older_scanners_are_read_again() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = library(dir.path());
+    let a = wav(&dir.path().join("a.wav"), &constant(1000, 1000));
+    wav(&dir.path().join("b.wav"), &constant(1000, 1000));
+    let mut cache = Cache::build_from_config(&config);
+    assert_eq!(cache.outdated(&config), 0);
+
+    let Ok(Some(CacheEntry::File { song })) = cache.get(&a) else {
+        panic!("{} is not in the cache", a.display());
+    };
+    let old = Song {
+        scanner_version: 0,
+        ..song.clone()
+    };
+    cache.apply(Rescan {
+        songs: vec![(a.clone(), old)],
+        ..Rescan::default()
+    });
+    assert_eq!(cache.outdated(&config), 1);
+
+    let rescan = cache.rescan(&config, false, &AtomicUsize::new(0));
+    assert_eq!(
+        rescan.songs.iter().map(|(p, _)| p).collect::<Vec<_>>(),
+        vec![&a]
+    );
+    cache.apply(rescan);
+    assert_eq!(cache.outdated(&config), 0);
+}
+
+#[test]
+fn lazy_tags_are_read_from_the_file_on_demand() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = Config {
+        lazy_tags: true,
+        ..library(dir.path())
+    };
+    let a = flac(
+        &dir.path().join("a.flac"),
+        &constant(1000, 1000),
+        &[
+            ("TITLE", "Title"),
+            ("LYRICS", "la la la"),
+            ("WORK", "Symphony No. 5"),
+            ("CUSTOM", "custom"),
+        ],
+    );
+    let mut cache = Cache::build_from_config(&config);
+
+    let Ok(Some(CacheEntry::File { song })) = cache.get(&a) else {
+        panic!("{} is not in the cache", a.display());
+    };
+    assert!(song.stripped);
+    assert_eq!(song.tag_string(StandardTagKey::TrackTitle), Some("Title"));
+    assert_eq!(song.tag_string(StandardTagKey::Lyrics), None);
+    assert_eq!(
+        song.other_tags.keys().collect::<Vec<_>>(),
+        vec![&"WORK".to_string()]
+    );
+
+    let full = song.full();
+    assert_eq!(full.tag_string(StandardTagKey::Lyrics), Some("la la la"));
+    assert!(full.other_tags.contains_key("CUSTOM"));
+
+    // turning lazy tags off reads the stripped songs again
+    let config = Config {
+        lazy_tags: false,
+        ..config
+    };
+    assert_eq!(cache.outdated(&config), 1);
+    let rescan = cache.rescan(&config, false, &AtomicUsize::new(0));
+    cache.apply(rescan);
+    assert_eq!(cache.outdated(&config), 0);
+    let Ok(Some(CacheEntry::File { song })) = cache.get(&a) else {
+        panic!("{} is not in the cache", a.display());
+    };
+    assert_eq!(song.tag_string(StandardTagKey::Lyrics), Some("la la la"));
+}
+
+#[test]
+fn cd_rips_with_one_album_gain_are_continuous() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = library(dir.path());
+    let tags = |album, gain| {
+        [
+            ("ALBUM", album),
+            ("ACCURATERIPRESULT", "AccurateRip: Accurate (confidence 5)"),
+            ("REPLAYGAIN_ALBUM_GAIN", gain),
+        ]
+    };
+    let live = flac(
+        &dir.path().join("live1.flac"),
+        &constant(1000, 1000),
+        &tags("Live", "-3.00 dB"),
+    );
+    flac(
+        &dir.path().join("live2.flac"),
+        &constant(1000, 1000),
+        &tags("Live", "-3.00 dB"),
+    );
+    let studio = flac(
+        &dir.path().join("studio1.flac"),
+        &constant(1000, 1000),
+        &tags("Studio", "-3.00 dB"),
+    );
+    flac(
+        &dir.path().join("studio2.flac"),
+        &constant(1000, 1000),
+        &tags("Studio", "-5.00 dB"),
+    );
+
+    let mut cache = Cache::build_from_config(&config);
+    cache.build_index();
+    let song = |path: &std::path::Path| match cache.get(path) {
+        Ok(Some(CacheEntry::File { song })) => song.clone(),
+        _ => panic!("{} is not in the cache", path.display()),
+    };
+
+    let live = song(&live);
+    assert!(cache.is_continuous(&live));
+    assert!((live.album_gain_factor().expect("No album gain") - 10_f32.powf(-0.15)).abs() < 1e-6);
+    // the tracks were analyzed separately
+    assert!(!cache.is_continuous(&song(&studio)));
+}
+
+#[test]
+fn cue_points_are_saved_with_the_cache() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = library(dir.path());
+    std::fs::create_dir_all(dir.path().join("config")).expect("Failed to create config directory");
+    let a = wav(&dir.path().join("a.wav"), &constant(1000, 1000));
+    let cache = Cache::build_from_config(&config);
+
+    cache
+        .set_cue_point(&a, 2, Some(Duration::from_secs(3)))
+        .expect("Failed to set cue point");
+    assert!(cache.set_cue_point(&a, 5, None).is_err());
+    assert!(cache.changed());
+    cache.save(&config).expect("Failed to save cache");
+
+    let (cache, _) = Cache::load(&config).expect("Failed to load cache");
+    assert_eq!(
+        cache.cue_points(&a),
+        [None, Some(Duration::from_secs(3)), None, None]
+    );
+
+    cache
+        .set_cue_point(&a, 2, None)
+        .expect("Failed to remove cue point");
+    assert_eq!(cache.cue_points(&a), [None; 4]);
+}
+
+#[test]
+fn envelopes_being_computed_are_not_saved() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = library(dir.path());
+    std::fs::create_dir_all(dir.path().join("config")).expect("Failed to create config directory");
+    let a = wav(&dir.path().join("a.wav"), &constant(1000, 1000));
+    let b = wav(&dir.path().join("b.wav"), &constant(1000, 1000));
+    let cache = Cache::build_from_config(&config);
+
+    assert!(cache.claim_envelope(&a));
+    assert!(!cache.claim_envelope(&a));
+    assert!(cache.claim_envelope(&b));
+    cache.insert_envelope(&b, Some(Box::new([1, 2, 3])));
+    assert!(!cache.claim_envelope(&b));
+    cache.save(&config).expect("Failed to save cache");
+
+    let (cache, _) = Cache::load(&config).expect("Failed to load cache");
+    assert_eq!(cache.envelope(&a), None);
+    assert_eq!(cache.envelope(&b).as_deref(), Some(&[1, 2, 3][..]));
+    assert!(cache.claim_envelope(&a));
+
+    // a failed computation can be tried again
+    cache.insert_envelope(&a, None);
+    assert_eq!(cache.envelope(&a), None);
+    assert!(cache.claim_envelope(&a));
+}
@@ -0,0 +1,331 @@
+//! synthetic audio fixtures and a headless player for the integration tests
+
+#![allow(dead_code)]
+
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex, RwLock},
+    time::{Duration, Instant},
+};
+
+use ramp_core::{
+    cache::{Cache, SharedCache},
+    config::Config,
+    notification::Notification,
+    player::{
+        command::Command,
+        facade::PlayerFacade,
+        sink::{AudioSink, Capture},
+        supervisor::Supervisor,
+        Player,
+    },
+    stats::Stats,
+};
+
+pub const SAMPLE_RATE: u32 = 44100;
+
+/// frames per flac frame
+const FLAC_BLOCK_SIZE: usize = 4096;
+
+/// how long `Harness::wait_until` waits for the player
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// `frames` stereo frames of the constant `value`, which makes gain easy to check
+pub fn constant(frames: usize, value: i16) -> Vec<i16> {
+    vec![value; frames * 2]
+}
+
+/// `frames` stereo frames of a sine of `amplitude` with a period of `period` samples, starting at `phase` radians
+pub fn sine(frames: usize, amplitude: f64, period: f64, phase: f64) -> Vec<i16> {
+    (0..frames)
+        .map(|i| {
+            let t = i as f64 / period * std::f64::consts::TAU + phase;
+            (t.sin() * amplitude * i16::MAX as f64).round() as i16
+        })
+        .flat_map(|s| [s, s])
+        .collect()
+}
+
+/// a click of 10 ms every beat of `bpm`, over a quiet sine so the song is not silent between the clicks
+pub fn click_track(bpm: f64, seconds: usize) -> Vec<i16> {
+    let mut samples = sine(SAMPLE_RATE as usize * seconds, 0.01, 100.0, 0.0);
+    let beat = 60.0 / bpm * SAMPLE_RATE as f64;
+    let mut position = 0.0;
+    while (position as usize + SAMPLE_RATE as usize / 100) * 2 < samples.len() {
+        let start = position as usize * 2;
+        let click = sine(SAMPLE_RATE as usize / 100, 0.5, 20.0, 0.0);
+        samples[start..start + click.len()].copy_from_slice(&click);
+        position += beat;
+    }
+    samples
+}
+
+/// a 16 bit stereo wav file
+pub fn wav(path: &Path, samples: &[i16]) -> PathBuf {
+    let data = samples
+        .iter()
+        .flat_map(|s| s.to_le_bytes())
+        .collect::<Vec<_>>();
+
+    wav_format(path, WAV_PCM, 16, 2, &data)
+}
+
+/// the wav format tag of integer samples
+pub const WAV_PCM: u16 = 1;
+/// the wav format tag of float samples
+pub const WAV_FLOAT: u16 = 3;
+
+/// a wav file of samples already encoded as `bits` bit samples of the wav format tag `format`
+pub fn wav_format(path: &Path, format: u16, bits: u16, channels: u16, data: &[u8]) -> PathBuf {
+    let block_align = channels * bits / 8;
+
+    let mut file = Vec::new();
+    file.extend(b"RIFF");
+    file.extend((36 + data.len() as u32).to_le_bytes());
+    file.extend(b"WAVE");
+    file.extend(b"fmt ");
+    file.extend(16u32.to_le_bytes());
+    file.extend(format.to_le_bytes());
+    file.extend(channels.to_le_bytes());
+    file.extend(SAMPLE_RATE.to_le_bytes());
+    file.extend((SAMPLE_RATE * block_align as u32).to_le_bytes());
+    file.extend(block_align.to_le_bytes());
+    file.extend(bits.to_le_bytes());
+    file.extend(b"data");
+    file.extend((data.len() as u32).to_le_bytes());
+    file.extend(data);
+
+    write(path, &file)
+}
+
+/// a 16 bit stereo flac file with the given vorbis comments, stored uncompressed in verbatim subframes
+pub fn flac(path: &Path, samples: &[i16], tags: &[(&str, &str)]) -> PathBuf {
+    let frames = samples.len() / 2;
+
+    let mut file = Vec::new();
+    file.extend(b"fLaC");
+
+    // streaminfo
+    let mut info = BitWriter::default();
+    info.write(FLAC_BLOCK_SIZE as u64, 16);
+    info.write(FLAC_BLOCK_SIZE as u64, 16);
+    // unknown frame sizes
+    info.write(0, 24);
+    info.write(0, 24);
+    info.write(SAMPLE_RATE as u64, 20);
+    info.write(2 - 1, 3);
+    info.write(16 - 1, 5);
+    info.write(frames as u64, 36);
+    // unknown md5
+    info.write(0, 64);
+    info.write(0, 64);
+    metadata_block(&mut file, 0, false, &info.bytes);
+
+    let mut comments = Vec::new();
+    let vendor = b"ramp tests";
+    comments.extend((vendor.len() as u32).to_le_bytes());
+    comments.extend(vendor);
+    comments.extend((tags.len() as u32).to_le_bytes());
+    for (key, value) in tags {
+        let comment = format!("{key}={value}");
+        comments.extend((comment.len() as u32).to_le_bytes());
+        comments.extend(comment.as_bytes());
+    }
+    metadata_block(&mut file, 4, true, &comments);
+
+    for (number, block) in samples.chunks(FLAC_BLOCK_SIZE * 2).enumerate() {
+        assert!(number < 128, "frame numbers are written as a single byte");
+
+        let mut frame = BitWriter::default();
+        // sync code and fixed block size
+        frame.write(0b11111111111110, 14);
+        frame.write(0, 2);
+        // block size and sample rate from the end of the header and streaminfo
+        frame.write(0b0111, 4);
+        frame.write(0b0000, 4);
+        // two independent channels of 16 bit
+        frame.write(2 - 1, 4);
+        frame.write(0b100, 3);
+        frame.write(0, 1);
+        frame.write(number as u64, 8);
+        frame.write((block.len() / 2 - 1) as u64, 16);
+        let crc = crc8(&frame.bytes);
+        frame.write(crc as u64, 8);
+
+        for channel in 0..2 {
+            // verbatim subframe without wasted bits
+            frame.write(0b00000010, 8);
+            for sample in block.iter().skip(channel).step_by(2) {
+                frame.write(*sample as u16 as u64, 16);
+            }
+        }
+
+        let crc = crc16(&frame.bytes);
+        frame.write(crc as u64, 16);
+        file.extend(frame.bytes);
+    }
+
+    write(path, &file)
+}
+
+fn metadata_block(file: &mut Vec<u8>, kind: u8, last: bool, data: &[u8]) {
+    file.push(((last as u8) << 7) | kind);
+    file.extend(&(data.len() as u32).to_be_bytes()[1..]);
+    file.extend(data);
+}
+
+fn write(path: &Path, data: &[u8]) -> PathBuf {
+    std::fs::File::create(path)
+        .and_then(|mut f| f.write_all(data))
+        .expect("Failed to write fixture");
+
+    path.to_path_buf()
+}
+
+/// writes big endian values of any bit width, all values written here end on a byte boundary
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, value: u64, width: u32) {
+        for i in (0..width).rev() {
+            if self.bits.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            let bit = ((value >> i) & 1) as u8;
+            *self.bytes.last_mut().unwrap() |= bit << (7 - self.bits % 8);
+            self.bits += 1;
+        }
+    }
+}
+
+fn crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| match crc & 0x80 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x07,
+        })
+    })
+}
+
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| match crc & 0x8000 {
+            0 => crc << 1,
+            _ => (crc << 1) ^ 0x8005,
+        })
+    })
+}
+
+/// a player rendering into memory, with its configuration and fixtures in a temporary directory
+pub struct Harness {
+    pub dir: tempfile::TempDir,
+    pub cmd: mpsc::Sender<Command>,
+    pub player: Arc<RwLock<PlayerFacade>>,
+    pub capture: Capture,
+    pub notifications: mpsc::Receiver<Notification>,
+    _supervisor: Supervisor,
+}
+
+impl Harness {
+    pub fn new() -> Self {
+        Self::with_config(|_| {})
+    }
+
+    /// a player with the default configuration changed by `configure`
+    pub fn with_config(configure: impl FnOnce(&mut Config)) -> Self {
+        let capture = Capture::default();
+        Self::run(capture.clone(), Arc::new(capture), configure)
+    }
+
+    /// a player sending its samples to `sink`, the capture stays empty
+    pub fn with_sink(sink: Arc<dyn AudioSink>) -> Self {
+        Self::run(Capture::default(), sink, |_| {})
+    }
+
+    fn run(
+        capture: Capture,
+        sink: Arc<dyn AudioSink>,
+        configure: impl FnOnce(&mut Config),
+    ) -> Self {
+        let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+        let mut config = Config::default_from_config_dir(dir.path());
+        configure(&mut config);
+
+        let (tx, notifications) = mpsc::channel();
+        let (cmd, player, supervisor) = Player::run_with_sink(
+            SharedCache::new(Cache::default()),
+            Arc::new(config),
+            Arc::new(Mutex::new(Stats::default())),
+            tx,
+            sink,
+        )
+        .expect("Failed to run player");
+
+        Self {
+            dir,
+            cmd,
+            player,
+            capture,
+            notifications,
+            _supervisor: supervisor,
+        }
+    }
+
+    /// the path of the fixture `name` in the temporary directory
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    pub fn send(&self, command: Command) {
+        self.cmd.send(command).expect("Failed to send command");
+    }
+
+    /// wait for the player state to satisfy `condition`, panicking after a timeout
+    pub fn wait_until(&self, condition: impl Fn(&PlayerFacade) -> bool) {
+        let start = Instant::now();
+        while !condition(&self.player.read().unwrap()) {
+            assert!(
+                start.elapsed() < TIMEOUT,
+                "Timed out waiting for the player"
+            );
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// wait for `count` samples that are not silence to be rendered, panicking after a timeout
+    pub fn wait_for_samples(&self, count: usize) {
+        let start = Instant::now();
+        while self.runs().iter().map(|(_, n)| n).sum::<usize>() < count {
+            assert!(start.elapsed() < TIMEOUT, "Timed out waiting for samples");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// the rendered samples without silence, as runs of equal samples and their length
+    pub fn runs(&self) -> Vec<(f32, usize)> {
+        let mut runs = Vec::<(f32, usize)>::new();
+        for sample in self.capture.samples().into_iter().filter(|s| *s != 0.0) {
+            match runs.last_mut() {
+                Some((value, count)) if *value == sample => *count += 1,
+                _ => runs.push((sample, 1)),
+            }
+        }
+
+        runs
+    }
+
+    /// the file name of the current song
+    pub fn current(&self) -> Option<String> {
+        self.player
+            .read()
+            .unwrap()
+            .current_song()
+            .and_then(|s| s.path.file_name())
+            .map(|f| f.to_string_lossy().to_string())
+    }
+}
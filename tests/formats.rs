@@ -0,0 +1,55 @@
+//! files of every integer and float sample format decode to the same f32 samples
+
+mod common;
+
+use common::{wav_format, Harness, SAMPLE_RATE, WAV_FLOAT, WAV_PCM};
+use ramp_core::player::command::Command;
+
+/// play a wav file of `frames` frames of the encoded `sample` and check that it renders as half of full scale
+fn plays_at_half_scale(format: u16, bits: u16, channels: u16, sample: &[u8]) {
+    let harness = Harness::new();
+    let frames = SAMPLE_RATE as usize / 10;
+    let data = sample.repeat(frames * channels as usize);
+    let path = wav_format(&harness.path("a.wav"), format, bits, channels, &data);
+
+    harness.send(Command::Enqueue(path.into_boxed_path()));
+    harness.wait_for_samples(frames * channels as usize);
+    harness.wait_until(|p| p.current_song().is_none() && p.queue.is_empty());
+
+    assert_eq!(harness.runs(), vec![(0.5, frames * channels as usize)]);
+}
+
+#[test]
+fn unsigned_8_bit() {
+    plays_at_half_scale(WAV_PCM, 8, 2, &[0xc0]);
+}
+
+#[test]
+fn signed_16_bit() {
+    plays_at_half_scale(WAV_PCM, 16, 2, &0x4000i16.to_le_bytes());
+}
+
+#[test]
+fn signed_24_bit() {
+    plays_at_half_scale(WAV_PCM, 24, 2, &0x400000i32.to_le_bytes()[..3]);
+}
+
+#[test]
+fn signed_32_bit() {
+    plays_at_half_scale(WAV_PCM, 32, 2, &0x40000000i32.to_le_bytes());
+}
+
+#[test]
+fn float_32_bit() {
+    plays_at_half_scale(WAV_FLOAT, 32, 2, &0.5f32.to_le_bytes());
+}
+
+#[test]
+fn float_64_bit() {
+    plays_at_half_scale(WAV_FLOAT, 64, 2, &0.5f64.to_le_bytes());
+}
+
+#[test]
+fn mono() {
+    plays_at_half_scale(WAV_PCM, 16, 1, &0x4000i16.to_le_bytes());
+}
@@ -0,0 +1,55 @@
+//! plain and synced lyrics of songs
+
+use std::time::Duration;
+
+use ramp_core::lyrics::{Lyrics, LyricsLine};
+
+#[test]
+fn lrc_lines_are_sorted_by_their_timestamps() {
+    let lyrics = Lyrics::parse(
+        "[ar:Artist]\n[ti:Title]\n[offset:+500]\n[00:12.00]first\n[00:20.50][01:02.25]chorus\n[00:15:30]second\n",
+    );
+
+    assert!(lyrics.synced());
+    assert_eq!(lyrics.offset, 500);
+    assert_eq!(
+        lyrics.lines,
+        [
+            (12_000, "first"),
+            (15_300, "second"),
+            (20_500, "chorus"),
+            (62_250, "chorus")
+        ]
+        .map(|(ms, text)| LyricsLine {
+            time: Some(Duration::from_millis(ms)),
+            text: text.to_string(),
+        })
+    );
+}
+
+#[test]
+fn current_line_is_shifted_by_the_offsets() {
+    let lyrics = Lyrics::parse("[00:01.00]a\n[00:02.00]b\n[00:03.00]c");
+
+    assert_eq!(lyrics.current(Duration::from_millis(500), 0), None);
+    assert_eq!(lyrics.current(Duration::from_millis(2500), 0), Some(1));
+    assert_eq!(lyrics.current(Duration::from_millis(2500), 600), Some(2));
+    assert_eq!(lyrics.current(Duration::from_millis(2500), -1000), Some(0));
+    assert_eq!(lyrics.current(Duration::from_secs(60), 0), Some(2));
+}
+
+#[test]
+fn text_without_timestamps_is_plain() {
+    let lyrics = Lyrics::parse("la la la\n\nla\n\n");
+
+    assert!(!lyrics.synced());
+    assert_eq!(
+        lyrics
+            .lines
+            .iter()
+            .map(|l| l.text.as_str())
+            .collect::<Vec<_>>(),
+        vec!["la la la", "", "la"]
+    );
+    assert_eq!(lyrics.current(Duration::from_secs(1), 0), None);
+}
@@ -0,0 +1,656 @@
+//! drives the player through its command channel, rendering into memory instead of an audio device
+
+mod common;
+
+use common::{constant, flac, wav, Harness, SAMPLE_RATE};
+use std::{
+    path::Path,
+    sync::{atomic::AtomicBool, Arc, RwLock},
+    time::{Duration, Instant},
+};
+
+use ramp_core::{
+    cache::Cache,
+    config::Config,
+    notification::Level,
+    player::{
+        command::Command,
+        dsp,
+        error::PlayerError,
+        export::export,
+        sink::{
+            AudioSink, Capture, FileSink, OnError, Render, SinkStream, StreamSpec, Zone, ZoneSink,
+        },
+    },
+    song::Song,
+};
+
+fn sample(value: i16) -> f32 {
+    value as f32 / 32768.0
+}
+
+#[test]
+fn songs_play_in_queue_order() {
+    let harness = Harness::new();
+    let frames = SAMPLE_RATE as usize / 2;
+    let a = wav(&harness.path("a.wav"), &constant(frames, 1000));
+    let b = flac(&harness.path("b.flac"), &constant(frames, 2000), &[]);
+    let c = wav(&harness.path("c.wav"), &constant(frames, 3000));
+
+    for path in [a, b, c] {
+        harness.send(Command::Enqueue(path.into_boxed_path()));
+    }
+    harness.wait_for_samples(3 * frames * 2);
+
+    assert_eq!(
+        harness.runs(),
+        vec![
+            (sample(1000), frames * 2),
+            (sample(2000), frames * 2),
+            (sample(3000), frames * 2),
+        ]
+    );
+}
+
+#[test]
+fn end_of_last_song_stops_the_player() {
+    let harness = Harness::new();
+    let frames = SAMPLE_RATE as usize / 4;
+    let a = flac(&harness.path("a.flac"), &constant(frames, 1000), &[]);
+
+    harness.send(Command::Enqueue(a.into_boxed_path()));
+    harness.wait_for_samples(frames * 2);
+    harness.wait_until(|p| p.current_song().is_none() && p.queue.is_empty());
+
+    assert!(harness
+        .notifications
+        .try_iter()
+        .all(|n| n.level != Level::Error));
+}
+
+#[test]
+fn replay_gain_and_volume_are_applied() {
+    let harness = Harness::new();
+    let frames = SAMPLE_RATE as usize / 4;
+    let a = flac(
+        &harness.path("a.flac"),
+        &constant(frames, 16000),
+        &[("REPLAYGAIN_TRACK_GAIN", "-6.0 dB")],
+    );
+
+    harness.send(Command::SetVolume(0.5));
+    harness.send(Command::Enqueue(a.into_boxed_path()));
+    harness.wait_for_samples(frames * 2);
+
+    let expected = sample(16000) * 10f32.powf(-6.0 / 20.0) * 0.5;
+    let runs = harness.runs();
+    assert_eq!(runs.len(), 1);
+    assert!((runs[0].0 - expected).abs() < 1e-6, "{runs:?}");
+}
+
+#[test]
+fn encoder_delay_and_padding_are_trimmed() {
+    let harness = Harness::new();
+    let (delay, frames, padding) = (1000, SAMPLE_RATE as usize / 4, 3000);
+    let samples = [
+        constant(delay, 1000),
+        constant(frames, 2000),
+        constant(padding, 3000),
+    ]
+    .concat();
+    let smpb = format!(" 00000000 {delay:08X} {padding:08X} {frames:016X}");
+    let a = flac(&harness.path("a.flac"), &samples, &[("ITUNSMPB", &smpb)]);
+
+    let song = Song::load(&a).expect("Failed to load song");
+    assert_eq!(
+        song.duration,
+        Duration::from_secs_f64(frames as f64 / SAMPLE_RATE as f64)
+    );
+
+    harness.send(Command::Enqueue(a.into_boxed_path()));
+    harness.wait_for_samples(frames * 2);
+    harness.wait_until(|p| p.current_song().is_none() && p.queue.is_empty());
+
+    assert_eq!(harness.runs(), vec![(sample(2000), frames * 2)]);
+}
+
+#[test]
+fn queue_can_be_edited_while_paused() {
+    let harness = Harness::new();
+    // long enough not to end before it is paused
+    let frames = SAMPLE_RATE as usize * 10;
+    let paths = ["a.wav", "b.wav", "c.wav"]
+        .map(|name| wav(&harness.path(name), &constant(frames, 1000)).into_boxed_path());
+
+    let [a, b, c] = paths.clone();
+    harness.send(Command::Batch(vec![
+        Command::Enqueue(a),
+        Command::Pause,
+        Command::Enqueue(b),
+        Command::Enqueue(c),
+        Command::Dequeue(0),
+    ]));
+    harness.wait_until(|p| p.is_paused() && p.queue.len() == 1);
+    assert_eq!(harness.current().as_deref(), Some("a.wav"));
+    assert_eq!(harness.player.read().unwrap().queue[0], paths[2]);
+
+    // paused playback renders nothing
+    let rendered = harness.capture.len();
+    std::thread::sleep(Duration::from_millis(50));
+    assert_eq!(harness.capture.len(), rendered);
+
+    harness.send(Command::Jump(0));
+    harness.wait_until(|p| p.queue.is_empty() && p.current_song().is_some());
+    assert_eq!(harness.current().as_deref(), Some("c.wav"));
+}
+
+/// the samples of a 32 bit float wav file written by a `FileSink` without silence
+fn written_samples(path: &Path) -> Vec<f32> {
+    // the samples follow the 44 byte header
+    std::fs::read(path)
+        .map(|data| {
+            data.get(44..)
+                .unwrap_or_default()
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                .filter(|s| *s != 0.0)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[test]
+fn file_sink_writes_a_wav_file() {
+    let output = tempfile::tempdir().expect("Failed to create temporary directory");
+    let path = output.path().join("out.wav");
+    let harness = Harness::with_sink(Arc::new(
+        FileSink::new(&path).expect("Failed to create sink"),
+    ));
+    let frames = SAMPLE_RATE as usize / 2;
+    let a = flac(&harness.path("a.flac"), &constant(frames, 1000), &[]);
+    let b = wav(&harness.path("b.wav"), &constant(frames, 2000));
+
+    harness.send(Command::Enqueue(a.into_boxed_path()));
+    harness.send(Command::Enqueue(b.into_boxed_path()));
+    let start = Instant::now();
+    while written_samples(&path).len() < 2 * frames * 2 {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "Timed out waiting for samples"
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+    harness.wait_until(|p| p.current_song().is_none() && p.queue.is_empty());
+
+    let samples = written_samples(&path);
+    assert_eq!(samples.len(), 2 * frames * 2);
+    assert!(samples[..frames * 2].iter().all(|s| *s == sample(1000)));
+    assert!(samples[frames * 2..].iter().all(|s| *s == sample(2000)));
+    // the header is kept valid while writing
+    assert!(Song::load(&path).is_ok_and(|s| s.duration.as_secs_f64() >= 1.0));
+    assert!(harness
+        .notifications
+        .try_iter()
+        .all(|n| n.level != Level::Error));
+}
+
+#[test]
+fn zones_play_the_same_samples_with_their_own_volume() {
+    let (a, b) = (Capture::default(), Capture::default());
+    let harness = Harness::with_sink(Arc::new(
+        ZoneSink::new(vec![
+            Zone::new("speakers".to_string(), Box::new(a.clone()), 1.0),
+            Zone::new("headphones".to_string(), Box::new(b.clone()), 0.5),
+        ])
+        .expect("Failed to create sink"),
+    ));
+    // shorter than the lag the other zones may have, as the capture of the first zone is not paced
+    let frames = SAMPLE_RATE as usize / 8;
+    let song = wav(&harness.path("a.wav"), &constant(frames, 16000));
+
+    harness.send(Command::Enqueue(song.into_boxed_path()));
+    harness.wait_until(|_| {
+        [&a, &b]
+            .iter()
+            .all(|c| c.samples().iter().filter(|s| **s != 0.0).count() == frames * 2)
+    });
+    assert!(a.samples().iter().all(|s| *s == 0.0 || *s == sample(16000)));
+    assert!(b
+        .samples()
+        .iter()
+        .all(|s| *s == 0.0 || *s == sample(16000) * 0.5));
+
+    harness.send(Command::SetZoneVolume("headphones".to_string(), 0.25));
+    harness.wait_until(|p| {
+        p.zones
+            == vec![
+                ("speakers".to_string(), 1.0),
+                ("headphones".to_string(), 0.25),
+            ]
+    });
+
+    harness.send(Command::SetZoneVolume("kitchen".to_string(), 1.0));
+    let start = Instant::now();
+    while !harness
+        .notifications
+        .try_iter()
+        .any(|n| n.level == Level::Error && n.message.contains("kitchen"))
+    {
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "Timed out waiting for the error"
+        );
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+/// a capture pretending to be the output device `device`, which can be switched while playing
+#[derive(Clone, Default)]
+struct Device {
+    capture: Capture,
+    device: Arc<RwLock<String>>,
+}
+
+impl AudioSink for Device {
+    fn open(
+        &self,
+        spec: StreamSpec,
+        pause: Arc<AtomicBool>,
+        render: Render,
+        error: OnError,
+    ) -> Result<SinkStream, PlayerError> {
+        self.capture.open(spec, pause, render, error)
+    }
+
+    fn device(&self) -> Option<String> {
+        Some(self.device.read().unwrap().clone())
+    }
+}
+
+#[test]
+fn volume_is_remembered_for_each_device() {
+    let device = Device::default();
+    let harness = Harness::with_sink(Arc::new(device.clone()));
+    let frames = SAMPLE_RATE as usize / 8;
+    let play = |device_name: &str| {
+        *device.device.write().unwrap() = device_name.to_string();
+        let rendered = device.capture.len();
+        let song = wav(&harness.path("a.wav"), &constant(frames, 1000));
+        harness.send(Command::Enqueue(song.into_boxed_path()));
+        harness.wait_until(|_| device.capture.len() >= rendered + frames * 2);
+    };
+
+    play("headphones");
+    harness.send(Command::SetVolume(0.3));
+    harness.wait_until(|p| p.volume == 0.3);
+
+    // a new device starts at the current volume
+    play("speakers");
+    harness.wait_until(|p| p.volume == 0.3);
+    harness.send(Command::SetVolume(0.8));
+    harness.wait_until(|p| p.volume == 0.8);
+
+    play("headphones");
+    harness.wait_until(|p| p.volume == 0.3);
+    play("speakers");
+    harness.wait_until(|p| p.volume == 0.8);
+}
+
+#[test]
+fn unmodified_playback_is_bit_perfect() {
+    let harness = Harness::new();
+    // long enough not to end before it is paused
+    let frames = SAMPLE_RATE as usize * 10;
+    let a = wav(&harness.path("a.wav"), &constant(frames, 1000));
+    let b = flac(
+        &harness.path("b.flac"),
+        &constant(frames, 1000),
+        &[("REPLAYGAIN_TRACK_GAIN", "-6.0 dB")],
+    );
+
+    harness.send(Command::Batch(vec![
+        Command::Enqueue(a.into_boxed_path()),
+        Command::Pause,
+    ]));
+    harness.wait_until(|p| p.is_paused() && p.bit_perfect);
+
+    harness.send(Command::SetVolume(0.5));
+    harness.wait_until(|p| p.volume == 0.5 && !p.bit_perfect);
+    harness.send(Command::SetVolume(1.0));
+    harness.wait_until(|p| p.volume == 1.0 && p.bit_perfect);
+
+    // replay gain changes the samples
+    harness.send(Command::Batch(vec![
+        Command::Enqueue(b.into_boxed_path()),
+        Command::Skip,
+        Command::Pause,
+    ]));
+    harness.wait_until(|p| {
+        p.is_paused() && p.current_song().is_some_and(|s| s.path.ends_with("b.flac"))
+    });
+    assert!(!harness.player.read().unwrap().bit_perfect);
+}
+
+/// a system without an output device
+struct Unplugged;
+
+impl AudioSink for Unplugged {
+    fn open(
+        &self,
+        _spec: StreamSpec,
+        _pause: Arc<AtomicBool>,
+        _render: Render,
+        _error: OnError,
+    ) -> Result<SinkStream, PlayerError> {
+        Err(PlayerError::DeviceUnavailable)
+    }
+
+    fn available(&self) -> bool {
+        false
+    }
+}
+
+#[test]
+fn playing_without_an_output_device_keeps_the_queue() {
+    let harness = Harness::with_sink(Arc::new(Unplugged));
+    let a = wav(&harness.path("a.wav"), &constant(1000, 1000));
+    let wait_for_error = || {
+        let start = Instant::now();
+        while !harness
+            .notifications
+            .try_iter()
+            .any(|n| n.level == Level::Error && n.message.contains("No audio output device"))
+        {
+            assert!(
+                start.elapsed() < Duration::from_secs(10),
+                "Timed out waiting for the error"
+            );
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    };
+    // on startup
+    wait_for_error();
+
+    harness.send(Command::Enqueue(a.clone().into_boxed_path()));
+    wait_for_error();
+    harness.wait_until(|p| !p.queue.is_empty());
+
+    let player = harness.player.read().unwrap();
+    assert!(player.current_song().is_none());
+    assert_eq!(player.queue.to_vec(), vec![a.into_boxed_path()]);
+}
+
+#[test]
+fn shutdown_stops_the_player_and_keeps_the_queue() {
+    let harness = Harness::new();
+    let frames = SAMPLE_RATE as usize * 10;
+    let a = wav(&harness.path("a.wav"), &constant(frames, 1000)).into_boxed_path();
+    let b = wav(&harness.path("b.wav"), &constant(frames, 1000)).into_boxed_path();
+
+    harness.send(Command::Batch(vec![
+        Command::Enqueue(a),
+        Command::Pause,
+        Command::Enqueue(b.clone()),
+    ]));
+    harness.wait_until(|p| p.is_paused() && p.queue.len() == 1);
+
+    harness.send(Command::Shutdown);
+    harness.wait_until(|p| p.current_song().is_none());
+    assert_eq!(harness.player.read().unwrap().queue.to_vec(), vec![b]);
+}
+
+#[test]
+fn preset_names_stay_in_the_presets_directory() {
+    let dir = Path::new("presets");
+    assert_eq!(
+        dsp::preset_path(dir, "v1.5").expect("Failed to name preset"),
+        dir.join("v1.5.json")
+    );
+    assert_ne!(
+        dsp::preset_path(dir, "v1.5").expect("Failed to name preset"),
+        dsp::preset_path(dir, "v1").expect("Failed to name preset")
+    );
+    for name in ["", " ", "../x", "..", "a/b", "/etc/x", "a\\b", "C:x"] {
+        assert!(dsp::preset_path(dir, name).is_err(), "{name:?}");
+    }
+}
+
+#[test]
+#[cfg(not(windows))]
+fn stop_hook_gets_the_song_that_stopped() {
+    let out = tempfile::tempdir().expect("Failed to create temporary directory");
+    let stopped = out.path().join("stopped");
+    let command = format!(
+        "printf '%s|%s|%s' \"$RAMP_EVENT\" \"$RAMP_TITLE\" \"$RAMP_PATH\" > '{}'",
+        stopped.display()
+    );
+    let harness = Harness::with_config(|c| c.hooks.on_stop = Some(command));
+    let a = flac(
+        &harness.path("a.flac"),
+        &constant(SAMPLE_RATE as usize / 4, 1000),
+        &[("TITLE", "Song A")],
+    );
+
+    harness.send(Command::Enqueue(a.clone().into_boxed_path()));
+    harness.wait_until(|p| p.current_song().is_some());
+    harness.send(Command::Stop);
+    harness.wait_until(|p| p.current_song().is_none());
+
+    let start = Instant::now();
+    let written = loop {
+        match std::fs::read_to_string(&stopped) {
+            Ok(written) if !written.is_empty() => break written,
+            _ => {
+                assert!(
+                    start.elapsed() < Duration::from_secs(10),
+                    "Hook did not run"
+                );
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+    };
+    assert_eq!(written, format!("stop|Song A|{}", a.display()));
+}
+
+#[test]
+fn preview_continues_with_the_previous_song() {
+    let harness = Harness::new();
+    let frames = SAMPLE_RATE as usize * 10;
+    let a = wav(&harness.path("a.wav"), &constant(frames, 1000)).into_boxed_path();
+    let b = wav(&harness.path("b.wav"), &constant(frames, 1000)).into_boxed_path();
+    let c = wav(&harness.path("c.wav"), &constant(frames / 10, 1000)).into_boxed_path();
+
+    harness.send(Command::Batch(vec![
+        Command::Enqueue(a.clone()),
+        Command::Pause,
+        Command::Enqueue(b.clone()),
+    ]));
+    harness.wait_until(|p| p.is_paused() && p.queue.len() == 1);
+
+    harness.send(Command::Preview(c.clone()));
+    harness.wait_until(|p| p.preview && p.current_song().is_some_and(|s| s.path == c));
+
+    // the end of the previewed song ends the preview instead of playing the queue
+    harness.wait_until(|p| {
+        !p.preview && p.is_paused() && p.current_song().is_some_and(|s| s.path == a)
+    });
+    assert_eq!(harness.player.read().unwrap().queue.to_vec(), vec![b]);
+}
+
+#[test]
+fn cue_points_are_jumped_to() {
+    let harness = Harness::new();
+    let frames = SAMPLE_RATE as usize;
+    let samples = [constant(2 * frames, 1000), constant(2 * frames, 2000)].concat();
+    let a = wav(&harness.path("a.wav"), &samples).into_boxed_path();
+
+    harness.send(Command::Batch(vec![
+        Command::Enqueue(a),
+        Command::Pause,
+        Command::JumpToCuePoint(1),
+    ]));
+    let error = harness
+        .notifications
+        .recv_timeout(Duration::from_secs(5))
+        .expect("No notification for a missing cue point");
+    assert_eq!(error.level, Level::Info);
+    harness.wait_until(|p| p.is_paused());
+
+    // seeking while paused is performed once playing again
+    harness.send(Command::Batch(vec![
+        Command::Seek(Duration::from_secs(1)),
+        Command::SetCuePoint(1),
+        Command::Seek(Duration::from_secs(3)),
+        Command::JumpToCuePoint(1),
+        Command::Play,
+    ]));
+    harness.wait_for_samples(3 * frames * 2);
+
+    let runs = harness.runs();
+    assert_eq!(runs.len(), 2, "{runs:?}");
+    // seeking lands on the start of a packet
+    assert_eq!(runs[0].0, sample(1000));
+    assert!(runs[0].1.abs_diff(frames * 2) < 4096, "{runs:?}");
+    assert_eq!(runs[1], (sample(2000), 2 * frames * 2));
+}
+
+#[test]
+fn clipping_is_shown_or_prevented() {
+    let tags = [
+        ("REPLAYGAIN_TRACK_GAIN", "+6.02 dB"),
+        ("REPLAYGAIN_TRACK_PEAK", "0.8"),
+    ];
+    let frames = SAMPLE_RATE as usize / 2;
+
+    let harness = Harness::new();
+    let a = flac(&harness.path("a.flac"), &constant(frames, 1000), &tags);
+    harness.send(Command::Batch(vec![
+        Command::Enqueue(a.into_boxed_path()),
+        Command::Pause,
+    ]));
+    harness.wait_until(|p| p.current_song().is_some() && p.clipping);
+    // the volume makes up for the gain
+    harness.send(Command::SetVolume(0.5));
+    harness.wait_until(|p| p.current_song().is_some() && !p.clipping);
+
+    let harness = Harness::with_config(|c| c.prevent_clipping = true);
+    let a = flac(&harness.path("a.flac"), &constant(frames, 1000), &tags);
+    harness.send(Command::Enqueue(a.into_boxed_path()));
+    harness.wait_for_samples(frames * 2);
+
+    // the peak of 0.8 is brought to full scale instead of 1.6
+    let runs = harness.runs();
+    assert!((runs[0].0 - sample(1000) / 0.8).abs() < 1e-4, "{runs:?}");
+    assert!(!harness.player.read().unwrap().clipping);
+}
+
+/// all samples of an audio file, decoded with symphonia
+fn decode(path: &Path) -> Vec<f32> {
+    use symphonia::core::{
+        audio::SampleBuffer, codecs::DecoderOptions, formats::FormatOptions, io::MediaSourceStream,
+        meta::MetadataOptions, probe::Hint,
+    };
+
+    let file = std::fs::File::open(path).expect("Failed to open output");
+    let mut format = symphonia::default::get_probe()
+        .format(
+            Hint::new().with_extension("flac"),
+            MediaSourceStream::new(Box::new(file), Default::default()),
+            &FormatOptions::default(),
+            &MetadataOptions::default(),
+        )
+        .expect("Failed to probe output")
+        .format;
+    let track = format.default_track().expect("Failed to find track");
+    let mut decoder = symphonia::default::get_codecs()
+        .make(&track.codec_params, &DecoderOptions { verify: true })
+        .expect("Failed to create decoder");
+
+    let mut samples = Vec::new();
+    while let Ok(packet) = format.next_packet() {
+        let decoded = decoder.decode(&packet).expect("Failed to decode packet");
+        let mut buffer = SampleBuffer::<f32>::new(decoded.capacity() as u64, *decoded.spec());
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend(buffer.samples());
+    }
+
+    samples
+}
+
+#[test]
+fn export_renders_songs_into_a_flac_file() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = Config::default_from_config_dir(dir.path());
+    // not a multiple of the block size, so that the last block of the first song is short
+    let frames = SAMPLE_RATE as usize / 3;
+    let a = wav(&dir.path().join("a.wav"), &constant(frames, 1000));
+    let b = flac(
+        &dir.path().join("b.flac"),
+        &constant(frames, 16000),
+        &[("REPLAYGAIN_TRACK_GAIN", "-6.0 dB")],
+    );
+    let output = dir.path().join("out.flac");
+
+    export(&Cache::default(), &config, &[a, b], &output, None, false).expect("Failed to export");
+
+    let samples = decode(&output);
+    assert_eq!(samples.len(), 2 * frames * 2);
+    let close = |s: &f32, expected: f32| (s - expected).abs() < 1e-6;
+    assert!(samples[..frames * 2].iter().all(|s| close(s, sample(1000))));
+    let expected = sample(16000) * 10f32.powf(-6.0 / 20.0);
+    assert!(samples[frames * 2..].iter().all(|s| close(s, expected)));
+}
+
+#[test]
+fn export_normalizes_to_the_loudness_target() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = Config {
+        loudness_target: (-23.0).into(),
+        ..Config::default_from_config_dir(dir.path())
+    };
+    let frames = SAMPLE_RATE as usize / 4;
+    let a = flac(
+        &dir.path().join("a.flac"),
+        &constant(frames, 16000),
+        &[("REPLAYGAIN_TRACK_GAIN", "-6.0 dB")],
+    );
+    let output = dir.path().join("out.flac");
+
+    export(&Cache::default(), &config, &[a], &output, None, false).expect("Failed to export");
+
+    let expected = sample(16000) * 10f32.powf(-11.0 / 20.0);
+    let samples = decode(&output);
+    assert_eq!(samples.len(), frames * 2);
+    assert!(samples.iter().all(|s| (s - expected).abs() < 1e-6));
+}
+
+#[test]
+fn r128_gain_is_relative_to_minus_23_lufs() {
+    let harness = Harness::new();
+    let a = flac(
+        &harness.path("a.flac"),
+        &constant(1000, 1000),
+        &[("R128_TRACK_GAIN", "-1280")],
+    );
+
+    let song = Song::load(&a).expect("Failed to load song");
+    assert!((song.normalization(-23.0) - 10f32.powf(-5.0 / 20.0)).abs() < 1e-6);
+    assert!((song.normalization(-18.0) - 1.0).abs() < 1e-6);
+}
+
+#[test]
+fn export_rejects_unknown_formats() {
+    let dir = tempfile::tempdir().expect("Failed to create temporary directory");
+    let config = Config::default_from_config_dir(dir.path());
+    let a = wav(&dir.path().join("a.wav"), &constant(1000, 1000));
+
+    let result = export(
+        &Cache::default(),
+        &config,
+        &[a],
+        &dir.path().join("out.mp3"),
+        None,
+        false,
+    );
+    assert!(result.is_err());
+}